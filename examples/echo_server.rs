@@ -1,6 +1,6 @@
 use std::net::SocketAddr;
 
-use futures_util::{SinkExt, StreamExt};
+use futures_util::StreamExt;
 use tokio::net::TcpListener;
 use tokio_websockets::{Config, Error, Limits, ServerBuilder};
 
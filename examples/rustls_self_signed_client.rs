@@ -0,0 +1,31 @@
+use std::{fs::File, io::BufReader};
+
+use futures_util::StreamExt;
+use http::Uri;
+use rustls_pemfile::certs;
+use tokio_websockets::{ClientBuilder, Connector, Error};
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let uri = Uri::from_static("wss://127.0.0.1:8080");
+    let cert = certs(&mut BufReader::new(File::open("certs/localhost.crt")?))
+        .next()
+        .unwrap()?;
+    let connector = Connector::builder()
+        .builtin_roots(false)
+        .add_root_certificate(cert)
+        .build()?;
+
+    let (mut client, _) = ClientBuilder::from_uri(uri)
+        .connector(&connector)
+        .connect()
+        .await?;
+
+    let msg = client.next().await;
+
+    println!("Got message: {msg:?}");
+
+    client.close().await?;
+
+    Ok(())
+}
@@ -9,8 +9,8 @@ use futures_util::SinkExt;
 use rustls_pemfile::{certs, pkcs8_private_keys};
 use rustls_pki_types::{CertificateDer, PrivateKeyDer};
 use tokio::net::TcpListener;
-use tokio_rustls::{rustls, TlsAcceptor};
-use tokio_websockets::Message;
+use tokio_rustls::rustls;
+use tokio_websockets::{server::Acceptor, Message, ServerBuilder};
 
 const PATH_TO_CERT: &str = "certs/localhost.crt";
 const PATH_TO_KEY: &str = "certs/localhost.key";
@@ -37,7 +37,7 @@ async fn main() -> io::Result<()> {
         .with_single_cert(certs, key)
         .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
 
-    let acceptor = TlsAcceptor::from(Arc::new(config));
+    let acceptor = Arc::new(Acceptor::new(ServerBuilder::new(), Arc::new(config)));
 
     let listener = TcpListener::bind(&addr).await?;
 
@@ -46,11 +46,7 @@ async fn main() -> io::Result<()> {
         let acceptor = acceptor.clone();
 
         let fut = async move {
-            let stream = acceptor.accept(stream).await?;
-
-            let mut ws = tokio_websockets::ServerBuilder::new()
-                .accept(stream)
-                .await?;
+            let mut ws = acceptor.accept(stream).await?;
 
             // From here, do what you want with it
             ws.send(Message::text(String::from("Hello, world!")))
@@ -0,0 +1,32 @@
+#![no_main]
+
+extern crate tokio_websockets;
+
+use futures::stream::StreamExt;
+use libfuzzer_sys::fuzz_target;
+use tokio_websockets::{ClientBuilder, CloseCode};
+
+fuzz_target!(|payload: &[u8]| {
+    // Control frames carry at most 125 bytes
+    let payload = &payload[..payload.len().min(125)];
+
+    // Unmasked server-to-client close frame
+    let mut data = vec![0x88, payload.len() as u8];
+    data.extend_from_slice(payload);
+
+    let mut ws = ClientBuilder::new().take_over(tokio::io::join(&data[..], tokio::io::sink()));
+
+    futures::executor::block_on(async move {
+        if let Some(Ok(message)) = ws.next().await {
+            let (code, reason) = message.as_close().expect("received a non-close message");
+
+            // Only valid close frames may be accepted and parsed losslessly
+            if payload.is_empty() {
+                assert_eq!(code, CloseCode::NO_STATUS_RECEIVED);
+            } else {
+                assert_eq!(u16::from(code).to_be_bytes(), payload[..2]);
+                assert_eq!(reason.as_bytes(), &payload[2..]);
+            }
+        }
+    });
+});
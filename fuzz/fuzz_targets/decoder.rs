@@ -0,0 +1,82 @@
+#![no_main]
+
+extern crate tokio_websockets;
+
+use std::{
+    io,
+    num::NonZeroU8,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use arbitrary::Arbitrary;
+use futures::stream::StreamExt;
+use libfuzzer_sys::fuzz_target;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_websockets::{ClientBuilder, Limits, ServerBuilder};
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    /// Whether the bytes are decoded by a client instead of a server.
+    client: bool,
+    /// Size of the chunks the bytes are read in, to exercise partial frames.
+    chunk_size: NonZeroU8,
+    /// Limit to a small int to avoid OOM.
+    max_payload_len: u16,
+    /// The bytes sent by the peer.
+    data: Vec<u8>,
+}
+
+/// Stream that yields the input in chunks and discards all writes.
+struct ChunkedStream {
+    data: Vec<u8>,
+    chunk_size: usize,
+}
+
+fuzz_target!(|input: Input| {
+    let limits = Limits::default().max_payload_len(Some(input.max_payload_len as _));
+    let stream = ChunkedStream {
+        data: input.data,
+        chunk_size: input.chunk_size.get().into(),
+    };
+
+    let mut ws = if input.client {
+        ClientBuilder::new().limits(limits).take_over(stream)
+    } else {
+        ServerBuilder::new().limits(limits).serve(stream)
+    };
+
+    futures::executor::block_on(async move { while let Some(Ok(_)) = ws.next().await {} });
+});
+
+impl AsyncRead for ChunkedStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let end = buf.remaining().min(self.chunk_size).min(self.data.len());
+        buf.put_slice(&self.data[..end]);
+        self.data.drain(..end);
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for ChunkedStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
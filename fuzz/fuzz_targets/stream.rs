@@ -3,7 +3,6 @@
 extern crate tokio_websockets;
 
 use std::{
-    convert::TryFrom,
     io,
     num::NonZeroUsize,
     pin::Pin,
@@ -11,38 +10,15 @@ use std::{
 };
 
 use arbitrary::Arbitrary;
-use futures::{stream::StreamExt, SinkExt};
+use futures::stream::StreamExt;
 use libfuzzer_sys::fuzz_target;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
-use tokio_websockets::{CloseCode, Config, Limits, Message};
-
-#[derive(Arbitrary, Debug)]
-enum ArbitraryMessage {
-    Binary(Vec<u8>),
-    Text(String),
-    Ping(Vec<u8>),
-    Pong(Vec<u8>),
-    Close(u16, String),
-}
-
-impl From<ArbitraryMessage> for Message {
-    fn from(message: ArbitraryMessage) -> Self {
-        match message {
-            ArbitraryMessage::Binary(payload) => Message::binary(payload),
-            ArbitraryMessage::Text(payload) => Message::text(payload),
-            ArbitraryMessage::Ping(payload) => Message::ping(payload),
-            ArbitraryMessage::Pong(payload) => Message::pong(payload),
-            ArbitraryMessage::Close(code, reason) => {
-                Message::close(CloseCode::try_from(code).ok(), &reason)
-            }
-        }
-    }
-}
+use tokio_websockets::{Config, Limits, Message};
 
 #[derive(Arbitrary, Debug)]
 enum Operation {
     Read,
-    Write(ArbitraryMessage),
+    Write(Message),
 }
 
 #[derive(Arbitrary, Debug)]
@@ -87,7 +63,7 @@ fn fuzz(
                     let _ = ws.next().await;
                 }
                 Operation::Write(message) => {
-                    let _ = ws.send(message.into()).await;
+                    let _ = ws.send(message).await;
                 }
             }
         }
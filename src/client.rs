@@ -7,21 +7,55 @@
 //!     established stream, via [`Builder::connect_on`]
 //!   - By performing the handshake yourself and then using
 //!     [`Builder::take_over`] to let it take over a WebSocket stream
-use std::{future::poll_fn, io, pin::Pin, str::FromStr};
+//!
+//! Many connections to the same endpoint can be opened via a [`Pool`], which
+//! shares the setup work between them.
+use std::{
+    borrow::Cow,
+    fmt,
+    future::{poll_fn, Future},
+    io,
+    mem::replace,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    ops::RangeInclusive,
+    pin::{pin, Pin},
+    str::FromStr,
+    sync::Arc,
+    task::Poll,
+    time::Duration,
+};
 
 use base64::{engine::general_purpose, Engine};
 use futures_core::Stream;
-use http::{header::HeaderName, HeaderMap, HeaderValue, Uri};
+use http::{
+    header::{
+        HeaderName, InvalidHeaderValue, AUTHORIZATION, COOKIE, LOCATION, SEC_WEBSOCKET_EXTENSIONS,
+        SET_COOKIE,
+    },
+    uri::PathAndQuery,
+    HeaderMap, HeaderValue, StatusCode, Uri,
+};
+use socket2::SockRef;
 use tokio::{
     io::{AsyncRead, AsyncWrite, AsyncWriteExt},
-    net::TcpStream,
+    net::{TcpSocket, TcpStream},
+    sync::Semaphore,
 };
 use tokio_util::codec::FramedRead;
 
 use crate::{
-    proto::{Config, Limits, Role},
+    cookie::CookieStore,
+    proto::{Config, ExtensionCodec, Limits, Role},
+    proxy::{self, Proxy},
+    rand::MaskGenerator,
     resolver::{self, Resolver},
-    upgrade::{self, server_response},
+    socket::SocketOptions,
+    unchecked::{self, unchecked},
+    upgrade::{
+        self,
+        extensions::{self, ClientExtension},
+        server_response,
+    },
     Connector, Error, MaybeTlsStream, WebSocketStream,
 };
 
@@ -31,10 +65,8 @@ pub(crate) fn make_key() -> [u8; 24] {
     let key_bytes = crate::rand::get_key();
 
     // SAFETY: We know that 16 bytes will be 24 bytes base64-encoded
-    unsafe {
-        general_purpose::STANDARD
-            .encode_slice(key_bytes, &mut key_base64)
-            .unwrap_unchecked()
+    unchecked! {
+        unchecked::unwrap(general_purpose::STANDARD.encode_slice(key_bytes, &mut key_base64))
     };
 
     key_base64
@@ -99,13 +131,413 @@ fn build_request(uri: &Uri, key: &[u8], headers: &HeaderMap) -> Vec<u8> {
     buf
 }
 
+/// Resolves the target of a redirect relative to the URI that was redirected.
+///
+/// Relative paths without a leading `/` are resolved against the directory of
+/// the path of `uri`.
+///
+/// # Errors
+///
+/// This function returns an error if the target is not a valid URI, uses an
+/// unsupported scheme or would downgrade the connection from `wss` to `ws`.
+fn redirect_uri(uri: &Uri, location: &str) -> Result<Uri, Error> {
+    let invalid = || Error::Upgrade(upgrade::Error::Parsing(httparse::Error::HeaderValue));
+
+    let location = if location.starts_with('/') || location.contains("://") {
+        Uri::try_from(location)
+    } else {
+        let path = uri.path();
+        let directory = &path[..path.rfind('/').map_or(0, |i| i + 1)];
+
+        Uri::try_from(format!("{directory}{location}"))
+    }
+    .map_err(|_| invalid())?;
+
+    let scheme = match location.scheme_str() {
+        None => uri.scheme_str().ok_or(Error::UnsupportedScheme)?,
+        Some("http" | "ws") => "ws",
+        Some("https" | "wss") => "wss",
+        Some(_) => return Err(Error::UnsupportedScheme),
+    };
+
+    if uri.scheme_str() == Some("wss") && scheme == "ws" {
+        return Err(Error::Upgrade(upgrade::Error::InsecureRedirect));
+    }
+
+    let authority = location
+        .authority()
+        .or(uri.authority())
+        .ok_or(Error::CannotResolveHost)?;
+    let path_and_query = location.path_and_query().map_or("/", PathAndQuery::as_str);
+
+    Uri::try_from(format!("{scheme}://{authority}{path_and_query}")).map_err(|_| invalid())
+}
+
+/// Phase of establishing a client connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConnectPhase {
+    /// Resolving the hostname of the URI.
+    Resolve,
+    /// Establishing the TCP connection.
+    Connect,
+    /// Performing the TLS handshake.
+    TlsHandshake,
+    /// Performing the HTTP upgrade handshake.
+    Upgrade,
+}
+
+impl ConnectPhase {
+    /// Stringify this variant.
+    pub(crate) const fn as_str(self) -> &'static str {
+        match self {
+            Self::Resolve => "DNS resolution",
+            Self::Connect => "TCP connect",
+            Self::TlsHandshake => "TLS handshake",
+            Self::Upgrade => "HTTP upgrade",
+        }
+    }
+}
+
+impl ConnectPhase {
+    /// Returns the bit representing this phase in a bit mask.
+    const fn bit(self) -> u8 {
+        match self {
+            Self::Resolve => 1,
+            Self::Connect => 1 << 1,
+            Self::TlsHandshake => 1 << 2,
+            Self::Upgrade => 1 << 3,
+        }
+    }
+}
+
+impl fmt::Display for ConnectPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Policy for retrying a failed connection attempt with exponential backoff,
+/// see [`Builder::retry_policy`].
+///
+/// By default, up to 3 retries are made for failures while resolving the
+/// hostname, establishing the TCP connection and performing the TLS
+/// handshake, since these are usually transient. Failures during the HTTP
+/// upgrade handshake, such as the server rejecting the request, are not
+/// retried unless enabled via [`RetryPolicy::retry_phase`]. Errors caused by
+/// the configuration, e.g. an unsupported scheme, are never retried.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Maximum number of retries after the first attempt.
+    max_retries: u32,
+    /// Delay before the first retry.
+    initial_backoff: Duration,
+    /// Upper bound of the delay between attempts.
+    max_backoff: Duration,
+    /// Whether the delays are randomized.
+    jitter: bool,
+    /// Phases in which failed attempts are retried, as a bit mask of
+    /// [`ConnectPhase::bit`].
+    phases: u8,
+}
+
+impl RetryPolicy {
+    /// Creates a retry policy with the default settings.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            jitter: true,
+            phases: ConnectPhase::Resolve.bit()
+                | ConnectPhase::Connect.bit()
+                | ConnectPhase::TlsHandshake.bit(),
+        }
+    }
+
+    /// Sets the maximum number of retries after the first attempt failed. The
+    /// default is 3.
+    #[must_use]
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+
+        self
+    }
+
+    /// Sets the delay before the first retry. It doubles with every further
+    /// retry. The default is 100 milliseconds.
+    #[must_use]
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+
+        self
+    }
+
+    /// Sets the upper bound of the delay between attempts. The default is 10
+    /// seconds.
+    #[must_use]
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+
+        self
+    }
+
+    /// Sets whether the delays are randomized, so that many clients that
+    /// failed at the same time, e.g. because the server restarted, do not
+    /// all retry at once. Randomized delays are between half and all of the
+    /// exponential backoff. The default is `true`.
+    #[must_use]
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+
+        self
+    }
+
+    /// Sets whether failures in `phase` are retried.
+    #[must_use]
+    pub fn retry_phase(mut self, phase: ConnectPhase, retry: bool) -> Self {
+        if retry {
+            self.phases |= phase.bit();
+        } else {
+            self.phases &= !phase.bit();
+        }
+
+        self
+    }
+
+    /// Whether the failed attempt with `error` in `phase` should be retried
+    /// after `retries` retries.
+    fn should_retry(&self, retries: u32, phase: ConnectPhase, error: &Error) -> bool {
+        let permanent = match error {
+            Error::NoUriConfigured | Error::UnsupportedScheme => true,
+            #[cfg(any(
+                feature = "rustls-webpki-roots",
+                feature = "rustls-native-roots",
+                feature = "rustls-platform-verifier",
+                feature = "rustls-bring-your-own-connector"
+            ))]
+            Error::InvalidDNSName(_) => true,
+            #[cfg(all(
+                any(
+                    feature = "rustls-webpki-roots",
+                    feature = "rustls-native-roots",
+                    feature = "rustls-platform-verifier"
+                ),
+                not(any(feature = "ring", feature = "aws_lc_rs"))
+            ))]
+            Error::NoCryptoProviderConfigured => true,
+            _ => false,
+        };
+
+        !permanent && retries < self.max_retries && self.phases & phase.bit() != 0
+    }
+
+    /// Returns the delay before the retry after `retries` previous retries.
+    fn backoff(&self, retries: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(1 << retries.min(31))
+            .min(self.max_backoff);
+
+        if self.jitter {
+            let random = u32::from_ne_bytes(crate::rand::get_mask());
+            let half = backoff / 2;
+
+            half + half.mul_f64(f64::from(random) / f64::from(u32::MAX))
+        } else {
+            backoff
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Awaits a future, failing with [`Error::ConnectTimeout`] if it does not
+/// complete within the timeout, if any.
+async fn with_timeout<F: Future>(
+    timeout: Option<Duration>,
+    phase: ConnectPhase,
+    future: F,
+) -> Result<F::Output, Error> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, future)
+            .await
+            .map_err(|_| Error::ConnectTimeout(phase)),
+        None => Ok(future.await),
+    }
+}
+
+/// Delay before starting the next connection attempt while the previous one
+/// is still pending, as recommended by RFC 8305.
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Sorts addresses so that IPv6 and IPv4 addresses alternate, starting with
+/// the family of the first address returned by the resolver.
+fn interleave_addrs(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let Some(first) = addrs.first() else {
+        return addrs;
+    };
+    let first_is_ipv6 = first.is_ipv6();
+
+    let (preferred, other): (Vec<_>, Vec<_>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == first_is_ipv6);
+
+    let mut interleaved = Vec::with_capacity(preferred.len() + other.len());
+    let mut preferred = preferred.into_iter();
+    let mut other = other.into_iter();
+
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => break,
+            (a, b) => interleaved.extend(a.into_iter().chain(b)),
+        }
+    }
+
+    interleaved
+}
+
+/// Connects to the first reachable address, racing connection attempts as
+/// described in RFC 8305 ("Happy Eyeballs").
+///
+/// A new attempt is started whenever the previous one failed or has not
+/// completed within [`CONNECTION_ATTEMPT_DELAY`]. The first attempt to
+/// succeed wins and all others are cancelled.
+async fn connect_tcp(
+    addrs: Vec<SocketAddr>,
+    socket_options: SocketOptions,
+    local: &LocalBind,
+) -> Result<TcpStream, Error> {
+    /// A pending connection attempt.
+    type Attempt = Pin<Box<dyn Future<Output = io::Result<TcpStream>> + Send>>;
+
+    let mut addrs = addrs.into_iter();
+    let mut attempts: Vec<Attempt> = Vec::new();
+    let mut last_error = None;
+    let mut delay = pin!(tokio::time::sleep(CONNECTION_ATTEMPT_DELAY));
+
+    let addr = addrs.next().ok_or(Error::CannotResolveHost)?;
+    attempts.push(Box::pin(connect_addr(addr, socket_options, local.clone())));
+
+    poll_fn(|cx| loop {
+        let mut attempt_failed = false;
+        let mut i = 0;
+
+        while i < attempts.len() {
+            match attempts[i].as_mut().poll(cx) {
+                Poll::Ready(Ok(stream)) => return Poll::Ready(Ok(stream)),
+                Poll::Ready(Err(e)) => {
+                    drop(attempts.swap_remove(i));
+                    last_error = Some(e);
+                    attempt_failed = true;
+                }
+                Poll::Pending => i += 1,
+            }
+        }
+
+        if attempt_failed || delay.as_mut().poll(cx).is_ready() {
+            if let Some(addr) = addrs.next() {
+                attempts.push(Box::pin(connect_addr(addr, socket_options, local.clone())));
+                delay
+                    .as_mut()
+                    .reset(tokio::time::Instant::now() + CONNECTION_ATTEMPT_DELAY);
+
+                continue;
+            }
+        }
+
+        if attempts.is_empty() {
+            return Poll::Ready(Err(last_error
+                .take()
+                .map_or(Error::CannotResolveHost, Error::Io)));
+        }
+
+        return Poll::Pending;
+    })
+    .await
+}
+
+/// Local end of outgoing TCP connections.
+#[derive(Debug, Clone, Default)]
+struct LocalBind {
+    /// Local IP address to bind to.
+    address: Option<IpAddr>,
+    /// Range of local ports to bind to.
+    ports: Option<RangeInclusive<u16>>,
+    /// Name of the network interface to bind to.
+    #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+    interface: Option<Arc<str>>,
+}
+
+impl LocalBind {
+    /// Whether connections to `addr` can be made from this local end.
+    fn can_reach(&self, addr: &SocketAddr) -> bool {
+        self.address
+            .map_or(true, |address| address.is_ipv4() == addr.is_ipv4())
+    }
+
+    /// Binds `socket` about to connect to `addr`.
+    fn bind(&self, socket: &TcpSocket, addr: SocketAddr) -> io::Result<()> {
+        #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+        if let Some(interface) = &self.interface {
+            SockRef::from(socket).bind_device(Some(interface.as_bytes()))?;
+        }
+
+        let address = self.address.unwrap_or(match addr {
+            SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+            SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+        });
+
+        match self.ports.clone() {
+            Some(ports) => {
+                let mut last_error = io::ErrorKind::AddrInUse.into();
+
+                for port in ports {
+                    match socket.bind(SocketAddr::new(address, port)) {
+                        Ok(()) => return Ok(()),
+                        Err(e) if e.kind() == io::ErrorKind::AddrInUse => last_error = e,
+                        Err(e) => return Err(e),
+                    }
+                }
+
+                Err(last_error)
+            }
+            None if self.address.is_some() => socket.bind(SocketAddr::new(address, 0)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Connects to `addr` with a socket that `socket_options` were applied to and
+/// that is bound to `local`.
+async fn connect_addr(
+    addr: SocketAddr,
+    socket_options: SocketOptions,
+    local: LocalBind,
+) -> io::Result<TcpStream> {
+    let socket = if addr.is_ipv4() {
+        TcpSocket::new_v4()?
+    } else {
+        TcpSocket::new_v6()?
+    };
+    socket_options.apply_to(&SockRef::from(&socket))?;
+    local.bind(&socket, addr)?;
+
+    socket.connect(addr).await
+}
+
 /// Builder for WebSocket client connections.
 pub struct Builder<'a, R: Resolver = resolver::Gai> {
     /// URI to connect to, required unless connecting to an established
     /// WebSocket stream.
     uri: Option<Uri>,
     /// A TLS connector to use for the connection. If not set and required, a
-    /// new one will be created.
+    /// connector shared by the process is used.
     connector: Option<&'a Connector>,
     /// A DNS resolver to use for looking up the hostname.
     resolver: R,
@@ -115,9 +547,67 @@ pub struct Builder<'a, R: Resolver = resolver::Gai> {
     limits: Limits,
     /// Headers to be sent with the upgrade request.
     headers: HeaderMap,
+    /// Source of masking keys for outgoing frames.
+    mask_generator: MaskGenerator,
+    /// Timeout for resolving the hostname.
+    resolve_timeout: Option<Duration>,
+    /// Timeout for establishing the TCP connection.
+    connect_timeout: Option<Duration>,
+    /// Timeout for the TLS handshake.
+    tls_handshake_timeout: Option<Duration>,
+    /// Timeout for the HTTP upgrade handshake.
+    upgrade_timeout: Option<Duration>,
+    /// Timeout for establishing the connection as a whole.
+    overall_timeout: Option<Duration>,
+    /// Maximum number of redirects to follow when connecting.
+    max_redirects: usize,
+    /// Policy for retrying failed connection attempts.
+    retry_policy: Option<RetryPolicy>,
+    /// Store for cookies sent with and received from handshakes.
+    cookie_store: Option<Arc<dyn CookieStore>>,
+    /// HTTP proxy to tunnel connections through.
+    proxy: Option<Proxy>,
+    /// Address to connect to instead of resolving the host of the URI.
+    address: Option<SocketAddr>,
+    /// Name of the server to present via SNI instead of the host of the URI.
+    server_name: Option<String>,
+    /// Extensions to offer to the server, in order of preference.
+    extensions: Vec<Arc<dyn ClientExtension>>,
+    /// Options for the TCP socket.
+    socket_options: SocketOptions,
+    /// Local end to bind the TCP socket to.
+    local: LocalBind,
+}
+
+impl<R: Resolver> fmt::Debug for Builder<'_, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Header values and proxy URIs may contain credentials
+        f.debug_struct("Builder")
+            .field("uri", &self.uri)
+            .field("connector", &self.connector)
+            .field("config", &self.config)
+            .field("limits", &self.limits)
+            .field("headers", &self.headers.keys().collect::<Vec<_>>())
+            .field("mask_generator", &self.mask_generator)
+            .field("resolve_timeout", &self.resolve_timeout)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("tls_handshake_timeout", &self.tls_handshake_timeout)
+            .field("upgrade_timeout", &self.upgrade_timeout)
+            .field("overall_timeout", &self.overall_timeout)
+            .field("max_redirects", &self.max_redirects)
+            .field("retry_policy", &self.retry_policy)
+            .field("cookie_store", &self.cookie_store.is_some())
+            .field("proxy", &self.proxy.is_some())
+            .field("address", &self.address)
+            .field("server_name", &self.server_name)
+            .field("extensions", &self.extensions.len())
+            .field("socket_options", &self.socket_options)
+            .field("local", &self.local)
+            .finish_non_exhaustive()
+    }
 }
 
-impl<'a> Builder<'a> {
+impl Builder<'_> {
     /// Creates a [`Builder`] with all defaults that is not configured to
     /// connect to any server.
     #[must_use]
@@ -129,6 +619,21 @@ impl<'a> Builder<'a> {
             config: Config::default(),
             limits: Limits::default(),
             headers: HeaderMap::new(),
+            mask_generator: MaskGenerator::Default,
+            resolve_timeout: None,
+            connect_timeout: None,
+            tls_handshake_timeout: None,
+            upgrade_timeout: None,
+            overall_timeout: None,
+            max_redirects: 0,
+            retry_policy: None,
+            cookie_store: None,
+            proxy: None,
+            address: None,
+            server_name: None,
+            extensions: Vec::new(),
+            socket_options: SocketOptions::new(),
+            local: LocalBind::default(),
         }
     }
 
@@ -145,6 +650,21 @@ impl<'a> Builder<'a> {
             config: Config::default(),
             limits: Limits::default(),
             headers: HeaderMap::new(),
+            mask_generator: MaskGenerator::Default,
+            resolve_timeout: None,
+            connect_timeout: None,
+            tls_handshake_timeout: None,
+            upgrade_timeout: None,
+            overall_timeout: None,
+            max_redirects: 0,
+            retry_policy: None,
+            cookie_store: None,
+            proxy: None,
+            address: None,
+            server_name: None,
+            extensions: Vec::new(),
+            socket_options: SocketOptions::new(),
+            local: LocalBind::default(),
         }
     }
 }
@@ -165,8 +685,10 @@ impl<'a, R: Resolver> Builder<'a, R> {
 
     /// Sets the TLS connector for the client.
     ///
-    /// By default, the client will create a new one for each connection instead
-    /// of reusing one.
+    /// By default, the client uses a connector that is created once and shared
+    /// by all clients without a configured one. Reusing a connector reuses
+    /// its TLS session cache, so reconnections to the same host complete with
+    /// an abbreviated handshake.
     #[must_use]
     pub fn connector(mut self, connector: &'a Connector) -> Self {
         self.connector = Some(connector);
@@ -177,27 +699,64 @@ impl<'a, R: Resolver> Builder<'a, R> {
     /// Sets the DNS resolver for the client.
     ///
     /// By default, the client will use the [`Gai`] resolver, a wrapper around
-    /// the blocking `getaddrinfo` syscall.
+    /// the blocking `getaddrinfo` syscall. Wrap it in a [`Cached`] resolver to
+    /// avoid resolving the hostname again for every reconnect.
     ///
     /// [`Gai`]: resolver::Gai
+    /// [`Cached`]: resolver::Cached
     #[must_use]
     pub fn resolver<NewR: Resolver>(self, resolver: NewR) -> Builder<'a, NewR> {
+        self.map_resolver(|_| resolver)
+    }
+
+    /// Replaces the DNS resolver with one derived from the current one.
+    fn map_resolver<NewR: Resolver>(self, f: impl FnOnce(R) -> NewR) -> Builder<'a, NewR> {
         let Builder {
             uri,
             connector,
-            resolver: _,
+            resolver,
             config,
             limits,
             headers,
+            mask_generator,
+            resolve_timeout,
+            connect_timeout,
+            tls_handshake_timeout,
+            upgrade_timeout,
+            overall_timeout,
+            max_redirects,
+            retry_policy,
+            cookie_store,
+            proxy,
+            address,
+            server_name,
+            extensions,
+            socket_options,
+            local,
         } = self;
 
         Builder {
             uri,
             connector,
-            resolver,
+            resolver: f(resolver),
             config,
             limits,
             headers,
+            mask_generator,
+            resolve_timeout,
+            connect_timeout,
+            tls_handshake_timeout,
+            upgrade_timeout,
+            overall_timeout,
+            max_redirects,
+            retry_policy,
+            cookie_store,
+            proxy,
+            address,
+            server_name,
+            extensions,
+            socket_options,
+            local,
         }
     }
 
@@ -225,6 +784,292 @@ impl<'a, R: Resolver> Builder<'a, R> {
         self
     }
 
+    /// Adds a cookie to the `Cookie` header of the handshake request.
+    ///
+    /// # Errors
+    ///
+    /// This method returns a [`http::header::InvalidHeaderValue`] error if the
+    /// name or value contain characters that are not allowed in a header
+    /// value.
+    pub fn add_cookie(mut self, name: &str, value: &str) -> Result<Self, InvalidHeaderValue> {
+        let mut cookie = Vec::new();
+
+        if let Some(existing) = self.headers.get(COOKIE) {
+            cookie.extend_from_slice(existing.as_bytes());
+            cookie.extend_from_slice(b"; ");
+        }
+
+        cookie.extend_from_slice(name.as_bytes());
+        cookie.push(b'=');
+        cookie.extend_from_slice(value.as_bytes());
+
+        self.headers
+            .insert(COOKIE, HeaderValue::from_bytes(&cookie)?);
+
+        Ok(self)
+    }
+
+    /// Sets the cookie store for the client.
+    ///
+    /// Cookies from the store are sent with every handshake request in
+    /// addition to those added via [`Builder::add_cookie`], and cookies set
+    /// by successful handshake responses are saved to it.
+    ///
+    /// By default, no cookie store is used.
+    #[must_use]
+    pub fn cookie_store(mut self, cookie_store: Arc<dyn CookieStore>) -> Self {
+        self.cookie_store = Some(cookie_store);
+
+        self
+    }
+
+    /// Sets the `Authorization` header of the handshake request to use HTTP
+    /// Basic authentication with the given credentials.
+    #[must_use]
+    #[allow(clippy::missing_panics_doc)] // Base64 is always a valid header value
+    pub fn basic_auth(mut self, username: &str, password: &str) -> Self {
+        let credentials = general_purpose::STANDARD.encode(format!("{username}:{password}"));
+        let mut value = HeaderValue::try_from(format!("Basic {credentials}"))
+            .expect("base64 is a valid header value");
+        value.set_sensitive(true);
+
+        self.headers.insert(AUTHORIZATION, value);
+
+        self
+    }
+
+    /// Sets the `Authorization` header of the handshake request to use a
+    /// bearer token.
+    ///
+    /// # Errors
+    ///
+    /// This method returns a [`http::header::InvalidHeaderValue`] error if the
+    /// token contains characters that are not allowed in a header value.
+    pub fn bearer_auth(mut self, token: &str) -> Result<Self, InvalidHeaderValue> {
+        let mut value = HeaderValue::try_from(format!("Bearer {token}"))?;
+        value.set_sensitive(true);
+
+        self.headers.insert(AUTHORIZATION, value);
+
+        Ok(self)
+    }
+
+    /// Sets the source of masking keys for frames sent by the client.
+    ///
+    /// By default, masking keys are generated by the random number generator
+    /// enabled via crate features. A custom generator can be used to get
+    /// deterministic output in protocol tests or to use a different random
+    /// number generator. RFC 6455 requires masking keys to be unpredictable,
+    /// so a custom generator should not be used for anything else.
+    #[must_use]
+    pub fn mask_generator<F>(mut self, generator: F) -> Self
+    where
+        F: Fn() -> [u8; 4] + Send + Sync + 'static,
+    {
+        self.mask_generator = MaskGenerator::Custom(Arc::new(generator));
+
+        self
+    }
+
+    /// Sets the timeout for resolving the hostname of the URI.
+    ///
+    /// By default, there is no timeout.
+    #[must_use]
+    pub fn resolve_timeout(mut self, timeout: Duration) -> Self {
+        self.resolve_timeout = Some(timeout);
+
+        self
+    }
+
+    /// Sets the timeout for establishing the TCP connection.
+    ///
+    /// By default, there is no timeout.
+    #[must_use]
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+
+        self
+    }
+
+    /// Sets the timeout for the TLS handshake with `wss` URIs.
+    ///
+    /// By default, there is no timeout.
+    #[must_use]
+    pub fn tls_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.tls_handshake_timeout = Some(timeout);
+
+        self
+    }
+
+    /// Sets the timeout for the HTTP upgrade handshake, from sending the
+    /// request until the response has been received.
+    ///
+    /// By default, there is no timeout.
+    #[must_use]
+    pub fn upgrade_timeout(mut self, timeout: Duration) -> Self {
+        self.upgrade_timeout = Some(timeout);
+
+        self
+    }
+
+    /// Sets the timeout for establishing the connection as a whole, from
+    /// resolving the hostname until the upgrade response has been received.
+    /// It includes following redirects and retrying failed attempts.
+    ///
+    /// Connecting fails with [`Error::ConnectTimeout`] for the phase that was
+    /// in progress once the timeout elapses. It applies in addition to the
+    /// timeouts of the individual phases.
+    ///
+    /// By default, there is no timeout.
+    #[must_use]
+    pub fn overall_timeout(mut self, timeout: Duration) -> Self {
+        self.overall_timeout = Some(timeout);
+
+        self
+    }
+
+    /// Sets the address that [`Builder::connect`] connects to instead of
+    /// resolving the host of the URI. The URI is still used for the `Host`
+    /// header and the TLS server name.
+    ///
+    /// This does not apply to redirects to other hosts.
+    #[must_use]
+    pub fn address(mut self, address: SocketAddr) -> Self {
+        self.address = Some(address);
+
+        self
+    }
+
+    /// Sets the server name that [`Builder::connect`] presents via SNI and
+    /// verifies the server certificate against, instead of the host of the
+    /// URI. The `Host` header is not affected.
+    ///
+    /// This does not apply to redirects to other hosts.
+    #[must_use]
+    pub fn server_name(mut self, server_name: &str) -> Self {
+        self.server_name = Some(server_name.to_owned());
+
+        self
+    }
+
+    /// Registers an extension to offer to the server via the
+    /// `Sec-WebSocket-Extensions` header. Extensions are offered in the order
+    /// they were registered.
+    ///
+    /// Extensions accepted by the server transform the messages of the
+    /// stream. The handshake fails with
+    /// [`upgrade::Error::UnexpectedExtension`] if the server accepts an
+    /// extension that was not offered or with unacceptable parameters.
+    #[must_use]
+    pub fn extension<E: ClientExtension + 'static>(mut self, extension: E) -> Self {
+        self.extensions.push(Arc::new(extension));
+
+        self
+    }
+
+    /// Sets the HTTP proxy that [`Builder::connect`] tunnels connections
+    /// through via `CONNECT` requests. Credentials in the URI are sent to the
+    /// proxy using HTTP Basic authentication.
+    ///
+    /// # Errors
+    ///
+    /// This method returns a [`http::uri::InvalidUri`] error if URI parsing
+    /// fails.
+    pub fn proxy(mut self, uri: &str) -> Result<Self, http::uri::InvalidUri> {
+        self.proxy = Some(Proxy::Uri(Uri::from_str(uri)?));
+
+        Ok(self)
+    }
+
+    /// Makes [`Builder::connect`] look up the HTTP proxy to use from the
+    /// `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY` environment
+    /// variables, like other networking tools do. Their lowercase variants
+    /// take precedence.
+    ///
+    /// Only HTTP proxies are supported, connecting fails with
+    /// [`Error::UnsupportedScheme`] if another kind of proxy is configured.
+    #[must_use]
+    pub fn proxy_from_env(mut self) -> Self {
+        self.proxy = Some(Proxy::Env);
+
+        self
+    }
+
+    /// Sets the options for the TCP socket used by [`Builder::connect`]. They
+    /// are applied before connecting. By default, the operating system's
+    /// defaults are used.
+    #[must_use]
+    pub fn socket_options(mut self, socket_options: SocketOptions) -> Self {
+        self.socket_options = socket_options;
+
+        self
+    }
+
+    /// Sets the local IP address that [`Builder::connect`] binds the TCP socket
+    /// to, which determines the source address of the connection.
+    ///
+    /// Only addresses of the same family as `address` are connected to.
+    #[must_use]
+    pub fn local_address(mut self, address: IpAddr) -> Self {
+        self.local.address = Some(address);
+
+        self
+    }
+
+    /// Sets the range of local ports that [`Builder::connect`] binds the TCP
+    /// socket to. The ports are tried in order until one is available.
+    ///
+    /// By default, the operating system picks an ephemeral port.
+    #[must_use]
+    pub fn local_port_range(mut self, ports: RangeInclusive<u16>) -> Self {
+        self.local.ports = Some(ports);
+
+        self
+    }
+
+    /// Sets the network interface that [`Builder::connect`] binds the TCP
+    /// socket to via `SO_BINDTODEVICE`, such as `eth0`.
+    ///
+    /// This usually requires the `CAP_NET_RAW` capability.
+    #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+    #[must_use]
+    pub fn interface(mut self, interface: &str) -> Self {
+        self.local.interface = Some(Arc::from(interface));
+
+        self
+    }
+
+    /// Sets the maximum number of HTTP redirects followed by
+    /// [`Builder::connect`].
+    ///
+    /// Redirects to `http` and `https` URIs are followed with the `ws` and
+    /// `wss` schemes respectively. Redirects from `wss` to `ws` are refused.
+    /// The `Authorization` and `Cookie` headers are not sent to a host other
+    /// than the one originally connected to.
+    ///
+    /// By default, redirects are not followed.
+    #[must_use]
+    pub fn max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+
+        self
+    }
+
+    /// Sets the policy for retrying [`Builder::connect`] when establishing the
+    /// connection fails. `None` disables retries.
+    ///
+    /// Every attempt starts from scratch, including resolving the hostname,
+    /// and is subject to the configured timeouts. The error of the last
+    /// attempt is returned.
+    ///
+    /// By default, connection attempts are not retried.
+    #[must_use]
+    pub fn retry_policy(mut self, policy: Option<RetryPolicy>) -> Self {
+        self.retry_policy = policy;
+
+        self
+    }
+
     /// Establishes a connection to the WebSocket server. This requires a URI to
     /// be configured via [`Builder::uri`].
     ///
@@ -241,7 +1086,124 @@ impl<'a, R: Resolver> Builder<'a, R> {
         ),
         Error,
     > {
-        let uri = self.uri.as_ref().ok_or(Error::NoUriConfigured)?;
+        let uri = self.uri.clone().ok_or(Error::NoUriConfigured)?;
+
+        self.connect_uri(uri, self.connector).await
+    }
+
+    /// Establishes a connection to a URI with the given TLS connector,
+    /// following redirects and retrying failed attempts if configured.
+    async fn connect_uri(
+        &self,
+        uri: Uri,
+        connector: Option<&Connector>,
+    ) -> Result<
+        (
+            WebSocketStream<MaybeTlsStream<TcpStream>>,
+            upgrade::Response,
+        ),
+        Error,
+    > {
+        let mut phase = ConnectPhase::Resolve;
+        let attempts = self.retry(uri, connector, &mut phase);
+
+        let Some(timeout) = self.overall_timeout else {
+            return attempts.await;
+        };
+
+        let res = tokio::time::timeout(timeout, attempts).await;
+        res.unwrap_or(Err(Error::ConnectTimeout(phase)))
+    }
+
+    /// Establishes a connection to a URI with the given TLS connector,
+    /// retrying failed attempts if configured. `phase` is updated to the phase
+    /// that the current attempt is in.
+    async fn retry(
+        &self,
+        uri: Uri,
+        connector: Option<&Connector>,
+        phase: &mut ConnectPhase,
+    ) -> Result<
+        (
+            WebSocketStream<MaybeTlsStream<TcpStream>>,
+            upgrade::Response,
+        ),
+        Error,
+    > {
+        let mut retries = 0;
+
+        loop {
+            let res = self.follow_redirects(uri.clone(), connector, phase).await;
+
+            let Some(policy) = &self.retry_policy else {
+                return res;
+            };
+
+            match res {
+                Err(e) if policy.should_retry(retries, *phase, &e) => {
+                    tokio::time::sleep(policy.backoff(retries)).await;
+                    retries += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Establishes a connection to a URI with the given TLS connector,
+    /// following redirects if configured. `phase` is updated to the phase
+    /// that the connection attempt is in.
+    async fn follow_redirects(
+        &self,
+        mut uri: Uri,
+        connector: Option<&Connector>,
+        phase: &mut ConnectPhase,
+    ) -> Result<
+        (
+            WebSocketStream<MaybeTlsStream<TcpStream>>,
+            upgrade::Response,
+        ),
+        Error,
+    > {
+        let mut headers = Cow::Borrowed(&self.headers);
+        let mut redirects = 0;
+
+        loop {
+            match self.connect_to(&uri, &headers, connector, phase).await {
+                Err(Error::Upgrade(upgrade::Error::Redirected(location)))
+                    if redirects < self.max_redirects =>
+                {
+                    if location.authority() != uri.authority() {
+                        let headers = headers.to_mut();
+                        headers.remove(AUTHORIZATION);
+                        headers.remove(COOKIE);
+                    }
+
+                    uri = location;
+                    redirects += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Establishes a connection to a URI and performs the handshake with the
+    /// given headers. `phase` is updated to the phase that the connection
+    /// attempt is in.
+    async fn connect_to(
+        &self,
+        uri: &Uri,
+        headers: &HeaderMap,
+        connector: Option<&Connector>,
+        phase: &mut ConnectPhase,
+    ) -> Result<
+        (
+            WebSocketStream<MaybeTlsStream<TcpStream>>,
+            upgrade::Response,
+        ),
+        Error,
+    > {
+        *phase = ConnectPhase::Resolve;
+
         // Uri::host contains square brackets around IPv6 addresses, which is required
         // by the RFC: https://datatracker.ietf.org/doc/html/rfc3986#section-3.2.2
         // These, however, do not resolve.
@@ -251,25 +1213,89 @@ impl<'a, R: Resolver> Builder<'a, R> {
             .trim_start_matches('[')
             .trim_end_matches(']');
         let port = default_port(uri).unwrap_or(80);
-        let addr = self.resolver.resolve(host, port).await?;
 
-        let stream = TcpStream::connect(&addr).await?;
+        let proxy = match &self.proxy {
+            Some(proxy) => proxy.for_uri(uri)?,
+            None => None,
+        };
+        let (connect_host, connect_port) = match &proxy {
+            Some(proxy) => (
+                proxy
+                    .host()
+                    .ok_or(Error::CannotResolveHost)?
+                    .trim_start_matches('[')
+                    .trim_end_matches(']'),
+                proxy.port_u16().unwrap_or(80),
+            ),
+            None => (host, port),
+        };
 
-        let stream = if uri.scheme_str() == Some("wss") {
-            if let Some(connector) = self.connector {
-                connector.wrap(host, stream).await?
-            } else {
-                let connector = Connector::new()?;
+        // Overrides only apply to the configured URI, not to redirects to other hosts
+        let is_configured_host = self.uri.as_ref().and_then(Uri::host) == uri.host();
+        let address = self.address.filter(|_| is_configured_host);
+        let server_name = self
+            .server_name
+            .as_deref()
+            .filter(|_| is_configured_host)
+            .unwrap_or(host);
 
-                connector.wrap(host, stream).await?
+        let addrs = match (address, &proxy) {
+            (Some(address), None) => vec![address],
+            _ => {
+                with_timeout(
+                    self.resolve_timeout,
+                    ConnectPhase::Resolve,
+                    self.resolver.resolve_all(connect_host, connect_port),
+                )
+                .await??
             }
+        };
+
+        let addrs = addrs
+            .into_iter()
+            .filter(|addr| self.local.can_reach(addr))
+            .collect();
+
+        *phase = ConnectPhase::Connect;
+        let stream = with_timeout(self.connect_timeout, ConnectPhase::Connect, async {
+            let mut stream =
+                connect_tcp(interleave_addrs(addrs), self.socket_options, &self.local).await?;
+
+            if let Some(proxy) = &proxy {
+                // The tunnel target keeps the square brackets of IPv6 addresses
+                let target = match address {
+                    Some(address) => address.to_string(),
+                    None => format!("{}:{port}", uri.host().ok_or(Error::CannotResolveHost)?),
+                };
+                proxy::tunnel(&mut stream, proxy, &target).await?;
+            }
+
+            Ok::<_, Error>(stream)
+        })
+        .await??;
+
+        *phase = ConnectPhase::TlsHandshake;
+        let stream = if uri.scheme_str() == Some("wss") {
+            let connector = match connector {
+                Some(connector) => connector,
+                None => Connector::shared()?,
+            };
+
+            with_timeout(
+                self.tls_handshake_timeout,
+                ConnectPhase::TlsHandshake,
+                connector.wrap(server_name, stream),
+            )
+            .await??
         } else if uri.scheme_str() == Some("ws") {
-            Connector::Plain.wrap(host, stream).await?
+            Connector::Plain.wrap(server_name, stream).await?
         } else {
             return Err(Error::UnsupportedScheme);
         };
 
-        self.connect_on(stream).await
+        *phase = ConnectPhase::Upgrade;
+        self.handshake(uri, headers, stream, self.max_redirects > 0)
+            .await
     }
 
     /// Takes over an already established stream and uses it to send and receive
@@ -286,25 +1312,145 @@ impl<'a, R: Resolver> Builder<'a, R> {
     /// fails or no URI has been configured.
     pub async fn connect_on<S: AsyncRead + AsyncWrite + Unpin>(
         &self,
-        mut stream: S,
+        stream: S,
     ) -> Result<(WebSocketStream<S>, upgrade::Response), Error> {
         let uri = self.uri.as_ref().ok_or(Error::NoUriConfigured)?;
 
+        self.handshake(uri, &self.headers, stream, false).await
+    }
+
+    /// Performs the HTTP upgrade handshake for a URI with the given headers on
+    /// an established stream.
+    async fn handshake<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        uri: &Uri,
+        headers: &HeaderMap,
+        mut stream: S,
+        follow_redirects: bool,
+    ) -> Result<(WebSocketStream<S>, upgrade::Response), Error> {
         let key_base64 = make_key();
 
-        let upgrade_codec = server_response::Codec::new(&key_base64);
-        let request = build_request(uri, &key_base64, &self.headers);
-        stream.write_all(&request).await?;
+        let mut headers = Cow::Borrowed(headers);
 
-        let mut framed = FramedRead::new(stream, upgrade_codec);
-        let res = poll_fn(|cx| Pin::new(&mut framed).poll_next(cx))
-            .await
-            .ok_or(Error::Io(io::ErrorKind::UnexpectedEof.into()))??;
+        if let Some(cookies) = self
+            .cookie_store
+            .as_ref()
+            .and_then(|store| store.cookies(uri))
+        {
+            let cookie = match headers.get(COOKIE) {
+                Some(existing) => {
+                    let mut cookie = existing.as_bytes().to_vec();
+                    cookie.extend_from_slice(b"; ");
+                    cookie.extend_from_slice(cookies.as_bytes());
+
+                    HeaderValue::from_bytes(&cookie)
+                        .map_err(|_| upgrade::Error::Parsing(httparse::Error::HeaderValue))?
+                }
+                None => cookies,
+            };
+
+            headers.to_mut().insert(COOKIE, cookie);
+        }
+
+        if !self.extensions.is_empty() {
+            let offers: Vec<_> = self.extensions.iter().map(|e| e.offer()).collect();
+            let offers = HeaderValue::try_from(extensions::serialize(&offers))
+                .map_err(|_| upgrade::Error::Parsing(httparse::Error::HeaderValue))?;
 
-        Ok((
-            WebSocketStream::from_framed(framed, Role::Client, self.config, self.limits),
-            res,
-        ))
+            headers.to_mut().insert(SEC_WEBSOCKET_EXTENSIONS, offers);
+        }
+
+        let upgrade_codec = server_response::Codec::new(&key_base64, follow_redirects);
+        let request = build_request(uri, &key_base64, &headers);
+
+        let (framed, res) = with_timeout(self.upgrade_timeout, ConnectPhase::Upgrade, async {
+            stream.write_all(&request).await?;
+            // Buffered streams only send the request once flushed, in particular
+            // `rustls` streams that send it as early data
+            stream.flush().await?;
+
+            let mut framed =
+                FramedRead::with_capacity(stream, upgrade_codec, self.config.read_buffer_capacity);
+            let res = poll_fn(|cx| Pin::new(&mut framed).poll_next(cx))
+                .await
+                .ok_or(Error::Io(io::ErrorKind::UnexpectedEof.into()))??;
+
+            Ok::<_, Error>((framed, res))
+        })
+        .await??;
+
+        if let Some(cookie_store) = &self.cookie_store {
+            cookie_store.set_cookies(&mut res.headers().get_all(SET_COOKIE).iter(), uri);
+        }
+
+        if res.status() != StatusCode::SWITCHING_PROTOCOLS {
+            let location = res
+                .headers()
+                .get(LOCATION)
+                .ok_or(upgrade::Error::MissingHeader("Location"))?
+                .to_str()
+                .map_err(|_| upgrade::Error::Parsing(httparse::Error::HeaderValue))?;
+
+            return Err(Error::Upgrade(upgrade::Error::Redirected(redirect_uri(
+                uri, location,
+            )?)));
+        }
+
+        let codecs = self.accept_extensions(&res)?;
+
+        let mut stream =
+            WebSocketStream::from_framed(framed, Role::Client, self.config, self.limits);
+        stream.set_mask_generator(self.mask_generator.clone());
+
+        if !codecs.is_empty() {
+            stream.set_extensions(codecs);
+        }
+
+        Ok((stream, res))
+    }
+
+    /// Accepts the extensions in the server's handshake response, returning
+    /// their codecs.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if the `Sec-WebSocket-Extensions` header cannot be
+    /// parsed or contains extensions that were not offered or are not
+    /// acceptable.
+    fn accept_extensions(
+        &self,
+        response: &upgrade::Response,
+    ) -> Result<Vec<Box<dyn ExtensionCodec>>, upgrade::Error> {
+        let mut codecs = Vec::new();
+        let mut accepted = vec![false; self.extensions.len()];
+
+        for value in response.headers().get_all(SEC_WEBSOCKET_EXTENSIONS) {
+            let value = value
+                .to_str()
+                .map_err(|_| upgrade::Error::Parsing(httparse::Error::HeaderValue))?;
+
+            for response in extensions::parse(value).map_err(upgrade::Error::InvalidExtensions)? {
+                let unexpected = || upgrade::Error::UnexpectedExtension(response.name().to_owned());
+
+                let index = self
+                    .extensions
+                    .iter()
+                    .position(|extension| extension.offer().name() == response.name())
+                    .ok_or_else(unexpected)?;
+
+                if replace(&mut accepted[index], true) {
+                    return Err(unexpected());
+                }
+
+                codecs.push(
+                    self.extensions[index]
+                        .accept(&response)
+                        .ok_or_else(unexpected)?,
+                );
+            }
+        }
+
+        Ok(codecs)
     }
 
     /// Takes over an already established stream that has already performed a
@@ -315,12 +1461,170 @@ impl<'a, R: Resolver> Builder<'a, R> {
     /// handshake, it assumes the stream is ready to use for writing and
     /// reading the WebSocket protocol.
     pub fn take_over<S: AsyncRead + AsyncWrite + Unpin>(&self, stream: S) -> WebSocketStream<S> {
-        WebSocketStream::from_raw_stream(stream, Role::Client, self.config, self.limits)
+        let mut stream =
+            WebSocketStream::from_raw_stream(stream, Role::Client, self.config, self.limits);
+        stream.set_mask_generator(self.mask_generator.clone());
+
+        stream
+    }
+
+    /// Turns the builder into a [`tower_service::Service`] that connects to
+    /// the URI of each request, so that middleware such as retries, timeouts
+    /// or rate limits can be applied to establishing connections.
+    ///
+    /// The URI configured via [`Builder::uri`] is ignored.
+    #[cfg(feature = "tower")]
+    #[must_use]
+    pub fn into_service(self) -> ConnectService<'a, R> {
+        ConnectService {
+            builder: Arc::new(self),
+        }
     }
 }
 
-impl<'a> Default for Builder<'a> {
+impl Default for Builder<'_> {
     fn default() -> Self {
         Self::new()
     }
 }
+
+/// A [`tower_service::Service`] that establishes a WebSocket connection to the
+/// [`Uri`] of each request, created via [`Builder::into_service`].
+///
+/// Responses are the same as those of [`Builder::connect`]. Clones of the
+/// service share the configuration of the builder.
+#[cfg(feature = "tower")]
+pub struct ConnectService<'a, R: Resolver = resolver::Gai> {
+    /// The builder that connections are established with.
+    builder: Arc<Builder<'a, R>>,
+}
+
+#[cfg(feature = "tower")]
+impl<R: Resolver> Clone for ConnectService<'_, R> {
+    fn clone(&self) -> Self {
+        Self {
+            builder: Arc::clone(&self.builder),
+        }
+    }
+}
+
+#[cfg(feature = "tower")]
+impl<R: Resolver> fmt::Debug for ConnectService<'_, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectService").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "tower")]
+impl<'a, R: Resolver + Sync + 'a> tower_service::Service<Uri> for ConnectService<'a, R> {
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Error>> + Send + 'a>>;
+    type Response = (
+        WebSocketStream<MaybeTlsStream<TcpStream>>,
+        upgrade::Response,
+    );
+
+    fn poll_ready(&mut self, _cx: &mut std::task::Context<'_>) -> Poll<Result<(), Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let builder = Arc::clone(&self.builder);
+
+        Box::pin(async move { builder.connect_uri(uri, builder.connector).await })
+    }
+}
+
+/// A pool for opening many connections to the same endpoint, e.g. for load
+/// testing or aggregating many streams.
+///
+/// All connections of a pool share the configuration of the [`Builder`] it
+/// was created from and the results of resolving hostnames. Like all clients,
+/// they share a TLS connector and therefore its session cache, which allows
+/// resuming TLS sessions.
+///
+/// Connections are established via [`Pool::connect`], which may be called
+/// concurrently. At most the configured number of connections are being
+/// established at the same time, further calls wait until one of them is
+/// done. Established connections do not count towards this limit.
+///
+/// Resolved addresses are cached for the lifetime of the pool, so create a
+/// new pool to pick up DNS changes.
+pub struct Pool<'a, R: Resolver + Sync = resolver::Gai> {
+    /// The builder that connections are established with.
+    builder: Builder<'a, resolver::Cached<R>>,
+    /// The shared TLS connector, if the builder has none and needs one.
+    connector: Option<&'static Connector>,
+    /// Limits the number of connections being established concurrently.
+    semaphore: Semaphore,
+}
+
+impl<'a, R: Resolver + Sync> Pool<'a, R> {
+    /// Creates a pool that establishes connections with `builder`, with at
+    /// most `max_concurrency` connections being established at the same time.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if the builder connects to a `wss`
+    /// URI without a configured [`Connector`] and creating one fails.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `max_concurrency` is zero or larger than
+    /// [`Semaphore::MAX_PERMITS`].
+    pub fn new(builder: Builder<'a, R>, max_concurrency: usize) -> Result<Self, Error> {
+        assert!(max_concurrency > 0, "max_concurrency must not be zero");
+
+        let needs_connector = builder.connector.is_none()
+            && builder
+                .uri
+                .as_ref()
+                .is_some_and(|uri| uri.scheme_str() == Some("wss"));
+        let connector = if needs_connector {
+            Some(Connector::shared()?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            builder: builder
+                .map_resolver(|resolver| resolver::Cached::new(resolver, Duration::MAX)),
+            connector,
+            semaphore: Semaphore::new(max_concurrency),
+        })
+    }
+
+    /// Establishes a new connection to the WebSocket server. This requires a
+    /// URI to be configured via [`Builder::uri`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if connecting to the server fails or no
+    /// URI has been configured.
+    pub async fn connect(
+        &self,
+    ) -> Result<
+        (
+            WebSocketStream<MaybeTlsStream<TcpStream>>,
+            upgrade::Response,
+        ),
+        Error,
+    > {
+        let uri = self.builder.uri.clone().ok_or(Error::NoUriConfigured)?;
+        // The semaphore is never closed
+        let _permit = self.semaphore.acquire().await.ok();
+
+        self.builder
+            .connect_uri(uri, self.builder.connector.or(self.connector))
+            .await
+    }
+}
+
+impl<R: Resolver + Sync> fmt::Debug for Pool<'_, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pool")
+            .field("connector", &self.connector)
+            .field("available_permits", &self.semaphore.available_permits())
+            .finish_non_exhaustive()
+    }
+}
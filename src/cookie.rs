@@ -0,0 +1,18 @@
+//! Abstractions over cookie stores.
+
+use http::{HeaderValue, Uri};
+
+/// Trait for a cookie store that keeps cookies between client handshakes.
+///
+/// The client asks the store for the cookies to send with each handshake
+/// request and hands it the `Set-Cookie` headers of each successful handshake
+/// response.
+pub trait CookieStore: Send + Sync {
+    /// Store the cookies set via `Set-Cookie` headers in the response to a
+    /// handshake request to `uri`.
+    fn set_cookies(&self, cookies: &mut dyn Iterator<Item = &HeaderValue>, uri: &Uri);
+
+    /// Get the value of the `Cookie` header to send with a handshake request
+    /// to `uri`, if any.
+    fn cookies(&self, uri: &Uri) -> Option<HeaderValue>;
+}
@@ -0,0 +1,145 @@
+//! Compression backends of the permessage-deflate extension.
+//!
+//! The extension drives a [`Compressor`] and a [`Decompressor`] per connection,
+//! created by a [`DeflateBackend`]. [`Flate2`] is used by default, other
+//! implementations such as zlib-ng, libdeflate or hardware accelerators can be
+//! plugged in via [`PerMessageDeflate::backend`].
+//!
+//! [`PerMessageDeflate::backend`]: super::PerMessageDeflate::backend
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+
+use crate::Error;
+
+/// Factory for the compressors and decompressors of connections.
+pub trait DeflateBackend: Send + Sync {
+    /// Creates a compressor producing a raw deflate stream (without zlib
+    /// header) with the given compression level from 0 to 9 and LZ77 window
+    /// size from 9 to 15, as a base-2 logarithm.
+    fn compressor(&self, level: u32, window_bits: u8) -> Box<dyn Compressor>;
+
+    /// Creates a decompressor for a raw deflate stream (without zlib header)
+    /// with the given LZ77 window size from 9 to 15, as a base-2 logarithm.
+    fn decompressor(&self, window_bits: u8) -> Box<dyn Decompressor>;
+}
+
+/// Compressor of a raw deflate stream.
+pub trait Compressor: Send {
+    /// Compresses all of `input` followed by a sync flush and appends the
+    /// output to `output`.
+    ///
+    /// The output of a sync flush ends with an empty stored block, i.e. the
+    /// bytes `0x00 0x00 0xff 0xff`, once all input was compressed.
+    ///
+    /// # Errors
+    ///
+    /// Errors are returned when sending the message.
+    fn compress(&mut self, input: &[u8], output: &mut Vec<u8>) -> Result<(), Error>;
+
+    /// Resets the compressor to start a new deflate stream, discarding the
+    /// LZ77 window.
+    fn reset(&mut self);
+}
+
+/// Decompressor of a raw deflate stream.
+pub trait Decompressor: Send {
+    /// Decompresses all of `input` and appends the output to `output`.
+    ///
+    /// Returns whether the end of the deflate stream was reached, in which
+    /// case the remaining input is ignored and the decompressor is reset
+    /// before the next call.
+    ///
+    /// # Errors
+    ///
+    /// Errors are returned when receiving the message and fail the connection.
+    fn decompress(&mut self, input: &[u8], output: &mut Vec<u8>) -> Result<bool, Error>;
+
+    /// Resets the decompressor to start a new deflate stream, discarding the
+    /// LZ77 window.
+    fn reset(&mut self);
+}
+
+/// Backend using [`flate2`] with the zlib-rs implementation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Flate2;
+
+impl DeflateBackend for Flate2 {
+    fn compressor(&self, level: u32, window_bits: u8) -> Box<dyn Compressor> {
+        Box::new(Compress::new_with_window_bits(
+            Compression::new(level),
+            false,
+            window_bits,
+        ))
+    }
+
+    fn decompressor(&self, window_bits: u8) -> Box<dyn Decompressor> {
+        Box::new(Decompress::new_with_window_bits(false, window_bits))
+    }
+}
+
+/// Reserves additional capacity in `output` if it is full.
+fn reserve_if_full(output: &mut Vec<u8>) {
+    if output.len() == output.capacity() {
+        output.reserve(output.capacity().max(64));
+    }
+}
+
+impl Compressor for Compress {
+    #[allow(clippy::cast_possible_truncation)]
+    fn compress(&mut self, mut input: &[u8], output: &mut Vec<u8>) -> Result<(), Error> {
+        loop {
+            reserve_if_full(output);
+
+            let total_in = self.total_in();
+            self.compress_vec(input, output, FlushCompress::Sync)
+                .map_err(|e| Error::Extension(Box::new(e)))?;
+            input = &input[(self.total_in() - total_in) as usize..];
+
+            // The flush is complete once there is space left in the output
+            if input.is_empty() && output.len() < output.capacity() {
+                return Ok(());
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        Compress::reset(self);
+    }
+}
+
+impl Decompressor for Decompress {
+    #[allow(clippy::cast_possible_truncation)]
+    fn decompress(&mut self, mut input: &[u8], output: &mut Vec<u8>) -> Result<bool, Error> {
+        loop {
+            reserve_if_full(output);
+
+            let total_in = self.total_in();
+            let total_out = self.total_out();
+            let status = self
+                .decompress_vec(input, output, FlushDecompress::Sync)
+                .map_err(|e| Error::Extension(Box::new(e)))?;
+            input = &input[(self.total_in() - total_in) as usize..];
+
+            match status {
+                Status::StreamEnd => {
+                    Decompress::reset(self, false);
+
+                    return Ok(true);
+                }
+                _ if input.is_empty() && output.len() < output.capacity() => return Ok(false),
+                // No progress is possible without more output space
+                Status::BufError
+                    if total_in == self.total_in()
+                        && total_out == self.total_out()
+                        && output.len() < output.capacity() =>
+                {
+                    return Ok(false)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        Decompress::reset(self, false);
+    }
+}
@@ -0,0 +1,187 @@
+//! Implementation of the legacy deflate-frame extension, which compresses
+//! individual frames instead of whole messages. It predates RFC 7692 and is
+//! still used by older Safari versions and embedded clients under the name
+//! `x-webkit-deflate-frame`.
+use std::sync::Arc;
+
+use flate2::Compression;
+
+use super::{
+    parse_window_bits, DeflateBackend, DeflateCodec, Settings, MAX_WINDOW_BITS, MIN_WINDOW_BITS,
+};
+#[cfg(feature = "client")]
+use crate::upgrade::extensions::ClientExtension;
+#[cfg(feature = "server")]
+use crate::upgrade::extensions::ServerExtension;
+use crate::{proto::ExtensionCodec, upgrade::extensions::Extension};
+
+/// Names the extension is offered under.
+#[cfg(feature = "server")]
+const NAMES: [&str; 2] = ["deflate-frame", "x-webkit-deflate-frame"];
+
+/// Name of the extension offered by clients.
+#[cfg(feature = "client")]
+const OFFERED_NAME: &str = "x-webkit-deflate-frame";
+
+/// The legacy deflate-frame extension, compressing the payloads of individual
+/// data frames.
+///
+/// Prefer [`PerMessageDeflate`], which all current browsers support. If both
+/// are registered on a server, the one registered first that was offered by
+/// the client is used.
+///
+/// [`PerMessageDeflate`]: super::PerMessageDeflate
+#[derive(Debug, Clone)]
+pub struct DeflateFrame {
+    /// Settings of the compressor.
+    settings: Settings,
+    /// Whether the compressor is reset after each frame.
+    no_context_takeover: bool,
+}
+
+impl Default for DeflateFrame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeflateFrame {
+    /// Creates the extension with the default configuration.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            settings: Settings::default(),
+            no_context_takeover: false,
+        }
+    }
+
+    /// Sets the backend that creates the compressors and decompressors of
+    /// connections.
+    ///
+    /// The default is [`Flate2`](super::Flate2).
+    #[must_use]
+    pub fn backend<B: DeflateBackend + 'static>(mut self, backend: B) -> Self {
+        self.settings.backend = Arc::new(backend);
+
+        self
+    }
+
+    /// Sets the compression level used for outgoing frames, from 0 (no
+    /// compression) to 9 (best compression). Larger values are clamped.
+    ///
+    /// The default is 6.
+    #[must_use]
+    pub fn compression_level(mut self, level: u32) -> Self {
+        self.settings.level = level.min(Compression::best().level());
+
+        self
+    }
+
+    /// Sets the size in bytes below which outgoing frames are sent
+    /// uncompressed.
+    ///
+    /// The default is 0, which compresses all frames.
+    #[must_use]
+    pub fn compression_threshold(mut self, threshold: usize) -> Self {
+        self.settings.threshold = threshold;
+
+        self
+    }
+
+    /// Sets the maximum size in bytes that an incoming frame may inflate to,
+    /// or [`None`] for no limit. Connections receiving larger frames are
+    /// failed with [`Error::PayloadTooLong`] and closed with
+    /// [`CloseCode::MESSAGE_TOO_BIG`].
+    ///
+    /// The default is 64 MiB.
+    ///
+    /// [`Error::PayloadTooLong`]: crate::Error::PayloadTooLong
+    /// [`CloseCode::MESSAGE_TOO_BIG`]: crate::CloseCode::MESSAGE_TOO_BIG
+    #[must_use]
+    pub fn max_inflated_size(mut self, size: Option<usize>) -> Self {
+        self.settings.max_inflated_size = size;
+
+        self
+    }
+
+    /// Sets whether outgoing frames are compressed without referring to
+    /// previous frames, which saves memory between frames at the cost of
+    /// compression ratio. The peer may request this as well.
+    ///
+    /// The default is `false`.
+    #[must_use]
+    pub fn no_context_takeover(mut self, no_context_takeover: bool) -> Self {
+        self.no_context_takeover = no_context_takeover;
+
+        self
+    }
+
+    /// Sets whether the compressor is released after each frame if context
+    /// takeover is disabled, instead of being reset and kept for the next
+    /// frame. This makes idle connections cost less memory at the cost of
+    /// allocating a compressor for every frame.
+    ///
+    /// The default is `false`.
+    #[must_use]
+    pub fn release_contexts(mut self, release_contexts: bool) -> Self {
+        self.settings.release_contexts = release_contexts;
+
+        self
+    }
+
+    /// Creates the codec for the parameters sent by the peer, which apply to
+    /// our compressor, or returns [`None`] if they cannot be satisfied.
+    fn codec(&self, params: &Extension) -> Option<Box<dyn ExtensionCodec>> {
+        let mut max_window_bits = None;
+        let mut no_context_takeover = false;
+
+        for (name, value) in params.params() {
+            match (name, value) {
+                ("max_window_bits", Some(value)) if max_window_bits.is_none() => {
+                    max_window_bits = Some(parse_window_bits(value)?);
+                }
+                ("no_context_takeover", None) if !no_context_takeover => {
+                    no_context_takeover = true;
+                }
+                _ => return None,
+            }
+        }
+
+        let window_bits = max_window_bits.unwrap_or(MAX_WINDOW_BITS);
+
+        if window_bits < MIN_WINDOW_BITS {
+            return None;
+        }
+
+        let codec = DeflateCodec::new(
+            &self.settings,
+            window_bits,
+            self.no_context_takeover || no_context_takeover,
+            false,
+            true,
+        );
+
+        Some(Box::new(codec))
+    }
+}
+
+#[cfg(feature = "server")]
+impl ServerExtension for DeflateFrame {
+    fn negotiate(&self, offers: &[Extension]) -> Option<(Extension, Box<dyn ExtensionCodec>)> {
+        offers
+            .iter()
+            .filter(|offer| NAMES.contains(&offer.name()))
+            .find_map(|offer| Some((Extension::new(offer.name()), self.codec(offer)?)))
+    }
+}
+
+#[cfg(feature = "client")]
+impl ClientExtension for DeflateFrame {
+    fn offer(&self) -> Extension {
+        Extension::new(OFFERED_NAME)
+    }
+
+    fn accept(&self, response: &Extension) -> Option<Box<dyn ExtensionCodec>> {
+        self.codec(response)
+    }
+}
@@ -0,0 +1,665 @@
+//! Implementation of the permessage-deflate extension as specified in
+//! [RFC 7692].
+//!
+//! Register [`PerMessageDeflate`] via [`ClientBuilder::extension`] or
+//! [`ServerBuilder::extension`] to compress messages with peers that support
+//! it.
+//!
+//! [`DeflateFrame`] implements the legacy `x-webkit-deflate-frame` extension
+//! for peers that do not support permessage-deflate.
+//!
+//! [RFC 7692]: https://datatracker.ietf.org/doc/html/rfc7692
+//! [`ClientBuilder::extension`]: crate::ClientBuilder::extension
+//! [`ServerBuilder::extension`]: crate::ServerBuilder::extension
+use std::{fmt, sync::Arc};
+
+use flate2::Compression;
+
+pub use self::{
+    backend::{Compressor, Decompressor, DeflateBackend, Flate2},
+    frame::DeflateFrame,
+};
+#[cfg(feature = "client")]
+use crate::upgrade::extensions::ClientExtension;
+#[cfg(feature = "server")]
+use crate::upgrade::extensions::ServerExtension;
+use crate::{
+    proto::{CompressionStats, ExtensionCodec, RSV1},
+    upgrade::extensions::Extension,
+    Error, Payload,
+};
+
+mod backend;
+mod frame;
+
+/// Name of the extension.
+const NAME: &str = "permessage-deflate";
+
+/// Trailer of a block compressed with a sync flush, which is removed from
+/// compressed payloads.
+const TRAILER: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// Smallest LZ77 window size supported for compression, as a base-2
+/// logarithm. zlib does not support a window size of 2^8 for raw deflate
+/// streams.
+const MIN_WINDOW_BITS: u8 = 9;
+
+/// Largest LZ77 window size, as a base-2 logarithm.
+const MAX_WINDOW_BITS: u8 = 15;
+
+/// Default maximum size of an inflated payload, matching the default payload
+/// length limit.
+const DEFAULT_MAX_INFLATED_SIZE: usize = 64 * 1024 * 1024;
+
+/// Number of compressed bytes inflated at once while a maximum inflated size
+/// is enforced. Deflate expands data by a factor of at most about 1032, so
+/// this bounds how far the limit can be exceeded before inflating is aborted.
+const INFLATE_CHUNK_SIZE: usize = 1024;
+
+/// The permessage-deflate extension, compressing the payloads of data
+/// messages.
+///
+/// The defaults offer and accept the extension with the largest window size
+/// and context takeover on both ends, which yields the best compression ratio
+/// at the cost of up to 32 KiB of memory per direction and connection for the
+/// LZ77 window, plus the memory used by zlib.
+#[derive(Debug, Clone)]
+pub struct PerMessageDeflate {
+    /// Settings of the compressor.
+    settings: Settings,
+    /// Maximum LZ77 window size of the client, as a base-2 logarithm.
+    client_max_window_bits: u8,
+    /// Maximum LZ77 window size of the server, as a base-2 logarithm.
+    server_max_window_bits: u8,
+    /// Whether the client resets its compression context after each message.
+    client_no_context_takeover: bool,
+    /// Whether the server resets its compression context after each message.
+    server_no_context_takeover: bool,
+}
+
+/// Settings of the compressor shared by the deflate extensions.
+#[derive(Clone)]
+struct Settings {
+    /// Backend creating compressors and decompressors.
+    backend: Arc<dyn DeflateBackend>,
+    /// Compression level from 0 to 9.
+    level: u32,
+    /// Size in bytes below which payloads are sent uncompressed.
+    threshold: usize,
+    /// Maximum size in bytes of an incoming payload once inflated.
+    max_inflated_size: Option<usize>,
+    /// Whether contexts that are reset after each message are dropped
+    /// instead.
+    release_contexts: bool,
+}
+
+impl fmt::Debug for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Settings")
+            .field("level", &self.level)
+            .field("threshold", &self.threshold)
+            .field("max_inflated_size", &self.max_inflated_size)
+            .field("release_contexts", &self.release_contexts)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            backend: Arc::new(Flate2),
+            level: Compression::default().level(),
+            threshold: 0,
+            max_inflated_size: Some(DEFAULT_MAX_INFLATED_SIZE),
+            release_contexts: false,
+        }
+    }
+}
+
+impl Default for PerMessageDeflate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PerMessageDeflate {
+    /// Creates the extension with the default configuration.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            settings: Settings::default(),
+            client_max_window_bits: MAX_WINDOW_BITS,
+            server_max_window_bits: MAX_WINDOW_BITS,
+            client_no_context_takeover: false,
+            server_no_context_takeover: false,
+        }
+    }
+
+    /// Sets the backend that creates the compressors and decompressors of
+    /// connections.
+    ///
+    /// The default is [`Flate2`].
+    #[must_use]
+    pub fn backend<B: DeflateBackend + 'static>(mut self, backend: B) -> Self {
+        self.settings.backend = Arc::new(backend);
+
+        self
+    }
+
+    /// Sets the compression level used for outgoing messages, from 0 (no
+    /// compression) to 9 (best compression). Larger values are clamped.
+    ///
+    /// The default is 6.
+    #[must_use]
+    pub fn compression_level(mut self, level: u32) -> Self {
+        self.settings.level = level.min(Compression::best().level());
+
+        self
+    }
+
+    /// Sets the size in bytes below which outgoing messages are sent
+    /// uncompressed.
+    ///
+    /// Compressing tiny payloads costs CPU time and often makes them larger due
+    /// to the overhead of the deflate format. The default is 0, which
+    /// compresses all messages.
+    #[must_use]
+    pub fn compression_threshold(mut self, threshold: usize) -> Self {
+        self.settings.threshold = threshold;
+
+        self
+    }
+
+    /// Sets the maximum size in bytes that an incoming message may inflate
+    /// to, or [`None`] for no limit.
+    ///
+    /// This guards against decompression bombs, tiny messages that inflate to
+    /// gigabytes, independently of the payload length limit, which only
+    /// applies to the compressed size. Connections receiving larger messages
+    /// are failed with [`Error::PayloadTooLong`] and closed with
+    /// [`CloseCode::MESSAGE_TOO_BIG`]. The default is 64 MiB.
+    ///
+    /// [`CloseCode::MESSAGE_TOO_BIG`]: crate::CloseCode::MESSAGE_TOO_BIG
+    #[must_use]
+    pub fn max_inflated_size(mut self, size: Option<usize>) -> Self {
+        self.settings.max_inflated_size = size;
+
+        self
+    }
+
+    /// Sets whether compression contexts are released after each message if
+    /// context takeover is disabled for their direction, instead of being
+    /// reset and kept for the next message.
+    ///
+    /// Contexts are only allocated once the first message is compressed or
+    /// decompressed in either direction. Releasing them as well makes idle
+    /// connections cost no memory for compression, which matters for servers
+    /// with very many mostly idle connections, at the cost of allocating a
+    /// context for every message. Combine this with
+    /// [`client_no_context_takeover`] and [`server_no_context_takeover`]. The
+    /// default is `false`.
+    ///
+    /// [`client_no_context_takeover`]: Self::client_no_context_takeover
+    /// [`server_no_context_takeover`]: Self::server_no_context_takeover
+    #[must_use]
+    pub fn release_contexts(mut self, release_contexts: bool) -> Self {
+        self.settings.release_contexts = release_contexts;
+
+        self
+    }
+
+    /// Sets the maximum LZ77 window size the client compresses with, as a
+    /// base-2 logarithm from 9 to 15. Values outside of the range are clamped.
+    ///
+    /// Clients offer this limit to the server, servers request it from
+    /// clients that indicated support for it. Smaller windows use less memory
+    /// but compress worse. The default is 15.
+    #[must_use]
+    pub fn client_max_window_bits(mut self, bits: u8) -> Self {
+        self.client_max_window_bits = bits.clamp(MIN_WINDOW_BITS, MAX_WINDOW_BITS);
+
+        self
+    }
+
+    /// Sets the maximum LZ77 window size the server compresses with, as a
+    /// base-2 logarithm from 9 to 15. Values outside of the range are clamped.
+    ///
+    /// Clients request this limit from the server, servers announce it to the
+    /// client. Smaller windows use less memory but compress worse. The default
+    /// is 15.
+    #[must_use]
+    pub fn server_max_window_bits(mut self, bits: u8) -> Self {
+        self.server_max_window_bits = bits.clamp(MIN_WINDOW_BITS, MAX_WINDOW_BITS);
+
+        self
+    }
+
+    /// Sets whether the client resets its compression context after each
+    /// message instead of referring to previous messages.
+    ///
+    /// Clients apply this themselves and announce it to the server, servers
+    /// request it from clients. This saves memory between messages at the
+    /// cost of compression ratio. The default is `false`.
+    #[must_use]
+    pub fn client_no_context_takeover(mut self, no_context_takeover: bool) -> Self {
+        self.client_no_context_takeover = no_context_takeover;
+
+        self
+    }
+
+    /// Sets whether the server resets its compression context after each
+    /// message instead of referring to previous messages.
+    ///
+    /// Clients request this from the server, servers apply this themselves and
+    /// announce it to the client. This saves memory between messages at the
+    /// cost of compression ratio. The default is `false`.
+    #[must_use]
+    pub fn server_no_context_takeover(mut self, no_context_takeover: bool) -> Self {
+        self.server_no_context_takeover = no_context_takeover;
+
+        self
+    }
+}
+
+/// Parameters of a permessage-deflate offer or response.
+#[derive(Debug, Default)]
+struct Params {
+    /// Whether `client_max_window_bits` is present.
+    client_max_window_bits_present: bool,
+    /// Value of `client_max_window_bits`, if present with a value.
+    client_max_window_bits: Option<u8>,
+    /// Value of `server_max_window_bits`, if present.
+    server_max_window_bits: Option<u8>,
+    /// Whether `client_no_context_takeover` is present.
+    client_no_context_takeover: bool,
+    /// Whether `server_no_context_takeover` is present.
+    server_no_context_takeover: bool,
+}
+
+impl Params {
+    /// Parses the parameters of an offer or response, returning [`None`] if
+    /// they contain unknown, duplicate or invalid parameters.
+    fn parse(extension: &Extension) -> Option<Self> {
+        let mut params = Self::default();
+
+        for (name, value) in extension.params() {
+            match (name, value) {
+                ("client_max_window_bits", value) if !params.client_max_window_bits_present => {
+                    params.client_max_window_bits_present = true;
+                    params.client_max_window_bits = match value {
+                        Some(value) => Some(parse_window_bits(value)?),
+                        None => None,
+                    };
+                }
+                ("server_max_window_bits", Some(value))
+                    if params.server_max_window_bits.is_none() =>
+                {
+                    params.server_max_window_bits = Some(parse_window_bits(value)?);
+                }
+                ("client_no_context_takeover", None) if !params.client_no_context_takeover => {
+                    params.client_no_context_takeover = true;
+                }
+                ("server_no_context_takeover", None) if !params.server_no_context_takeover => {
+                    params.server_no_context_takeover = true;
+                }
+                _ => return None,
+            }
+        }
+
+        Some(params)
+    }
+}
+
+/// Parses a window size parameter value, which must be a decimal integer from
+/// 8 to 15 without leading zeroes.
+fn parse_window_bits(value: &str) -> Option<u8> {
+    if value.starts_with('0') || !value.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+
+    value
+        .parse()
+        .ok()
+        .filter(|bits| (8..=MAX_WINDOW_BITS).contains(bits))
+}
+
+#[cfg(feature = "server")]
+impl PerMessageDeflate {
+    /// Accepts an offer of the extension, returning the response to it and
+    /// the codec for the connection, or [`None`] if it cannot be accepted.
+    fn accept_offer(&self, offer: &Extension) -> Option<(Extension, Box<dyn ExtensionCodec>)> {
+        let params = Params::parse(offer)?;
+        let mut response = Extension::new(NAME);
+
+        let server_no_context_takeover =
+            self.server_no_context_takeover || params.server_no_context_takeover;
+
+        if server_no_context_takeover {
+            response = response.param("server_no_context_takeover", None::<String>);
+        }
+
+        if self.client_no_context_takeover {
+            response = response.param("client_no_context_takeover", None::<String>);
+        }
+
+        let server_max_window_bits = params
+            .server_max_window_bits
+            .map_or(self.server_max_window_bits, |bits| {
+                bits.min(self.server_max_window_bits)
+            });
+
+        if server_max_window_bits < MIN_WINDOW_BITS {
+            return None;
+        }
+
+        if server_max_window_bits < MAX_WINDOW_BITS || params.server_max_window_bits.is_some() {
+            response = response.param(
+                "server_max_window_bits",
+                Some(server_max_window_bits.to_string()),
+            );
+        }
+
+        // The client's window size may only be limited if it indicated support for it
+        if params.client_max_window_bits_present {
+            let client_max_window_bits = params
+                .client_max_window_bits
+                .map_or(self.client_max_window_bits, |bits| {
+                    bits.min(self.client_max_window_bits)
+                });
+
+            if client_max_window_bits < MAX_WINDOW_BITS || params.client_max_window_bits.is_some() {
+                response = response.param(
+                    "client_max_window_bits",
+                    Some(client_max_window_bits.to_string()),
+                );
+            }
+        }
+
+        // A client that offers client_no_context_takeover resets its context after
+        // every message even if it is not part of the response, so the context
+        // for decompressing its messages is never needed again
+        let codec = DeflateCodec::new(
+            &self.settings,
+            server_max_window_bits,
+            server_no_context_takeover,
+            self.client_no_context_takeover || params.client_no_context_takeover,
+            false,
+        );
+
+        Some((response, Box::new(codec)))
+    }
+}
+
+#[cfg(feature = "server")]
+impl ServerExtension for PerMessageDeflate {
+    fn negotiate(&self, offers: &[Extension]) -> Option<(Extension, Box<dyn ExtensionCodec>)> {
+        offers
+            .iter()
+            .filter(|offer| offer.name() == NAME)
+            .find_map(|offer| self.accept_offer(offer))
+    }
+}
+
+#[cfg(feature = "client")]
+impl ClientExtension for PerMessageDeflate {
+    fn offer(&self) -> Extension {
+        let mut offer = Extension::new(NAME);
+
+        if self.client_no_context_takeover {
+            offer = offer.param("client_no_context_takeover", None::<String>);
+        }
+
+        if self.server_no_context_takeover {
+            offer = offer.param("server_no_context_takeover", None::<String>);
+        }
+
+        if self.server_max_window_bits < MAX_WINDOW_BITS {
+            offer = offer.param(
+                "server_max_window_bits",
+                Some(self.server_max_window_bits.to_string()),
+            );
+        }
+
+        // Always indicate support for limiting the client's window size
+        offer.param(
+            "client_max_window_bits",
+            (self.client_max_window_bits < MAX_WINDOW_BITS)
+                .then(|| self.client_max_window_bits.to_string()),
+        )
+    }
+
+    fn accept(&self, response: &Extension) -> Option<Box<dyn ExtensionCodec>> {
+        let params = Params::parse(response)?;
+
+        // The server must not use a larger window than requested
+        if params
+            .server_max_window_bits
+            .is_some_and(|bits| bits > self.server_max_window_bits)
+        {
+            return None;
+        }
+
+        let client_max_window_bits = match (
+            params.client_max_window_bits_present,
+            params.client_max_window_bits,
+        ) {
+            (false, _) => self.client_max_window_bits,
+            (true, Some(bits)) if bits >= MIN_WINDOW_BITS => bits.min(self.client_max_window_bits),
+            (true, _) => return None,
+        };
+
+        let codec = DeflateCodec::new(
+            &self.settings,
+            client_max_window_bits,
+            self.client_no_context_takeover || params.client_no_context_takeover,
+            params.server_no_context_takeover,
+            false,
+        );
+
+        Some(Box::new(codec))
+    }
+}
+
+/// What happens to a compression context after each message.
+#[derive(Debug, Clone, Copy)]
+enum Takeover {
+    /// The context is kept for the next message.
+    Keep,
+    /// The context is reset, discarding the LZ77 window.
+    Reset,
+    /// The context is dropped and created again for the next message.
+    Release,
+}
+
+/// Per-connection state of the permessage-deflate extension.
+struct DeflateCodec {
+    /// Backend creating the compressor and decompressor.
+    backend: Arc<dyn DeflateBackend>,
+    /// Compression level from 0 to 9.
+    level: u32,
+    /// LZ77 window size of the compressor, as a base-2 logarithm.
+    window_bits: u8,
+    /// Compressor for outgoing messages, created on first use.
+    compressor: Option<Box<dyn Compressor>>,
+    /// Decompressor for incoming messages, created on first use.
+    decompressor: Option<Box<dyn Decompressor>>,
+    /// What happens to the compressor after each message.
+    takeover: Takeover,
+    /// Whether the decompressor is dropped after each message as the peer
+    /// resets its compressor.
+    release_decompressor: bool,
+    /// Size in bytes below which payloads are sent uncompressed.
+    threshold: usize,
+    /// Maximum size in bytes of an incoming payload once inflated.
+    max_inflated_size: Option<usize>,
+    /// Whether individual frames are compressed instead of whole messages.
+    per_frame: bool,
+    /// Byte counts of the payloads compressed and decompressed.
+    stats: CompressionStats,
+}
+
+impl DeflateCodec {
+    /// Creates a codec compressing with the configured level and the given
+    /// window size, the decompressor always supports the largest window size.
+    fn new(
+        settings: &Settings,
+        window_bits: u8,
+        no_context_takeover: bool,
+        peer_no_context_takeover: bool,
+        per_frame: bool,
+    ) -> Self {
+        Self {
+            backend: settings.backend.clone(),
+            level: settings.level,
+            window_bits,
+            compressor: None,
+            decompressor: None,
+            takeover: match (no_context_takeover, settings.release_contexts) {
+                (false, _) => Takeover::Keep,
+                (true, false) => Takeover::Reset,
+                (true, true) => Takeover::Release,
+            },
+            release_decompressor: peer_no_context_takeover && settings.release_contexts,
+            threshold: settings.threshold,
+            max_inflated_size: settings.max_inflated_size,
+            per_frame,
+            stats: CompressionStats::new(),
+        }
+    }
+
+    /// Inflates `input` and appends the output to `output`, failing once it
+    /// exceeds the maximum inflated size. Returns whether the end of the
+    /// deflate stream was reached.
+    fn inflate(&mut self, input: &[u8], output: &mut Vec<u8>) -> Result<bool, Error> {
+        let decompressor = self
+            .decompressor
+            .get_or_insert_with(|| self.backend.decompressor(MAX_WINDOW_BITS));
+
+        let Some(max_len) = self.max_inflated_size else {
+            return decompressor.decompress(input, output);
+        };
+
+        for chunk in input.chunks(INFLATE_CHUNK_SIZE) {
+            let end = decompressor.decompress(chunk, output)?;
+
+            if output.len() > max_len {
+                return Err(Error::PayloadTooLong {
+                    len: output.len(),
+                    max_len,
+                });
+            }
+
+            if end {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+impl ExtensionCodec for DeflateCodec {
+    fn rsv_bits(&self) -> u8 {
+        RSV1
+    }
+
+    fn encode(&mut self, payload: Payload) -> Result<(Payload, u8), Error> {
+        // Small messages are sent as is, with RSV1 unset
+        if payload.len() < self.threshold {
+            self.stats.record_sent(payload.len(), payload.len());
+
+            return Ok((payload, 0));
+        }
+
+        let mut output = Vec::with_capacity(payload.len() / 2 + 64);
+        let compressor = self
+            .compressor
+            .get_or_insert_with(|| self.backend.compressor(self.level, self.window_bits));
+        compressor.compress(&payload, &mut output)?;
+
+        if output.ends_with(&TRAILER) {
+            output.truncate(output.len() - TRAILER.len());
+        } else if output.is_empty() {
+            // zlib emits nothing when flushing without new input, but empty messages
+            // must be sent as an empty uncompressed block (RFC 7692, Section 7.2.3.6)
+            output.push(0x00);
+        }
+
+        match self.takeover {
+            Takeover::Keep => {}
+            Takeover::Reset => compressor.reset(),
+            Takeover::Release => self.compressor = None,
+        }
+
+        self.stats.record_sent(payload.len(), output.len());
+
+        Ok((Payload::from(output), RSV1))
+    }
+
+    fn decode(&mut self, payload: Payload, rsv: u8) -> Result<Payload, Error> {
+        // Some implementations send empty messages without any compressed data
+        if rsv & RSV1 == 0 || payload.is_empty() {
+            self.stats.record_received(payload.len(), payload.len());
+
+            return Ok(payload);
+        }
+
+        let mut output = Vec::with_capacity(payload.len().saturating_mul(2).max(64));
+
+        // The peer may finish the deflate stream, a new one starts with the next
+        // message
+        if !self.inflate(&payload, &mut output)? {
+            self.inflate(&TRAILER, &mut output)?;
+        }
+
+        if self.release_decompressor {
+            self.decompressor = None;
+        }
+
+        self.stats.record_received(payload.len(), output.len());
+
+        Ok(Payload::from(output))
+    }
+
+    fn per_frame(&self) -> bool {
+        self.per_frame
+    }
+
+    fn compression_stats(&self) -> Option<CompressionStats> {
+        Some(self.stats)
+    }
+}
+
+#[cfg(all(test, feature = "client", feature = "server"))]
+#[test]
+fn test_negotiation() {
+    let client = PerMessageDeflate::new()
+        .client_no_context_takeover(true)
+        .server_max_window_bits(10);
+    let server = PerMessageDeflate::new().client_max_window_bits(12);
+
+    let offer = client.offer();
+    assert_eq!(
+        offer.to_string(),
+        "permessage-deflate; client_no_context_takeover; server_max_window_bits=10; \
+         client_max_window_bits"
+    );
+
+    let (response, _) = server.negotiate(&[offer]).unwrap();
+    assert_eq!(
+        response.to_string(),
+        "permessage-deflate; server_max_window_bits=10; client_max_window_bits=12"
+    );
+    assert!(client.accept(&response).is_some());
+
+    // Unknown parameters and window sizes larger than requested are rejected
+    assert!(server
+        .negotiate(&[Extension::new(NAME).param("foo", None::<String>)])
+        .is_none());
+    assert!(client
+        .accept(&Extension::new(NAME).param("server_max_window_bits", Some("11")))
+        .is_none());
+    assert!(client
+        .accept(&Extension::new(NAME).param("client_max_window_bits", Some("08")))
+        .is_none());
+}
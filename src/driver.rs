@@ -0,0 +1,407 @@
+//! Actor-style API that runs a [`WebSocketStream`] as a background task.
+//!
+//! Instead of polling the stream yourself, [`Builder::spawn`] moves it into a
+//! task that sends and receives messages on your behalf and performs the close
+//! handshake. Since the task polls the stream continuously, pings configured
+//! via [`Config::keepalive_interval`] are sent by it as well. Messages
+//! are sent via cloneable [`Sender`]s and received via a [`Receiver`]:
+//!
+//! ```
+//! # #[cfg(feature = "server")]
+//! # async fn example(stream: tokio::net::TcpStream) -> Result<(), tokio_websockets::Error> {
+//! use std::time::Duration;
+//!
+//! use tokio_websockets::{driver, Config, ServerBuilder};
+//!
+//! let config = Config::default().keepalive_interval(Some(Duration::from_secs(30)));
+//! let stream = ServerBuilder::new().config(config).accept(stream).await?;
+//! let (sender, mut receiver) = driver::Builder::new().spawn(stream);
+//!
+//! while let Some(message) = receiver.recv().await {
+//!     sender.send(message?).await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Config::keepalive_interval`]: crate::Config::keepalive_interval
+use std::{
+    fmt,
+    future::{poll_fn, Future},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures_core::{ready, Stream};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::{mpsc, Semaphore},
+};
+
+use crate::{
+    slow_consumer::{self, Hook, Policy, QueueReceiver, QueueSender},
+    CloseCode, Error, Message, WebSocketStream,
+};
+
+/// Message or error received by the task, along with the number of permits of
+/// the buffered bytes budget it holds.
+type Incoming = (Result<Message, Error>, u32);
+
+/// Budget of buffered bytes shared by the task and the [`Receiver`].
+#[derive(Debug)]
+struct Budget {
+    /// Permits for the bytes that may still be buffered.
+    permits: Semaphore,
+    /// Total number of permits.
+    max: u32,
+}
+
+/// Builder for the background task driving a [`WebSocketStream`].
+#[derive(Clone)]
+pub struct Builder {
+    /// Capacity of the channels for outgoing and incoming messages.
+    channel_capacity: usize,
+    /// Maximum total payload size of received messages that are buffered
+    /// until the [`Receiver`] reads them.
+    max_buffered_bytes: Option<usize>,
+    /// What to do with outgoing messages while the send queue is full.
+    slow_consumer_policy: Policy,
+    /// Hook called when the send queue is full.
+    on_slow_consumer: Option<Hook>,
+}
+
+impl Builder {
+    /// Creates a [`Builder`] with all defaults: channels with a capacity of 32
+    /// messages.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            channel_capacity: 32,
+            max_buffered_bytes: None,
+            slow_consumer_policy: Policy::Block,
+            on_slow_consumer: None,
+        }
+    }
+
+    /// Sets the number of messages that can be buffered in each direction
+    /// before sending waits for the task and the task waits for the
+    /// [`Receiver`], respectively. The default is 32.
+    ///
+    /// While the [`Receiver`] is full, the task stops reading from the
+    /// connection, which applies backpressure to the peer.
+    ///
+    /// # Panics
+    ///
+    /// If `capacity` is `0`.
+    #[must_use]
+    pub fn channel_capacity(mut self, capacity: usize) -> Self {
+        assert_ne!(capacity, 0, "capacity must be non-zero");
+        self.channel_capacity = capacity;
+
+        self
+    }
+
+    /// Sets the maximum total payload size in bytes of received messages that
+    /// are buffered until the [`Receiver`] reads them. `None` only limits
+    /// their number via [`Builder::channel_capacity`]. The default is `None`.
+    ///
+    /// Once the limit is reached, the task stops reading from the connection
+    /// like when the [`Receiver`] is full, which applies backpressure to the
+    /// peer. Messages larger than the limit are buffered once no other
+    /// messages are. Limits above 4 GiB, or above 512 MiB on 32-bit targets,
+    /// are capped.
+    #[must_use]
+    pub fn max_buffered_bytes(mut self, max_bytes: Option<usize>) -> Self {
+        self.max_buffered_bytes = max_bytes;
+
+        self
+    }
+
+    /// Sets what happens to messages sent via a [`Sender`] while the send
+    /// queue is full because the peer does not keep up. The default is
+    /// [`Policy::Block`], which makes [`Sender::send`] wait.
+    ///
+    /// With [`Policy::Disconnect`], the queued messages are discarded and the
+    /// connection is closed with the close code.
+    #[must_use]
+    pub fn slow_consumer_policy(mut self, policy: Policy) -> Self {
+        self.slow_consumer_policy = policy;
+
+        self
+    }
+
+    /// Sets a hook that is called with an [`Event`] whenever the send queue
+    /// is full, e.g. to record metrics.
+    ///
+    /// [`Event`]: slow_consumer::Event
+    #[must_use]
+    pub fn on_slow_consumer<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(slow_consumer::Event) + Send + Sync + 'static,
+    {
+        self.on_slow_consumer = Some(Arc::new(hook));
+
+        self
+    }
+
+    /// Spawns a task on the current tokio runtime that drives `stream` and
+    /// returns the handles to communicate with it.
+    ///
+    /// The task ends once the connection is closed. Once all [`Sender`]s are
+    /// dropped, it closes the connection with [`CloseCode::NORMAL_CLOSURE`]
+    /// and keeps forwarding the messages received until the peer acknowledges
+    /// the close. Messages received after the [`Receiver`] was dropped are
+    /// discarded.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if called outside of a tokio runtime.
+    pub fn spawn<T>(self, stream: WebSocketStream<T>) -> (Sender, Receiver)
+    where
+        T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let (task, sender, receiver) = self.task(stream);
+        tokio::spawn(task);
+
+        (sender, receiver)
+    }
+
+    /// Spawns a task on the current [`LocalSet`] like [`Builder::spawn`],
+    /// for streams over transports that are not [`Send`].
+    ///
+    /// # Panics
+    ///
+    /// This method panics if called outside of a [`LocalSet`].
+    ///
+    /// [`LocalSet`]: tokio::task::LocalSet
+    pub fn spawn_local<T>(self, stream: WebSocketStream<T>) -> (Sender, Receiver)
+    where
+        T: AsyncRead + AsyncWrite + Unpin + 'static,
+    {
+        let (task, sender, receiver) = self.task(stream);
+        tokio::task::spawn_local(task);
+
+        (sender, receiver)
+    }
+
+    /// Creates the task that drives `stream` and the handles to it.
+    fn task<T>(self, stream: WebSocketStream<T>) -> (impl Future<Output = ()>, Sender, Receiver)
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
+        let (outgoing_tx, outgoing_rx) = slow_consumer::queue(
+            self.channel_capacity,
+            self.slow_consumer_policy,
+            self.on_slow_consumer,
+        );
+        let (incoming_tx, incoming_rx) = mpsc::channel(self.channel_capacity);
+        let budget = self.max_buffered_bytes.map(|max_bytes| {
+            // Semaphores cannot hold more than 512 MiB of permits on 32-bit targets
+            let max = u32::try_from(max_bytes.min(Semaphore::MAX_PERMITS)).unwrap_or(u32::MAX);
+
+            Arc::new(Budget {
+                permits: Semaphore::new(max as usize),
+                max,
+            })
+        });
+
+        (
+            drive(stream, outgoing_rx, incoming_tx, budget.clone()),
+            Sender { inner: outgoing_tx },
+            Receiver {
+                inner: incoming_rx,
+                budget,
+            },
+        )
+    }
+}
+
+impl fmt::Debug for Builder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Builder")
+            .field("channel_capacity", &self.channel_capacity)
+            .field("max_buffered_bytes", &self.max_buffered_bytes)
+            .field("slow_consumer_policy", &self.slow_consumer_policy)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Cloneable handle for sending messages over a connection driven by a
+/// background task, created via [`Builder::spawn`].
+#[derive(Debug, Clone)]
+pub struct Sender {
+    /// Queue to the task.
+    inner: QueueSender,
+}
+
+impl Sender {
+    /// Queues a message for sending. If the queue to the task is full, the
+    /// configured [`Policy`] applies, which waits by default.
+    ///
+    /// Errors that occur while the task sends the message are returned by the
+    /// [`Receiver`]. Sending a close message starts the close handshake.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::AlreadyClosed`] if the task has ended or
+    /// the connection is closed due to [`Policy::Disconnect`].
+    pub async fn send<M: Into<Message>>(&self, message: M) -> Result<(), Error> {
+        self.inner.send(message.into()).await.map(drop)
+    }
+
+    /// Starts the close handshake with the given close code and reason.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::AlreadyClosed`] if the task has ended.
+    pub async fn close(&self, code: Option<CloseCode>, reason: &str) -> Result<(), Error> {
+        self.send(Message::close(code, reason)).await
+    }
+
+    /// Whether the task has ended.
+    #[must_use]
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+}
+
+/// Handle for receiving messages from a connection driven by a background
+/// task, created via [`Builder::spawn`].
+///
+/// It also implements [`Stream`].
+#[derive(Debug)]
+pub struct Receiver {
+    /// Channel from the task.
+    inner: mpsc::Receiver<Incoming>,
+    /// Budget of buffered bytes, if limited via
+    /// [`Builder::max_buffered_bytes`].
+    budget: Option<Arc<Budget>>,
+}
+
+impl Receiver {
+    /// Receives the next message or error of the connection.
+    ///
+    /// Returns [`None`] once the task has ended.
+    pub async fn recv(&mut self) -> Option<Result<Message, Error>> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Polls for the next message or error of the connection, returning the
+    /// permits it held to the budget.
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Message, Error>>> {
+        let Some((item, permits)) = ready!(self.inner.poll_recv(cx)) else {
+            return Poll::Ready(None);
+        };
+
+        if let Some(budget) = &self.budget {
+            budget.permits.add_permits(permits as usize);
+        }
+
+        Poll::Ready(Some(item))
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        // Wake the task if it is waiting for the budget, messages are
+        // discarded from now on
+        if let Some(budget) = &self.budget {
+            budget.permits.close();
+        }
+    }
+}
+
+impl Stream for Receiver {
+    type Item = Result<Message, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_recv(cx)
+    }
+}
+
+/// Something the task has to react to.
+enum Event {
+    /// A message was sent via a [`Sender`], or all of them were dropped.
+    Outgoing(Option<Message>),
+    /// A message or error was received, or the stream ended.
+    Incoming(Option<Result<Message, Error>>),
+}
+
+/// Drives `stream` until the connection is closed, forwarding messages
+/// between it and the channels.
+async fn drive<T>(
+    mut stream: WebSocketStream<T>,
+    mut outgoing: QueueReceiver,
+    incoming: mpsc::Sender<Incoming>,
+    budget: Option<Arc<Budget>>,
+) where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut senders_alive = true;
+
+    loop {
+        let event = poll_fn(|cx| {
+            if senders_alive {
+                if let Poll::Ready(message) = outgoing.poll_recv(cx) {
+                    return Poll::Ready(Event::Outgoing(message));
+                }
+            }
+
+            Pin::new(&mut stream).poll_next(cx).map(Event::Incoming)
+        })
+        .await;
+
+        let item = match event {
+            Event::Outgoing(Some(message)) => stream.send(message).await.err().map(Err),
+            Event::Outgoing(None) => {
+                senders_alive = false;
+
+                // The connection might already be closing, which is fine here
+                match stream
+                    .send(Message::close(Some(CloseCode::NORMAL_CLOSURE), ""))
+                    .await
+                {
+                    Ok(()) | Err(Error::AlreadyClosed) => None,
+                    Err(e) => Some(Err(e)),
+                }
+            }
+            Event::Incoming(Some(item)) => Some(item),
+            Event::Incoming(None) => return,
+        };
+
+        if let Some(item) = item {
+            let permits = match (&budget, &item) {
+                (Some(budget), Ok(message)) => acquire(budget, message.as_payload().len()).await,
+                _ => 0,
+            };
+
+            // Messages are discarded once the receiver was dropped
+            let _ = incoming.send((item, permits)).await;
+        }
+    }
+}
+
+/// Waits until a payload of `len` bytes fits into the budget of buffered bytes
+/// and takes it from the budget, returning the number of permits taken.
+async fn acquire(budget: &Budget, len: usize) -> u32 {
+    let len = u32::try_from(len).unwrap_or(u32::MAX);
+    // Larger messages take the whole budget, otherwise they would never fit
+    let permits = len.min(budget.max);
+
+    match budget.permits.acquire_many(permits).await {
+        Ok(permit) => {
+            permit.forget();
+
+            permits
+        }
+        // The receiver was dropped
+        Err(_) => 0,
+    }
+}
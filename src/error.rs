@@ -24,10 +24,49 @@ pub enum Error {
     /// Attempted to connect a client to a remote without configured URI.
     #[cfg(feature = "client")]
     NoUriConfigured,
+    /// A phase of establishing a client connection timed out.
+    #[cfg(feature = "client")]
+    ConnectTimeout(crate::client::ConnectPhase),
+    /// The HTTP proxy responded to the `CONNECT` request with a non-2xx
+    /// status code.
+    #[cfg(feature = "client")]
+    ProxyConnectFailed(u16),
     /// WebSocket protocol violation.
     Protocol(ProtocolError),
     /// Payload length limit was exceeded.
     PayloadTooLong { len: usize, max_len: usize },
+    /// No message was received within the timeout passed to
+    /// [`WebSocketStream::recv_timeout`].
+    ///
+    /// [`WebSocketStream::recv_timeout`]: crate::WebSocketStream::recv_timeout
+    #[cfg(any(feature = "client", feature = "server"))]
+    ReadTimeout,
+    /// No frames were received within the idle timeout configured via
+    /// [`Config::idle_timeout`] and the connection was closed.
+    ///
+    /// [`Config::idle_timeout`]: crate::Config::idle_timeout
+    #[cfg(any(feature = "client", feature = "server"))]
+    IdleTimeout,
+    /// The peer exceeded the rate of received messages or bytes configured
+    /// via [`Limits::max_message_rate`] or [`Limits::max_byte_rate`] and the
+    /// connection was closed.
+    ///
+    /// [`Limits::max_message_rate`]: crate::Limits::max_message_rate
+    /// [`Limits::max_byte_rate`]: crate::Limits::max_byte_rate
+    #[cfg(any(feature = "client", feature = "server"))]
+    RateLimitExceeded,
+    /// Writing to the underlying I/O made no progress within the timeout
+    /// configured via [`Config::write_timeout`] and the connection was
+    /// abandoned.
+    ///
+    /// [`Config::write_timeout`]: crate::Config::write_timeout
+    WriteTimeout,
+    /// A negotiated extension failed to transform a message payload.
+    Extension(Box<dyn std::error::Error + Send + Sync>),
+    /// A frame was rejected by the [`FrameInterceptor`] of the stream.
+    ///
+    /// [`FrameInterceptor`]: crate::proto::FrameInterceptor
+    FrameRejected(Box<dyn std::error::Error + Send + Sync>),
     /// I/O error.
     Io(io::Error),
     /// TLS error originating in [`native_tls`].
@@ -75,6 +114,19 @@ pub enum Error {
         not(feature = "rustls-platform-verifier"),
     ))]
     NoNativeRootCertificatesFound(Vec<rustls_native_certs::Error>),
+    /// Building the verifier of client certificates for an
+    /// [`Acceptor`] failed.
+    ///
+    /// [`Acceptor`]: crate::server::Acceptor
+    #[cfg(all(
+        feature = "server",
+        any(
+            feature = "rustls-webpki-roots",
+            feature = "rustls-native-roots",
+            feature = "rustls-platform-verifier"
+        )
+    ))]
+    ClientCertVerifier(tokio_rustls::rustls::server::VerifierBuilderError),
 }
 
 #[cfg(feature = "native-tls")]
@@ -120,6 +172,20 @@ impl From<tokio_rustls::rustls::Error> for Error {
     }
 }
 
+#[cfg(all(
+    feature = "server",
+    any(
+        feature = "rustls-webpki-roots",
+        feature = "rustls-native-roots",
+        feature = "rustls-platform-verifier"
+    )
+))]
+impl From<tokio_rustls::rustls::server::VerifierBuilderError> for Error {
+    fn from(err: tokio_rustls::rustls::server::VerifierBuilderError) -> Self {
+        Self::ClientCertVerifier(err)
+    }
+}
+
 #[cfg(any(feature = "client", feature = "server"))]
 impl From<crate::upgrade::Error> for Error {
     fn from(err: crate::upgrade::Error) -> Self {
@@ -136,6 +202,16 @@ impl fmt::Display for Error {
             Error::CannotResolveHost => f.write_str("client DNS lookup failed"),
             #[cfg(feature = "client")]
             Error::NoUriConfigured => f.write_str("client has no URI configured"),
+            #[cfg(feature = "client")]
+            Error::ConnectTimeout(phase) => {
+                f.write_str("client timed out during ")?;
+                phase.fmt(f)
+            }
+            #[cfg(feature = "client")]
+            Error::ProxyConnectFailed(status) => {
+                f.write_str("proxy refused to connect with status code ")?;
+                status.fmt(f)
+            }
             Error::Protocol(e) => e.fmt(f),
             Error::PayloadTooLong { len, max_len } => {
                 f.write_str("payload length of ")?;
@@ -143,6 +219,21 @@ impl fmt::Display for Error {
                 f.write_str(" exceeds the limit of ")?;
                 max_len.fmt(f)
             }
+            #[cfg(any(feature = "client", feature = "server"))]
+            Error::ReadTimeout => f.write_str("timed out waiting for a message"),
+            #[cfg(any(feature = "client", feature = "server"))]
+            Error::IdleTimeout => f.write_str("connection closed after being idle"),
+            #[cfg(any(feature = "client", feature = "server"))]
+            Error::RateLimitExceeded => f.write_str("peer exceeded the receive rate limit"),
+            Error::WriteTimeout => f.write_str("timed out writing to the connection"),
+            Error::Extension(e) => {
+                f.write_str("extension error: ")?;
+                e.fmt(f)
+            }
+            Error::FrameRejected(e) => {
+                f.write_str("frame rejected: ")?;
+                e.fmt(f)
+            }
             Error::Io(e) => e.fmt(f),
             #[cfg(feature = "native-tls")]
             Error::NativeTls(e) => e.fmt(f),
@@ -184,6 +275,15 @@ impl fmt::Display for Error {
                 f.write_str("no native root certificates were found, errors encountered: ")?;
                 std::fmt::Debug::fmt(e, f)
             }
+            #[cfg(all(
+                feature = "server",
+                any(
+                    feature = "rustls-webpki-roots",
+                    feature = "rustls-native-roots",
+                    feature = "rustls-platform-verifier"
+                )
+            ))]
+            Error::ClientCertVerifier(e) => e.fmt(f),
         }
     }
 }
@@ -191,9 +291,14 @@ impl fmt::Display for Error {
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
-            Error::AlreadyClosed | Error::CannotResolveHost | Error::PayloadTooLong { .. } => None,
+            Error::AlreadyClosed
+            | Error::CannotResolveHost
+            | Error::PayloadTooLong { .. }
+            | Error::WriteTimeout => None,
             #[cfg(feature = "client")]
-            Error::NoUriConfigured => None,
+            Error::NoUriConfigured | Error::ConnectTimeout(_) | Error::ProxyConnectFailed(_) => {
+                None
+            }
             #[cfg(all(
                 any(
                     feature = "rustls-webpki-roots",
@@ -211,7 +316,10 @@ impl std::error::Error for Error {
             Error::NoNativeRootCertificatesFound(e) => Some(e.first()?),
             #[cfg(feature = "client")]
             Error::UnsupportedScheme => None,
+            #[cfg(any(feature = "client", feature = "server"))]
+            Error::ReadTimeout | Error::IdleTimeout | Error::RateLimitExceeded => None,
             Error::Protocol(e) => Some(e),
+            Error::Extension(e) | Error::FrameRejected(e) => Some(e.as_ref()),
             Error::Io(e) => Some(e),
             #[cfg(feature = "native-tls")]
             Error::NativeTls(e) => Some(e),
@@ -231,6 +339,15 @@ impl std::error::Error for Error {
             Error::Rustls(e) => Some(e),
             #[cfg(any(feature = "client", feature = "server"))]
             Error::Upgrade(e) => Some(e),
+            #[cfg(all(
+                feature = "server",
+                any(
+                    feature = "rustls-webpki-roots",
+                    feature = "rustls-native-roots",
+                    feature = "rustls-platform-verifier"
+                )
+            ))]
+            Error::ClientCertVerifier(e) => Some(e),
         }
     }
 }
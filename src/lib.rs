@@ -2,9 +2,13 @@
     clippy::pedantic,
     clippy::missing_docs_in_private_items,
     clippy::missing_errors_doc,
+    missing_debug_implementations,
     rustdoc::broken_intra_doc_links,
     warnings
 )]
+// The `checked` feature rules out all unsafe code apart from the `Sync`
+// implementation of `WebSocketStream`, which allows this lint explicitly
+#![cfg_attr(feature = "checked", deny(unsafe_code))]
 #![cfg_attr(docsrs, feature(doc_auto_cfg))]
 // Required for AVX512 until stable
 #![cfg_attr(
@@ -28,24 +32,44 @@ compile_error!("client and server implementation require at least one SHA1 backe
 
 #[cfg(feature = "client")]
 pub mod client;
+#[cfg(feature = "client")]
+pub mod cookie;
+#[cfg(all(feature = "deflate", any(feature = "client", feature = "server")))]
+pub mod deflate;
+#[cfg(any(feature = "client", feature = "server"))]
+pub mod driver;
 pub mod error;
 mod mask;
+#[cfg(any(feature = "client", feature = "server"))]
+pub mod mux;
 pub mod proto;
 #[cfg(feature = "client")]
+mod proxy;
+#[cfg(feature = "server")]
+mod proxy_protocol;
+#[cfg(feature = "client")]
 mod rand;
+pub mod record;
 #[cfg(feature = "client")]
 pub mod resolver;
 #[cfg(feature = "server")]
 pub mod server;
 #[cfg(any(feature = "client", feature = "server"))]
 mod sha;
+#[cfg(any(feature = "client", feature = "server"))]
+pub mod slow_consumer;
+#[cfg(any(feature = "client", feature = "server"))]
+pub mod socket;
 pub mod tls;
 #[cfg(any(feature = "client", feature = "server"))]
+pub mod topics;
+mod unchecked;
+#[cfg(any(feature = "client", feature = "server"))]
 pub mod upgrade;
 mod utf8;
 
 #[cfg(feature = "client")]
-pub use client::Builder as ClientBuilder;
+pub use client::{Builder as ClientBuilder, Pool as ClientPool};
 pub use error::Error;
 pub use proto::{CloseCode, Config, Limits, Message, Payload, WebSocketStream};
 #[cfg(feature = "server")]
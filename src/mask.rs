@@ -5,12 +5,20 @@
 //!   - One AVX2-based implementation that masks 32 bytes per cycle
 //!   - One SSE2-based implementation that masks 16 bytes per cycle
 //!   - One NEON-based implementation that masks 16 bytes per cycle
-//!   - A fallback implementation without SIMD
+//!   - A fallback implementation without SIMD that masks 8 bytes per cycle
 //!
-//! The SIMD implementations will only be used if the `simd` feature is active.
+//! The SIMD implementations will only be used if the `simd` feature is active
+//! and the `checked` feature is not, since they require unsafe code.
+#[cfg(feature = "client")]
+use bytes::{BufMut, BytesMut};
 
 /// Websocket frame masking implementation using AVX512.
-#[cfg(all(feature = "simd", feature = "nightly", target_feature = "avx512f"))]
+#[cfg(all(
+    feature = "simd",
+    not(feature = "checked"),
+    feature = "nightly",
+    target_feature = "avx512f"
+))]
 mod imp {
     use std::{
         alloc::{alloc, dealloc, Layout},
@@ -73,6 +81,7 @@ mod imp {
 /// Websocket frame masking implementation using AVX2.
 #[cfg(all(
     feature = "simd",
+    not(feature = "checked"),
     not(all(feature = "nightly", target_feature = "avx512f")),
     target_feature = "avx2"
 ))]
@@ -138,6 +147,7 @@ mod imp {
 /// Websocket frame masking implementation using SSE2.
 #[cfg(all(
     feature = "simd",
+    not(feature = "checked"),
     not(all(feature = "nightly", target_feature = "avx512f")),
     not(target_feature = "avx2"),
     target_feature = "sse2"
@@ -202,7 +212,7 @@ mod imp {
 }
 
 /// Websocket frame masking implementation using NEON.
-#[cfg(all(feature = "simd", target_feature = "neon"))]
+#[cfg(all(feature = "simd", not(feature = "checked"), target_feature = "neon"))]
 mod imp {
     #[cfg(target_arch = "aarch64")]
     use std::arch::aarch64::{uint8x16_t, veorq_u8, vld1q_u8};
@@ -271,6 +281,7 @@ mod imp {
 
 /// Websocket frame masking fallback implementation.
 #[cfg(any(
+    feature = "checked",
     not(feature = "simd"),
     all(
         feature = "simd",
@@ -295,14 +306,98 @@ mod imp {
 /// The input bytes may be further in the payload and therefore the offset into
 /// the payload must be specified.
 ///
-/// This is used as the internal implementation in non-SIMD builds and as a
-/// fallback in SIMD builds.
+/// This masks 8 bytes at a time using a rotated 64-bit key and only handles
+/// the unaligned head and tail of the input byte by byte. It is used as the
+/// internal implementation in non-SIMD builds and as a fallback in SIMD
+/// builds.
+#[cfg(not(feature = "checked"))]
+pub fn fallback_frame(key: &[u8], input: &mut [u8], mut offset: usize) {
+    // SAFETY: Any bit pattern is a valid u64
+    let (prefix, words, suffix) = unsafe { input.align_to_mut::<u64>() };
+
+    for (index, byte) in prefix.iter_mut().enumerate() {
+        *byte ^= key[(index + offset) & 3];
+    }
+    offset = (offset + prefix.len()) & 3;
+
+    if !words.is_empty() {
+        let mut mask = [0; 8];
+        for (index, byte) in mask.iter_mut().enumerate() {
+            *byte = key[(index + offset) & 3];
+        }
+        let mask = u64::from_ne_bytes(mask);
+
+        for word in &mut *words {
+            *word ^= mask;
+        }
+        // Every word is 8 bytes long, so the offset into the key is unchanged
+    }
+
+    for (index, byte) in suffix.iter_mut().enumerate() {
+        *byte ^= key[(index + offset) & 3];
+    }
+}
+
+/// (Un-)masks input bytes with the framing key.
+///
+/// The input bytes may be further in the payload and therefore the offset into
+/// the payload must be specified.
+///
+/// This masks 8 bytes at a time using a rotated 64-bit key, copying each chunk
+/// of 8 bytes to and from a word instead of reinterpreting the aligned part of
+/// the input, and only handles the tail of the input byte by byte.
+#[cfg(feature = "checked")]
 pub fn fallback_frame(key: &[u8], input: &mut [u8], offset: usize) {
-    for (index, byte) in input.iter_mut().enumerate() {
+    let mut mask = [0; 8];
+    for (index, byte) in mask.iter_mut().enumerate() {
+        *byte = key[(index + offset) & 3];
+    }
+    let mask = u64::from_ne_bytes(mask);
+
+    let mut chunks = input.chunks_exact_mut(8);
+    for chunk in &mut chunks {
+        let mut word = [0; 8];
+        word.copy_from_slice(chunk);
+        chunk.copy_from_slice(&(u64::from_ne_bytes(word) ^ mask).to_ne_bytes());
+    }
+
+    // Every chunk is 8 bytes long, so the offset into the key is unchanged
+    for (index, byte) in chunks.into_remainder().iter_mut().enumerate() {
         *byte ^= key[(index + offset) & 3];
     }
 }
 
+/// Copies input bytes to a new buffer while (un-)masking them with the
+/// framing key.
+///
+/// Like [`fallback_frame`], this masks 8 bytes at a time using a rotated
+/// 64-bit key. It is used for shared payloads, which cannot be masked in place,
+/// to read them only once instead of copying and then masking them.
+#[cfg(feature = "client")]
+pub fn copy_frame(key: &[u8], input: &[u8]) -> BytesMut {
+    let mut mask = [0; 8];
+    for (index, byte) in mask.iter_mut().enumerate() {
+        *byte = key[index & 3];
+    }
+    let mask = u64::from_ne_bytes(mask);
+
+    let mut output = BytesMut::with_capacity(input.len());
+    let mut chunks = input.chunks_exact(8);
+    for chunk in &mut chunks {
+        let mut word = [0; 8];
+        word.copy_from_slice(chunk);
+        output.put_u64_ne(u64::from_ne_bytes(word) ^ mask);
+    }
+
+    // Every chunk is 8 bytes long, so the remainder starts at the beginning of
+    // the key
+    for (index, byte) in chunks.remainder().iter().enumerate() {
+        output.put_u8(byte ^ key[index & 3]);
+    }
+
+    output
+}
+
 pub use imp::frame;
 
 #[cfg(all(test, feature = "client", feature = "fastrand"))]
@@ -322,3 +417,35 @@ fn test_mask() {
 
     assert_eq!(&data, &data_clone);
 }
+
+#[cfg(all(test, feature = "client"))]
+#[test]
+fn test_copy_mask() {
+    let key = [0x12, 0x34, 0x56, 0x78];
+    let data: Vec<u8> = (0..=255).collect();
+
+    for len in 0..20 {
+        let mut masked = data[..len].to_vec();
+        fallback_frame(&key, &mut masked, 0);
+
+        assert_eq!(&copy_frame(&key, &data[..len])[..], &masked[..]);
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_fallback_mask() {
+    let key = [0x12, 0x34, 0x56, 0x78];
+    let data: Vec<u8> = (0..=255).collect();
+
+    for start in 0..8 {
+        for offset in 0..4 {
+            let mut masked = data[start..].to_vec();
+            fallback_frame(&key, &mut masked, offset);
+
+            for (index, (masked, original)) in masked.iter().zip(&data[start..]).enumerate() {
+                assert_eq!(*masked, original ^ key[(index + offset) & 3]);
+            }
+        }
+    }
+}
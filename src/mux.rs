@@ -0,0 +1,647 @@
+//! Multiplexing of independent logical channels over a single WebSocket
+//! connection.
+//!
+//! [`Builder::spawn`] moves a [`WebSocketStream`] into a background task that
+//! carries the messages of any number of [`Channel`]s, identified by a `u32`.
+//! Both ends of the connection have to use the multiplexer, as every message
+//! is a binary message prefixed with a 5 byte header: a frame kind followed by
+//! the big-endian channel ID.
+//!
+//! Channels are opened via [`Multiplexer::open`] and the peer receives them
+//! from [`Multiplexer::accept`] once it receives the first message. Each
+//! channel has its own flow control: at most [`Builder::window`] messages may
+//! be in flight before sending waits for the receiver to consume them, so a
+//! slow channel does not hold up the others.
+//!
+//! ```
+//! # #[cfg(feature = "server")]
+//! # async fn example(
+//! #     stream: tokio_websockets::WebSocketStream<tokio::net::TcpStream>,
+//! # ) -> Result<(), tokio_websockets::Error> {
+//! use tokio_websockets::mux;
+//!
+//! let mut multiplexer = mux::Builder::new().spawn(stream);
+//!
+//! let mut control = multiplexer.open(0).expect("channel 0 is not open yet");
+//! control.send("hello").await?;
+//!
+//! while let Some(mut channel) = multiplexer.accept().await {
+//!     tokio::spawn(async move {
+//!         while let Some(payload) = channel.recv().await {
+//!             // Echo everything back on the same channel
+//!             if channel.send(payload).await.is_err() {
+//!                 break;
+//!             }
+//!         }
+//!     });
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fmt,
+    future::{poll_fn, Future},
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    task::{ready, Context, Poll},
+};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_core::Stream;
+use futures_sink::Sink;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::{mpsc, Semaphore},
+};
+use tokio_util::sync::PollSemaphore;
+
+use crate::{CloseCode, Error, Message, WebSocketStream};
+
+/// Frame kind carrying a message of a channel.
+const DATA: u8 = 0;
+/// Frame kind granting the peer credit to send one more message on a channel.
+const CREDIT: u8 = 1;
+/// Frame kind closing a channel.
+const CLOSE: u8 = 2;
+/// Length of the header of every frame.
+const HEADER_LEN: usize = 5;
+
+/// Builder for the background task multiplexing a [`WebSocketStream`].
+#[derive(Debug, Clone)]
+pub struct Builder {
+    /// Number of messages that may be in flight per channel.
+    window: u32,
+    /// Number of channels that may be open at the same time.
+    max_channels: usize,
+}
+
+impl Builder {
+    /// Creates a [`Builder`] with all defaults: a window of 16 messages per
+    /// channel and at most 1024 open channels.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            window: 16,
+            max_channels: 1024,
+        }
+    }
+
+    /// Sets the number of messages per channel that may be sent before the
+    /// receiver consumed them. The default is 16.
+    ///
+    /// Both ends of the connection have to use the same window. Peers that
+    /// exceed it are disconnected with [`CloseCode::POLICY_VIOLATION`].
+    ///
+    /// # Panics
+    ///
+    /// If `window` is `0`.
+    #[must_use]
+    pub fn window(mut self, window: u32) -> Self {
+        assert_ne!(window, 0, "window must be non-zero");
+        self.window = window;
+
+        self
+    }
+
+    /// Sets the number of channels that may be open at the same time. The
+    /// default is 1024.
+    ///
+    /// Every channel the peer opens allocates state until it is closed, so
+    /// peers that open a channel while this many are open are disconnected
+    /// with [`CloseCode::POLICY_VIOLATION`]. Channels opened via
+    /// [`Multiplexer::open`] count towards the limit, but are not refused.
+    #[must_use]
+    pub fn max_channels(mut self, max_channels: usize) -> Self {
+        self.max_channels = max_channels;
+
+        self
+    }
+
+    /// Spawns a task on the current tokio runtime that multiplexes `stream`
+    /// and returns the handle to open and accept channels with.
+    ///
+    /// The task ends once the connection is closed, which ends all channels.
+    /// Once the [`Multiplexer`] and all [`Channel`]s are dropped, it closes
+    /// the connection with [`CloseCode::NORMAL_CLOSURE`].
+    ///
+    /// # Panics
+    ///
+    /// This method panics if called outside of a tokio runtime.
+    pub fn spawn<T>(self, stream: WebSocketStream<T>) -> Multiplexer
+    where
+        T: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let (task, multiplexer) = self.task(stream);
+        tokio::spawn(task);
+
+        multiplexer
+    }
+
+    /// Spawns a task on the current [`LocalSet`] like [`Builder::spawn`],
+    /// for streams over transports that are not [`Send`].
+    ///
+    /// # Panics
+    ///
+    /// This method panics if called outside of a [`LocalSet`].
+    ///
+    /// [`LocalSet`]: tokio::task::LocalSet
+    pub fn spawn_local<T>(self, stream: WebSocketStream<T>) -> Multiplexer
+    where
+        T: AsyncRead + AsyncWrite + Unpin + 'static,
+    {
+        let (task, multiplexer) = self.task(stream);
+        tokio::task::spawn_local(task);
+
+        multiplexer
+    }
+
+    /// Creates the task that multiplexes `stream` and the handle to it.
+    fn task<T>(self, stream: WebSocketStream<T>) -> (impl Future<Output = ()>, Multiplexer)
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
+        let shared = Arc::new(Shared {
+            channels: Mutex::new(HashMap::new()),
+            window: self.window,
+            max_channels: self.max_channels,
+        });
+        let (commands_tx, commands_rx) = mpsc::unbounded_channel();
+        let (accepted_tx, accepted_rx) = mpsc::unbounded_channel();
+
+        let task = drive(
+            stream,
+            Arc::clone(&shared),
+            commands_rx,
+            commands_tx.downgrade(),
+            accepted_tx,
+        );
+
+        (
+            task,
+            Multiplexer {
+                shared,
+                commands: commands_tx,
+                accepted: accepted_rx,
+            },
+        )
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Request from a handle to the task.
+enum Command {
+    /// Send a message on a channel.
+    Data(u32, Bytes),
+    /// Grant the peer credit for a message consumed from a channel.
+    Credit(u32),
+    /// Close a channel.
+    Close(u32),
+}
+
+/// State of an open channel, kept by the task.
+struct ChannelState {
+    /// Channel to deliver received messages to the [`Channel`] through.
+    incoming: mpsc::UnboundedSender<Bytes>,
+    /// Credit for sending messages, shared with the [`Channel`].
+    credits: Arc<Semaphore>,
+    /// Number of received messages that were not consumed yet.
+    unacknowledged: u32,
+}
+
+/// State shared by the task and the handles.
+struct Shared {
+    /// Open channels by ID.
+    channels: Mutex<HashMap<u32, ChannelState>>,
+    /// Number of messages that may be in flight per channel.
+    window: u32,
+    /// Number of channels that may be open at the same time.
+    max_channels: usize,
+}
+
+impl Shared {
+    /// Locks the open channels, ignoring poisoning since they are never left
+    /// in an inconsistent state.
+    fn lock(&self) -> MutexGuard<'_, HashMap<u32, ChannelState>> {
+        self.channels.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Creates a channel with `id` and the state the task keeps for it.
+    fn channel(
+        &self,
+        id: u32,
+        commands: mpsc::UnboundedSender<Command>,
+    ) -> (ChannelState, Channel) {
+        let (incoming_tx, incoming_rx) = mpsc::unbounded_channel();
+        let credits = Arc::new(Semaphore::new(self.window as usize));
+
+        let state = ChannelState {
+            incoming: incoming_tx,
+            credits: Arc::clone(&credits),
+            unacknowledged: 0,
+        };
+        let channel = Channel {
+            id,
+            incoming: incoming_rx,
+            credits: PollSemaphore::new(credits),
+            has_credit: false,
+            commands,
+            closed: false,
+        };
+
+        (state, channel)
+    }
+}
+
+/// Handle for opening and accepting channels of a multiplexed connection,
+/// created via [`Builder::spawn`].
+pub struct Multiplexer {
+    /// State shared with the task.
+    shared: Arc<Shared>,
+    /// Channel to the task, cloned into opened channels.
+    commands: mpsc::UnboundedSender<Command>,
+    /// Channels opened by the peer.
+    accepted: mpsc::UnboundedReceiver<Channel>,
+}
+
+impl Multiplexer {
+    /// Opens the channel with `id`. The peer accepts it once it receives the
+    /// first message sent on it.
+    ///
+    /// Returns [`None`] if the channel is already open, including if it was
+    /// opened by the peer and not accepted yet.
+    #[must_use]
+    pub fn open(&self, id: u32) -> Option<Channel> {
+        let mut channels = self.shared.lock();
+        let Entry::Vacant(entry) = channels.entry(id) else {
+            return None;
+        };
+
+        let (state, channel) = self.shared.channel(id, self.commands.clone());
+        entry.insert(state);
+
+        Some(channel)
+    }
+
+    /// Accepts the next channel opened by the peer.
+    ///
+    /// Returns [`None`] once the connection is closed.
+    pub async fn accept(&mut self) -> Option<Channel> {
+        self.accepted.recv().await
+    }
+}
+
+impl fmt::Debug for Multiplexer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Multiplexer")
+            .field("channels", &self.shared.lock().len())
+            .field("window", &self.shared.window)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Logical channel of a multiplexed connection, created via
+/// [`Multiplexer::open`] or [`Multiplexer::accept`].
+///
+/// It also implements [`Stream`] and [`Sink`]. Dropping it closes the
+/// channel.
+pub struct Channel {
+    /// ID of the channel.
+    id: u32,
+    /// Channel received messages are delivered through.
+    incoming: mpsc::UnboundedReceiver<Bytes>,
+    /// Credit for sending messages, replenished by the peer.
+    credits: PollSemaphore,
+    /// Whether credit for the next message was acquired.
+    has_credit: bool,
+    /// Channel to the task.
+    commands: mpsc::UnboundedSender<Command>,
+    /// Whether the channel was closed.
+    closed: bool,
+}
+
+impl Channel {
+    /// Returns the ID of the channel.
+    #[must_use]
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Sends a message on the channel, waiting while the peer has not
+    /// consumed enough of the previous ones.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::AlreadyClosed`] if the channel or the
+    /// connection was closed.
+    pub async fn send<P: Into<Bytes>>(&mut self, payload: P) -> Result<(), Error> {
+        poll_fn(|cx| self.poll_credit(cx)).await?;
+
+        self.send_data(payload.into())
+    }
+
+    /// Receives the next message of the channel.
+    ///
+    /// Returns [`None`] once the channel or the connection was closed.
+    pub async fn recv(&mut self) -> Option<Bytes> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Polls for credit to send the next message.
+    fn poll_credit(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        if self.closed {
+            return Poll::Ready(Err(Error::AlreadyClosed));
+        }
+
+        if !self.has_credit {
+            let Some(permit) = ready!(self.credits.poll_acquire(cx)) else {
+                return Poll::Ready(Err(Error::AlreadyClosed));
+            };
+
+            // Credit is replenished by the peer, not by returning the permit
+            permit.forget();
+            self.has_credit = true;
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    /// Sends a message using the previously acquired credit.
+    fn send_data(&mut self, payload: Bytes) -> Result<(), Error> {
+        self.has_credit = false;
+
+        self.commands
+            .send(Command::Data(self.id, payload))
+            .map_err(|_| Error::AlreadyClosed)
+    }
+
+    /// Polls for the next message, granting the peer credit for it.
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<Bytes>> {
+        let payload = ready!(self.incoming.poll_recv(cx));
+
+        if payload.is_some() {
+            let _ = self.commands.send(Command::Credit(self.id));
+        }
+
+        Poll::Ready(payload)
+    }
+
+    /// Closes the channel unless it was closed before.
+    fn close(&mut self) {
+        if !self.closed {
+            self.closed = true;
+            let _ = self.commands.send(Command::Close(self.id));
+        }
+    }
+}
+
+impl Stream for Channel {
+    type Item = Bytes;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Bytes>> {
+        self.poll_recv(cx)
+    }
+}
+
+impl Sink<Bytes> for Channel {
+    type Error = Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.poll_credit(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Bytes) -> Result<(), Error> {
+        self.send_data(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        // Messages are handed to the task immediately
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.close();
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for Channel {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl fmt::Debug for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Channel")
+            .field("id", &self.id)
+            .field("closed", &self.closed)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Something the task has to react to.
+enum Event {
+    /// A handle sent a command, or all of them were dropped.
+    Command(Option<Command>),
+    /// A message or error was received, or the stream ended.
+    Incoming(Option<Result<Message, Error>>),
+}
+
+/// Encodes a frame of `kind` for channel `id` with `payload`.
+fn encode(kind: u8, id: u32, payload: &[u8]) -> Message {
+    let mut frame = BytesMut::with_capacity(HEADER_LEN + payload.len());
+    frame.put_u8(kind);
+    frame.put_u32(id);
+    frame.put_slice(payload);
+
+    Message::binary(frame)
+}
+
+/// Drives `stream` until the connection is closed, translating between
+/// frames and the channels.
+async fn drive<T>(
+    mut stream: WebSocketStream<T>,
+    shared: Arc<Shared>,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+    weak_commands: mpsc::WeakUnboundedSender<Command>,
+    accepted: mpsc::UnboundedSender<Channel>,
+) where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    // Channels closed by us that the peer has not acknowledged yet
+    let mut closing = HashSet::new();
+    let mut handles_alive = true;
+
+    loop {
+        let event = poll_fn(|cx| {
+            if handles_alive {
+                if let Poll::Ready(command) = commands.poll_recv(cx) {
+                    return Poll::Ready(Event::Command(command));
+                }
+            }
+
+            Pin::new(&mut stream).poll_next(cx).map(Event::Incoming)
+        })
+        .await;
+
+        let reply = match event {
+            Event::Command(Some(command)) => handle_command(&shared, &mut closing, command),
+            Event::Command(None) => {
+                handles_alive = false;
+
+                Some(Message::close(Some(CloseCode::NORMAL_CLOSURE), ""))
+            }
+            Event::Incoming(Some(Ok(message))) => {
+                handle_message(&shared, &mut closing, &weak_commands, &accepted, message)
+            }
+            Event::Incoming(Some(Err(_)) | None) => break,
+        };
+
+        if let Some(reply) = reply {
+            // The connection might already be closing, which is fine here
+            match stream.send(reply).await {
+                Ok(()) | Err(Error::AlreadyClosed) => {}
+                Err(_) => break,
+            }
+        }
+    }
+
+    // End all channels, failing pending sends
+    for (_, state) in shared.lock().drain() {
+        state.credits.close();
+    }
+}
+
+/// Handles a command of a handle, returning the message to send.
+fn handle_command(
+    shared: &Shared,
+    closing: &mut HashSet<u32>,
+    command: Command,
+) -> Option<Message> {
+    let mut channels = shared.lock();
+
+    match command {
+        Command::Data(id, payload) => channels
+            .contains_key(&id)
+            .then(|| encode(DATA, id, &payload)),
+        Command::Credit(id) => {
+            let state = channels.get_mut(&id)?;
+            state.unacknowledged = state.unacknowledged.saturating_sub(1);
+
+            Some(encode(CREDIT, id, &1_u32.to_be_bytes()))
+        }
+        Command::Close(id) => {
+            channels.remove(&id)?.credits.close();
+            closing.insert(id);
+
+            Some(encode(CLOSE, id, &[]))
+        }
+    }
+}
+
+/// Handles a message received from the peer, returning the message to reply
+/// with.
+fn handle_message(
+    shared: &Shared,
+    closing: &mut HashSet<u32>,
+    weak_commands: &mpsc::WeakUnboundedSender<Command>,
+    accepted: &mpsc::UnboundedSender<Channel>,
+    message: Message,
+) -> Option<Message> {
+    /// Reply to frames that are not valid multiplexer frames.
+    fn invalid() -> Message {
+        Message::close(Some(CloseCode::PROTOCOL_ERROR), "invalid multiplexer frame")
+    }
+
+    // Control messages are handled by the stream
+    if message.is_ping() || message.is_pong() || message.is_close() {
+        return None;
+    }
+
+    if !message.is_binary() {
+        return Some(invalid());
+    }
+
+    let frame = Bytes::from(message.into_payload());
+    let Some((&[kind, a, b, c, d], _)) = frame.split_first_chunk::<HEADER_LEN>() else {
+        return Some(invalid());
+    };
+    let id = u32::from_be_bytes([a, b, c, d]);
+    let payload = frame.slice(HEADER_LEN..);
+
+    let mut channels = shared.lock();
+
+    match kind {
+        DATA => {
+            if closing.contains(&id) {
+                return None;
+            }
+
+            if channels.len() >= shared.max_channels && !channels.contains_key(&id) {
+                return Some(Message::close(
+                    Some(CloseCode::POLICY_VIOLATION),
+                    "too many multiplexer channels",
+                ));
+            }
+
+            let state = match channels.entry(id) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    // Nobody could accept the channel anymore
+                    let commands = weak_commands.upgrade()?;
+                    let (state, channel) = shared.channel(id, commands);
+                    let _ = accepted.send(channel);
+
+                    entry.insert(state)
+                }
+            };
+
+            state.unacknowledged += 1;
+            if state.unacknowledged > shared.window {
+                return Some(Message::close(
+                    Some(CloseCode::POLICY_VIOLATION),
+                    "multiplexer window exceeded",
+                ));
+            }
+
+            let _ = state.incoming.send(payload);
+
+            None
+        }
+        CREDIT => {
+            let Ok(credits) = <[u8; 4]>::try_from(&payload[..]) else {
+                return Some(invalid());
+            };
+
+            let state = channels.get(&id)?;
+
+            // The peer can only grant credit for messages that are in flight
+            let credits = u32::from_be_bytes(credits) as usize;
+            if state.credits.available_permits() + credits > shared.window as usize {
+                return Some(invalid());
+            }
+
+            state.credits.add_permits(credits);
+
+            None
+        }
+        CLOSE => {
+            if closing.remove(&id) {
+                return None;
+            }
+
+            // Acknowledge the close, dropping the state ends the channel
+            channels.remove(&id)?.credits.close();
+
+            Some(encode(CLOSE, id, &[]))
+        }
+        _ => Some(invalid()),
+    }
+}
@@ -0,0 +1,106 @@
+//! Implementations of [`Arbitrary`] for fuzzing and property testing.
+//!
+//! All generated values are valid according to RFC 6455, so that they can be
+//! sent and are accepted when received.
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+
+use super::types::{CloseCode, Frame, Message, OpCode, Payload};
+
+/// Close codes with a registered meaning that may be sent over the wire.
+const REGISTERED_CLOSE_CODES: [CloseCode; 12] = [
+    CloseCode::NORMAL_CLOSURE,
+    CloseCode::GOING_AWAY,
+    CloseCode::PROTOCOL_ERROR,
+    CloseCode::UNSUPPORTED_DATA,
+    CloseCode::INVALID_FRAME_PAYLOAD_DATA,
+    CloseCode::POLICY_VIOLATION,
+    CloseCode::MESSAGE_TOO_BIG,
+    CloseCode::MANDATORY_EXTENSION,
+    CloseCode::INTERNAL_SERVER_ERROR,
+    CloseCode::SERVICE_RESTART,
+    CloseCode::SERVICE_OVERLOAD,
+    CloseCode::BAD_GATEWAY,
+];
+
+/// Maximum payload length of control frames.
+const MAX_CONTROL_PAYLOAD_LEN: usize = 125;
+
+/// Generates arbitrary bytes of at most `max_len` length.
+fn bytes(u: &mut Unstructured<'_>, max_len: usize) -> Result<Vec<u8>> {
+    let len = u.arbitrary_len::<u8>()?.min(max_len);
+
+    Ok(u.bytes(len)?.to_vec())
+}
+
+/// Generates an arbitrary close message.
+fn close(u: &mut Unstructured<'_>) -> Result<Message> {
+    let code = Option::<CloseCode>::arbitrary(u)?;
+    let mut reason = String::arbitrary(u)?;
+
+    // The reason has to fit into the payload after the code
+    let mut len = reason.len().min(MAX_CONTROL_PAYLOAD_LEN - 2);
+    while !reason.is_char_boundary(len) {
+        len -= 1;
+    }
+    reason.truncate(len);
+
+    Ok(Message::close(code, &reason))
+}
+
+impl<'a> Arbitrary<'a> for CloseCode {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        if u.arbitrary()? {
+            Ok(*u.choose(&REGISTERED_CLOSE_CODES)?)
+        } else {
+            let code = u.int_in_range(3000..=4999)?;
+
+            Ok(Self::try_from(code).expect("close code in the valid range"))
+        }
+    }
+}
+
+impl<'a> Arbitrary<'a> for Payload {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Vec::<u8>::arbitrary(u)?.into())
+    }
+}
+
+impl<'a> Arbitrary<'a> for Message {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0..=4)? {
+            0 => Self::text(String::arbitrary(u)?),
+            1 => Self::binary(Payload::arbitrary(u)?),
+            2 => Self::ping(bytes(u, MAX_CONTROL_PAYLOAD_LEN)?),
+            3 => Self::pong(bytes(u, MAX_CONTROL_PAYLOAD_LEN)?),
+            _ => close(u)?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Frame {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let opcode = *u.choose(&[
+            OpCode::Continuation,
+            OpCode::Text,
+            OpCode::Binary,
+            OpCode::Close,
+            OpCode::Ping,
+            OpCode::Pong,
+        ])?;
+
+        // Control frames must not be fragmented and have limited payloads
+        let (is_final, payload) = match opcode {
+            OpCode::Close => (true, close(u)?.payload),
+            OpCode::Ping | OpCode::Pong => (true, bytes(u, MAX_CONTROL_PAYLOAD_LEN)?.into()),
+            OpCode::Text => (u.arbitrary()?, String::arbitrary(u)?.into()),
+            _ => (u.arbitrary()?, Payload::arbitrary(u)?),
+        };
+
+        Ok(Self {
+            opcode,
+            is_final,
+            rsv: 0,
+            payload,
+        })
+    }
+}
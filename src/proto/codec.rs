@@ -6,15 +6,19 @@
 //! [`WebSocketStream`].
 //!
 //! [`WebSocketStream`]: super::WebSocketStream
-use std::hint::unreachable_unchecked;
+
+use std::mem::{replace, take};
 
 use bytes::{Buf, BytesMut};
 use tokio_util::codec::Decoder;
 
+#[cfg(any(feature = "client", feature = "server"))]
+use super::types::Config;
 use super::types::{Frame, Limits, OpCode, Role};
 use crate::{
     mask,
     proto::ProtocolError,
+    unchecked::{self, unchecked},
     utf8::{self, Validator},
     CloseCode, Error, Payload,
 };
@@ -33,28 +37,148 @@ pub(super) struct WebSocketProtocol {
     pub(super) role: Role,
     /// The [`Limits`] imposed on this stream.
     pub(super) limits: Limits,
+    /// Whether unmasked frames are accepted in the server role.
+    accept_unmasked_frames: bool,
+    /// RSV bits reserved by negotiated extensions.
+    pub(super) rsv_bits: u8,
+    /// Whether the reserved RSV bits may be set on every data frame, for
+    /// extensions that transform individual frames.
+    pub(super) per_frame_rsv: bool,
     /// Opcode of the full message.
     fragmented_message_opcode: OpCode,
+    /// RSV bits of the first frame of the full message.
+    fragmented_message_rsv: u8,
     /// Index up to which the payload was processed (unmasked and validated).
     payload_processed: usize,
     /// UTF-8 validator.
     validator: Validator,
+    /// Payload size above which data frames are returned in chunks.
+    chunk_threshold: Option<usize>,
+    /// Data frame whose payload is being returned in chunks.
+    chunked_frame: Option<ChunkedFrame>,
+}
+
+/// State of a data frame whose payload is returned in chunks as it arrives,
+/// each of them as a frame of its own.
+#[derive(Debug)]
+struct ChunkedFrame {
+    /// Opcode of the next chunk, [`OpCode::Continuation`] after the first.
+    opcode: OpCode,
+    /// RSV bits of the next chunk, only set for the first.
+    rsv: u8,
+    /// Whether the frame is the final frame of its message.
+    fin: bool,
+    /// Masking key of the frame, if it is masked.
+    mask: Option<[u8; 4]>,
+    /// Whether the payload is validated as UTF-8.
+    is_text: bool,
+    /// Number of payload bytes that were already returned.
+    returned: usize,
+    /// Number of payload bytes that were not returned yet.
+    remaining: usize,
+    /// Minimum size of a chunk that is not the last one.
+    min_len: usize,
 }
 
 impl WebSocketProtocol {
     /// Creates a new WebSocket codec.
     #[cfg(any(feature = "client", feature = "server"))]
-    pub(super) fn new(role: Role, limits: Limits) -> Self {
+    pub(super) fn new(role: Role, config: Config, limits: Limits) -> Self {
         Self {
             role,
             limits,
+            accept_unmasked_frames: config.accept_unmasked_frames,
+            rsv_bits: 0,
+            per_frame_rsv: false,
             fragmented_message_opcode: OpCode::Continuation,
+            fragmented_message_rsv: 0,
             payload_processed: 0,
             validator: Validator::new(),
+            chunk_threshold: config.chunk_threshold,
+            chunked_frame: None,
         }
     }
 }
 
+impl WebSocketProtocol {
+    /// Returns whether `buf` starts with a complete frame, which can be decoded
+    /// without reading more data from the I/O.
+    pub(super) fn contains_frame(&self, buf: &[u8]) -> bool {
+        if self.chunked_frame.is_some() {
+            return false;
+        }
+
+        let Some(&payload_len_1) = buf.get(1) else {
+            return false;
+        };
+
+        let (header_len, payload_len) = match payload_len_1 & 127 {
+            126 => match buf.get(2..4) {
+                Some(len) => (4, u64::from(u16::from_be_bytes([len[0], len[1]]))),
+                None => return false,
+            },
+            127 => match buf.get(2..10).and_then(|len| <[u8; 8]>::try_from(len).ok()) {
+                Some(len) => (10, u64::from_be_bytes(len)),
+                None => return false,
+            },
+            len => (2, u64::from(len)),
+        };
+        let mask_len = if payload_len_1 >> 7 == 0 { 0 } else { 4 };
+
+        buf.len() as u64 >= payload_len.saturating_add(header_len + mask_len)
+    }
+
+    /// Returns the next chunk of the payload of the chunked frame, or [`None`]
+    /// if not enough of it was received yet.
+    fn decode_chunk(&mut self, src: &mut BytesMut) -> Result<Option<Frame>, Error> {
+        let Some(chunked) = &mut self.chunked_frame else {
+            return Ok(None);
+        };
+        let len = src.len().min(chunked.remaining);
+
+        if len < chunked.remaining.min(chunked.min_len) {
+            src.reserve(chunked.remaining.min(chunked.min_len) - len);
+
+            return Ok(None);
+        }
+
+        let mut payload = src.split_to(len);
+
+        if let Some(mask) = &chunked.mask {
+            mask::frame(mask, &mut payload, chunked.returned & 3);
+        }
+
+        chunked.returned += len;
+        chunked.remaining -= len;
+
+        let opcode = replace(&mut chunked.opcode, OpCode::Continuation);
+        let rsv = take(&mut chunked.rsv);
+        let is_final = chunked.fin && chunked.remaining == 0;
+
+        if chunked.is_text {
+            self.validator.feed(&payload, is_final)?;
+        }
+
+        if chunked.remaining == 0 {
+            self.chunked_frame = None;
+        }
+
+        if (is_final && opcode == OpCode::Continuation)
+            || (!is_final && opcode != OpCode::Continuation)
+        {
+            self.fragmented_message_opcode = opcode;
+            self.fragmented_message_rsv = rsv;
+        }
+
+        Ok(Some(Frame {
+            opcode,
+            payload: Payload::from(payload),
+            is_final,
+            rsv,
+        }))
+    }
+}
+
 /// Macro that returns `Ok(None)` early and reserves missing capacity if buf is
 /// not large enough.
 macro_rules! ensure_buffer_has_space {
@@ -73,12 +197,16 @@ impl Decoder for WebSocketProtocol {
 
     #[allow(clippy::cast_possible_truncation, clippy::too_many_lines)]
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if self.chunked_frame.is_some() {
+            return self.decode_chunk(src);
+        }
+
         // Opcode and payload length must be present
         ensure_buffer_has_space!(src, 2);
 
         // SAFETY: The ensure_buffer_has_space call has validated this
-        let fin_and_rsv = unsafe { src.get_unchecked(0) };
-        let payload_len_1 = unsafe { src.get_unchecked(1) };
+        let fin_and_rsv = unchecked! { unchecked::get(src, 0) };
+        let payload_len_1 = unchecked! { unchecked::get(src, 1) };
 
         // Bit 0
         let fin = fin_and_rsv >> 7 != 0;
@@ -86,12 +214,29 @@ impl Decoder for WebSocketProtocol {
         // Bits 1-3
         let rsv = fin_and_rsv & 0x70;
 
-        if rsv != 0 {
+        // Bits 4-7
+        let opcode = OpCode::try_from(fin_and_rsv & 0xF)?;
+
+        // Only the first frame of a data message may have RSV bits set, unless
+        // extensions transform individual frames, and only those reserved by
+        // negotiated extensions
+        if rsv != 0
+            && (rsv & !self.rsv_bits != 0
+                || opcode.is_control()
+                || (opcode == OpCode::Continuation && !self.per_frame_rsv))
+        {
             return Err(Error::Protocol(ProtocolError::InvalidRsv));
         }
 
-        // Bits 4-7
-        let opcode = OpCode::try_from(fin_and_rsv & 0xF)?;
+        // Any frame may be transformed by per-frame extensions, so their messages are
+        // only validated once decoded
+        let message_rsv = if self.per_frame_rsv {
+            self.rsv_bits
+        } else if opcode == OpCode::Continuation {
+            self.fragmented_message_rsv
+        } else {
+            rsv
+        };
 
         if opcode.is_control() {
             if !fin {
@@ -110,7 +255,7 @@ impl Decoder for WebSocketProtocol {
 
         if mask && self.role == Role::Client {
             return Err(Error::Protocol(ProtocolError::UnexpectedMaskedFrame));
-        } else if !mask && self.role == Role::Server {
+        } else if !mask && self.role == Role::Server && !self.accept_unmasked_frames {
             return Err(Error::Protocol(ProtocolError::UnexpectedUnmaskedFrame));
         }
 
@@ -131,8 +276,8 @@ impl Decoder for WebSocketProtocol {
                 ensure_buffer_has_space!(src, offset + 2);
                 // SAFETY: The ensure_buffer_has_space call has validated this
                 // A conversion from two u8s to a u16 cannot fail
-                payload_length = u16::from_be_bytes(unsafe {
-                    src.get_unchecked(2..4).try_into().unwrap_unchecked()
+                payload_length = u16::from_be_bytes(unchecked! {
+                    unchecked::unwrap(unchecked::get(src, 2..4).try_into())
                 }) as usize;
                 if payload_length <= 125 {
                     return Err(Error::Protocol(ProtocolError::InvalidPayloadLength));
@@ -142,16 +287,22 @@ impl Decoder for WebSocketProtocol {
                 ensure_buffer_has_space!(src, offset + 8);
                 // SAFETY: The ensure_buffer_has_space call has validated this
                 // A conversion from 8 u8s to a u64 cannot fail
-                payload_length = u64::from_be_bytes(unsafe {
-                    src.get_unchecked(2..10).try_into().unwrap_unchecked()
-                }) as usize;
+                let extended_length = u64::from_be_bytes(unchecked! {
+                    unchecked::unwrap(unchecked::get(src, 2..10).try_into())
+                });
+                // The most significant bit must be 0 and the length has to be addressable
+                if extended_length >> 63 != 0 {
+                    return Err(Error::Protocol(ProtocolError::InvalidPayloadLength));
+                }
+                payload_length = usize::try_from(extended_length)
+                    .map_err(|_| Error::Protocol(ProtocolError::InvalidPayloadLength))?;
                 if u16::try_from(payload_length).is_ok() {
                     return Err(Error::Protocol(ProtocolError::InvalidPayloadLength));
                 }
                 offset = 10;
             } else {
                 // SAFETY: Constructed from 7 bits so the max value is 127
-                unsafe { unreachable_unchecked() }
+                unchecked! { unchecked::unreachable() }
             }
         }
 
@@ -169,22 +320,54 @@ impl Decoder for WebSocketProtocol {
             ensure_buffer_has_space!(src, offset);
         }
 
-        if payload_length != 0 {
-            let is_text = opcode == OpCode::Text
+        // Payloads transformed by extensions are validated once decoded
+        let is_text = message_rsv == 0
+            && (opcode == OpCode::Text
                 || (opcode == OpCode::Continuation
-                    && self.fragmented_message_opcode == OpCode::Text);
+                    && self.fragmented_message_opcode == OpCode::Text));
+
+        // Large data frames are returned in chunks, unless extensions transform
+        // each frame as a whole
+        if let Some(threshold) = self.chunk_threshold.filter(|threshold| {
+            payload_length > *threshold && !opcode.is_control() && !self.per_frame_rsv
+        }) {
+            // SAFETY: The ensure_buffer_has_space call has validated this
+            // A conversion from four u8s to an array cannot fail
+            let mask = mask.then(|| {
+                unchecked! {
+                    unchecked::unwrap(unchecked::get(src, offset - 4..offset).try_into())
+                }
+            });
+            src.advance(offset);
+
+            self.chunked_frame = Some(ChunkedFrame {
+                opcode,
+                rsv,
+                fin,
+                mask,
+                is_text,
+                returned: 0,
+                remaining: payload_length,
+                min_len: threshold.max(1),
+            });
+
+            return self.decode_chunk(src);
+        }
+
+        if payload_length != 0 {
             let payload_available = src.len() - offset;
 
             if payload_length > payload_available {
                 // Validate partial frame payload data
                 if is_text {
                     if mask {
-                        unsafe {
-                            let (masking_key, rest_of_payload) = src
-                                .get_unchecked_mut(offset - 4..)
-                                .split_at_mut_unchecked(4);
-                            let payload_masked = rest_of_payload
-                                .get_unchecked_mut(self.payload_processed..payload_available);
+                        unchecked! {
+                            let (masking_key, rest_of_payload) =
+                                unchecked::split_at_mut(unchecked::get_mut(src, offset - 4..), 4);
+                            let payload_masked = unchecked::get_mut(
+                                rest_of_payload,
+                                self.payload_processed..payload_available,
+                            );
 
                             mask::frame(masking_key, payload_masked, self.payload_processed & 3);
                         };
@@ -192,8 +375,9 @@ impl Decoder for WebSocketProtocol {
 
                     // SAFETY: self.payload_data_validated <= payload_available
                     self.validator.feed(
-                        unsafe {
-                            src.get_unchecked(
+                        unchecked! {
+                            unchecked::get(
+                                src,
                                 offset + self.payload_processed..offset + payload_available,
                             )
                         },
@@ -203,18 +387,20 @@ impl Decoder for WebSocketProtocol {
                     self.payload_processed = payload_available;
                 }
 
+                // Reserving does not initialize the memory. The read buffer is filled
+                // directly from its spare capacity and the payload is later split off
+                // of it, so large payloads are never zeroed or copied.
                 src.reserve(payload_length - payload_available);
 
                 return Ok(None);
             }
 
             if mask {
-                unsafe {
-                    let (masking_key, rest_of_payload) = src
-                        .get_unchecked_mut(offset - 4..)
-                        .split_at_mut_unchecked(4);
+                unchecked! {
+                    let (masking_key, rest_of_payload) =
+                        unchecked::split_at_mut(unchecked::get_mut(src, offset - 4..), 4);
                     let payload_masked =
-                        rest_of_payload.get_unchecked_mut(self.payload_processed..payload_length);
+                        unchecked::get_mut(rest_of_payload, self.payload_processed..payload_length);
 
                     mask::frame(masking_key, payload_masked, self.payload_processed & 3);
                 };
@@ -223,8 +409,11 @@ impl Decoder for WebSocketProtocol {
             if is_text {
                 // SAFETY: self.payload_data_validated <= payload_length
                 self.validator.feed(
-                    unsafe {
-                        src.get_unchecked(offset + self.payload_processed..offset + payload_length)
+                    unchecked! {
+                        unchecked::get(
+                            src,
+                            offset + self.payload_processed..offset + payload_length,
+                        )
                     },
                     fin,
                 )?;
@@ -232,18 +421,16 @@ impl Decoder for WebSocketProtocol {
                 // SAFETY: Close frames with a non-zero payload length are validated to not have
                 // a length of 1
                 // A conversion from two u8s to a u16 cannot fail
-                let code = CloseCode::try_from(u16::from_be_bytes(unsafe {
-                    src.get_unchecked(offset..offset + 2)
-                        .try_into()
-                        .unwrap_unchecked()
+                let code = CloseCode::try_from(u16::from_be_bytes(unchecked! {
+                    unchecked::unwrap(unchecked::get(src, offset..offset + 2).try_into())
                 }))?;
                 if !code.is_sendable() {
                     return Err(Error::Protocol(ProtocolError::InvalidCloseCode));
                 }
 
                 // SAFETY: payload_length <= src.len()
-                let _reason = utf8::parse_str(unsafe {
-                    src.get_unchecked(offset + 2..offset + payload_length)
+                let _reason = utf8::parse_str(unchecked! {
+                    unchecked::get(src, offset + 2..offset + payload_length)
                 })?;
             }
         }
@@ -252,7 +439,7 @@ impl Decoder for WebSocketProtocol {
         src.advance(offset);
         // Take the payload
         let mut payload = Payload::from(src.split_to(payload_length));
-        payload.set_utf8_validated(opcode == OpCode::Text && fin);
+        payload.set_utf8_validated(opcode == OpCode::Text && fin && message_rsv == 0);
 
         // It is possible to receive intermediate control frames between a large other
         // frame. We therefore can't simply reset the fragmented opcode after we receive
@@ -261,6 +448,7 @@ impl Decoder for WebSocketProtocol {
             // Full chunked message received (and opcode is Continuation)
             // or first frame of a multi-frame message received
             self.fragmented_message_opcode = opcode;
+            self.fragmented_message_rsv = rsv;
         }
         // In all other cases, we have either a continuation or control frame, neither
         // of which change change the opcode being assembled
@@ -271,6 +459,7 @@ impl Decoder for WebSocketProtocol {
             opcode,
             payload,
             is_final: fin,
+            rsv,
         }))
     }
 }
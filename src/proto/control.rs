@@ -0,0 +1,137 @@
+//! Handle for sending control frames over a [`WebSocketStream`] from other
+//! tasks.
+//!
+//! [`WebSocketStream`]: super::WebSocketStream
+use std::{
+    mem::take,
+    sync::{Arc, Mutex, MutexGuard, PoisonError, Weak},
+    task::Waker,
+};
+
+use super::types::{Frame, Message, OpCode, Payload};
+use crate::{CloseCode, Error};
+
+/// Frames queued via [`ControlSender`]s and the waker of the task polling the
+/// stream.
+#[derive(Debug, Default)]
+struct State {
+    /// Frames that were queued but not taken by the stream yet.
+    frames: Vec<Frame>,
+    /// Waker of the task that last polled the stream.
+    waker: Option<Waker>,
+    /// Whether a close frame was queued.
+    closed: bool,
+}
+
+/// Queue of control frames, owned by the stream.
+#[derive(Debug, Default)]
+pub(super) struct ControlQueue {
+    /// State shared with the senders.
+    state: Mutex<State>,
+}
+
+impl ControlQueue {
+    /// Locks the state, ignoring poisoning since it is never left in an
+    /// inconsistent state.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Takes the queued frames and registers `waker` to be woken once more
+    /// frames are queued.
+    pub(super) fn take(&self, waker: &Waker) -> Vec<Frame> {
+        let mut state = self.lock();
+
+        if !state.waker.as_ref().is_some_and(|w| w.will_wake(waker)) {
+            state.waker = Some(waker.clone());
+        }
+
+        take(&mut state.frames)
+    }
+}
+
+/// Cloneable handle for sending ping, pong and close frames over a
+/// [`WebSocketStream`] without exclusive access to it, created via
+/// [`WebSocketStream::control_sender`].
+///
+/// This allows keeping a connection alive or closing it while another task
+/// writes a long fragmented message, for example via
+/// [`WebSocketStream::send_binary_reader`]. Queued frames are sent the next
+/// time the stream is polled for reading or writing and pings and pongs are
+/// sent in between the frames of the message being written.
+///
+/// [`WebSocketStream`]: super::WebSocketStream
+/// [`WebSocketStream::control_sender`]: super::WebSocketStream::control_sender
+/// [`WebSocketStream::send_binary_reader`]: super::WebSocketStream::send_binary_reader
+#[derive(Debug, Clone)]
+pub struct ControlSender {
+    /// Queue of the stream, gone once the stream is dropped.
+    queue: Weak<ControlQueue>,
+}
+
+impl ControlSender {
+    /// Creates a sender for `queue`.
+    pub(super) fn new(queue: &Arc<ControlQueue>) -> Self {
+        Self {
+            queue: Arc::downgrade(queue),
+        }
+    }
+
+    /// Queues a ping frame.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::AlreadyClosed`] if the stream was dropped
+    /// or a close frame was queued via a [`ControlSender`].
+    pub fn ping<P: Into<Payload>>(&self, payload: P) -> Result<(), Error> {
+        self.push(Message::ping(payload).into())
+    }
+
+    /// Queues a pong frame.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::AlreadyClosed`] if the stream was dropped
+    /// or a close frame was queued via a [`ControlSender`].
+    pub fn pong<P: Into<Payload>>(&self, payload: P) -> Result<(), Error> {
+        self.push(Message::pong(payload).into())
+    }
+
+    /// Queues a close frame with the given close code and reason, which starts
+    /// the close handshake.
+    ///
+    /// No more data frames are queued afterwards, so a message that is being
+    /// written via [`WebSocketStream::send_binary_reader`] is cut short and
+    /// sending it fails with [`Error::AlreadyClosed`].
+    ///
+    /// [`WebSocketStream::send_binary_reader`]: super::WebSocketStream::send_binary_reader
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::AlreadyClosed`] if the stream was dropped
+    /// or a close frame was queued via a [`ControlSender`] before.
+    pub fn close(&self, code: Option<CloseCode>, reason: &str) -> Result<(), Error> {
+        self.push(Message::close(code, reason).into())
+    }
+
+    /// Queues a frame and wakes the task polling the stream.
+    fn push(&self, frame: Frame) -> Result<(), Error> {
+        let queue = self.queue.upgrade().ok_or(Error::AlreadyClosed)?;
+        let mut state = queue.lock();
+
+        if state.closed {
+            return Err(Error::AlreadyClosed);
+        }
+
+        state.closed = frame.opcode == OpCode::Close;
+        state.frames.push(frame);
+        let waker = state.waker.take();
+        drop(state);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        Ok(())
+    }
+}
@@ -14,8 +14,7 @@ pub enum ProtocolError {
     InvalidOpcode,
     /// An invalid payload length was received.
     InvalidPayloadLength,
-    /// An invalid RSV was received. This is used by extensions, which are
-    /// currently unsupported.
+    /// An RSV bit that is not reserved by a negotiated extension was received.
     InvalidRsv,
     /// An invalid UTF-8 segment was received when valid UTF-8 was expected.
     InvalidUtf8,
@@ -0,0 +1,250 @@
+//! Hooks for WebSocket extensions that transform the payloads of data
+//! messages, as described in [RFC 6455, Section 9].
+//!
+//! [RFC 6455, Section 9]: https://datatracker.ietf.org/doc/html/rfc6455#section-9
+use std::fmt;
+
+use super::Payload;
+use crate::Error;
+
+/// The RSV1 bit of a frame header.
+pub const RSV1: u8 = 0x40;
+/// The RSV2 bit of a frame header.
+pub const RSV2: u8 = 0x20;
+/// The RSV3 bit of a frame header.
+pub const RSV3: u8 = 0x10;
+
+/// Per-connection state of a negotiated WebSocket extension, transforming the
+/// payloads of outgoing and incoming data messages.
+///
+/// Extensions operate on whole messages: outgoing payloads are transformed
+/// before they are split into frames and incoming payloads after all frames of
+/// a message were received. Control frames are never transformed.
+///
+/// Codecs are boxed by the stream, so they must be [`Send`] for a
+/// `WebSocketStream<T>` to be [`Send`] whenever `T` is.
+pub trait ExtensionCodec: Send {
+    /// Returns the RSV bits of the frame header reserved by this extension, as
+    /// a combination of [`RSV1`], [`RSV2`] and [`RSV3`].
+    fn rsv_bits(&self) -> u8;
+
+    /// Transforms the payload of an outgoing data message and returns it along
+    /// with the RSV bits to set on the first frame of the message, which must
+    /// be a subset of [`ExtensionCodec::rsv_bits`].
+    ///
+    /// # Errors
+    ///
+    /// Errors returned by this method are returned when sending the message.
+    fn encode(&mut self, payload: Payload) -> Result<(Payload, u8), Error>;
+
+    /// Transforms the payload of an incoming data message. `rsv` contains the
+    /// bits reserved by this extension that were set on the first frame of the
+    /// message.
+    ///
+    /// # Errors
+    ///
+    /// Errors returned by this method are returned when receiving the message
+    /// and fail the connection.
+    fn decode(&mut self, payload: Payload, rsv: u8) -> Result<Payload, Error>;
+
+    /// Returns whether this extension transforms the payloads of individual
+    /// frames instead of whole messages, like legacy per-frame compression
+    /// extensions do. In that case, the RSV bits reserved by it may be set on
+    /// any data frame.
+    ///
+    /// If any negotiated extension operates on frames, all of them do. The
+    /// default is `false`.
+    fn per_frame(&self) -> bool {
+        false
+    }
+
+    /// Returns the byte counts of the payloads transformed by this extension
+    /// if it compresses them. The default is [`None`].
+    fn compression_stats(&self) -> Option<CompressionStats> {
+        None
+    }
+}
+
+/// Byte counts of the payloads of data messages compressed and decompressed
+/// by an extension, accumulated over the lifetime of a connection.
+///
+/// Payloads that were sent or received uncompressed, e.g. because they are
+/// smaller than the compression threshold, count towards both sizes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompressionStats {
+    /// Bytes of outgoing payloads before compression.
+    sent_uncompressed: u64,
+    /// Bytes of outgoing payloads after compression.
+    sent_compressed: u64,
+    /// Bytes of incoming payloads before decompression.
+    received_compressed: u64,
+    /// Bytes of incoming payloads after decompression.
+    received_uncompressed: u64,
+}
+
+impl CompressionStats {
+    /// Creates statistics without any bytes recorded.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records an outgoing payload of `uncompressed` bytes that was sent as
+    /// `compressed` bytes.
+    pub fn record_sent(&mut self, uncompressed: usize, compressed: usize) {
+        self.sent_uncompressed += uncompressed as u64;
+        self.sent_compressed += compressed as u64;
+    }
+
+    /// Records an incoming payload of `compressed` bytes that decompressed to
+    /// `uncompressed` bytes.
+    pub fn record_received(&mut self, compressed: usize, uncompressed: usize) {
+        self.received_compressed += compressed as u64;
+        self.received_uncompressed += uncompressed as u64;
+    }
+
+    /// Returns the number of bytes of outgoing payloads before compression.
+    #[must_use]
+    pub fn sent_uncompressed(&self) -> u64 {
+        self.sent_uncompressed
+    }
+
+    /// Returns the number of bytes of outgoing payloads after compression.
+    #[must_use]
+    pub fn sent_compressed(&self) -> u64 {
+        self.sent_compressed
+    }
+
+    /// Returns the number of bytes of incoming payloads before decompression.
+    #[must_use]
+    pub fn received_compressed(&self) -> u64 {
+        self.received_compressed
+    }
+
+    /// Returns the number of bytes of incoming payloads after decompression.
+    #[must_use]
+    pub fn received_uncompressed(&self) -> u64 {
+        self.received_uncompressed
+    }
+
+    /// Returns the compression ratio of outgoing payloads, i.e. their size
+    /// before compression divided by their size after it, or [`None`] if
+    /// nothing was sent yet. Values above `1.0` mean that compression saved
+    /// bandwidth.
+    #[must_use]
+    pub fn sent_ratio(&self) -> Option<f64> {
+        ratio(self.sent_uncompressed, self.sent_compressed)
+    }
+
+    /// Returns the compression ratio of incoming payloads, i.e. their size
+    /// after decompression divided by their size before it, or [`None`] if
+    /// nothing was received yet.
+    #[must_use]
+    pub fn received_ratio(&self) -> Option<f64> {
+        ratio(self.received_uncompressed, self.received_compressed)
+    }
+
+    /// Adds the byte counts of `other` to these.
+    fn add(&mut self, other: &Self) {
+        self.sent_uncompressed += other.sent_uncompressed;
+        self.sent_compressed += other.sent_compressed;
+        self.received_compressed += other.received_compressed;
+        self.received_uncompressed += other.received_uncompressed;
+    }
+}
+
+/// Divides `uncompressed` by `compressed`, unless `compressed` is zero.
+#[allow(clippy::cast_precision_loss)]
+fn ratio(uncompressed: u64, compressed: u64) -> Option<f64> {
+    (compressed != 0).then(|| uncompressed as f64 / compressed as f64)
+}
+
+/// The extensions negotiated for a connection, in the order they were agreed
+/// on.
+#[derive(Default)]
+pub(super) struct Extensions(Vec<Box<dyn ExtensionCodec>>);
+
+impl Extensions {
+    /// Creates the set of negotiated extensions from their codecs.
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(super) fn new(codecs: Vec<Box<dyn ExtensionCodec>>) -> Self {
+        Self(codecs)
+    }
+
+    /// Adds an extension that was negotiated out of band, which transforms
+    /// payloads after the other extensions.
+    pub(super) fn push(&mut self, codec: Box<dyn ExtensionCodec>) {
+        self.0.push(codec);
+    }
+
+    /// Returns the number of negotiated extensions.
+    pub(super) fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns whether no extensions were negotiated.
+    pub(super) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the RSV bits reserved by all extensions.
+    pub(super) fn rsv_bits(&self) -> u8 {
+        self.0
+            .iter()
+            .fold(0, |bits, extension| bits | extension.rsv_bits())
+    }
+
+    /// Returns whether the extensions transform individual frames instead of
+    /// whole messages.
+    pub(super) fn per_frame(&self) -> bool {
+        self.0.iter().any(|extension| extension.per_frame())
+    }
+
+    /// Returns the sum of the compression statistics of all extensions that
+    /// report them, or [`None`] if none do.
+    pub(super) fn compression_stats(&self) -> Option<CompressionStats> {
+        self.0
+            .iter()
+            .filter_map(|extension| extension.compression_stats())
+            .reduce(|mut total, stats| {
+                total.add(&stats);
+
+                total
+            })
+    }
+
+    /// Transforms the payload of an outgoing data message with all extensions
+    /// in order and returns the RSV bits to set on its first frame.
+    pub(super) fn encode(&mut self, mut payload: Payload) -> Result<(Payload, u8), Error> {
+        let mut rsv = 0;
+
+        for extension in &mut self.0 {
+            let (encoded, bits) = extension.encode(payload)?;
+            payload = encoded;
+            rsv |= bits & extension.rsv_bits();
+        }
+
+        Ok((payload, rsv))
+    }
+
+    /// Transforms the payload of an incoming data message with all extensions
+    /// in reverse order.
+    pub(super) fn decode(&mut self, mut payload: Payload, rsv: u8) -> Result<Payload, Error> {
+        for extension in self.0.iter_mut().rev() {
+            let bits = rsv & extension.rsv_bits();
+            payload = extension.decode(payload, bits)?;
+        }
+
+        Ok(payload)
+    }
+}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.0.len())
+            .field("rsv_bits", &self.rsv_bits())
+            .field("per_frame", &self.per_frame())
+            .finish()
+    }
+}
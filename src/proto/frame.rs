@@ -0,0 +1,162 @@
+//! Stateless encoding and decoding of single frames, independent of a
+//! [`WebSocketStream`].
+//!
+//! These functions allow writing roundtrip property tests and building custom
+//! transports on top of the same wire format handling. Unlike the decoder of
+//! the [`WebSocketStream`], they have no knowledge of previous frames, so
+//! validation that spans multiple frames, such as the order of continuation
+//! frames or the UTF-8 validity of fragmented text messages, is up to the
+//! caller.
+//!
+//! [`WebSocketStream`]: super::WebSocketStream
+use bytes::{Buf, BufMut, BytesMut};
+
+use super::types::{Frame, Limits, OpCode};
+use crate::{mask, proto::ProtocolError, utf8, CloseCode, Error, Payload};
+
+/// Encodes `frame` into `dst`, masking its payload with `mask` if given.
+///
+/// Clients must mask all frames they send with a random masking key, servers
+/// must not mask their frames.
+pub fn encode_frame(frame: &Frame, mask: Option<[u8; 4]>, dst: &mut BytesMut) {
+    let mut header = [0; 10];
+    let header_len = frame.encode(&mut header) as usize;
+
+    if mask.is_some() {
+        header[1] |= 1 << 7;
+    }
+
+    dst.reserve(header_len + usize::from(mask.is_some()) * 4 + frame.payload.len());
+    dst.put_slice(&header[..header_len]);
+
+    if let Some(mask) = mask {
+        dst.put_slice(&mask);
+        let payload_start = dst.len();
+        dst.put_slice(&frame.payload);
+        mask::frame(&mask, &mut dst[payload_start..], 0);
+    } else {
+        dst.put_slice(&frame.payload);
+    }
+}
+
+/// Decodes a single frame from the start of `src`, unmasking its payload if it
+/// is masked.
+///
+/// Returns [`None`] and leaves `src` untouched if it does not contain a full
+/// frame yet. Otherwise, the frame is split off of `src`.
+///
+/// The frame is validated as far as possible without knowing the previous
+/// frames: control frames must be final and at most 125 bytes large, payload
+/// lengths must be minimally encoded and close frames must carry a valid close
+/// code and a UTF-8 reason. The payload of final text frames that are not part
+/// of a fragmented message and have no RSV bits set is validated to be UTF-8.
+/// RSV bits are not validated since they depend on the negotiated extensions.
+///
+/// # Errors
+///
+/// This function fails if the frame is invalid or its payload is larger than
+/// allowed by `limits`.
+#[allow(clippy::cast_possible_truncation)]
+pub fn decode_frame(src: &mut BytesMut, limits: &Limits) -> Result<Option<Frame>, Error> {
+    if src.len() < 2 {
+        return Ok(None);
+    }
+
+    let is_final = src[0] >> 7 != 0;
+    let rsv = src[0] & 0x70;
+    let opcode = OpCode::try_from(src[0] & 0xF)?;
+    let masked = src[1] >> 7 != 0;
+    let mut payload_length = (src[1] & 127) as usize;
+    let mut offset = 2;
+
+    if opcode.is_control() {
+        if !is_final {
+            return Err(Error::Protocol(ProtocolError::FragmentedControlFrame));
+        }
+
+        if payload_length > 125 || (opcode == OpCode::Close && payload_length == 1) {
+            return Err(Error::Protocol(ProtocolError::InvalidPayloadLength));
+        }
+    }
+
+    if payload_length == 126 {
+        let Some(length) = src.get(2..4) else {
+            return Ok(None);
+        };
+        payload_length = u16::from_be_bytes([length[0], length[1]]) as usize;
+        if payload_length <= 125 {
+            return Err(Error::Protocol(ProtocolError::InvalidPayloadLength));
+        }
+        offset = 4;
+    } else if payload_length == 127 {
+        let Some(length) = src.get(2..10) else {
+            return Ok(None);
+        };
+        let mut length_bytes = [0; 8];
+        length_bytes.copy_from_slice(length);
+        let extended_length = u64::from_be_bytes(length_bytes);
+        // The most significant bit must be 0 and the length has to be addressable
+        if extended_length >> 63 != 0 {
+            return Err(Error::Protocol(ProtocolError::InvalidPayloadLength));
+        }
+        payload_length = usize::try_from(extended_length)
+            .map_err(|_| Error::Protocol(ProtocolError::InvalidPayloadLength))?;
+        if u16::try_from(payload_length).is_ok() {
+            return Err(Error::Protocol(ProtocolError::InvalidPayloadLength));
+        }
+        offset = 10;
+    }
+
+    if payload_length > limits.max_payload_len {
+        return Err(Error::PayloadTooLong {
+            len: payload_length,
+            max_len: limits.max_payload_len,
+        });
+    }
+
+    let mut masking_key = [0; 4];
+    if masked {
+        let Some(key) = src.get(offset..offset + 4) else {
+            return Ok(None);
+        };
+        masking_key.copy_from_slice(key);
+        offset += 4;
+    }
+
+    let payload_available = src.len() - offset;
+    if payload_available < payload_length {
+        src.reserve(payload_length - payload_available);
+
+        return Ok(None);
+    }
+
+    src.advance(offset);
+    let mut payload = src.split_to(payload_length);
+
+    if masked {
+        mask::frame(&masking_key, &mut payload, 0);
+    }
+
+    if opcode == OpCode::Close && !payload.is_empty() {
+        let code = CloseCode::try_from(u16::from_be_bytes([payload[0], payload[1]]))?;
+        if !code.is_sendable() {
+            return Err(Error::Protocol(ProtocolError::InvalidCloseCode));
+        }
+
+        utf8::parse_str(&payload[2..])?;
+    }
+
+    let mut payload = Payload::from(payload);
+
+    if opcode == OpCode::Text && is_final && rsv == 0 {
+        utf8::parse_str(&payload)?;
+        payload.set_utf8_validated(true);
+    }
+
+    Ok(Some(Frame {
+        opcode,
+        is_final,
+        rsv,
+        payload,
+    }))
+}
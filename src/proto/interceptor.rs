@@ -0,0 +1,71 @@
+//! Hooks for observing and rejecting the frames of a connection, e.g. for
+//! audit logging or metering.
+use std::fmt;
+
+use super::{ConnectionId, Frame};
+
+/// Hooks that are called for every frame received or sent by a
+/// [`WebSocketStream`], installed via
+/// [`WebSocketStream::set_frame_interceptor`].
+///
+/// Frames are passed as they appear on the wire, i.e. after outgoing payloads
+/// were transformed by extensions and before incoming payloads are, but never
+/// masked. Both hooks are passed the [`ConnectionId`] of the stream, so that a
+/// single interceptor can attribute frames of several connections, and accept
+/// all frames by default.
+///
+/// Interceptors must be [`Send`] since the stream stores them type-erased,
+/// which keeps a `WebSocketStream<T>` [`Send`] whenever `T` is. When the
+/// stream runs on a [`LocalSet`], the hooks are called on its thread and can
+/// reach state that is not [`Send`] through a [`thread_local!`].
+///
+/// [`WebSocketStream`]: super::WebSocketStream
+/// [`WebSocketStream::set_frame_interceptor`]: super::WebSocketStream::set_frame_interceptor
+/// [`LocalSet`]: https://docs.rs/tokio/latest/tokio/task/struct.LocalSet.html
+pub trait FrameInterceptor: Send {
+    /// Called for every frame received, before it is processed.
+    ///
+    /// # Errors
+    ///
+    /// Rejecting a frame fails the connection with
+    /// [`CloseCode::POLICY_VIOLATION`] and returns the error as
+    /// [`Error::FrameRejected`] when receiving.
+    ///
+    /// [`CloseCode::POLICY_VIOLATION`]: super::CloseCode::POLICY_VIOLATION
+    /// [`Error::FrameRejected`]: crate::Error::FrameRejected
+    fn on_frame_received(
+        &mut self,
+        id: ConnectionId,
+        frame: &Frame,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let _ = (id, frame);
+
+        Ok(())
+    }
+
+    /// Called for every frame before it is queued for sending.
+    ///
+    /// # Errors
+    ///
+    /// Rejecting a frame of a message sent by the application fails sending
+    /// the message with [`Error::FrameRejected`] and none of its frames are
+    /// sent. Rejected frames that the stream sends on its own, such as replies
+    /// to pings, are dropped, except for close frames, which are always sent.
+    ///
+    /// [`Error::FrameRejected`]: crate::Error::FrameRejected
+    fn on_frame_sent(
+        &mut self,
+        id: ConnectionId,
+        frame: &Frame,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let _ = (id, frame);
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for dyn FrameInterceptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FrameInterceptor")
+    }
+}
@@ -1,15 +1,38 @@
 //! This module contains a correct and complete implementation of [RFC6455](https://datatracker.ietf.org/doc/html/rfc6455).
 //!
-//! Any extensions are currently not implemented.
+//! Extensions can be implemented via [`ExtensionCodec`] and frames can be
+//! observed via [`FrameInterceptor`].
+//!
+//! Single frames can be encoded and decoded independently of a
+//! [`WebSocketStream`] via [`encode_frame`] and [`decode_frame`].
 #[cfg(any(feature = "client", feature = "server"))]
 pub(crate) use self::types::Role;
 pub use self::{
+    control::ControlSender,
     error::ProtocolError,
+    extension::{CompressionStats, ExtensionCodec, RSV1, RSV2, RSV3},
+    frame::{decode_frame, encode_frame},
+    interceptor::FrameInterceptor,
+    pool::{BoundedPool, BufferPool},
     stream::WebSocketStream,
-    types::{CloseCode, Config, Limits, Message, Payload},
+    types::{
+        CloseCode, CloseEvent, CloseInitiator, CloseReason, Config, ConnectionId, Frame, Limits,
+        Message, OpCode, Payload, PingPayload, SendInfo, StreamState, Utf8Payload,
+    },
 };
 
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
 mod codec;
+mod control;
 mod error;
+mod extension;
+mod frame;
+mod interceptor;
+mod pool;
+#[cfg(feature = "serde")]
+mod serde;
 mod stream;
+#[cfg(feature = "tungstenite")]
+mod tungstenite;
 mod types;
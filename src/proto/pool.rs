@@ -0,0 +1,118 @@
+//! Pooling of the buffers that streams read into, to recycle them across
+//! connections.
+use std::{
+    fmt,
+    sync::{Mutex, PoisonError},
+};
+
+use bytes::BytesMut;
+
+/// Source of the buffers that a [`WebSocketStream`] reads frames into,
+/// installed via [`Config::buffer_pool`].
+///
+/// Streams acquire their read buffer from the pool when they are created and
+/// after shrinking it, and acquire the buffer that fragmented messages are
+/// assembled in when their first frame is received. The read buffer and an
+/// incomplete message are released back to the pool when the stream is
+/// dropped. This recycles allocations across connections instead of
+/// allocating them anew for every connection.
+///
+/// Received payloads are split off these buffers without copying, so a
+/// released buffer may still share its allocation with payloads that the
+/// application holds on to. Reserving capacity in it only reuses the
+/// allocation once those were dropped. Payloads of received messages can be
+/// released to the pool via [`BytesMut::from`] once they are no longer
+/// needed.
+///
+/// [`WebSocketStream`]: super::WebSocketStream
+/// [`Config::buffer_pool`]: super::Config::buffer_pool
+pub trait BufferPool: Send + Sync {
+    /// Returns an empty buffer with a capacity of at least `capacity` bytes.
+    fn acquire(&self, capacity: usize) -> BytesMut;
+
+    /// Takes back a buffer that is no longer used. Its contents are stale.
+    fn release(&self, buf: BytesMut);
+}
+
+impl fmt::Debug for dyn BufferPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BufferPool")
+    }
+}
+
+/// A [`BufferPool`] that keeps up to a fixed number of released buffers and
+/// allocates new ones once it runs out of them.
+///
+/// It can be created in a `static`, which makes it usable with
+/// [`Config::buffer_pool`]:
+///
+/// ```
+/// use tokio_websockets::{proto::BoundedPool, Config};
+///
+/// static POOL: BoundedPool = BoundedPool::new(1024);
+///
+/// let config = Config::default().buffer_pool(&POOL);
+/// ```
+///
+/// [`Config::buffer_pool`]: super::Config::buffer_pool
+#[derive(Debug)]
+pub struct BoundedPool {
+    /// The released buffers.
+    buffers: Mutex<Vec<BytesMut>>,
+    /// Maximum number of released buffers that are kept.
+    max_buffers: usize,
+}
+
+impl BoundedPool {
+    /// Creates a new pool that keeps up to `max_buffers` released buffers.
+    #[must_use]
+    pub const fn new(max_buffers: usize) -> Self {
+        Self {
+            buffers: Mutex::new(Vec::new()),
+            max_buffers,
+        }
+    }
+
+    /// Returns the number of released buffers that are currently kept.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.buffers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .len()
+    }
+
+    /// Returns whether no released buffers are currently kept.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl BufferPool for BoundedPool {
+    fn acquire(&self, capacity: usize) -> BytesMut {
+        let buf = self
+            .buffers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .pop();
+
+        match buf {
+            Some(mut buf) => {
+                buf.clear();
+                buf.reserve(capacity);
+
+                buf
+            }
+            None => BytesMut::with_capacity(capacity),
+        }
+    }
+
+    fn release(&self, buf: BytesMut) {
+        let mut buffers = self.buffers.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if buffers.len() < self.max_buffers {
+            buffers.push(buf);
+        }
+    }
+}
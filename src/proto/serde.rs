@@ -0,0 +1,161 @@
+//! Implementations of [`Serialize`] and [`Deserialize`] for persisting and
+//! replaying messages.
+//!
+//! Messages are represented as an externally tagged enum with the variants
+//! `Text`, `Binary`, `Ping`, `Pong` and `Close`. The latter holds an optional
+//! `CloseFrame` with the close code and reason, and is empty for close messages
+//! without a payload. Close codes are represented as integers.
+use ::serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+use bytes::Bytes;
+
+use super::types::{CloseCode, Message, OpCode, Payload};
+use crate::{
+    unchecked::{self, unchecked},
+    utf8,
+};
+
+/// Maximum payload length of control frames.
+const MAX_CONTROL_PAYLOAD_LEN: usize = 125;
+
+/// Borrowed representation of a [`Message`] for serialization.
+#[derive(Serialize)]
+#[serde(rename = "Message")]
+enum MessageRef<'a> {
+    /// A text message.
+    Text(&'a str),
+    /// A binary message.
+    Binary(&'a Payload),
+    /// A ping message.
+    Ping(&'a Payload),
+    /// A pong message.
+    Pong(&'a Payload),
+    /// A close message.
+    Close(Option<CloseFrameRef<'a>>),
+}
+
+/// Borrowed representation of the payload of a close message.
+#[derive(Serialize)]
+#[serde(rename = "CloseFrame")]
+struct CloseFrameRef<'a> {
+    /// The close code.
+    code: CloseCode,
+    /// The close reason.
+    reason: &'a str,
+}
+
+/// Owned representation of a [`Message`] for deserialization.
+#[derive(Deserialize)]
+#[serde(rename = "Message")]
+enum MessageOwned {
+    /// A text message.
+    Text(String),
+    /// A binary message.
+    Binary(Bytes),
+    /// A ping message.
+    Ping(Bytes),
+    /// A pong message.
+    Pong(Bytes),
+    /// A close message.
+    Close(Option<CloseFrameOwned>),
+}
+
+/// Owned representation of the payload of a close message.
+#[derive(Deserialize)]
+#[serde(rename = "CloseFrame")]
+struct CloseFrameOwned {
+    /// The close code.
+    code: CloseCode,
+    /// The close reason.
+    reason: String,
+}
+
+/// Fails if `len` exceeds the maximum payload length of control frames.
+fn check_control_len<E: de::Error>(len: usize) -> Result<(), E> {
+    if len > MAX_CONTROL_PAYLOAD_LEN {
+        return Err(E::invalid_length(
+            len,
+            &"at most 125 bytes of control frame payload",
+        ));
+    }
+
+    Ok(())
+}
+
+impl Serialize for CloseCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u16((*self).into())
+    }
+}
+
+impl<'de> Deserialize<'de> for CloseCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = u16::deserialize(deserializer)?;
+
+        Self::try_from(code).map_err(|_| {
+            de::Error::invalid_value(de::Unexpected::Unsigned(code.into()), &"a valid close code")
+        })
+    }
+}
+
+impl Serialize for Payload {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Payload {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Bytes::deserialize(deserializer).map(Self::from)
+    }
+}
+
+impl Serialize for Message {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let message = match self.opcode {
+            // Messages created via Message::text from bytes may be invalid UTF-8
+            OpCode::Text if self.payload.is_utf8_validated() => {
+                // SAFETY: Received messages were validated to be valid UTF-8
+                MessageRef::Text(unchecked! { unchecked::from_utf8(&self.payload) })
+            }
+            OpCode::Text => {
+                MessageRef::Text(utf8::parse_str(&self.payload).map_err(ser::Error::custom)?)
+            }
+            OpCode::Ping => MessageRef::Ping(&self.payload),
+            OpCode::Pong => MessageRef::Pong(&self.payload),
+            OpCode::Close if self.payload.is_empty() => MessageRef::Close(None),
+            OpCode::Close => {
+                let (code, reason) = self.as_close().expect("opcode is Close");
+
+                MessageRef::Close(Some(CloseFrameRef { code, reason }))
+            }
+            OpCode::Binary | OpCode::Continuation => MessageRef::Binary(&self.payload),
+        };
+
+        message.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Message {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match MessageOwned::deserialize(deserializer)? {
+            MessageOwned::Text(text) => Self::text(text),
+            MessageOwned::Binary(payload) => Self::binary(payload),
+            MessageOwned::Ping(payload) => {
+                check_control_len(payload.len())?;
+
+                Self::ping(payload)
+            }
+            MessageOwned::Pong(payload) => {
+                check_control_len(payload.len())?;
+
+                Self::pong(payload)
+            }
+            MessageOwned::Close(None) => Self::close(None, ""),
+            MessageOwned::Close(Some(frame)) => {
+                check_control_len(frame.reason.len() + 2)?;
+
+                Self::close(Some(frame.code), &frame.reason)
+            }
+        })
+    }
+}
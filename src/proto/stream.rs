@@ -2,29 +2,70 @@
 //! implementation that provides [`futures_sink::Sink`] and
 //! [`futures_core::Stream`] implementations that take [`Message`] as a
 //! parameter.
+#[cfg(feature = "checked")]
+use std::sync::OnceLock;
+#[cfg(not(feature = "checked"))]
+use std::task::{RawWaker, RawWakerVTable};
+#[cfg(any(feature = "client", feature = "server"))]
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
 use std::{
     collections::VecDeque,
-    hint::unreachable_unchecked,
-    io,
+    fmt,
+    future::{poll_fn, Future},
+    io::{self, IoSlice},
     mem::{replace, take},
     pin::Pin,
-    task::{ready, Context, Poll},
+    sync::Arc,
+    task::{ready, Context, Poll, Waker},
+    time::Duration,
 };
 
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
 use futures_core::Stream;
 use futures_sink::Sink;
+#[cfg(all(feature = "client", feature = "server"))]
+use tokio::io::DuplexStream;
 use tokio::io::{AsyncRead, AsyncWrite};
-use tokio_util::{codec::FramedRead, io::poll_write_buf};
+#[cfg(any(feature = "client", feature = "server"))]
+use tokio::time::{Instant, Sleep};
+use tokio_util::{codec::FramedRead, io::poll_read_buf, sync::CancellationToken};
 
 #[cfg(any(feature = "client", feature = "server"))]
 use super::types::Limits;
 use super::{
     codec::WebSocketProtocol,
-    types::{Frame, Message, OpCode, Payload, Role, StreamState},
+    control::{ControlQueue, ControlSender},
+    extension::{CompressionStats, ExtensionCodec, Extensions, RSV1, RSV2, RSV3},
+    interceptor::FrameInterceptor,
+    types::{
+        parse_close_payload, CloseEvent, CloseInitiator, ConnectionId, Frame, Message, OpCode,
+        Payload, Role, SendInfo, StreamState, Utf8Payload,
+    },
     Config,
 };
-use crate::{CloseCode, Error};
+use crate::{
+    unchecked::{self, unchecked},
+    utf8, CloseCode, Error,
+};
+
+/// Maximum number of buffers passed to a single vectored write when flushing
+/// queued frames.
+const MAX_WRITE_SLICES: usize = 64;
+
+/// Maximum number of sent pings that received pongs are matched against.
+#[cfg(any(feature = "client", feature = "server"))]
+const MAX_OUTSTANDING_PINGS: usize = 16;
+
+/// Size of the in-memory buffer in each direction of a
+/// [`WebSocketStream::pair`].
+#[cfg(all(feature = "client", feature = "server"))]
+const PAIR_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Observer called once a [`WebSocketStream`] is closed.
+type CloseObserver = Box<dyn FnOnce(CloseEvent<'_>) + Send>;
 
 /// Helper struct for storing a frame header, the header size and payload.
 #[derive(Debug)]
@@ -39,6 +80,227 @@ struct EncodedFrame {
     payload: Payload,
 }
 
+impl EncodedFrame {
+    /// Encodes the header of a frame whose payload was masked with `mask`, if
+    /// any. The payload itself is written to the I/O as-is.
+    fn new(frame: Frame, mask: Option<[u8; 4]>) -> Self {
+        let mut header = [0; 10];
+        let header_len = frame.encode(&mut header);
+
+        if mask.is_some() {
+            header[1] |= 1 << 7;
+        }
+
+        Self {
+            header,
+            header_len,
+            mask,
+            payload: frame.payload,
+        }
+    }
+
+    /// Returns the header, mask and payload of this frame in the order they
+    /// are written to the I/O.
+    fn parts(&self) -> [&[u8]; 3] {
+        [
+            // SAFETY: header_len is at most 10
+            unchecked! { unchecked::get(&self.header, ..self.header_len as usize) },
+            self.mask
+                .as_ref()
+                .map(<[u8; 4]>::as_slice)
+                .unwrap_or_default(),
+            &self.payload,
+        ]
+    }
+
+    /// Whether this is a ping or pong frame, which may be sent in between the
+    /// frames of a fragmented message.
+    fn is_ping_or_pong(&self) -> bool {
+        // The low nibble of the first header byte is the opcode
+        matches!(self.header[0] & 0x0F, 0x9 | 0xA)
+    }
+
+    /// Total amount of bytes this frame occupies on the wire.
+    fn len(&self) -> usize {
+        self.header_len as usize + usize::from(self.mask.is_some()) * 4 + self.payload.len()
+    }
+}
+
+/// Deadline for making progress while writing to the underlying I/O, armed
+/// once writing stalls.
+#[derive(Debug, Default)]
+struct WriteDeadline {
+    /// Timer of the deadline, created once writing first stalls.
+    #[cfg(any(feature = "client", feature = "server"))]
+    timer: Option<Pin<Box<Sleep>>>,
+    /// Whether the timer is armed for the current stall.
+    armed: bool,
+}
+
+impl WriteDeadline {
+    /// Polls whether writing has been stalled for longer than `timeout`,
+    /// arming the deadline if writing just stalled.
+    fn poll_elapsed(&mut self, timeout: Option<Duration>, cx: &mut Context<'_>) -> Poll<()> {
+        #[cfg(any(feature = "client", feature = "server"))]
+        if let Some(timeout) = timeout {
+            let deadline = Instant::now() + timeout;
+            let timer = self
+                .timer
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deadline)));
+
+            if !self.armed {
+                timer.as_mut().reset(deadline);
+                self.armed = true;
+            }
+
+            return timer.as_mut().poll(cx);
+        }
+
+        #[cfg(not(any(feature = "client", feature = "server")))]
+        let _ = (self, timeout, cx);
+
+        Poll::Pending
+    }
+
+    /// Disarms the deadline after writing made progress.
+    fn disarm(&mut self) {
+        self.armed = false;
+    }
+}
+
+/// State of a limit on the rate of received messages or bytes, implemented as
+/// a generic cell rate algorithm.
+#[cfg(any(feature = "client", feature = "server"))]
+#[derive(Debug, Default)]
+struct RateLimiter {
+    /// Time at which everything counted so far would be within the limit
+    /// again, if anything was counted.
+    drained_at: Option<Instant>,
+}
+
+#[cfg(any(feature = "client", feature = "server"))]
+impl RateLimiter {
+    /// Counts `amount` towards a limit of `max` per `per`, returning whether
+    /// the limit allows for it. Amounts that exceed the limit are not counted.
+    fn try_acquire(&mut self, max: u64, per: Duration, amount: u64) -> bool {
+        if max == 0 {
+            return amount == 0;
+        }
+
+        let now = Instant::now();
+        let cost = per.as_nanos() * u128::from(amount) / u128::from(max);
+        let cost = Duration::from_nanos(u64::try_from(cost).unwrap_or(u64::MAX));
+        let start = self
+            .drained_at
+            .map_or(now, |drained_at| drained_at.max(now));
+
+        match start.checked_add(cost) {
+            Some(drained_at) if drained_at - now <= per => {
+                self.drained_at = Some(drained_at);
+
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Returns a waker that does nothing when woken.
+#[cfg(not(feature = "checked"))]
+fn noop_waker() -> Waker {
+    /// Virtual function table that ignores all calls.
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(|_| RAW, |_| {}, |_| {}, |_| {});
+    /// Waker without any data.
+    const RAW: RawWaker = RawWaker::new(std::ptr::null(), &VTABLE);
+
+    // SAFETY: None of the functions of the vtable access the data pointer
+    unsafe { Waker::from_raw(RAW) }
+}
+
+/// Returns a waker that does nothing when woken.
+#[cfg(feature = "checked")]
+fn noop_waker() -> Waker {
+    /// Wakes nothing.
+    struct Noop;
+
+    impl std::task::Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    /// The waker, which is shared to only allocate it once.
+    static WAKER: OnceLock<Waker> = OnceLock::new();
+
+    WAKER.get_or_init(|| Waker::from(Arc::new(Noop))).clone()
+}
+
+/// Adds a random duration of up to `jitter` to `interval`.
+#[cfg(any(feature = "client", feature = "server"))]
+fn jittered(interval: Duration, jitter: Option<Duration>) -> Duration {
+    let Some(max_nanos) = jitter
+        .map(|jitter| u64::try_from(jitter.as_nanos()).unwrap_or(u64::MAX))
+        .filter(|max_nanos| *max_nanos > 0)
+    else {
+        return interval;
+    };
+
+    // Every RandomState is seeded differently, which is random enough to spread
+    // pings without requiring a random number generator feature
+    let random = RandomState::new().build_hasher().finish();
+
+    interval.saturating_add(Duration::from_nanos(random % max_nanos))
+}
+
+/// Writes all of `buf` into `writer`.
+async fn write_all<W: AsyncWrite + Unpin>(writer: &mut W, mut buf: &[u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        let n = poll_fn(|cx| Pin::new(&mut *writer).poll_write(cx, buf)).await?;
+
+        if n == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+
+        buf = &buf[n..];
+    }
+
+    Ok(())
+}
+
+/// Cancellation of a stream via a [`CancellationToken`].
+struct Cancellation {
+    /// Resolves once the token is cancelled, [`None`] once it has resolved.
+    /// Only ever holds the future of a [`CancellationToken`], which is `Send`.
+    cancelled: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+    /// Close code to close the connection with once cancelled.
+    code: CloseCode,
+}
+
+impl Cancellation {
+    /// Polls whether the stream was cancelled, if it is cancellable, and
+    /// returns the close code to close the connection with if so.
+    fn poll_cancelled(cancellation: &mut Option<Self>, cx: &mut Context<'_>) -> Option<CloseCode> {
+        let cancellation = cancellation.as_mut()?;
+
+        if let Some(cancelled) = &mut cancellation.cancelled {
+            if cancelled.as_mut().poll(cx).is_pending() {
+                return None;
+            }
+
+            cancellation.cancelled = None;
+        }
+
+        Some(cancellation.code)
+    }
+}
+
+impl fmt::Debug for Cancellation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cancellation")
+            .field("cancelled", &self.cancelled.is_none())
+            .field("code", &self.code)
+            .finish()
+    }
+}
+
 /// A WebSocket stream that full messages can be read from and written to.
 ///
 /// The stream implements [`futures_sink::Sink`] and [`futures_core::Stream`].
@@ -52,8 +314,10 @@ struct EncodedFrame {
 /// [`ClientBuilder`]: crate::ClientBuilder
 /// [`ServerBuilder`]: crate::ServerBuilder
 #[allow(clippy::module_name_repetitions)]
-#[derive(Debug)]
 pub struct WebSocketStream<T> {
+    /// Unique identifier of the connection.
+    id: ConnectionId,
+
     /// The underlying stream using the [`WebSocketProtocol`] to read and write
     /// full frames.
     inner: FramedRead<T, WebSocketProtocol>,
@@ -68,9 +332,32 @@ pub struct WebSocketStream<T> {
     partial_payload: BytesMut,
     /// Opcode of the full message that is being assembled.
     partial_opcode: OpCode,
+    /// RSV bits of the first frame of the full message that is being
+    /// assembled.
+    partial_rsv: u8,
+
+    /// Extensions negotiated during the handshake.
+    extensions: Extensions,
+    /// Hooks called for every frame received and sent.
+    interceptor: Option<Box<dyn FrameInterceptor>>,
+    /// Observer called once the connection is closed, taken when called.
+    close_observer: Option<CloseObserver>,
+    /// Payload of the first close frame sent or received.
+    close_payload: Option<Bytes>,
+    /// Payload of the first close frame sent.
+    sent_close_payload: Option<Bytes>,
+    /// Cancellation token that closes the connection once cancelled.
+    cancellation: Option<Cancellation>,
+    /// Queue of control frames sent via [`ControlSender`]s, created once the
+    /// first sender is requested.
+    control: Option<Arc<ControlQueue>>,
 
-    /// Buffer that outgoing frame headers are formatted into.
-    header_buf: [u8; 10],
+    /// Frames that were decoded from the read buffer along with a previous
+    /// frame and not returned yet. Decoding stops at the first error.
+    decoded_frames: VecDeque<Result<Frame, Error>>,
+    /// Number of frames received since the stream last returned
+    /// [`Poll::Pending`], counted against the configured poll budget.
+    frames_since_yield: u32,
 
     /// Queue of outgoing frames to send.
     frame_queue: VecDeque<EncodedFrame>,
@@ -78,13 +365,81 @@ pub struct WebSocketStream<T> {
     bytes_written: usize,
     /// Total amount of bytes remaining to be sent in the frame queue.
     pending_bytes: usize,
+    /// How the most recently queued message was written to the wire.
+    last_sent: SendInfo,
+    /// Deadline for the configured write timeout.
+    write_deadline: WriteDeadline,
+
+    /// Source of masking keys for outgoing frames in the client role.
+    #[cfg(feature = "client")]
+    mask_generator: crate::rand::MaskGenerator,
+
+    /// Timer for the configured idle timeout, created once the stream is first
+    /// polled and reset whenever a frame is received.
+    #[cfg(any(feature = "client", feature = "server"))]
+    idle_timer: Option<Pin<Box<Sleep>>>,
+    /// Timer for the configured keepalive interval, created once the stream
+    /// is first polled.
+    #[cfg(any(feature = "client", feature = "server"))]
+    keepalive_timer: Option<Pin<Box<Sleep>>>,
+    /// Payloads of sent pings that were not answered yet, oldest first, along
+    /// with the time they were queued.
+    #[cfg(any(feature = "client", feature = "server"))]
+    outstanding_pings: VecDeque<(Bytes, Instant)>,
+    /// Round-trip time measured with the most recently answered ping.
+    #[cfg(any(feature = "client", feature = "server"))]
+    rtt: Option<Duration>,
+    /// State of the limit on the rate of received messages.
+    #[cfg(any(feature = "client", feature = "server"))]
+    message_rate: RateLimiter,
+    /// State of the limit on the rate of received payload bytes.
+    #[cfg(any(feature = "client", feature = "server"))]
+    byte_rate: RateLimiter,
+
+    /// Subprotocol negotiated during the handshake.
+    subprotocol: Option<String>,
+
+    /// Listener for a graceful shutdown of the server this stream belongs to.
+    #[cfg(feature = "server")]
+    shutdown: Option<crate::server::ShutdownListener>,
+    /// Guard that counts this stream towards the server's connection limits.
+    /// It is only held until the stream is dropped and never read.
+    #[cfg(feature = "server")]
+    #[allow(dead_code)]
+    connection_guard: Option<crate::server::ConnectionGuard>,
 }
 
-// SAFETY: The only !Sync field in `WebSocketStream` is `frame_queue`.
-// `frame_queue` must be used with exclusive, mutable access, which is
-// currently the case. It is only used in methods that take `&mut self`
-// and not borrowed in the methods.
-unsafe impl<T> Sync for WebSocketStream<T> {}
+// The `checked` feature denies all other unsafe code, but these fields cannot
+// be made `Sync` without locking them on every access
+#[allow(unsafe_code)]
+// SAFETY: Apart from the underlying stream, the only !Sync fields in
+// `WebSocketStream` are `frame_queue`, `extensions`, `interceptor`,
+// `close_observer`, `cancellation` and `shutdown`. They must be used with
+// exclusive, mutable access, which is currently the case. They are only used in
+// methods that take `&mut self` and not borrowed in the methods. The underlying
+// stream is borrowed via `get_ref`, so it has to be `Sync` itself.
+unsafe impl<T: Sync> Sync for WebSocketStream<T> {}
+
+impl<T: fmt::Debug> fmt::Debug for WebSocketStream<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Payloads are left out, they may be large or contain secrets
+        let codec = self.inner.decoder();
+
+        f.debug_struct("WebSocketStream")
+            .field("id", &self.id)
+            .field("io", self.inner.get_ref())
+            .field("role", &codec.role)
+            .field("state", &self.state)
+            .field("config", &self.config)
+            .field("limits", &codec.limits)
+            .field("subprotocol", &self.subprotocol)
+            .field("extensions", &self.extensions.len())
+            .field("partial_message_len", &self.partial_payload.len())
+            .field("queued_frames", &self.frame_queue.len())
+            .field("pending_bytes", &self.pending_bytes)
+            .finish_non_exhaustive()
+    }
+}
 
 impl<T> WebSocketStream<T>
 where
@@ -94,15 +449,49 @@ where
     #[cfg(any(feature = "client", feature = "server"))]
     pub(crate) fn from_raw_stream(stream: T, role: Role, config: Config, limits: Limits) -> Self {
         Self {
-            inner: FramedRead::new(stream, WebSocketProtocol::new(role, limits)),
+            id: ConnectionId::next(),
+            inner: {
+                let mut inner = FramedRead::with_capacity(
+                    stream,
+                    WebSocketProtocol::new(role, config, limits),
+                    0,
+                );
+                *inner.read_buffer_mut() = config.acquire_buffer(config.read_buffer_capacity);
+
+                inner
+            },
             config,
             state: StreamState::Active,
             partial_payload: BytesMut::new(),
             partial_opcode: OpCode::Continuation,
-            header_buf: [0; 10],
+            partial_rsv: 0,
+            extensions: Extensions::default(),
+            interceptor: None,
+            close_observer: None,
+            close_payload: None,
+            sent_close_payload: None,
+            cancellation: None,
+            control: None,
+            decoded_frames: VecDeque::new(),
+            frames_since_yield: 0,
             frame_queue: VecDeque::with_capacity(1),
             bytes_written: 0,
             pending_bytes: 0,
+            last_sent: SendInfo::default(),
+            write_deadline: WriteDeadline::default(),
+            #[cfg(feature = "client")]
+            mask_generator: crate::rand::MaskGenerator::Default,
+            idle_timer: None,
+            keepalive_timer: None,
+            outstanding_pings: VecDeque::new(),
+            rtt: None,
+            message_rate: RateLimiter::default(),
+            byte_rate: RateLimiter::default(),
+            subprotocol: None,
+            #[cfg(feature = "server")]
+            shutdown: None,
+            #[cfg(feature = "server")]
+            connection_guard: None,
         }
     }
 
@@ -116,18 +505,238 @@ where
         limits: Limits,
     ) -> Self {
         Self {
-            inner: framed.map_decoder(|_| WebSocketProtocol::new(role, limits)),
+            id: ConnectionId::next(),
+            inner: framed.map_decoder(|_| WebSocketProtocol::new(role, config, limits)),
             config,
             state: StreamState::Active,
             partial_payload: BytesMut::new(),
             partial_opcode: OpCode::Continuation,
-            header_buf: [0; 10],
+            partial_rsv: 0,
+            extensions: Extensions::default(),
+            interceptor: None,
+            close_observer: None,
+            close_payload: None,
+            sent_close_payload: None,
+            cancellation: None,
+            control: None,
+            decoded_frames: VecDeque::new(),
+            frames_since_yield: 0,
             frame_queue: VecDeque::with_capacity(1),
             bytes_written: 0,
             pending_bytes: 0,
+            last_sent: SendInfo::default(),
+            write_deadline: WriteDeadline::default(),
+            #[cfg(feature = "client")]
+            mask_generator: crate::rand::MaskGenerator::Default,
+            idle_timer: None,
+            keepalive_timer: None,
+            outstanding_pings: VecDeque::new(),
+            rtt: None,
+            message_rate: RateLimiter::default(),
+            byte_rate: RateLimiter::default(),
+            subprotocol: None,
+            #[cfg(feature = "server")]
+            shutdown: None,
+            #[cfg(feature = "server")]
+            connection_guard: None,
         }
     }
 
+    /// Sets the source of masking keys for outgoing frames.
+    #[cfg(feature = "client")]
+    pub(crate) fn set_mask_generator(&mut self, mask_generator: crate::rand::MaskGenerator) {
+        self.mask_generator = mask_generator;
+    }
+
+    /// Sets the extensions negotiated during the handshake.
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(crate) fn set_extensions(&mut self, extensions: Vec<Box<dyn ExtensionCodec>>) {
+        self.extensions = Extensions::new(extensions);
+        self.inner.decoder_mut().rsv_bits = self.extensions.rsv_bits();
+        self.inner.decoder_mut().per_frame_rsv = self.extensions.per_frame();
+    }
+
+    /// Sets the subprotocol negotiated during the handshake.
+    #[cfg(feature = "server")]
+    pub(crate) fn set_subprotocol(&mut self, subprotocol: String) {
+        self.subprotocol = Some(subprotocol);
+    }
+
+    /// Sets the listener for a graceful shutdown of the server.
+    #[cfg(feature = "server")]
+    pub(crate) fn set_shutdown(&mut self, shutdown: crate::server::ShutdownListener) {
+        self.shutdown = Some(shutdown);
+    }
+
+    /// Sets the guard that counts this stream towards the server's connection
+    /// limits until it is dropped.
+    #[cfg(feature = "server")]
+    pub(crate) fn set_connection_guard(&mut self, guard: crate::server::ConnectionGuard) {
+        self.connection_guard = Some(guard);
+    }
+
+    /// Returns the unique identifier of this connection.
+    pub fn id(&self) -> ConnectionId {
+        self.id
+    }
+
+    /// Returns the state of the connection, e.g. for inspecting its health
+    /// without reading from or writing to it.
+    pub fn state(&self) -> StreamState {
+        self.state
+    }
+
+    /// Returns whether the connection is active, i.e. no close frame was sent
+    /// or received yet and messages can be sent.
+    pub fn is_active(&self) -> bool {
+        self.state == StreamState::Active
+    }
+
+    /// Returns whether the connection is closed, i.e. the close handshake
+    /// completed or the connection failed. The stream ends and sending fails
+    /// from then on.
+    pub fn is_closed(&self) -> bool {
+        self.state == StreamState::CloseAcknowledged
+    }
+
+    /// Returns a reference to the underlying I/O stream, e.g. to inspect the
+    /// TLS session of a [`MaybeTlsStream`].
+    ///
+    /// [`MaybeTlsStream`]: crate::MaybeTlsStream
+    pub fn get_ref(&self) -> &T {
+        self.inner.get_ref()
+    }
+
+    /// Registers an extension that was negotiated out of band, e.g. via a
+    /// subprotocol or application messages, so that frames with the RSV bits
+    /// it reserves are accepted and transformed by it.
+    ///
+    /// The extension applies to messages sent and received afterwards, so it
+    /// should be registered in between messages at a point that both peers
+    /// agree on. It transforms outgoing payloads after any extensions
+    /// negotiated during the handshake and incoming payloads before them.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the extension reserves bits other than
+    /// [`RSV1`], [`RSV2`] and [`RSV3`], or bits that are already reserved by
+    /// another extension.
+    pub fn register_extension(&mut self, codec: Box<dyn ExtensionCodec>) {
+        let bits = codec.rsv_bits();
+        assert_eq!(
+            bits & !(RSV1 | RSV2 | RSV3),
+            0,
+            "extensions may only reserve RSV bits"
+        );
+        assert_eq!(
+            bits & self.extensions.rsv_bits(),
+            0,
+            "RSV bits are already reserved by another extension"
+        );
+
+        self.extensions.push(codec);
+        self.inner.decoder_mut().rsv_bits = self.extensions.rsv_bits();
+        self.inner.decoder_mut().per_frame_rsv = self.extensions.per_frame();
+    }
+
+    /// Returns the round-trip time measured with the most recent pong that
+    /// answered a ping sent on this stream, or [`None`] if no ping was
+    /// answered yet.
+    ///
+    /// Pings sent via [`Config::keepalive_interval`] keep this up to date.
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+
+    /// Returns the close code and reason of the first close frame sent on this
+    /// stream, or [`None`] if none was sent.
+    ///
+    /// When the peer violates the protocol or exceeds a limit, the stream
+    /// responds with a close frame on its own before the [`Error`] describing
+    /// the violation is returned. This tells the application exactly what the
+    /// peer was told, e.g. for logging. The close code is [`None`] if the
+    /// frame did not contain a valid one.
+    pub fn sent_close(&self) -> Option<(Option<CloseCode>, &str)> {
+        self.sent_close_payload.as_deref().map(parse_close_payload)
+    }
+
+    /// Returns the subprotocol negotiated via the `Sec-WebSocket-Protocol`
+    /// header during the handshake, if any.
+    pub fn subprotocol(&self) -> Option<&str> {
+        self.subprotocol.as_deref()
+    }
+
+    /// Returns the byte counts of the payloads compressed and decompressed by
+    /// the negotiated extensions, or [`None`] if no compression extension was
+    /// negotiated.
+    ///
+    /// This takes `&mut self` since extension codecs are not required to be
+    /// [`Sync`].
+    pub fn compression_stats(&mut self) -> Option<CompressionStats> {
+        self.extensions.compression_stats()
+    }
+
+    /// Installs hooks that are called for every frame received and sent from
+    /// now on, replacing previously installed ones.
+    pub fn set_frame_interceptor<I: FrameInterceptor + 'static>(&mut self, interceptor: I) {
+        self.interceptor = Some(Box::new(interceptor));
+    }
+
+    /// Installs an observer that is called exactly once when the connection is
+    /// closed, replacing a previously installed one.
+    ///
+    /// It receives a [`CloseEvent`] describing which end initiated the close,
+    /// the code and reason of its close frame and the error that failed the
+    /// connection, if any. This allows for centralizing logging and metrics of
+    /// disconnects.
+    ///
+    /// The observer is called once the close handshake completed, the
+    /// connection failed or the peer closed the underlying stream. If the
+    /// stream is dropped before that, it is called when dropping it, without
+    /// an error.
+    ///
+    /// Like [`FrameInterceptor`]s, the observer has to be [`Send`] for the
+    /// stream to remain [`Send`]. On a [`LocalSet`], it is called on the
+    /// thread polling the stream, so it can use a [`thread_local!`] to record
+    /// the event in state that is not [`Send`].
+    ///
+    /// [`LocalSet`]: https://docs.rs/tokio/latest/tokio/task/struct.LocalSet.html
+    pub fn set_close_observer<F>(&mut self, observer: F)
+    where
+        F: FnOnce(CloseEvent<'_>) + Send + 'static,
+    {
+        self.close_observer = Some(Box::new(observer));
+    }
+
+    /// Closes the connection with `code` once `token` is cancelled, replacing
+    /// a previously set token.
+    ///
+    /// Once cancelled, reading ends promptly: a close frame is sent if the
+    /// underlying I/O accepts it right away and the stream ends without
+    /// waiting for the peer to acknowledge the close. Pending writes that make
+    /// no progress are abandoned and fail with [`Error::AlreadyClosed`].
+    pub fn set_cancellation_token(&mut self, token: CancellationToken, code: CloseCode) {
+        self.cancellation = Some(Cancellation {
+            cancelled: Some(Box::pin(async move { token.cancelled().await })),
+            code,
+        });
+    }
+
+    /// Returns a handle for sending ping, pong and close frames from other
+    /// tasks, e.g. while this stream is busy writing a large message.
+    pub fn control_sender(&mut self) -> ControlSender {
+        ControlSender::new(self.control.get_or_insert_with(Arc::default))
+    }
+
+    /// Returns a mutable reference to the underlying I/O stream.
+    ///
+    /// Reading from or writing to the stream directly will corrupt the
+    /// WebSocket connection.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+
     /// Attempt to pull out the next frame from the [`Framed`] this stream and
     /// from that update the stream's internal state.
     ///
@@ -135,6 +744,11 @@ where
     ///
     /// This method returns an [`Error`] if reading from the stream fails or a
     /// protocol violation is encountered.
+    // Without the client and server features, pongs are never dropped
+    #[cfg_attr(
+        not(any(feature = "client", feature = "server")),
+        allow(clippy::never_loop)
+    )]
     fn poll_next_frame(
         mut self: Pin<&mut Self>,
         cx: &mut Context<'_>,
@@ -144,161 +758,1356 @@ where
         // close acknowledge goes through.
         if self.state == StreamState::CloseAcknowledged {
             return Poll::Ready(None);
-        } else if self.state == StreamState::ClosedByPeer {
-            ready!(self.as_mut().poll_flush(cx))?;
-            self.state = StreamState::CloseAcknowledged;
-            return Poll::Ready(None);
         }
 
-        // If there are pending items, try to flush the sink
-        if !self.frame_queue.is_empty() {
-            _ = self.as_mut().poll_flush(cx)?;
+        // Frames decoded along with a previous one are returned right away while the
+        // stream is active, the checks below are done once they were all returned
+        if self.decoded_frames.is_empty() || self.state != StreamState::Active {
+            if let Some(code) = Cancellation::poll_cancelled(&mut self.cancellation, cx) {
+                self.cancel(code, cx);
+
+                return Poll::Ready(None);
+            }
+
+            if self.state == StreamState::ClosedByPeer {
+                ready!(self.as_mut().poll_flush(cx))?;
+                self.state = StreamState::CloseAcknowledged;
+                self.notify_closed(CloseInitiator::Peer, None);
+                return Poll::Ready(None);
+            }
+
+            // Start the close handshake if the server is shutting down
+            #[cfg(feature = "server")]
+            if self.state == StreamState::Active
+                && self
+                    .shutdown
+                    .as_mut()
+                    .is_some_and(|shutdown| shutdown.poll_triggered(cx).is_ready())
+            {
+                self.queue_frame(Message::close(Some(CloseCode::GOING_AWAY), "").into());
+            }
+
+            self.queue_control_frames(cx);
+
+            // If there are pending items, try to flush the sink
+            if !self.frame_queue.is_empty() {
+                _ = self.as_mut().poll_flush(cx)?;
+            }
         }
 
-        let frame = match ready!(Pin::new(&mut self.inner).poll_next(cx)) {
-            Some(Ok(frame)) => frame,
-            Some(Err(e)) => {
-                if self.state == StreamState::ClosedByUs {
-                    self.state = StreamState::CloseAcknowledged;
-                } else {
-                    self.state = StreamState::ClosedByPeer;
-
-                    match &e {
-                        Error::Protocol(e) => self.queue_frame(Frame::from(e)),
-                        Error::PayloadTooLong { max_len, .. } => self.queue_frame(
-                            Message::close(
-                                Some(CloseCode::MESSAGE_TOO_BIG),
-                                &format!("max length: {max_len}"),
-                            )
-                            .into(),
-                        ),
-                        _ => {}
+        // Unsolicited pongs may be dropped, read frames until one is returned
+        loop {
+            let frame = match self.poll_decoded_frame(cx) {
+                Poll::Ready(Some(Ok(frame))) => frame,
+                Poll::Ready(Some(Err(e))) => {
+                    self.fail(&e);
+
+                    return Poll::Ready(Some(Err(e)));
+                }
+                Poll::Ready(None) => {
+                    let initiator = if self.state == StreamState::ClosedByUs {
+                        CloseInitiator::Us
+                    } else {
+                        CloseInitiator::Peer
+                    };
+                    self.notify_closed(initiator, None);
+
+                    return Poll::Ready(None);
+                }
+                Poll::Pending => {
+                    #[cfg(any(feature = "client", feature = "server"))]
+                    if self.as_mut().poll_idle_timeout(cx).is_ready() {
+                        let e = Error::IdleTimeout;
+                        self.notify_closed(CloseInitiator::Us, Some(&e));
+
+                        return Poll::Ready(Some(Err(e)));
                     }
+
+                    #[cfg(any(feature = "client", feature = "server"))]
+                    self.as_mut().poll_keepalive(cx);
+
+                    return Poll::Pending;
                 }
+            };
+
+            #[cfg(any(feature = "client", feature = "server"))]
+            if let Some(timeout) = self.config.idle_timeout {
+                if let Some(timer) = &mut self.idle_timer {
+                    timer.as_mut().reset(Instant::now() + timeout);
+                }
+            }
+
+            if let Err(e) = self.check_received_frame(&frame) {
+                self.fail(&e);
+
                 return Poll::Ready(Some(Err(e)));
             }
-            None => return Poll::Ready(None),
-        };
 
-        match frame.opcode {
-            OpCode::Close => match self.state {
-                StreamState::Active => {
-                    self.state = StreamState::ClosedByPeer;
+            match frame.opcode {
+                OpCode::Close => match self.state {
+                    StreamState::Active => {
+                        self.state = StreamState::ClosedByPeer;
+                        self.close_payload = Some(Bytes::from(frame.payload.clone()));
+
+                        let mut frame = frame.clone();
+                        frame.payload.truncate(2);
 
+                        self.queue_frame(frame);
+                    }
+                    // SAFETY: match statement at the start of the method ensures that this is not
+                    // the case
+                    StreamState::ClosedByPeer | StreamState::CloseAcknowledged => unchecked! {
+                        unchecked::unreachable()
+                    },
+                    StreamState::ClosedByUs => {
+                        self.state = StreamState::CloseAcknowledged;
+                        self.notify_closed(CloseInitiator::Us, None);
+                    }
+                },
+                OpCode::Ping if self.state == StreamState::Active && self.config.auto_pong => {
                     let mut frame = frame.clone();
-                    frame.payload.truncate(2);
+                    frame.opcode = OpCode::Pong;
 
                     self.queue_frame(frame);
                 }
-                // SAFETY: match statement at the start of the method ensures that this is not the
-                // case
-                StreamState::ClosedByPeer | StreamState::CloseAcknowledged => unsafe {
-                    unreachable_unchecked()
-                },
-                StreamState::ClosedByUs => {
-                    self.state = StreamState::CloseAcknowledged;
+                #[cfg(any(feature = "client", feature = "server"))]
+                OpCode::Pong
+                    if !self.take_outstanding_ping(&frame.payload)
+                        && self.config.drop_unsolicited_pongs =>
+                {
+                    continue;
                 }
-            },
-            OpCode::Ping if self.state == StreamState::Active => {
-                let mut frame = frame.clone();
-                frame.opcode = OpCode::Pong;
-
-                self.queue_frame(frame);
+                _ => {}
             }
-            _ => {}
-        }
 
-        Poll::Ready(Some(Ok(frame)))
+            return Poll::Ready(Some(Ok(frame)));
+        }
     }
 
-    /// Masks and queues a frame for sending when [`poll_flush`] gets called.
-    fn queue_frame(&mut self, frame: Frame) {
-        if frame.opcode == OpCode::Close && self.state != StreamState::ClosedByPeer {
-            self.state = StreamState::ClosedByUs;
-        }
+    /// Polls the next frame from the frames decoded along with a previous one,
+    /// or from the underlying stream.
+    ///
+    /// Once a frame was read, all further complete frames in the read buffer
+    /// up to the end of the current message are decoded in the same pass. Many
+    /// small frames of a message and control frames delivered by a single read
+    /// then do not go through the whole polling of the stream each. Frames of
+    /// the next message are not decoded ahead, since extensions may be
+    /// registered in between messages. The read buffer is shrunk afterwards if
+    /// one of the frames exceeded the configured shrink threshold.
+    ///
+    /// Once the configured poll budget is used up, the task is woken and
+    /// [`Poll::Pending`] is returned instead.
+    fn poll_decoded_frame(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Frame, Error>>> {
+        if self
+            .config
+            .poll_budget
+            .is_some_and(|budget| self.frames_since_yield >= budget)
+        {
+            self.frames_since_yield = 0;
+            cx.waker().wake_by_ref();
 
-        let (frame, mask): (Frame, Option<[u8; 4]>) = if self.inner.decoder().role == Role::Client {
-            #[cfg(feature = "client")]
-            {
-                let mut frame = frame;
-                let mut payload = BytesMut::from(frame.payload);
-                let mask = crate::rand::get_mask();
-                crate::mask::frame(&mask, &mut payload, 0);
-                frame.payload = Payload::from(payload);
+            return Poll::Pending;
+        }
 
-                (frame, Some(mask))
-            }
-            #[cfg(not(feature = "client"))]
-            {
-                // SAFETY: This allows for making the dependency on random generators
-                // only required for clients, servers can avoid it entirely.
-                // Since it is not possible to create a stream with client role
-                // without the client builder (and that is locked behind the client feature),
-                // this branch is impossible to reach.
-                unsafe { std::hint::unreachable_unchecked() }
-            }
-        } else {
-            (frame, None)
-        };
+        self.frames_since_yield = self.frames_since_yield.saturating_add(1);
 
-        let header_len = frame.encode(&mut self.header_buf);
-        if mask.is_some() {
-            self.header_buf[1] |= 1 << 7;
+        if let Some(frame) = self.decoded_frames.pop_front() {
+            return Poll::Ready(Some(frame));
         }
-        self.pending_bytes +=
-            header_len as usize + (u8::from(mask.is_some()) * 4) as usize + frame.payload.len();
-        self.frame_queue.push_back(EncodedFrame {
-            header: self.header_buf,
-            header_len,
-            mask,
-            payload: frame.payload,
-        });
-    }
-}
 
-impl<T> Stream for WebSocketStream<T>
-where
-    T: AsyncRead + AsyncWrite + Unpin,
-{
-    type Item = Result<Message, Error>;
+        let Poll::Ready(frame) = Pin::new(&mut self.inner).poll_next(cx) else {
+            self.frames_since_yield = 0;
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let max_len = self.inner.decoder().limits.max_payload_len;
+            return Poll::Pending;
+        };
 
-        loop {
-            let (opcode, payload, fin) = match ready!(self.as_mut().poll_next_frame(cx)?) {
-                Some(frame) => (frame.opcode, frame.payload, frame.is_final),
-                None => return Poll::Ready(None),
+        let Some(Ok(first)) = &frame else {
+            return Poll::Ready(frame);
+        };
+        let mut largest = first.payload.len();
+        let mut message_end = first.is_final && !first.opcode.is_control();
+
+        // Decoding a complete frame never reads from the I/O, so no waker is
+        // registered
+        let waker = noop_waker();
+        let mut noop_cx = Context::from_waker(&waker);
+
+        while !message_end
+            && self
+                .inner
+                .decoder()
+                .contains_frame(self.inner.read_buffer())
+        {
+            let Poll::Ready(Some(decoded)) = Pin::new(&mut self.inner).poll_next(&mut noop_cx)
+            else {
+                break;
             };
-            let len = self.partial_payload.len() + payload.len();
+            let failed = match &decoded {
+                Ok(frame) => {
+                    largest = largest.max(frame.payload.len());
+                    message_end = frame.is_final && !frame.opcode.is_control();
 
-            if opcode != OpCode::Continuation {
-                if fin {
-                    return Poll::Ready(Some(Ok(Message { opcode, payload })));
+                    false
                 }
-                self.partial_opcode = opcode;
-                self.partial_payload = BytesMut::from(payload);
-            } else if len > max_len {
-                return Poll::Ready(Some(Err(Error::PayloadTooLong { len, max_len })));
-            } else {
-                self.partial_payload.extend_from_slice(&payload);
-            }
+                Err(_) => true,
+            };
+            self.decoded_frames.push_back(decoded);
 
-            if fin {
+            if failed {
                 break;
             }
         }
 
-        let opcode = replace(&mut self.partial_opcode, OpCode::Continuation);
-        let mut payload = Payload::from(take(&mut self.partial_payload));
-        payload.set_utf8_validated(opcode == OpCode::Text);
+        if self
+            .config
+            .read_buffer_shrink_threshold
+            .is_some_and(|threshold| largest > threshold)
+        {
+            self.shrink_read_buffer();
+        }
 
-        Poll::Ready(Some(Ok(Message { opcode, payload })))
+        Poll::Ready(frame)
     }
-}
 
-// The tokio-util implementation of a sink uses a buffer which start_send
-// appends to and poll_flush tries to write from. This makes sense, but comes
+    /// Replaces the read buffer with a new one of the initial capacity, so
+    /// that the memory allocated for large frames is released once they are
+    /// dropped instead of being reused for the rest of the connection. Nothing
+    /// is done if the buffer holds more data than fits the initial capacity.
+    fn shrink_read_buffer(&mut self) {
+        let capacity = self.config.read_buffer_capacity;
+        let buf = self.inner.read_buffer_mut();
+
+        // The large buffer is dropped rather than released to the buffer pool,
+        // which would keep its memory allocated
+        if buf.len() <= capacity {
+            let mut shrunk = self.config.acquire_buffer(capacity);
+            shrunk.extend_from_slice(buf);
+            *buf = shrunk;
+        }
+    }
+
+    /// Closes the connection with `code` after it was cancelled, without
+    /// waiting for the peer's acknowledgement.
+    fn cancel(mut self: Pin<&mut Self>, code: CloseCode, cx: &mut Context<'_>) {
+        let initiator = if self.state == StreamState::ClosedByPeer {
+            CloseInitiator::Peer
+        } else {
+            CloseInitiator::Us
+        };
+        if self.state == StreamState::Active {
+            self.queue_frame(Message::close(Some(code), "").into());
+        }
+        self.state = StreamState::CloseAcknowledged;
+        self.notify_closed(initiator, None);
+
+        // Closing promptly takes precedence over delivering the close frame
+        _ = self.poll_flush(cx);
+    }
+
+    /// Polls the idle timer, closing the connection without waiting for the
+    /// peer's acknowledgement once it elapses.
+    #[cfg(any(feature = "client", feature = "server"))]
+    fn poll_idle_timeout(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let Some(timeout) = self.config.idle_timeout else {
+            return Poll::Pending;
+        };
+
+        let timer = self
+            .idle_timer
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+        ready!(timer.as_mut().poll(cx));
+
+        if self.state == StreamState::Active {
+            let code = self.config.idle_timeout_close_code;
+            self.queue_frame(Message::close(Some(code), "idle timeout").into());
+        }
+        self.state = StreamState::CloseAcknowledged;
+
+        // The peer is likely gone, so sending the close frame is best-effort
+        _ = self.poll_flush(cx);
+
+        Poll::Ready(())
+    }
+
+    /// Polls the keepalive timer, sending a ping whenever it elapses.
+    #[cfg(any(feature = "client", feature = "server"))]
+    fn poll_keepalive(mut self: Pin<&mut Self>, cx: &mut Context<'_>) {
+        let Some(interval) = self.config.keepalive_interval else {
+            return;
+        };
+        let jitter = self.config.keepalive_jitter;
+
+        let timer = self
+            .keepalive_timer
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(jittered(interval, jitter))));
+        if timer.as_mut().poll(cx).is_pending() {
+            return;
+        }
+
+        // Register the waker for the next ping
+        timer
+            .as_mut()
+            .reset(Instant::now() + jittered(interval, jitter));
+        _ = timer.as_mut().poll(cx);
+
+        if self.state == StreamState::Active {
+            self.queue_frame(Message::ping("").into());
+            // Errors surface on the next read or write
+            _ = self.poll_flush(cx);
+        }
+    }
+
+    /// Checks a received frame against the frame interceptor and the
+    /// configured rate limits.
+    fn check_received_frame(&mut self, frame: &Frame) -> Result<(), Error> {
+        if let Some(interceptor) = &mut self.interceptor {
+            interceptor
+                .on_frame_received(self.id, frame)
+                .map_err(Error::FrameRejected)?;
+        }
+
+        #[cfg(any(feature = "client", feature = "server"))]
+        {
+            let limits = self.inner.decoder().limits;
+            let len = u64::try_from(frame.payload.len()).unwrap_or(u64::MAX);
+
+            if let Some((max_bytes, per)) = limits.byte_rate {
+                let max_bytes = u64::try_from(max_bytes).unwrap_or(u64::MAX);
+
+                if !self.byte_rate.try_acquire(max_bytes, per, len) {
+                    return Err(Error::RateLimitExceeded);
+                }
+            }
+
+            if let Some((max_messages, per)) = limits.message_rate {
+                if frame.is_final && !self.message_rate.try_acquire(max_messages.into(), per, 1) {
+                    return Err(Error::RateLimitExceeded);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Records a sent ping so that received pongs can be matched against it,
+    /// forgetting the oldest one if too many are outstanding.
+    #[cfg(any(feature = "client", feature = "server"))]
+    fn add_outstanding_ping(&mut self, payload: &[u8]) {
+        if self.outstanding_pings.len() == MAX_OUTSTANDING_PINGS {
+            self.outstanding_pings.pop_front();
+        }
+
+        self.outstanding_pings
+            .push_back((Bytes::copy_from_slice(payload), Instant::now()));
+    }
+
+    /// Matches the payload of a received pong against the outstanding pings,
+    /// updating the round-trip time and returning whether it answered one.
+    ///
+    /// Peers may only answer the most recent of multiple pings, so older pings
+    /// are considered answered as well.
+    #[cfg(any(feature = "client", feature = "server"))]
+    fn take_outstanding_ping(&mut self, payload: &[u8]) -> bool {
+        let Some(index) = self
+            .outstanding_pings
+            .iter()
+            .position(|(ping, _)| **ping == *payload)
+        else {
+            return false;
+        };
+
+        let (_, sent_at) = self.outstanding_pings.drain(..=index).last().unwrap();
+        self.rtt = Some(sent_at.elapsed());
+
+        true
+    }
+
+    /// Queues the frames sent via [`ControlSender`]s, registering the waker to
+    /// be woken once more are sent. They are discarded once the connection is
+    /// closing.
+    fn queue_control_frames(&mut self, cx: &Context<'_>) {
+        let Some(control) = &self.control else {
+            return;
+        };
+
+        for frame in control.take(cx.waker()) {
+            if self.state == StreamState::Active {
+                self.queue_frame(frame);
+            }
+        }
+    }
+
+    /// Fails the connection after an error was encountered while reading,
+    /// queueing a close frame describing the error if appropriate.
+    fn fail(&mut self, e: &Error) {
+        if self.state == StreamState::ClosedByUs {
+            self.state = StreamState::CloseAcknowledged;
+        } else {
+            self.state = StreamState::ClosedByPeer;
+
+            match e {
+                Error::Protocol(e) => self.queue_frame(Frame::from(e)),
+                Error::PayloadTooLong { max_len, .. } => self.queue_frame(
+                    Message::close(
+                        Some(CloseCode::MESSAGE_TOO_BIG),
+                        &format!("max length: {max_len}"),
+                    )
+                    .into(),
+                ),
+                Error::Extension(_) => self.queue_frame(
+                    Message::close(Some(CloseCode::PROTOCOL_ERROR), "extension error").into(),
+                ),
+                Error::FrameRejected(_) => self.queue_frame(
+                    Message::close(Some(CloseCode::POLICY_VIOLATION), "frame rejected").into(),
+                ),
+                #[cfg(any(feature = "client", feature = "server"))]
+                Error::RateLimitExceeded => self.queue_frame(
+                    Message::close(Some(CloseCode::POLICY_VIOLATION), "rate limit exceeded").into(),
+                ),
+                _ => {}
+            }
+        }
+
+        self.notify_closed(CloseInitiator::Us, Some(e));
+    }
+
+    /// Calls the close observer, if it was not called yet.
+    fn notify_closed(&mut self, initiator: CloseInitiator, error: Option<&Error>) {
+        if let Some(observer) = self.close_observer.take() {
+            observer(CloseEvent::new(
+                self.id,
+                initiator,
+                self.close_payload.as_deref(),
+                error,
+            ));
+        }
+    }
+
+    /// Adds a received frame to the message that is being assembled,
+    /// returning the message once it is complete.
+    fn assemble_message(&mut self, frame: Frame) -> Result<Option<Message>, Error> {
+        let max_len = self.inner.decoder().limits.max_payload_len;
+        let Frame {
+            opcode,
+            is_final: fin,
+            rsv,
+            payload,
+        } = frame;
+
+        let payload = if opcode.is_control() {
+            payload
+        } else {
+            self.decode_frame_payload(payload, rsv)?
+        };
+
+        let len = self.partial_payload.len() + payload.len();
+
+        let (opcode, payload, rsv) = if opcode != OpCode::Continuation {
+            if !fin {
+                self.partial_opcode = opcode;
+                self.partial_rsv = rsv;
+                self.partial_payload = if self.config.buffer_pool.is_some() {
+                    // Further frames are appended, which would reallocate the
+                    // payload split off the read buffer anyway
+                    let mut buf = self.config.acquire_buffer(payload.len());
+                    buf.extend_from_slice(&payload);
+
+                    buf
+                } else {
+                    BytesMut::from(payload)
+                };
+
+                return Ok(None);
+            }
+
+            (opcode, payload, rsv)
+        } else if len > max_len {
+            return Err(Error::PayloadTooLong { len, max_len });
+        } else {
+            self.partial_payload.extend_from_slice(&payload);
+
+            if !fin {
+                return Ok(None);
+            }
+
+            let opcode = replace(&mut self.partial_opcode, OpCode::Continuation);
+            let rsv = take(&mut self.partial_rsv);
+            let mut payload = Payload::from(take(&mut self.partial_payload));
+            payload.set_utf8_validated(opcode == OpCode::Text && rsv == 0);
+
+            (opcode, payload, rsv)
+        };
+
+        if opcode.is_control() {
+            return Ok(Some(Message { opcode, payload }));
+        }
+
+        let message = self.decode_message(opcode, payload, rsv);
+
+        if let Err(e) = &message {
+            self.fail(e);
+        }
+
+        message.map(Some)
+    }
+
+    /// Transforms the payload of an incoming data frame with the negotiated
+    /// extensions if they operate on individual frames.
+    fn decode_frame_payload(&mut self, payload: Payload, rsv: u8) -> Result<Payload, Error> {
+        if !self.extensions.per_frame() {
+            return Ok(payload);
+        }
+
+        let payload = self.extensions.decode(payload, rsv);
+
+        if let Err(e) = &payload {
+            self.fail(e);
+        }
+
+        payload
+    }
+
+    /// Transforms the payload of a complete incoming data message with the
+    /// negotiated extensions and validates text messages that were not
+    /// validated while reading their frames.
+    fn decode_message(
+        &mut self,
+        opcode: OpCode,
+        payload: Payload,
+        rsv: u8,
+    ) -> Result<Message, Error> {
+        if self.extensions.is_empty() {
+            return Ok(Message { opcode, payload });
+        }
+
+        let per_frame = self.extensions.per_frame();
+        let mut payload = if per_frame {
+            payload
+        } else {
+            self.extensions.decode(payload, rsv)?
+        };
+
+        if opcode == OpCode::Text && (rsv != 0 || per_frame) {
+            utf8::parse_str(&payload)?;
+            payload.set_utf8_validated(true);
+        }
+
+        Ok(Message { opcode, payload })
+    }
+
+    /// Queues a frame that the stream sends on its own, dropping it if it is
+    /// rejected by the frame interceptor and not a close frame.
+    fn queue_frame(&mut self, frame: Frame) {
+        if let Some(interceptor) = &mut self.interceptor {
+            if interceptor.on_frame_sent(self.id, &frame).is_err() && frame.opcode != OpCode::Close
+            {
+                return;
+            }
+        }
+
+        self.enqueue_frame(frame);
+    }
+
+    /// Queues the frames of a message sent by the application, unless the
+    /// frame interceptor rejects any of them.
+    fn queue_message_frames<I>(&mut self, frames: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = Frame>,
+    {
+        let Some(interceptor) = &mut self.interceptor else {
+            self.enqueue_message_frames(frames);
+
+            return Ok(());
+        };
+
+        let frames: Vec<Frame> = frames.into_iter().collect();
+        for frame in &frames {
+            interceptor
+                .on_frame_sent(self.id, frame)
+                .map_err(Error::FrameRejected)?;
+        }
+
+        self.enqueue_message_frames(frames);
+
+        Ok(())
+    }
+
+    /// Queues the frames of a message and records how they are written to the
+    /// wire.
+    fn enqueue_message_frames<I>(&mut self, frames: I)
+    where
+        I: IntoIterator<Item = Frame>,
+    {
+        let pending_bytes = self.pending_bytes;
+        let mut count = 0;
+        let mut compressed = false;
+
+        for frame in frames {
+            count += 1;
+            compressed |= frame.rsv & RSV1 != 0;
+            self.enqueue_frame(frame);
+        }
+
+        self.last_sent = SendInfo::new(count, self.pending_bytes - pending_bytes, compressed);
+    }
+
+    /// Masks and queues a frame for sending when [`poll_flush`] gets called.
+    fn enqueue_frame(&mut self, frame: Frame) {
+        if frame.opcode == OpCode::Close {
+            if self.state != StreamState::ClosedByPeer {
+                self.state = StreamState::ClosedByUs;
+            }
+            if self.sent_close_payload.is_none() {
+                // Shares the payload rather than copying it before it is masked
+                let payload = Bytes::from(frame.payload.clone());
+                self.close_payload.get_or_insert_with(|| payload.clone());
+                self.sent_close_payload = Some(payload);
+            }
+        }
+
+        #[cfg(any(feature = "client", feature = "server"))]
+        if frame.opcode == OpCode::Ping {
+            self.add_outstanding_ping(&frame.payload);
+        }
+
+        let (frame, mask): (Frame, Option<[u8; 4]>) = if self.inner.decoder().role == Role::Client {
+            #[cfg(feature = "client")]
+            {
+                let mut frame = frame;
+                let mask = self.mask_generator.generate();
+                frame.payload = frame.payload.into_masked(mask);
+
+                (frame, Some(mask))
+            }
+            #[cfg(not(feature = "client"))]
+            {
+                // SAFETY: This allows for making the dependency on random generators
+                // only required for clients, servers can avoid it entirely.
+                // Since it is not possible to create a stream with client role
+                // without the client builder (and that is locked behind the client feature),
+                // this branch is impossible to reach.
+                unchecked! { unchecked::unreachable() }
+            }
+        } else {
+            (frame, None)
+        };
+
+        let frame = EncodedFrame::new(frame, mask);
+        self.pending_bytes += frame.len();
+
+        if frame.is_ping_or_pong() {
+            // Pings and pongs skip the queued data frames so that they are not
+            // held up by large messages. They are only sent after the frame
+            // that is currently being written and the pings and pongs queued
+            // before them. Close frames keep their place, since no data frames
+            // may follow them.
+            let partial = usize::from(self.bytes_written != 0);
+            let index = self
+                .frame_queue
+                .iter()
+                .skip(partial)
+                .take_while(|queued| queued.is_ping_or_pong())
+                .count()
+                + partial;
+            self.frame_queue.insert(index, frame);
+        } else {
+            self.frame_queue.push_back(frame);
+        }
+    }
+
+    /// Receives the next message, failing with [`Error::ReadTimeout`] if none
+    /// arrives within `timeout`.
+    ///
+    /// Returns [`None`] once the stream has ended. Timing out does not affect
+    /// the stream, partially received messages are kept and reading may be
+    /// continued afterwards.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if no message was received in time,
+    /// the peer violated the protocol or reading from the underlying I/O
+    /// fails.
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub async fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<Message>, Error> {
+        tokio::time::timeout(timeout, poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)))
+            .await
+            .map_err(|_| Error::ReadTimeout)?
+            .transpose()
+    }
+
+    /// Returns the next message if it can be received without waiting, e.g.
+    /// because it is already buffered, and [`None`] otherwise.
+    ///
+    /// This never blocks and allows polling connections opportunistically,
+    /// e.g. from a custom scheduler. Parts of a message that were received
+    /// are kept, so the message is returned by a later call once it is
+    /// complete. Since this does not register for wakeups, use the
+    /// [`Stream`] implementation to wait for messages.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if the peer violated the protocol or
+    /// reading from the underlying I/O fails, and [`Error::AlreadyClosed`]
+    /// once the stream has ended.
+    pub fn try_read_message(&mut self) -> Result<Option<Message>, Error> {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        match Pin::new(self).poll_next(&mut cx) {
+            Poll::Ready(Some(message)) => message.map(Some),
+            Poll::Ready(None) => Err(Error::AlreadyClosed),
+            Poll::Pending => Ok(None),
+        }
+    }
+
+    /// Waits for the next message and appends it to `messages` along with all
+    /// further messages that can be received without waiting, up to `limit`
+    /// messages in total. Returns the number of messages appended, which is
+    /// only 0 if `limit` is 0 or the stream has ended.
+    ///
+    /// This allows consumers that process messages in batches, e.g. by writing
+    /// them to a database, to receive everything that was read from the
+    /// underlying I/O at once instead of awaiting each message. Like
+    /// [`StreamExt::next`], this method is cancellation safe.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if the peer violated the protocol or
+    /// reading from the underlying I/O fails. Messages received before the
+    /// error are appended to `messages` all the same.
+    ///
+    /// [`StreamExt::next`]: https://docs.rs/futures-util/latest/futures_util/stream/trait.StreamExt.html#method.next
+    pub async fn read_ready_messages(
+        &mut self,
+        messages: &mut Vec<Message>,
+        limit: usize,
+    ) -> Result<usize, Error> {
+        if limit == 0 {
+            return Ok(0);
+        }
+
+        let Some(message) = poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await else {
+            return Ok(0);
+        };
+        messages.push(message?);
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut count = 1;
+
+        while count < limit {
+            let Poll::Ready(Some(message)) = Pin::new(&mut *self).poll_next(&mut cx) else {
+                break;
+            };
+            messages.push(message?);
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Receives the next message like [`StreamExt::next`], but writes the
+    /// payload of binary messages into `writer` as their frames arrive instead
+    /// of assembling them in memory, e.g. to store large uploads in a file.
+    ///
+    /// Once the entire payload of a binary message was written and `writer`
+    /// was flushed, a binary message with an empty payload is returned. All
+    /// other messages are returned as usual. The total payload length is
+    /// limited by [`Limits::max_payload_len`] just like for messages
+    /// received in memory. If extensions that transform entire messages are
+    /// negotiated, binary messages are assembled in memory before they are
+    /// written.
+    ///
+    /// Ping and pong messages received while a binary message is being written
+    /// are handled as usual, but not returned. If a close message is received
+    /// instead of the rest of the binary message, it is returned and the
+    /// partially written payload is incomplete.
+    ///
+    /// This method is not cancellation safe. If it is cancelled or writing to
+    /// `writer` fails, the rest of a partially received message is lost and no
+    /// further messages should be read from the stream.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if the peer violated the protocol, the
+    /// message is too long, or reading from the underlying I/O or writing to
+    /// `writer` fails.
+    ///
+    /// [`StreamExt::next`]: https://docs.rs/futures-util/latest/futures_util/stream/trait.StreamExt.html#method.next
+    /// [`Limits::max_payload_len`]: super::Limits::max_payload_len
+    pub async fn read_message_into<W: AsyncWrite + Unpin>(
+        &mut self,
+        writer: &mut W,
+    ) -> Result<Option<Message>, Error> {
+        let max_len = self.inner.decoder().limits.max_payload_len;
+        let streamable = self.extensions.is_empty() || self.extensions.per_frame();
+        let mut written: Option<usize> = None;
+
+        loop {
+            let Some(frame) = poll_fn(|cx| Pin::new(&mut *self).poll_next_frame(cx))
+                .await
+                .transpose()?
+            else {
+                return Ok(None);
+            };
+
+            if let Some(written) = &mut written {
+                match frame.opcode {
+                    OpCode::Continuation => {
+                        let payload = self.decode_frame_payload(frame.payload, frame.rsv)?;
+
+                        *written += payload.len();
+                        if *written > max_len {
+                            return Err(Error::PayloadTooLong {
+                                len: *written,
+                                max_len,
+                            });
+                        }
+
+                        write_all(writer, &payload).await?;
+
+                        if frame.is_final {
+                            poll_fn(|cx| Pin::new(&mut *writer).poll_flush(cx)).await?;
+
+                            return Ok(Some(Message::binary(Bytes::new())));
+                        }
+                    }
+                    OpCode::Close => {
+                        return Ok(Some(Message {
+                            opcode: frame.opcode,
+                            payload: frame.payload,
+                        }))
+                    }
+                    // Pings are answered when reading the frame and the codec
+                    // rejects data frames in the middle of a message
+                    _ => {}
+                }
+
+                continue;
+            }
+
+            if frame.opcode == OpCode::Binary
+                && !frame.is_final
+                && streamable
+                && self.partial_opcode == OpCode::Continuation
+            {
+                let payload = self.decode_frame_payload(frame.payload, frame.rsv)?;
+                written = Some(payload.len());
+                write_all(writer, &payload).await?;
+
+                continue;
+            }
+
+            match self.assemble_message(frame)? {
+                Some(message) if message.is_binary() => {
+                    write_all(writer, &message.payload).await?;
+                    poll_fn(|cx| Pin::new(&mut *writer).poll_flush(cx)).await?;
+
+                    return Ok(Some(Message::binary(Bytes::new())));
+                }
+                Some(message) => return Ok(Some(message)),
+                None => {}
+            }
+        }
+    }
+
+    /// Sends a message and flushes the underlying I/O.
+    ///
+    /// This is equivalent to [`SinkExt::send`], but accepts anything that
+    /// converts into a [`Message`]. Strings are sent as text messages and
+    /// bytes as binary messages.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if the stream is already closed or
+    /// writing to the underlying I/O fails.
+    ///
+    /// [`SinkExt::send`]: https://docs.rs/futures-util/latest/futures_util/sink/trait.SinkExt.html#method.send
+    pub async fn send<M: Into<Message>>(&mut self, message: M) -> Result<(), Error> {
+        self.feed(message).await?;
+        self.flush().await
+    }
+
+    /// Sends a message like [`Self::send`] and returns how it was written to
+    /// the wire, to meter the actual network usage rather than just payload
+    /// sizes.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if the stream is already closed, the
+    /// frame interceptor rejects a frame or writing to the underlying I/O
+    /// fails.
+    pub async fn send_with_info<M: Into<Message>>(
+        &mut self,
+        message: M,
+    ) -> Result<SendInfo, Error> {
+        self.feed(message).await?;
+        let info = self.last_sent;
+        self.flush().await?;
+
+        Ok(info)
+    }
+
+    /// Queues a message for sending without explicitly flushing the underlying
+    /// I/O.
+    ///
+    /// The message is only guaranteed to be written once [`Self::flush`] is
+    /// called. Queued frames are written out in as few writes as possible, so
+    /// feeding many small messages and flushing once is considerably cheaper
+    /// than sending them one by one. If more than the configured
+    /// [`Config::flush_threshold`] bytes are pending, this will flush before
+    /// queueing the message.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if the stream is already closed or
+    /// writing to the underlying I/O fails.
+    pub async fn feed<M: Into<Message>>(&mut self, message: M) -> Result<(), Error> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_ready(cx)).await?;
+        Pin::new(&mut *self).start_send(message.into())
+    }
+
+    /// Sends a single frame exactly as given and flushes the underlying I/O.
+    ///
+    /// Unlike messages, the frame is neither split into multiple frames nor
+    /// transformed by extensions, so its opcode, FIN bit and RSV bits are
+    /// written to the wire as is. This allows gateways and test tools to emit
+    /// exact frame sequences, including deliberately invalid ones. The frame
+    /// is not validated, call [`Frame::validate`] to check it beforehand. The
+    /// frame interceptor is still called for it.
+    ///
+    /// It is up to the caller not to interleave the frames of a fragmented
+    /// message with other data frames. Sending a close frame starts the close
+    /// handshake.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if the stream is already closed, the
+    /// frame interceptor rejects the frame or writing to the underlying I/O
+    /// fails.
+    pub async fn send_frame(&mut self, frame: Frame) -> Result<(), Error> {
+        self.feed_frame(frame).await?;
+        self.flush().await
+    }
+
+    /// Queues a single frame exactly as given without explicitly flushing the
+    /// underlying I/O, see [`Self::send_frame`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if the stream is already closed, the
+    /// frame interceptor rejects the frame or writing to the underlying I/O
+    /// fails.
+    pub async fn feed_frame(&mut self, frame: Frame) -> Result<(), Error> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_ready(cx)).await?;
+
+        if self.state != StreamState::Active {
+            return Err(Error::AlreadyClosed);
+        }
+
+        self.queue_message_frames([frame])
+    }
+
+    /// Sends all messages yielded by a stream.
+    ///
+    /// Every message that the stream has readily available is queued before
+    /// flushing, so the underlying I/O is only flushed once the stream is
+    /// pending or exhausted. This makes forwarding a channel of outgoing
+    /// messages considerably cheaper than sending them one by one.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if the stream is already closed or
+    /// writing to the underlying I/O fails.
+    pub async fn send_all<S, M>(&mut self, stream: &mut S) -> Result<(), Error>
+    where
+        S: Stream<Item = M> + Unpin,
+        M: Into<Message>,
+    {
+        poll_fn(|cx| loop {
+            ready!(Pin::new(&mut *self).poll_ready(cx))?;
+
+            match Pin::new(&mut *stream).poll_next(cx) {
+                Poll::Ready(Some(message)) => Pin::new(&mut *self).start_send(message.into())?,
+                Poll::Ready(None) => return Pin::new(&mut *self).poll_flush(cx),
+                Poll::Pending => {
+                    ready!(Pin::new(&mut *self).poll_flush(cx))?;
+                    return Poll::Pending;
+                }
+            }
+        })
+        .await
+    }
+
+    /// Writes all queued messages to the underlying I/O and flushes it.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if writing to the underlying I/O fails.
+    pub async fn flush(&mut self) -> Result<(), Error> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_flush(cx)).await
+    }
+
+    /// Closes the connection with the complete closing handshake and returns
+    /// the close message of the peer, if one was received.
+    ///
+    /// A close frame is sent unless one was sent before. Messages received
+    /// afterwards are discarded until the close frame of the peer arrives,
+    /// the stream ends or the [close timeout] elapses. Finally, the write side
+    /// of the underlying I/O is shut down. Use [`Self::close_collect`] to keep
+    /// the messages instead.
+    ///
+    /// If the peer closed the connection first, its close message was
+    /// already returned when reading and this returns [`None`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if the peer violated the protocol or
+    /// reading from or writing to the underlying I/O fails.
+    ///
+    /// [close timeout]: Config::close_timeout
+    pub async fn close(&mut self) -> Result<Option<Message>, Error> {
+        self.close_handshake(None).await
+    }
+
+    /// Closes the connection like [`Self::close`], but appends the text and
+    /// binary messages that the peer sent before answering the close frame to
+    /// `messages` instead of discarding them.
+    ///
+    /// The peer may still send data after we sent a close frame until it
+    /// acknowledges it, which protocols that flush final data at the end of
+    /// the connection rely on.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if the peer violated the protocol or
+    /// reading from or writing to the underlying I/O fails. Messages received
+    /// before the error are appended to `messages` all the same.
+    pub async fn close_collect(
+        &mut self,
+        messages: &mut Vec<Message>,
+    ) -> Result<Option<Message>, Error> {
+        self.close_handshake(Some(messages)).await
+    }
+
+    /// Performs the closing handshake for [`Self::close`] and
+    /// [`Self::close_collect`], appending received data messages to `messages`
+    /// if given.
+    async fn close_handshake(
+        &mut self,
+        mut messages: Option<&mut Vec<Message>>,
+    ) -> Result<Option<Message>, Error> {
+        if self.state == StreamState::Active {
+            self.queue_frame(Frame::DEFAULT_CLOSE);
+        }
+        self.flush().await?;
+
+        let peer_close = {
+            #[cfg(any(feature = "client", feature = "server"))]
+            let timeout = self.config.close_timeout;
+            let read = async {
+                let mut peer_close = None;
+
+                while let Some(message) = poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await {
+                    let message = message?;
+
+                    if message.is_close() {
+                        peer_close = Some(message);
+                    } else if let Some(messages) = &mut messages {
+                        if message.is_text() || message.is_binary() {
+                            messages.push(message);
+                        }
+                    }
+                }
+
+                Ok::<_, Error>(peer_close)
+            };
+
+            #[cfg(any(feature = "client", feature = "server"))]
+            let read = async move {
+                match timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, read)
+                        .await
+                        .unwrap_or(Ok(None)),
+                    None => read.await,
+                }
+            };
+
+            read.await?
+        };
+
+        // Answers to frames received while closing may have been queued
+        self.flush().await?;
+        poll_fn(|cx| Pin::new(self.inner.get_mut()).poll_shutdown(cx)).await?;
+
+        Ok(peer_close)
+    }
+
+    /// Sends a text message and flushes the underlying I/O.
+    ///
+    /// The payload is valid UTF-8 by construction, unlike that of a message
+    /// created via [`Message::text`] from bytes.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if the stream is already closed or
+    /// writing to the underlying I/O fails.
+    pub async fn send_text<P: Into<Utf8Payload>>(&mut self, payload: P) -> Result<(), Error> {
+        self.send(Message::text(payload.into())).await
+    }
+
+    /// Sends a binary message and flushes the underlying I/O.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if the stream is already closed or
+    /// writing to the underlying I/O fails.
+    pub async fn send_binary<P: Into<Payload>>(&mut self, payload: P) -> Result<(), Error> {
+        self.send(Message::binary(payload)).await
+    }
+
+    /// Sends a binary message whose payload is provided as a [`Buf`] and
+    /// flushes the underlying I/O.
+    ///
+    /// The payload is not concatenated into a single allocation first.
+    /// Instead, the message is fragmented at the boundaries of the chunks of
+    /// `payload` in addition to [`Config::frame_size`], so that chunks backed
+    /// by [`Bytes`], e.g. chained via [`Buf::chain`], are queued without
+    /// copying. If extensions are negotiated, the payload is concatenated
+    /// before they transform it.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if the stream is already closed or
+    /// writing to the underlying I/O fails.
+    ///
+    /// [`Bytes`]: bytes::Bytes
+    pub async fn send_binary_buf<B: Buf>(&mut self, payload: B) -> Result<(), Error> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_ready(cx)).await?;
+        self.start_send_buf(payload)?;
+        self.flush().await
+    }
+
+    /// Sends the contents of `reader`, e.g. a [`tokio::fs::File`], as a
+    /// binary message fragmented into frames of up to `chunk_size` bytes and
+    /// flushes the underlying I/O.
+    ///
+    /// Only a single chunk is held in memory at a time, so large payloads can
+    /// be sent without loading them into memory first, unless extensions that
+    /// transform entire messages are negotiated. Frames are flushed according
+    /// to [`Config::flush_threshold`] while reading.
+    ///
+    /// If reading fails after the first frame was queued, the message cannot
+    /// be completed anymore and the connection is closed with
+    /// [`CloseCode::INTERNAL_SERVER_ERROR`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if the stream is already closed,
+    /// reading from `reader` fails or writing to the underlying I/O fails.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `chunk_size` is `0`.
+    ///
+    /// [`tokio::fs::File`]: https://docs.rs/tokio/latest/tokio/fs/struct.File.html
+    pub async fn send_binary_reader<R: AsyncRead + Unpin>(
+        &mut self,
+        mut reader: R,
+        chunk_size: usize,
+    ) -> Result<(), Error> {
+        assert_ne!(chunk_size, 0, "chunk_size must be non-zero");
+
+        if self.state != StreamState::Active {
+            return Err(Error::AlreadyClosed);
+        }
+
+        if !self.extensions.is_empty() && !self.extensions.per_frame() {
+            let mut payload = BytesMut::new();
+            while poll_fn(|cx| poll_read_buf(Pin::new(&mut reader), cx, &mut payload)).await? != 0 {
+            }
+
+            return self.send_binary(payload).await;
+        }
+
+        let mut started = false;
+        let result = self
+            .send_reader_frames(&mut reader, chunk_size, &mut started)
+            .await;
+
+        if result.is_err() && started && self.state == StreamState::Active {
+            self.queue_frame(Message::close(Some(CloseCode::INTERNAL_SERVER_ERROR), "").into());
+            let _ = self.flush().await;
+        }
+
+        result
+    }
+
+    /// Queues the contents of `reader` as frames of a binary message,
+    /// recording in `started` once the first frame was queued.
+    async fn send_reader_frames<R: AsyncRead + Unpin>(
+        &mut self,
+        reader: &mut R,
+        chunk_size: usize,
+        started: &mut bool,
+    ) -> Result<(), Error> {
+        let mut opcode = OpCode::Binary;
+
+        loop {
+            let mut chunk = BytesMut::with_capacity(chunk_size);
+            let mut is_final = false;
+
+            while chunk.len() < chunk_size {
+                let limit = chunk_size - chunk.len();
+                let n = poll_fn(|cx| {
+                    poll_read_buf(Pin::new(&mut *reader), cx, &mut (&mut chunk).limit(limit))
+                })
+                .await?;
+
+                if n == 0 {
+                    is_final = true;
+                    break;
+                }
+            }
+
+            poll_fn(|cx| Pin::new(&mut *self).poll_ready(cx)).await?;
+
+            // A close frame might have been sent via a control sender
+            if self.state != StreamState::Active {
+                self.flush().await?;
+
+                return Err(Error::AlreadyClosed);
+            }
+
+            let mut frame = Frame {
+                opcode: replace(&mut opcode, OpCode::Continuation),
+                is_final,
+                rsv: 0,
+                payload: chunk.into(),
+            };
+
+            if !self.extensions.is_empty() {
+                let (payload, rsv) = self.extensions.encode(frame.payload)?;
+                frame.payload = payload;
+                frame.rsv = rsv;
+            }
+
+            self.queue_message_frames([frame])?;
+            *started = true;
+
+            if is_final {
+                return self.flush().await;
+            }
+        }
+    }
+
+    /// Queues a binary message whose payload is provided as a [`Buf`],
+    /// splitting it into frames at the boundaries of its chunks.
+    fn start_send_buf<B: Buf>(&mut self, mut payload: B) -> Result<(), Error> {
+        if self.state != StreamState::Active {
+            return Err(Error::AlreadyClosed);
+        }
+
+        if !self.extensions.is_empty() {
+            let payload = payload.copy_to_bytes(payload.remaining());
+
+            return Pin::new(&mut *self).start_send(Message::binary(payload));
+        }
+
+        let mut frames = Vec::new();
+        let mut opcode = OpCode::Binary;
+
+        loop {
+            let len = payload.chunk().len().min(self.config.frame_size);
+            let chunk = payload.copy_to_bytes(len);
+            let is_final = !payload.has_remaining();
+
+            frames.push(Frame {
+                opcode: replace(&mut opcode, OpCode::Continuation),
+                is_final,
+                rsv: 0,
+                payload: chunk.into(),
+            });
+
+            if is_final {
+                break;
+            }
+        }
+
+        self.queue_message_frames(frames)
+    }
+}
+
+impl<T> Drop for WebSocketStream<T> {
+    fn drop(&mut self) {
+        self.config
+            .release_buffer(take(self.inner.read_buffer_mut()));
+        self.config.release_buffer(take(&mut self.partial_payload));
+
+        // The observer is called exactly once, even if the connection ends
+        // without completing the close handshake
+        if let Some(observer) = self.close_observer.take() {
+            let initiator = if self.state == StreamState::ClosedByPeer {
+                CloseInitiator::Peer
+            } else {
+                CloseInitiator::Us
+            };
+
+            observer(CloseEvent::new(
+                self.id,
+                initiator,
+                self.close_payload.as_deref(),
+                None,
+            ));
+        }
+    }
+}
+
+#[cfg(all(feature = "client", feature = "server"))]
+impl WebSocketStream<DuplexStream> {
+    /// Creates two streams connected in memory via [`tokio::io::duplex`], the
+    /// first one in the client role and the second one in the server role.
+    ///
+    /// This allows testing message handlers without sockets or a handshake.
+    #[must_use]
+    pub fn pair() -> (Self, Self) {
+        Self::pair_with_config(Config::default(), Limits::default())
+    }
+
+    /// Creates two streams connected in memory like [`WebSocketStream::pair`],
+    /// with the given configuration and limits applied to both of them.
+    #[must_use]
+    pub fn pair_with_config(config: Config, limits: Limits) -> (Self, Self) {
+        let (client, server) = tokio::io::duplex(PAIR_BUFFER_SIZE);
+
+        (
+            Self::from_raw_stream(client, Role::Client, config, limits),
+            Self::from_raw_stream(server, Role::Server, config, limits),
+        )
+    }
+}
+
+impl<T> Stream for WebSocketStream<T>
+where
+    T: AsyncRead + AsyncWrite + Unpin,
+{
+    type Item = Result<Message, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let Some(frame) = ready!(self.as_mut().poll_next_frame(cx)?) else {
+                return Poll::Ready(None);
+            };
+
+            if let Some(message) = self.assemble_message(frame).transpose() {
+                return Poll::Ready(Some(message));
+            }
+        }
+    }
+}
+
+// The tokio-util implementation of a sink uses a buffer which start_send
+// appends to and poll_flush tries to write from. This makes sense, but comes
 // with a hefty performance penalty when sending large payloads, since this adds
 // a memmove from the payload to the buffer. We completely avoid that overhead
 // by storing messages in a deque.
@@ -309,6 +2118,8 @@ where
     type Error = Error;
 
     fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.queue_control_frames(cx);
+
         // tokio-util calls poll_flush when more than 8096 bytes are pending, otherwise
         // it returns Ready. We will just replicate that behavior
         if self.pending_bytes >= self.config.flush_threshold {
@@ -323,60 +2134,167 @@ where
             return Err(Error::AlreadyClosed);
         }
 
+        if !item.opcode.is_control() && self.extensions.per_frame() {
+            // Chunk the message into frames and transform each of them
+            let frames = item
+                .into_frames(self.config.frame_size, 0)
+                .map(|mut frame| {
+                    let (payload, rsv) = self.extensions.encode(frame.payload)?;
+                    frame.payload = payload;
+                    frame.rsv = rsv;
+
+                    Ok(frame)
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+
+            return self.queue_message_frames(frames);
+        }
+
+        let (item, rsv) = if item.opcode.is_control() || self.extensions.is_empty() {
+            (item, 0)
+        } else {
+            let (payload, rsv) = self.extensions.encode(item.payload)?;
+
+            (
+                Message {
+                    opcode: item.opcode,
+                    payload,
+                },
+                rsv,
+            )
+        };
+
         if item.opcode.is_control() || item.payload.len() <= self.config.frame_size {
-            let frame: Frame = item.into();
-            self.queue_frame(frame);
+            let mut frame: Frame = item.into();
+            frame.rsv = rsv;
+            self.queue_message_frames([frame])
         } else {
-            // Chunk the message into frames
-            for frame in item.into_frames(self.config.frame_size) {
-                self.queue_frame(frame);
-            }
+            // Chunk the message into frames, which slices the payload without
+            // copying it
+            let frames = item.into_frames(self.config.frame_size, rsv);
+            self.queue_message_frames(frames)
         }
-
-        Ok(())
     }
 
     fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         // Borrow checker hacks... It needs this to understand that we can separately
         // borrow the fields of the struct mutably
         let this = self.get_mut();
+        this.queue_control_frames(cx);
         let frame_queue = &mut this.frame_queue;
         let io = this.inner.get_mut();
         let bytes_written = &mut this.bytes_written;
         let pending_bytes = &mut this.pending_bytes;
+        let write_deadline = &mut this.write_deadline;
+        let write_timeout = this.config.write_timeout;
+        let cancellation = &mut this.cancellation;
+        let mut abandoned = None;
 
         while !frame_queue.is_empty() {
-            let frame = unsafe { frame_queue.front().unwrap_unchecked() };
-            let frame_header = unsafe { frame.header.get_unchecked(..frame.header_len as usize) };
-            let mut buf = frame_header
-                .chain(
-                    frame
-                        .mask
-                        .as_ref()
-                        .map(<[u8; 4]>::as_slice)
-                        .unwrap_or_default(),
-                )
-                .chain(&*frame.payload);
-            buf.advance(*bytes_written);
-
-            while buf.has_remaining() {
-                let n = ready!(poll_write_buf(Pin::new(io), cx, &mut buf))?;
+            // Gather as many queued frames as possible into a single (vectored) write
+            // to avoid a syscall per frame when many small messages were fed
+            let mut slices = [IoSlice::new(&[]); MAX_WRITE_SLICES];
+            let mut slice_count = 0;
+            let mut skip = *bytes_written;
 
-                if n == 0 {
-                    return Poll::Ready(Err(Error::Io(io::ErrorKind::WriteZero.into())));
+            'gather: for frame in &*frame_queue {
+                for part in frame.parts() {
+                    if skip >= part.len() {
+                        skip -= part.len();
+                        continue;
+                    }
+
+                    // SAFETY: skip < part.len() was just checked
+                    slices[slice_count] = IoSlice::new(unchecked! { unchecked::get(part, skip..) });
+                    skip = 0;
+                    slice_count += 1;
+
+                    if slice_count == MAX_WRITE_SLICES {
+                        break 'gather;
+                    }
                 }
+            }
 
-                *bytes_written += n;
-                *pending_bytes -= n;
+            let poll = if io.is_write_vectored() {
+                Pin::new(&mut *io).poll_write_vectored(cx, &slices[..slice_count])
+            } else {
+                Pin::new(&mut *io).poll_write(cx, &slices[0])
+            };
+
+            let n = match poll {
+                Poll::Ready(Ok(n)) => n,
+                Poll::Ready(Err(e)) => {
+                    abandoned = Some(Error::Io(e));
+                    break;
+                }
+                Poll::Pending if write_deadline.poll_elapsed(write_timeout, cx).is_ready() => {
+                    abandoned = Some(Error::WriteTimeout);
+                    break;
+                }
+                Poll::Pending if Cancellation::poll_cancelled(cancellation, cx).is_some() => {
+                    abandoned = Some(Error::AlreadyClosed);
+                    break;
+                }
+                Poll::Pending => return Poll::Pending,
+            };
+
+            if n == 0 {
+                abandoned = Some(Error::Io(io::ErrorKind::WriteZero.into()));
+                break;
             }
 
-            frame_queue.pop_front();
-            *bytes_written = 0;
+            *pending_bytes -= n;
+
+            // Pop all frames that were fully written
+            let mut written = *bytes_written + n;
+            while let Some(frame) = frame_queue.front() {
+                let frame_len = frame.len();
+
+                if written < frame_len {
+                    break;
+                }
+
+                written -= frame_len;
+                frame_queue.pop_front();
+            }
+            *bytes_written = written;
+            write_deadline.disarm();
         }
 
-        ready!(Pin::new(io).poll_flush(cx))?;
+        let error = match abandoned {
+            Some(error) => error,
+            None => match Pin::new(io).poll_flush(cx) {
+                Poll::Ready(Ok(())) => {
+                    write_deadline.disarm();
+
+                    return Poll::Ready(Ok(()));
+                }
+                Poll::Ready(Err(e)) => Error::Io(e),
+                Poll::Pending if write_deadline.poll_elapsed(write_timeout, cx).is_ready() => {
+                    Error::WriteTimeout
+                }
+                Poll::Pending if Cancellation::poll_cancelled(cancellation, cx).is_some() => {
+                    Error::AlreadyClosed
+                }
+                Poll::Pending => return Poll::Pending,
+            },
+        };
+
+        // Writing failed, stalled for longer than the write timeout or the stream
+        // was cancelled, so the partially written frames are dropped
+        write_deadline.disarm();
+        frame_queue.clear();
+        *bytes_written = 0;
+        *pending_bytes = 0;
+        let initiator = if this.state == StreamState::ClosedByPeer {
+            CloseInitiator::Peer
+        } else {
+            CloseInitiator::Us
+        };
+        this.state = StreamState::CloseAcknowledged;
+        this.notify_closed(initiator, Some(&error));
 
-        Poll::Ready(Ok(()))
+        Poll::Ready(Err(error))
     }
 
     fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
@@ -0,0 +1,110 @@
+//! Conversions between the types of this crate and their [`tungstenite`]
+//! equivalents, to allow for incremental migration or bridging libraries that
+//! still use [`tungstenite`] types.
+use ::tungstenite::{
+    protocol::{frame::coding::CloseCode as TungsteniteCloseCode, CloseFrame},
+    Bytes, Message as TungsteniteMessage, Utf8Bytes,
+};
+
+use super::{
+    types::{CloseCode, Message, OpCode, Payload},
+    ProtocolError,
+};
+use crate::unchecked::{self, unchecked};
+
+impl From<CloseCode> for TungsteniteCloseCode {
+    fn from(value: CloseCode) -> Self {
+        u16::from(value).into()
+    }
+}
+
+impl TryFrom<TungsteniteCloseCode> for CloseCode {
+    type Error = ProtocolError;
+
+    fn try_from(value: TungsteniteCloseCode) -> Result<Self, Self::Error> {
+        Self::try_from(u16::from(value))
+    }
+}
+
+impl From<Message> for TungsteniteMessage {
+    /// Converts the message without copying its payload.
+    ///
+    /// # Panics
+    ///
+    /// This conversion will panic when the message was created via
+    /// [`Message::text`] with invalid UTF-8.
+    fn from(value: Message) -> Self {
+        match value.opcode {
+            OpCode::Text => {
+                let validated = value.payload.is_utf8_validated();
+                let payload = Bytes::from(value.payload);
+
+                if validated {
+                    // SAFETY: The payload was validated to be valid UTF-8
+                    Self::Text(unchecked! { unchecked::utf8_bytes(payload) })
+                } else {
+                    Self::Text(
+                        Utf8Bytes::try_from(payload).expect(
+                            "converted text message created from payload with invalid utf-8",
+                        ),
+                    )
+                }
+            }
+            OpCode::Binary | OpCode::Continuation => Self::Binary(value.payload.into()),
+            OpCode::Ping => Self::Ping(value.payload.into()),
+            OpCode::Pong => Self::Pong(value.payload.into()),
+            OpCode::Close => {
+                if value.payload.is_empty() {
+                    return Self::Close(None);
+                }
+
+                let mut payload = Bytes::from(value.payload);
+                let code = u16::from_be_bytes([payload[0], payload[1]]).into();
+                let reason = payload.split_off(2);
+
+                Self::Close(Some(CloseFrame {
+                    code,
+                    // SAFETY: Close messages are created from a string reason or validated when
+                    // received
+                    reason: unchecked! { unchecked::utf8_bytes(reason) },
+                }))
+            }
+        }
+    }
+}
+
+impl TryFrom<TungsteniteMessage> for Message {
+    type Error = ProtocolError;
+
+    /// Converts the message without copying its payload, except for the reason
+    /// of close messages.
+    ///
+    /// # Errors
+    ///
+    /// This conversion fails with [`ProtocolError::InvalidCloseCode`] if a
+    /// close message carries a close code that is invalid on the wire and with
+    /// [`ProtocolError::InvalidOpcode`] for raw frames, which are not messages.
+    fn try_from(value: TungsteniteMessage) -> Result<Self, Self::Error> {
+        match value {
+            TungsteniteMessage::Text(text) => {
+                let mut payload = Payload::from(Bytes::from(text));
+                payload.set_utf8_validated(true);
+
+                Ok(Self::text(payload))
+            }
+            TungsteniteMessage::Binary(data) => Ok(Self::binary(data)),
+            TungsteniteMessage::Ping(data) => Ok(Self::ping(data)),
+            TungsteniteMessage::Pong(data) => Ok(Self::pong(data)),
+            TungsteniteMessage::Close(None) => Ok(Self::close(None, "")),
+            TungsteniteMessage::Close(Some(frame)) => {
+                let code = CloseCode::try_from(frame.code)?;
+                if !code.is_sendable() {
+                    return Err(ProtocolError::InvalidCloseCode);
+                }
+
+                Ok(Self::close(Some(code), &frame.reason))
+            }
+            TungsteniteMessage::Frame(_) => Err(ProtocolError::InvalidOpcode),
+        }
+    }
+}
@@ -1,19 +1,24 @@
 //! Types required for the WebSocket protocol implementation.
-use std::{
-    cell::UnsafeCell, fmt, hint::unreachable_unchecked, mem::replace, num::NonZeroU16, ops::Deref,
-};
+#[cfg(not(feature = "checked"))]
+use std::cell::UnsafeCell;
+#[cfg(any(feature = "client", feature = "server"))]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::{fmt, mem::replace, num::NonZeroU16, ops::Deref, time::Duration};
 
 use bytes::{BufMut, Bytes, BytesMut};
 
-use super::error::ProtocolError;
-use crate::utf8;
+use super::{error::ProtocolError, pool::BufferPool};
+use crate::{
+    unchecked::{self, unchecked},
+    utf8,
+};
 
 /// The opcode of a WebSocket frame. It denotes the type of the frame or an
 /// assembled message.
 ///
 /// A fully assembled [`Message`] will never have a continuation opcode.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
-pub(super) enum OpCode {
+pub enum OpCode {
     /// A continuation opcode. This will never be encountered in a full
     /// [`Message`].
     Continuation,
@@ -31,7 +36,8 @@ pub(super) enum OpCode {
 
 impl OpCode {
     /// Whether this is a control opcode (i.e. close, ping or pong).
-    pub(super) fn is_control(self) -> bool {
+    #[must_use]
+    pub fn is_control(self) -> bool {
         matches!(self, Self::Close | Self::Ping | Self::Pong)
     }
 }
@@ -74,54 +80,62 @@ pub struct CloseCode(NonZeroU16);
 impl CloseCode {
     /// Normal closure, meaning that the purpose for which the connection was
     /// established has been fulfilled.
-    pub const NORMAL_CLOSURE: Self = Self(unsafe { NonZeroU16::new_unchecked(1000) });
+    pub const NORMAL_CLOSURE: Self = Self::constant(1000);
     /// Endpoint is "going away", such as a server going down or a browser
     /// having navigated away from a page.
-    pub const GOING_AWAY: Self = Self(unsafe { NonZeroU16::new_unchecked(1001) });
+    pub const GOING_AWAY: Self = Self::constant(1001);
     /// Endpoint is terminating the connection due to a protocol error.
-    pub const PROTOCOL_ERROR: Self = Self(unsafe { NonZeroU16::new_unchecked(1002) });
+    pub const PROTOCOL_ERROR: Self = Self::constant(1002);
     /// Endpoint is terminating the connection because it has received a type of
     /// data it cannot accept.
-    pub const UNSUPPORTED_DATA: Self = Self(unsafe { NonZeroU16::new_unchecked(1003) });
+    pub const UNSUPPORTED_DATA: Self = Self::constant(1003);
     /// No status code was actually present.
-    pub const NO_STATUS_RECEIVED: Self = Self(unsafe { NonZeroU16::new_unchecked(1005) });
+    pub const NO_STATUS_RECEIVED: Self = Self::constant(1005);
     /// Endpoint is terminating the connection because it has received data
     /// within a message that was not consistent with the type of the message.
-    pub const INVALID_FRAME_PAYLOAD_DATA: Self = Self(unsafe { NonZeroU16::new_unchecked(1007) });
+    pub const INVALID_FRAME_PAYLOAD_DATA: Self = Self::constant(1007);
     /// Endpoint is terminating the connection because it has received a message
     /// that violates its policy.
-    pub const POLICY_VIOLATION: Self = Self(unsafe { NonZeroU16::new_unchecked(1008) });
+    pub const POLICY_VIOLATION: Self = Self::constant(1008);
     /// Endpoint is terminating the connection because it has received a message
     /// that is too big for it to process.
-    pub const MESSAGE_TOO_BIG: Self = Self(unsafe { NonZeroU16::new_unchecked(1009) });
+    pub const MESSAGE_TOO_BIG: Self = Self::constant(1009);
     /// Client is terminating the connection because it has expected the server
     /// to negotiate one or more extension, but the server didn't return them in
     /// the response message of the WebSocket handshake.
-    pub const MANDATORY_EXTENSION: Self = Self(unsafe { NonZeroU16::new_unchecked(1010) });
+    pub const MANDATORY_EXTENSION: Self = Self::constant(1010);
     /// Server is terminating the connection because it encountered an
     /// unexpected condition that prevented it from fulfilling the request.
-    pub const INTERNAL_SERVER_ERROR: Self = Self(unsafe { NonZeroU16::new_unchecked(1011) });
+    pub const INTERNAL_SERVER_ERROR: Self = Self::constant(1011);
     /// Service is restarted. A client may reconnect, and if it choses to do,
     /// should reconnect using a randomized delay of 5--30s.
-    pub const SERVICE_RESTART: Self = Self(unsafe { NonZeroU16::new_unchecked(1012) });
+    pub const SERVICE_RESTART: Self = Self::constant(1012);
     /// Service is experiencing overload. A client should only connect to a
     /// different IP (when there are multiple for the target) or reconnect to
     /// the same IP upon user action.
-    pub const SERVICE_OVERLOAD: Self = Self(unsafe { NonZeroU16::new_unchecked(1013) });
+    pub const SERVICE_OVERLOAD: Self = Self::constant(1013);
     /// The server was acting as a gateway or proxy and received an invalid
     /// response from the upstream server. This is similar to the HTTP 502
     /// status code.
-    pub const BAD_GATEWAY: Self = Self(unsafe { NonZeroU16::new_unchecked(1014) });
+    pub const BAD_GATEWAY: Self = Self::constant(1014);
 }
 
 impl CloseCode {
+    /// Creates a close code from a constant that is known to be valid.
+    const fn constant(code: u16) -> Self {
+        match NonZeroU16::new(code) {
+            Some(code) => Self(code),
+            None => panic!("close codes are non-zero"),
+        }
+    }
+
     /// Whether the close code is allowed to be sent over the wire.
     pub(super) fn is_sendable(self) -> bool {
         match self.0.get() {
             1004 | 1005 | 1006 | 1015 => false,
             1000..=4999 => true,
             // SAFETY: `TryFrom` is the only way to acquire self and it errors for these values
-            0..=999 | 5000..=u16::MAX => unsafe { unreachable_unchecked() },
+            0..=999 | 5000..=u16::MAX => unchecked! { unchecked::unreachable() },
         }
     }
 }
@@ -137,8 +151,10 @@ impl TryFrom<u16> for CloseCode {
 
     fn try_from(value: u16) -> Result<Self, Self::Error> {
         match value {
-            // SAFETY: We just checked that the value is non-zero
-            1000..=1015 | 3000..=4999 => Ok(Self(unsafe { NonZeroU16::new_unchecked(value) })),
+            // The value is known to be non-zero, so this optimizes to a plain conversion
+            1000..=1015 | 3000..=4999 => NonZeroU16::new(value)
+                .map(Self)
+                .ok_or(ProtocolError::InvalidCloseCode),
             0..=999 | 1016..=2999 | 5000..=u16::MAX => Err(ProtocolError::InvalidCloseCode),
         }
     }
@@ -158,11 +174,20 @@ impl TryFrom<u16> for CloseCode {
 /// All conversions to other types are zero-cost, except [`Into<BytesMut>`] if
 /// the backing type is [`Bytes`] with a reference counter greater than one.
 ///
+/// Payloads of received messages are split off the connection's read buffer
+/// without copying. Once all payloads referencing an allocation of the read
+/// buffer have been dropped, the allocation is reused for subsequent reads,
+/// which effectively pools buffers per connection. Holding on to received
+/// payloads for a long time prevents this reuse and keeps the entire
+/// allocation alive, so consider copying payloads that are retained. Read
+/// buffers can also be recycled across connections via
+/// [`Config::buffer_pool`].
+///
 /// [`From<Bytes>`]: #impl-From<Bytes>-for-Payload
 /// [`Into<BytesMut>`]: #impl-From<Payload>-for-BytesMut
 pub struct Payload {
     /// The raw payload data.
-    data: UnsafeCell<PayloadStorage>,
+    data: PayloadCell,
     /// Whether the payload data was validated to be valid UTF-8.
     utf8_validated: bool,
 }
@@ -171,11 +196,17 @@ impl Payload {
     /// Creates a new shared `Payload` from a static slice.
     const fn from_static(bytes: &'static [u8]) -> Self {
         Self {
-            data: UnsafeCell::new(PayloadStorage::Shared(Bytes::from_static(bytes))),
+            data: PayloadCell::new(PayloadStorage::Shared(Bytes::from_static(bytes))),
             utf8_validated: false,
         }
     }
 
+    /// Whether the payload contents were validated to be valid UTF-8.
+    #[cfg(any(feature = "serde", feature = "tungstenite"))]
+    pub(super) fn is_utf8_validated(&self) -> bool {
+        self.utf8_validated
+    }
+
     /// Marks whether the payload contents were validated to be valid UTF-8.
     pub(super) fn set_utf8_validated(&mut self, value: bool) {
         self.utf8_validated = value;
@@ -197,7 +228,7 @@ impl Payload {
         // split a utf8 codepoint), we set it to false.
         self.utf8_validated = false;
         Self {
-            data: UnsafeCell::new(match self.data.get_mut() {
+            data: PayloadCell::new(match self.data.get_mut() {
                 PayloadStorage::Unique(b) => PayloadStorage::Unique(b.split_to(at)),
                 PayloadStorage::Shared(b) => PayloadStorage::Shared(b.split_to(at)),
             }),
@@ -205,7 +236,25 @@ impl Payload {
         }
     }
 
+    /// Masks the payload with `mask` for sending it as a client. Unique
+    /// payloads are masked in place, shared ones are masked while copying them
+    /// to a new buffer, so that they are only read once.
+    #[cfg(feature = "client")]
+    pub(super) fn into_masked(self, mask: [u8; 4]) -> Self {
+        let mut payload = match self.data.into_inner() {
+            PayloadStorage::Unique(payload) => payload,
+            PayloadStorage::Shared(payload) => match payload.try_into_mut() {
+                Ok(payload) => payload,
+                Err(payload) => return Self::from(crate::mask::copy_frame(&mask, &payload)),
+            },
+        };
+        crate::mask::frame(&mask, &mut payload, 0);
+
+        Self::from(payload)
+    }
+
     /// Converts the payload's internal representation to [`Bytes`].
+    #[cfg(not(feature = "checked"))]
     fn as_bytes(&self) -> &Bytes {
         if let PayloadStorage::Shared(bytes) = self.as_ref() {
             bytes
@@ -216,13 +265,13 @@ impl Payload {
                 let payload = self.data.get().read();
                 let bytes = match payload {
                     PayloadStorage::Unique(p) => p.freeze(),
-                    PayloadStorage::Shared(_) => unreachable_unchecked(),
+                    PayloadStorage::Shared(_) => unchecked::unreachable(),
                 };
                 self.data.get().write(PayloadStorage::Shared(bytes));
             }
             match self.as_ref() {
                 // SAFETY: We just wrote `Shared` into `value`
-                PayloadStorage::Unique(_) => unsafe { unreachable_unchecked() },
+                PayloadStorage::Unique(_) => unchecked! { unchecked::unreachable() },
                 PayloadStorage::Shared(p) => p,
             }
         }
@@ -230,17 +279,32 @@ impl Payload {
 }
 
 impl AsRef<PayloadStorage> for Payload {
+    #[cfg(not(feature = "checked"))]
     fn as_ref(&self) -> &PayloadStorage {
         // SAFETY: No outstanding mutable references exists.
         unsafe { &*self.data.get() }
     }
+
+    #[cfg(feature = "checked")]
+    fn as_ref(&self) -> &PayloadStorage {
+        &self.data.0
+    }
 }
 
 impl Clone for Payload {
     fn clone(&self) -> Self {
-        let bytes = self.as_bytes();
+        #[cfg(not(feature = "checked"))]
+        let bytes = self.as_bytes().clone();
+        // Unique data cannot be frozen in place without interior mutability,
+        // so it is copied instead
+        #[cfg(feature = "checked")]
+        let bytes = match self.as_ref() {
+            PayloadStorage::Unique(b) => Bytes::copy_from_slice(b),
+            PayloadStorage::Shared(b) => b.clone(),
+        };
+
         Self {
-            data: UnsafeCell::new(PayloadStorage::Shared(bytes.clone())),
+            data: PayloadCell::new(PayloadStorage::Shared(bytes)),
             utf8_validated: self.utf8_validated,
         }
     }
@@ -267,11 +331,11 @@ impl From<Bytes> for Payload {
     fn from(value: Bytes) -> Self {
         match value.try_into_mut() {
             Ok(value) => Self {
-                data: UnsafeCell::new(PayloadStorage::Unique(value)),
+                data: PayloadCell::new(PayloadStorage::Unique(value)),
                 utf8_validated: false,
             },
             Err(value) => Self {
-                data: UnsafeCell::new(PayloadStorage::Shared(value)),
+                data: PayloadCell::new(PayloadStorage::Shared(value)),
                 utf8_validated: false,
             },
         }
@@ -281,7 +345,7 @@ impl From<Bytes> for Payload {
 impl From<BytesMut> for Payload {
     fn from(value: BytesMut) -> Self {
         Self {
-            data: UnsafeCell::new(PayloadStorage::Unique(value)),
+            data: PayloadCell::new(PayloadStorage::Unique(value)),
             utf8_validated: false,
         }
     }
@@ -311,7 +375,7 @@ impl From<Vec<u8>> for Payload {
         // Vec, effectively allowing us to use BytesMut::from_vec which isn't
         // exposed in bytes. See https://github.com/tokio-rs/bytes/issues/723 for details.
         Self {
-            data: UnsafeCell::new(PayloadStorage::Unique(BytesMut::from_iter(value))),
+            data: PayloadCell::new(PayloadStorage::Unique(BytesMut::from_iter(value))),
             utf8_validated: false,
         }
     }
@@ -321,7 +385,7 @@ impl From<String> for Payload {
     fn from(value: String) -> Self {
         // See From<Vec<u8>> impl for reasoning behind this.
         Self {
-            data: UnsafeCell::new(PayloadStorage::Unique(BytesMut::from_iter(
+            data: PayloadCell::new(PayloadStorage::Unique(BytesMut::from_iter(
                 value.into_bytes(),
             ))),
             utf8_validated: true,
@@ -332,7 +396,7 @@ impl From<String> for Payload {
 impl From<&'static [u8]> for Payload {
     fn from(value: &'static [u8]) -> Self {
         Self {
-            data: UnsafeCell::new(PayloadStorage::Shared(Bytes::from_static(value))),
+            data: PayloadCell::new(PayloadStorage::Shared(Bytes::from_static(value))),
             utf8_validated: false,
         }
     }
@@ -341,12 +405,39 @@ impl From<&'static [u8]> for Payload {
 impl From<&'static str> for Payload {
     fn from(value: &'static str) -> Self {
         Self {
-            data: UnsafeCell::new(PayloadStorage::Shared(Bytes::from_static(value.as_bytes()))),
+            data: PayloadCell::new(PayloadStorage::Shared(Bytes::from_static(value.as_bytes()))),
             utf8_validated: true,
         }
     }
 }
 
+/// Cell holding the [`PayloadStorage`] of a [`Payload`], which allows for
+/// freezing unique data when cloning it.
+#[cfg(not(feature = "checked"))]
+type PayloadCell = UnsafeCell<PayloadStorage>;
+
+/// Holds the [`PayloadStorage`] of a [`Payload`] without interior mutability.
+#[cfg(feature = "checked")]
+struct PayloadCell(PayloadStorage);
+
+#[cfg(feature = "checked")]
+impl PayloadCell {
+    /// Creates a new cell holding `storage`.
+    const fn new(storage: PayloadStorage) -> Self {
+        Self(storage)
+    }
+
+    /// Returns a mutable reference to the storage.
+    fn get_mut(&mut self) -> &mut PayloadStorage {
+        &mut self.0
+    }
+
+    /// Returns the storage.
+    fn into_inner(self) -> PayloadStorage {
+        self.0
+    }
+}
+
 /// [`Payload`] backend.
 #[derive(Debug)]
 enum PayloadStorage {
@@ -356,6 +447,201 @@ enum PayloadStorage {
     Shared(Bytes),
 }
 
+/// A [`Payload`] that is known to contain valid UTF-8, obtained via
+/// [`Message::into_text`] or converted from a [`String`] or `&'static str`.
+///
+/// It dereferences to [`str`] without copying or validating the payload again.
+#[derive(Clone)]
+pub struct Utf8Payload(Payload);
+
+impl Utf8Payload {
+    /// Returns the payload as a string slice.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        // SAFETY: The payload was validated to be valid UTF-8 on creation
+        unchecked! { unchecked::from_utf8(&self.0) }
+    }
+
+    /// Returns the underlying [`Payload`].
+    #[must_use]
+    pub fn into_payload(self) -> Payload {
+        self.0
+    }
+}
+
+impl Deref for Utf8Payload {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Utf8Payload {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<[u8]> for Utf8Payload {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Utf8Payload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Utf8Payload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl PartialEq<str> for Utf8Payload {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Utf8Payload {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl From<String> for Utf8Payload {
+    fn from(value: String) -> Self {
+        Self(Payload::from(value))
+    }
+}
+
+impl From<&'static str> for Utf8Payload {
+    fn from(value: &'static str) -> Self {
+        Self(Payload::from(value))
+    }
+}
+
+impl From<Utf8Payload> for Payload {
+    fn from(value: Utf8Payload) -> Self {
+        value.0
+    }
+}
+
+impl From<Utf8Payload> for Bytes {
+    fn from(value: Utf8Payload) -> Self {
+        value.0.into()
+    }
+}
+
+/// Maximum payload length of control frames.
+const MAX_CONTROL_PAYLOAD_LEN: usize = 125;
+
+/// A [`Payload`] of a ping or pong message that is known to fit into a
+/// control frame, i.e. to be at most 125 bytes long.
+///
+/// It converts into a [`Payload`] for [`Message::ping`] and
+/// [`Message::pong`].
+#[derive(Debug, Clone)]
+pub struct PingPayload(Payload);
+
+impl PingPayload {
+    /// Creates a ping payload, checking its length.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`ProtocolError::InvalidPayloadLength`] if the
+    /// payload is longer than 125 bytes.
+    pub fn new<P: Into<Payload>>(payload: P) -> Result<Self, ProtocolError> {
+        let payload = payload.into();
+
+        if payload.len() > MAX_CONTROL_PAYLOAD_LEN {
+            return Err(ProtocolError::InvalidPayloadLength);
+        }
+
+        Ok(Self(payload))
+    }
+
+    /// Returns the underlying [`Payload`].
+    #[must_use]
+    pub fn into_payload(self) -> Payload {
+        self.0
+    }
+}
+
+impl Deref for PingPayload {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<PingPayload> for Payload {
+    fn from(value: PingPayload) -> Self {
+        value.0
+    }
+}
+
+/// A reason of a close message that is known to be valid UTF-8 and to fit
+/// into a control frame along with the close code, i.e. to be at most 123
+/// bytes long.
+///
+/// Pass it to [`Message::close`] via [`CloseReason::as_str`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloseReason(String);
+
+impl CloseReason {
+    /// Creates a close reason, checking its length.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`ProtocolError::InvalidPayloadLength`] if the
+    /// reason is longer than 123 bytes.
+    pub fn new(reason: &str) -> Result<Self, ProtocolError> {
+        if reason.len() > MAX_CONTROL_PAYLOAD_LEN - 2 {
+            return Err(ProtocolError::InvalidPayloadLength);
+        }
+
+        Ok(Self(reason.to_owned()))
+    }
+
+    /// Creates a close reason from bytes, checking that they are valid UTF-8
+    /// and their length.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`ProtocolError::InvalidUtf8`] if the reason is not
+    /// valid UTF-8 and [`ProtocolError::InvalidPayloadLength`] if it is longer
+    /// than 123 bytes.
+    pub fn from_utf8(reason: &[u8]) -> Result<Self, ProtocolError> {
+        Self::new(utf8::parse_str(reason)?)
+    }
+
+    /// Returns the reason as a string slice.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for CloseReason {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl fmt::Display for CloseReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
 /// A WebSocket message. This is cheaply clonable and uses [`Payload`] as the
 /// payload storage underneath.
 ///
@@ -469,6 +755,9 @@ impl Message {
     /// Returns a reference to the message payload as a string if it is a text
     /// message.
     ///
+    /// This does not copy the payload and only validates it if the message was
+    /// not received, but created via [`Message::text`] from bytes.
+    ///
     /// # Panics
     ///
     /// This method will panic when the message was created via
@@ -481,7 +770,32 @@ impl Message {
                 self.payload.utf8_validated || utf8::parse_str(&self.payload).is_ok(),
                 "called as_text on message created from payload with invalid utf-8"
             );
-            unsafe { std::str::from_utf8_unchecked(&self.payload) }
+            unchecked! { unchecked::from_utf8(&self.payload) }
+        })
+    }
+
+    /// Returns the message payload as a [`Utf8Payload`] and consumes the
+    /// message if it is a text message.
+    ///
+    /// Like [`Message::as_text`], this does not copy the payload and only
+    /// validates it if the message was created via [`Message::text`] from
+    /// bytes.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic when the message was created via
+    /// [`Message::text`] with invalid UTF-8.
+    #[must_use]
+    pub fn into_text(self) -> Option<Utf8Payload> {
+        (self.opcode == OpCode::Text).then(|| {
+            let mut payload = self.payload;
+            assert!(
+                payload.utf8_validated || utf8::parse_str(&payload).is_ok(),
+                "called into_text on message created from payload with invalid utf-8"
+            );
+            payload.set_utf8_validated(true);
+
+            Utf8Payload(payload)
         })
     }
 
@@ -494,20 +808,16 @@ impl Message {
             } else {
                 // SAFETY: Opcode is Close with a non-empty payload so it's at least 2 bytes
                 // long
-                unsafe {
-                    CloseCode::try_from(u16::from_be_bytes(
-                        self.payload
-                            .get_unchecked(0..2)
-                            .try_into()
-                            .unwrap_unchecked(),
-                    ))
-                    .unwrap_unchecked()
+                unchecked! {
+                    unchecked::unwrap(CloseCode::try_from(u16::from_be_bytes(unchecked::unwrap(
+                        unchecked::get(&self.payload, 0..2).try_into(),
+                    ))))
                 }
             };
 
             // SAFETY: Opcode is Close so the rest of the payload is valid UTF-8
             let reason =
-                unsafe { std::str::from_utf8_unchecked(self.payload.get(2..).unwrap_or_default()) };
+                unchecked! { unchecked::from_utf8(self.payload.get(2..).unwrap_or_default()) };
 
             (code, reason)
         })
@@ -515,15 +825,52 @@ impl Message {
 
     /// Returns an iterator over frames of `frame_size` length to split this
     /// message into.
-    pub(super) fn into_frames(self, frame_size: usize) -> MessageFrames {
+    pub(super) fn into_frames(self, frame_size: usize, rsv: u8) -> MessageFrames {
         MessageFrames {
             frame_size,
             payload: self.payload,
             opcode: self.opcode,
+            rsv,
         }
     }
 }
 
+impl From<String> for Message {
+    fn from(value: String) -> Self {
+        Self::text(value)
+    }
+}
+
+impl From<&'static str> for Message {
+    fn from(value: &'static str) -> Self {
+        Self::text(value)
+    }
+}
+
+impl From<Bytes> for Message {
+    fn from(value: Bytes) -> Self {
+        Self::binary(value)
+    }
+}
+
+impl From<BytesMut> for Message {
+    fn from(value: BytesMut) -> Self {
+        Self::binary(value)
+    }
+}
+
+impl From<Vec<u8>> for Message {
+    fn from(value: Vec<u8>) -> Self {
+        Self::binary(value)
+    }
+}
+
+impl From<&'static [u8]> for Message {
+    fn from(value: &'static [u8]) -> Self {
+        Self::binary(value)
+    }
+}
+
 /// Iterator over frames of a chunked message.
 pub(super) struct MessageFrames {
     /// Iterator over payload chunks.
@@ -532,6 +879,8 @@ pub(super) struct MessageFrames {
     payload: Payload,
     /// Opcode for the next frame.
     opcode: OpCode,
+    /// RSV bits for the next frame.
+    rsv: u8,
 }
 
 impl Iterator for MessageFrames {
@@ -548,12 +897,47 @@ impl Iterator for MessageFrames {
             Frame {
                 opcode: replace(&mut self.opcode, OpCode::Continuation),
                 is_final: self.payload.is_empty(),
+                rsv: replace(&mut self.rsv, 0),
                 payload,
             }
         })
     }
 }
 
+/// Process-wide unique identifier of a [`WebSocketStream`], assigned when the
+/// stream is created.
+///
+/// It allows correlating diagnostics of tasks that operate on the same
+/// connection, e.g. a read task, a write task and a keepalive timer. The
+/// [`Debug`](fmt::Debug) output of the stream includes it.
+///
+/// [`WebSocketStream`]: super::WebSocketStream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ConnectionId(u64);
+
+impl ConnectionId {
+    /// Returns the next unused identifier.
+    #[cfg(any(feature = "client", feature = "server"))]
+    pub(super) fn next() -> Self {
+        /// Identifier of the next connection.
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Returns the numeric value of the identifier.
+    #[must_use]
+    pub fn get(self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for ConnectionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 /// Configuration for limitations on reading of [`Message`]s from a
 /// [`WebSocketStream`] to prevent high memory usage caused by malicious actors.
 ///
@@ -563,6 +947,12 @@ pub struct Limits {
     /// The maximum allowed payload length. The default
     /// is 64 MiB.
     pub(super) max_payload_len: usize,
+    /// The maximum number of received messages per interval. The default is
+    /// `None`.
+    pub(super) message_rate: Option<(u32, Duration)>,
+    /// The maximum number of received payload bytes per interval. The default
+    /// is `None`.
+    pub(super) byte_rate: Option<(usize, Duration)>,
 }
 
 impl Limits {
@@ -571,6 +961,8 @@ impl Limits {
     pub fn unlimited() -> Self {
         Self {
             max_payload_len: usize::MAX,
+            message_rate: None,
+            byte_rate: None,
         }
     }
 
@@ -582,12 +974,43 @@ impl Limits {
 
         self
     }
+
+    /// Limits the rate of received messages to `max_messages` per `per`, with
+    /// bursts of up to `max_messages` messages. The default is no limit.
+    ///
+    /// Every received frame that completes a message counts, including control
+    /// frames. Once the limit is exceeded, the stream sends a close frame with
+    /// [`CloseCode::POLICY_VIOLATION`] and reading fails with
+    /// [`Error::RateLimitExceeded`].
+    ///
+    /// [`Error::RateLimitExceeded`]: crate::Error::RateLimitExceeded
+    #[must_use]
+    pub fn max_message_rate(mut self, max_messages: u32, per: Duration) -> Self {
+        self.message_rate = Some((max_messages, per));
+
+        self
+    }
+
+    /// Limits the rate of received payload bytes to `max_bytes` per `per`,
+    /// with bursts of up to `max_bytes` bytes. The default is no limit.
+    ///
+    /// Payloads are counted as received before decompression. Frames larger
+    /// than `max_bytes` always exceed the limit, which closes the connection
+    /// like [`Limits::max_message_rate`].
+    #[must_use]
+    pub fn max_byte_rate(mut self, max_bytes: usize, per: Duration) -> Self {
+        self.byte_rate = Some((max_bytes, per));
+
+        self
+    }
 }
 
 impl Default for Limits {
     fn default() -> Self {
         Self {
             max_payload_len: 64 * 1024 * 1024,
+            message_rate: None,
+            byte_rate: None,
         }
     }
 }
@@ -602,10 +1025,57 @@ pub struct Config {
     ///
     /// Consider decreasing this if the remote imposes a limit on the frame
     /// payload size. The default is 4MiB.
+    ///
+    /// Pings and pongs are sent in between the frames of a message that is
+    /// being written, so smaller frames also reduce how long they are held up
+    /// by large messages.
     pub(super) frame_size: usize,
     /// Threshold of queued up bytes after which the underlying I/O is flushed
     /// before the sink is declared ready. The default is 8 KiB.
     pub(super) flush_threshold: usize,
+    /// Initial capacity of the read buffer. The default is 8 KiB.
+    pub(crate) read_buffer_capacity: usize,
+    /// Whether to accept unmasked frames from clients in the server role. The
+    /// default is `false`.
+    pub(super) accept_unmasked_frames: bool,
+    /// Duration without received frames after which the connection is
+    /// closed. The default is `None`.
+    pub(super) idle_timeout: Option<Duration>,
+    /// Close code sent when the idle timeout elapses. The default is
+    /// [`CloseCode::GOING_AWAY`].
+    pub(super) idle_timeout_close_code: CloseCode,
+    /// Duration after which writing to a stalled underlying I/O fails. The
+    /// default is `None`.
+    pub(super) write_timeout: Option<Duration>,
+    /// Duration that [`WebSocketStream::close`] waits for the close frame of
+    /// the peer. The default is `None`.
+    ///
+    /// [`WebSocketStream::close`]: super::WebSocketStream::close
+    pub(super) close_timeout: Option<Duration>,
+    /// Whether received pings are answered automatically. The default is
+    /// `true`.
+    pub(super) auto_pong: bool,
+    /// Interval at which pings are sent to keep the connection alive. The
+    /// default is `None`.
+    pub(super) keepalive_interval: Option<Duration>,
+    /// Maximum random duration added to every keepalive interval. The default
+    /// is `None`.
+    pub(super) keepalive_jitter: Option<Duration>,
+    /// Whether pongs that do not answer a ping sent by the stream are dropped
+    /// instead of being returned. The default is `false`.
+    pub(super) drop_unsolicited_pongs: bool,
+    /// Number of frames received in a row after which the stream yields to
+    /// the runtime. The default is `None`.
+    pub(super) poll_budget: Option<u32>,
+    /// Payload size above which received data frames are returned in chunks
+    /// as they arrive. The default is `None`.
+    pub(super) chunk_threshold: Option<usize>,
+    /// Payload size of received frames above which the read buffer is shrunk
+    /// back to its initial capacity. The default is 1 MiB.
+    pub(super) read_buffer_shrink_threshold: Option<usize>,
+    /// Pool that read buffers are acquired from and released to. The default
+    /// is `None`.
+    pub(super) buffer_pool: Option<&'static dyn BufferPool>,
 }
 
 impl Config {
@@ -627,12 +1097,258 @@ impl Config {
 
     /// Sets the threshold of queued up bytes after which the underlying I/O is
     /// flushed before the sink is declared ready. The default is 8 KiB.
+    ///
+    /// This is the high-watermark of the write buffer: messages are queued
+    /// without copying until this many bytes are pending. Lower it to bound
+    /// the memory held by slow peers, raise it to batch large frames into
+    /// fewer writes.
     #[must_use]
     pub fn flush_threshold(mut self, threshold: usize) -> Self {
         self.flush_threshold = threshold;
 
         self
     }
+
+    /// Sets the initial capacity of the buffer that data is read from the
+    /// underlying I/O into. The default is 8 KiB.
+    ///
+    /// The buffer grows as needed to fit received frames, so this only
+    /// determines how much memory every connection allocates up front and how
+    /// much is read per syscall. Lower it for deployments with many mostly
+    /// idle connections, raise it for backends that send large frames.
+    #[must_use]
+    pub fn read_buffer_capacity(mut self, capacity: usize) -> Self {
+        self.read_buffer_capacity = capacity;
+
+        self
+    }
+
+    /// Sets whether to accept unmasked frames from clients in the server role.
+    /// The default is `false`.
+    ///
+    /// RFC 6455 requires servers to fail the connection with a protocol error
+    /// when a client sends an unmasked frame. Only enable this to interoperate
+    /// with broken clients that do not mask their frames.
+    #[must_use]
+    pub fn accept_unmasked_frames(mut self, accept: bool) -> Self {
+        self.accept_unmasked_frames = accept;
+
+        self
+    }
+
+    /// Sets the duration without any received frames after which the stream
+    /// sends a close frame and terminates with [`Error::IdleTimeout`], without
+    /// waiting for the peer to acknowledge the close. `None` disables the idle
+    /// timeout. The default is `None`.
+    ///
+    /// This allows servers to reap connections of peers that disappeared
+    /// without closing them. The timeout only elapses while the stream is
+    /// polled for messages. Ping frames count as received frames, so clients
+    /// that send keepalive pings are never considered idle.
+    ///
+    /// [`Error::IdleTimeout`]: crate::Error::IdleTimeout
+    #[must_use]
+    pub fn idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.idle_timeout = timeout;
+
+        self
+    }
+
+    /// Sets the close code sent when the idle timeout elapses. The default is
+    /// [`CloseCode::GOING_AWAY`].
+    #[must_use]
+    pub fn idle_timeout_close_code(mut self, code: CloseCode) -> Self {
+        self.idle_timeout_close_code = code;
+
+        self
+    }
+
+    /// Sets the duration for which writing to the underlying I/O may make no
+    /// progress before sending and flushing fail with [`Error::WriteTimeout`].
+    /// `None` disables the write timeout. The default is `None`.
+    ///
+    /// This prevents a peer that stops reading, e.g. because it disappeared
+    /// or is overloaded, from blocking the sending task indefinitely. Once the
+    /// write timeout elapses, the connection is considered dead: queued frames
+    /// are dropped and all further operations on the stream fail.
+    ///
+    /// [`Error::WriteTimeout`]: crate::Error::WriteTimeout
+    #[must_use]
+    pub fn write_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.write_timeout = timeout;
+
+        self
+    }
+
+    /// Sets the duration that [`WebSocketStream::close`] waits for the peer to
+    /// answer the close frame before shutting down the underlying I/O anyway.
+    /// `None` waits until the peer answers or the stream ends. The default is
+    /// `None`.
+    ///
+    /// Without a timeout, a peer that never answers the close frame keeps the
+    /// closing task waiting indefinitely.
+    ///
+    /// [`WebSocketStream::close`]: super::WebSocketStream::close
+    #[must_use]
+    pub fn close_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.close_timeout = timeout;
+
+        self
+    }
+
+    /// Sets whether received pings are answered with a pong automatically.
+    /// The default is `true`.
+    ///
+    /// Disable this to control the pongs yourself, for example to delay them.
+    /// RFC 6455 still requires answering every ping with a pong carrying the
+    /// same payload.
+    #[must_use]
+    pub fn auto_pong(mut self, auto_pong: bool) -> Self {
+        self.auto_pong = auto_pong;
+
+        self
+    }
+
+    /// Sets the interval at which the stream sends pings to keep the
+    /// connection alive. `None` disables keepalive pings. The default is
+    /// `None`.
+    ///
+    /// Pings are only sent while the stream is polled for messages. Combine
+    /// this with [`Config::idle_timeout`] on the other end to detect peers
+    /// that disappeared.
+    #[must_use]
+    pub fn keepalive_interval(mut self, interval: Option<Duration>) -> Self {
+        self.keepalive_interval = interval;
+
+        self
+    }
+
+    /// Sets the maximum random duration added to every
+    /// [`Config::keepalive_interval`]. `None` sends pings at exactly the
+    /// configured interval. The default is `None`.
+    ///
+    /// Connections that were established around the same time, e.g. after a
+    /// server restart, otherwise send their pings in synchronized bursts.
+    #[must_use]
+    pub fn keepalive_jitter(mut self, jitter: Option<Duration>) -> Self {
+        self.keepalive_jitter = jitter;
+
+        self
+    }
+
+    /// Sets whether received pongs that do not answer a ping sent on this
+    /// stream are dropped silently instead of being returned. The default is
+    /// `false`.
+    ///
+    /// Peers may send pongs as unidirectional heartbeats, which are usually
+    /// just noise for the application. Pongs are matched against the payloads
+    /// of outstanding pings, including the ones sent via
+    /// [`Config::keepalive_interval`] and [`ControlSender`]s, so pongs
+    /// answering a ping are always returned.
+    ///
+    /// [`ControlSender`]: super::ControlSender
+    #[must_use]
+    pub fn drop_unsolicited_pongs(mut self, drop: bool) -> Self {
+        self.drop_unsolicited_pongs = drop;
+
+        self
+    }
+
+    /// Sets the number of frames that may be received in a row before the
+    /// stream yields to the runtime once, by waking its task and returning
+    /// [`Poll::Pending`]. `None` never yields. The default is `None`.
+    ///
+    /// A peer that sends faster than the frames are processed keeps data
+    /// buffered at all times, so a task reading from its connection in a loop
+    /// would never yield and could starve other tasks on the same worker
+    /// thread. Note that [`WebSocketStream::try_read_message`] returns
+    /// [`None`] whenever the stream yields.
+    ///
+    /// [`Poll::Pending`]: std::task::Poll::Pending
+    /// [`WebSocketStream::try_read_message`]: super::WebSocketStream::try_read_message
+    #[must_use]
+    pub fn poll_budget(mut self, frames: Option<u32>) -> Self {
+        self.poll_budget = frames;
+
+        self
+    }
+
+    /// Sets the payload size in bytes above which received data frames are
+    /// split into chunks of at least this size that are returned as their
+    /// payload arrives, instead of once the entire frame was received. `None`
+    /// always waits for entire frames. The default is `None`.
+    ///
+    /// Each chunk is returned as a frame of its own, the first one with the
+    /// opcode of the frame and the following ones as continuation frames. With
+    /// [`WebSocketStream::read_message_into`], a binary message sent as a
+    /// single 100 MB frame is then written in chunks instead of occupying
+    /// 100 MB of memory in the read buffer first. Messages received as usual
+    /// are assembled from the chunks like from any other fragments.
+    ///
+    /// Frames are not chunked while an extension that transforms individual
+    /// frames is negotiated.
+    ///
+    /// [`WebSocketStream::read_message_into`]: super::WebSocketStream::read_message_into
+    #[must_use]
+    pub fn chunk_threshold(mut self, threshold: Option<usize>) -> Self {
+        self.chunk_threshold = threshold;
+
+        self
+    }
+
+    /// Sets the payload size in bytes of received frames above which the read
+    /// buffer is shrunk back to [`Config::read_buffer_capacity`] once they
+    /// were read. `None` never shrinks the buffer. The default is 1 MiB.
+    ///
+    /// The read buffer grows to fit the largest frame received and would
+    /// otherwise keep that memory for the rest of the connection, so
+    /// long-lived connections with rare large transfers hold on to their peak
+    /// memory usage. Shrinking copies at most the initial capacity worth of
+    /// data that was already read past the frame. Raise this for connections
+    /// that receive large frames regularly to avoid reallocating the buffer
+    /// for each of them.
+    #[must_use]
+    pub fn read_buffer_shrink_threshold(mut self, threshold: Option<usize>) -> Self {
+        self.read_buffer_shrink_threshold = threshold;
+
+        self
+    }
+
+    /// Sets the pool that read buffers are acquired from and released to. The
+    /// default is `None`, which allocates them for every connection.
+    ///
+    /// The read buffer of a stream and the buffers that fragmented messages
+    /// are assembled in are acquired from the pool and released to it once the
+    /// stream is dropped, so that servers with many short-lived connections
+    /// reuse their allocations. The pool is shared by all streams created with
+    /// this configuration, e.g. a `static` [`BoundedPool`].
+    ///
+    /// [`BoundedPool`]: super::BoundedPool
+    #[must_use]
+    pub fn buffer_pool(mut self, pool: &'static dyn BufferPool) -> Self {
+        self.buffer_pool = Some(pool);
+
+        self
+    }
+
+    /// Returns an empty buffer with a capacity of at least `capacity` bytes,
+    /// from the buffer pool if one is configured.
+    pub(super) fn acquire_buffer(&self, capacity: usize) -> BytesMut {
+        match self.buffer_pool {
+            Some(pool) => pool.acquire(capacity),
+            None => BytesMut::with_capacity(capacity),
+        }
+    }
+
+    /// Releases a buffer that is no longer used to the buffer pool, if one is
+    /// configured and the buffer holds an allocation.
+    pub(super) fn release_buffer(&self, buf: BytesMut) {
+        if let Some(pool) = self.buffer_pool {
+            if buf.capacity() != 0 {
+                pool.release(buf);
+            }
+        }
+    }
 }
 
 impl Default for Config {
@@ -640,6 +1356,20 @@ impl Default for Config {
         Self {
             frame_size: 4 * 1024 * 1024,
             flush_threshold: 8 * 1024,
+            read_buffer_capacity: 8 * 1024,
+            accept_unmasked_frames: false,
+            idle_timeout: None,
+            idle_timeout_close_code: CloseCode::GOING_AWAY,
+            write_timeout: None,
+            close_timeout: None,
+            auto_pong: true,
+            keepalive_interval: None,
+            keepalive_jitter: None,
+            drop_unsolicited_pongs: false,
+            poll_budget: None,
+            chunk_threshold: None,
+            read_buffer_shrink_threshold: Some(1024 * 1024),
+            buffer_pool: None,
         }
     }
 }
@@ -653,44 +1383,309 @@ pub(crate) enum Role {
     Server,
 }
 
-/// The connection state of the stream.
-#[derive(Debug, PartialEq)]
-pub(super) enum StreamState {
+/// The connection state of a [`WebSocketStream`], as returned by
+/// [`WebSocketStream::state`].
+///
+/// [`WebSocketStream`]: super::WebSocketStream
+/// [`WebSocketStream::state`]: super::WebSocketStream::state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StreamState {
     /// The connection is fully active and no close has been initiated.
     Active,
     /// The connection has been closed by the peer, but not yet acknowledged by
-    /// us.
+    /// us. Messages can no longer be sent.
     ClosedByPeer,
-    /// The connection has been closed by us, but not yet acknowledged.
+    /// The connection has been closed by us, but not yet acknowledged. Messages
+    /// can no longer be sent, but are still received until the peer
+    /// acknowledges the close.
     ClosedByUs,
     /// The close has been acknowledged by the end that did not initiate the
-    /// close.
+    /// close, or the connection was closed without a close handshake, e.g.
+    /// after an error. Nothing is sent or received anymore.
     CloseAcknowledged,
 }
 
+/// The end of a connection that initiated its close, as reported by
+/// [`CloseEvent::initiator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseInitiator {
+    /// We sent the first close frame, or failed the connection after an error.
+    Us,
+    /// The peer sent the first close frame, or closed the underlying stream
+    /// without one.
+    Peer,
+}
+
+/// Describes how a message was written to the wire, returned by
+/// [`WebSocketStream::send_with_info`].
+///
+/// [`WebSocketStream::send_with_info`]: super::WebSocketStream::send_with_info
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SendInfo {
+    /// Number of frames the message was split into.
+    frames: usize,
+    /// Number of bytes of the frames including their headers.
+    wire_bytes: usize,
+    /// Whether the payload was compressed by an extension.
+    compressed: bool,
+}
+
+impl SendInfo {
+    /// Creates a new [`SendInfo`].
+    pub(super) fn new(frames: usize, wire_bytes: usize, compressed: bool) -> Self {
+        Self {
+            frames,
+            wire_bytes,
+            compressed,
+        }
+    }
+
+    /// Returns the number of frames the message was split into.
+    #[must_use]
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Returns the number of bytes written to the wire for the message,
+    /// including the frame headers and masking keys.
+    #[must_use]
+    pub fn wire_bytes(&self) -> usize {
+        self.wire_bytes
+    }
+
+    /// Returns whether the payload was compressed by an extension such as
+    /// permessage-deflate, i.e. whether RSV1 is set on its first frame.
+    #[must_use]
+    pub fn compressed(&self) -> bool {
+        self.compressed
+    }
+}
+
+/// Describes how a connection was closed, passed to the observer set via
+/// [`WebSocketStream::set_close_observer`].
+///
+/// [`WebSocketStream::set_close_observer`]: super::WebSocketStream::set_close_observer
+#[derive(Debug)]
+pub struct CloseEvent<'a> {
+    /// The identifier of the connection that was closed.
+    connection_id: ConnectionId,
+    /// The end that initiated the close.
+    initiator: CloseInitiator,
+    /// The close code of the first close frame, if any.
+    code: Option<CloseCode>,
+    /// The close reason of the first close frame.
+    reason: &'a str,
+    /// The error that failed the connection, if any.
+    error: Option<&'a crate::Error>,
+}
+
+impl<'a> CloseEvent<'a> {
+    /// Creates a new [`CloseEvent`] from the payload of the first close frame
+    /// sent or received, if any.
+    pub(super) fn new(
+        connection_id: ConnectionId,
+        initiator: CloseInitiator,
+        close_payload: Option<&'a [u8]>,
+        error: Option<&'a crate::Error>,
+    ) -> Self {
+        let (code, reason) = parse_close_payload(close_payload.unwrap_or_default());
+
+        Self {
+            connection_id,
+            initiator,
+            code,
+            reason,
+            error,
+        }
+    }
+
+    /// Returns the identifier of the connection that was closed, see
+    /// [`WebSocketStream::id`].
+    ///
+    /// [`WebSocketStream::id`]: super::WebSocketStream::id
+    #[must_use]
+    pub fn connection_id(&self) -> ConnectionId {
+        self.connection_id
+    }
+
+    /// Returns the end that initiated the close.
+    #[must_use]
+    pub fn initiator(&self) -> CloseInitiator {
+        self.initiator
+    }
+
+    /// Returns the close code of the close frame sent by the initiator, or
+    /// [`None`] if it did not contain one or no close frame was exchanged.
+    #[must_use]
+    pub fn code(&self) -> Option<CloseCode> {
+        self.code
+    }
+
+    /// Returns the close reason of the close frame sent by the initiator, which
+    /// is empty if there was none.
+    #[must_use]
+    pub fn reason(&self) -> &'a str {
+        self.reason
+    }
+
+    /// Returns the error that failed the connection, or [`None`] if it was
+    /// closed regularly.
+    #[must_use]
+    pub fn error(&self) -> Option<&'a crate::Error> {
+        self.error
+    }
+}
+
+/// Parses the close code and reason of a close frame payload.
+///
+/// Frames sent via `send_frame` are not validated, so the payload is parsed
+/// defensively: invalid close codes are ignored and invalid reasons are
+/// returned as empty.
+pub(super) fn parse_close_payload(payload: &[u8]) -> (Option<CloseCode>, &str) {
+    let code = payload
+        .get(..2)
+        .and_then(|code| CloseCode::try_from(u16::from_be_bytes([code[0], code[1]])).ok());
+    let reason = payload
+        .get(2..)
+        .and_then(|reason| std::str::from_utf8(reason).ok())
+        .unwrap_or_default();
+
+    (code, reason)
+}
+
 /// A frame of a WebSocket [`Message`].
+///
+/// Frames are usually handled by the [`WebSocketStream`], they are only
+/// exposed for use with [`encode_frame`] and [`decode_frame`].
+///
+/// [`WebSocketStream`]: super::WebSocketStream
+/// [`encode_frame`]: super::encode_frame
+/// [`decode_frame`]: super::decode_frame
 #[derive(Clone, Debug)]
-pub(super) struct Frame {
+pub struct Frame {
     /// The [`OpCode`] of the frame.
-    pub opcode: OpCode,
+    pub(super) opcode: OpCode,
     /// Whether this is the last frame of a message.
-    pub is_final: bool,
+    pub(super) is_final: bool,
+    /// The RSV bits of the frame, used by extensions.
+    pub(super) rsv: u8,
     /// The payload bytes of the frame.
-    pub payload: Payload,
+    pub(super) payload: Payload,
 }
 
 impl Frame {
     /// Default close frame.
     #[allow(clippy::declare_interior_mutable_const)]
-    pub const DEFAULT_CLOSE: Self = Self {
+    pub(super) const DEFAULT_CLOSE: Self = Self {
         opcode: OpCode::Close,
         is_final: true,
+        rsv: 0,
         payload: Payload::from_static(&CloseCode::NORMAL_CLOSURE.0.get().to_be_bytes()),
     };
 
+    /// Creates a new frame. `rsv` holds the RSV bits in their position in the
+    /// first byte of the frame header, i.e. a combination of [`RSV1`],
+    /// [`RSV2`] and [`RSV3`].
+    ///
+    /// The frame is not validated, sending a fragmented control frame or a
+    /// control frame with a payload larger than 125 bytes is a protocol
+    /// violation.
+    ///
+    /// # Panics
+    ///
+    /// If `rsv` has bits set other than the RSV bits.
+    ///
+    /// [`RSV1`]: super::RSV1
+    /// [`RSV2`]: super::RSV2
+    /// [`RSV3`]: super::RSV3
+    #[must_use]
+    pub fn new<P: Into<Payload>>(opcode: OpCode, is_final: bool, rsv: u8, payload: P) -> Self {
+        assert_eq!(rsv & !0x70, 0, "rsv must only contain the RSV bits");
+
+        Self {
+            opcode,
+            is_final,
+            rsv,
+            payload: payload.into(),
+        }
+    }
+
+    /// Returns the opcode of the frame.
+    #[must_use]
+    pub fn opcode(&self) -> OpCode {
+        self.opcode
+    }
+
+    /// Returns whether this is the last frame of a message.
+    #[must_use]
+    pub fn is_final(&self) -> bool {
+        self.is_final
+    }
+
+    /// Returns the RSV bits of the frame.
+    #[must_use]
+    pub fn rsv(&self) -> u8 {
+        self.rsv
+    }
+
+    /// Returns a reference to the payload of the frame.
+    #[must_use]
+    pub fn payload(&self) -> &Payload {
+        &self.payload
+    }
+
+    /// Returns the payload of the frame.
+    #[must_use]
+    pub fn into_payload(self) -> Payload {
+        self.payload
+    }
+
+    /// Checks that the frame could be received by a conforming peer on its
+    /// own: control frames must not be fragmented, their payload must be at
+    /// most 125 bytes long and close frames must carry a valid close code and
+    /// reason. Unfinished text frames and frames with RSV bits set are not
+    /// checked for valid UTF-8.
+    ///
+    /// Whether the frame fits into the sequence of sent frames, e.g. that
+    /// continuation frames only follow unfinished data frames, is not checked.
+    ///
+    /// # Errors
+    ///
+    /// This method returns the [`ProtocolError`] that a peer would fail the
+    /// connection with when receiving the frame.
+    pub fn validate(&self) -> Result<(), ProtocolError> {
+        if self.opcode.is_control() {
+            if !self.is_final {
+                return Err(ProtocolError::FragmentedControlFrame);
+            }
+
+            if self.payload.len() > MAX_CONTROL_PAYLOAD_LEN
+                || (self.opcode == OpCode::Close && self.payload.len() == 1)
+            {
+                return Err(ProtocolError::InvalidPayloadLength);
+            }
+        }
+
+        if self.opcode == OpCode::Close && !self.payload.is_empty() {
+            let code = CloseCode::try_from(u16::from_be_bytes([self.payload[0], self.payload[1]]))?;
+            if !code.is_sendable() {
+                return Err(ProtocolError::InvalidCloseCode);
+            }
+
+            utf8::parse_str(&self.payload[2..])?;
+        }
+
+        if self.opcode == OpCode::Text && self.is_final && self.rsv == 0 {
+            utf8::parse_str(&self.payload)?;
+        }
+
+        Ok(())
+    }
+
     /// Encode the frame head into `out`, returning how many bytes were written.
-    pub fn encode(&self, out: &mut [u8; 10]) -> u8 {
-        out[0] = u8::from(self.is_final) << 7 | u8::from(self.opcode);
+    pub(super) fn encode(&self, out: &mut [u8; 10]) -> u8 {
+        out[0] = u8::from(self.is_final) << 7 | self.rsv | u8::from(self.opcode);
         if u16::try_from(self.payload.len()).is_err() {
             out[1] = 127;
             let len = u64::try_from(self.payload.len()).unwrap();
@@ -713,6 +1708,7 @@ impl From<Message> for Frame {
         Self {
             opcode: value.opcode,
             is_final: true,
+            rsv: 0,
             payload: value.payload,
         }
     }
@@ -0,0 +1,212 @@
+//! Tunneling client connections through HTTP proxies via `CONNECT` requests.
+
+use std::{env, io};
+
+use base64::{engine::general_purpose, Engine};
+use http::Uri;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::Error;
+
+/// Maximum size of the proxy's response to a `CONNECT` request.
+const MAX_RESPONSE_SIZE: usize = 8192;
+
+/// Proxy configuration of a client.
+#[derive(Debug, Clone)]
+pub(crate) enum Proxy {
+    /// Tunnel all connections through the HTTP proxy at this URI.
+    Uri(Uri),
+    /// Look up the proxy from environment variables for every connection.
+    Env,
+}
+
+impl Proxy {
+    /// Returns the URI of the proxy to use for connecting to `uri`, if any.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if a proxy URI from the environment
+    /// cannot be parsed or the proxy does not use the `http` scheme.
+    pub(crate) fn for_uri(&self, uri: &Uri) -> Result<Option<Uri>, Error> {
+        let proxy = match self {
+            Self::Uri(proxy) => Some(proxy.clone()),
+            Self::Env => from_env(uri)?,
+        };
+
+        match proxy {
+            Some(proxy) if !matches!(proxy.scheme_str(), None | Some("http")) => {
+                Err(Error::UnsupportedScheme)
+            }
+            proxy => Ok(proxy),
+        }
+    }
+}
+
+/// Reads the first non-empty environment variable of a list.
+fn var(names: &[&str]) -> Option<String> {
+    names
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+}
+
+/// Looks up the proxy to use for connecting to `uri` from the `HTTP_PROXY`,
+/// `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY` environment variables, preferring
+/// their lowercase variants.
+fn from_env(uri: &Uri) -> Result<Option<Uri>, Error> {
+    let Some(host) = uri.host() else {
+        return Ok(None);
+    };
+
+    if let Some(no_proxy) = var(&["no_proxy", "NO_PROXY"]) {
+        if is_excluded(&no_proxy, host) {
+            return Ok(None);
+        }
+    }
+
+    let proxy = if uri.scheme_str() == Some("wss") {
+        var(&["https_proxy", "HTTPS_PROXY", "all_proxy", "ALL_PROXY"])
+    } else {
+        var(&["http_proxy", "HTTP_PROXY", "all_proxy", "ALL_PROXY"])
+    };
+
+    proxy
+        .map(|proxy| Uri::try_from(proxy).map_err(|_| Error::UnsupportedScheme))
+        .transpose()
+}
+
+/// Checks whether a host is excluded from proxying by a comma-separated
+/// `NO_PROXY` list. Entries match the host itself and all of its subdomains.
+fn is_excluded(no_proxy: &str, host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    no_proxy
+        .split(',')
+        .map(|entry| entry.trim().trim_start_matches('.'))
+        .filter(|entry| !entry.is_empty())
+        .any(|entry| {
+            if entry == "*" || host.eq_ignore_ascii_case(entry) {
+                return true;
+            }
+
+            host.len() > entry.len() && {
+                let (subdomain, domain) = host.split_at(host.len() - entry.len());
+
+                subdomain.ends_with('.') && domain.eq_ignore_ascii_case(entry)
+            }
+        })
+}
+
+/// Decodes the percent-encoded octets of the userinfo of a URI, leaving
+/// invalid escapes as they are.
+fn percent_decode(input: &str) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len());
+    let mut bytes = input.as_bytes();
+
+    while let [byte, rest @ ..] = bytes {
+        let hex = rest
+            .get(..2)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match hex {
+            Some(decoded) if *byte == b'%' => {
+                output.push(decoded);
+                bytes = &rest[2..];
+            }
+            _ => {
+                output.push(*byte);
+                bytes = rest;
+            }
+        }
+    }
+
+    output
+}
+
+/// Sends a `CONNECT` request for `target`, a host and port, to an HTTP proxy
+/// and waits for it to establish the tunnel.
+///
+/// # Errors
+///
+/// This function returns an error if writing to or reading from the stream
+/// fails or the proxy refuses to establish the tunnel.
+pub(crate) async fn tunnel<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    proxy: &Uri,
+    target: &str,
+) -> Result<(), Error> {
+    let mut request = format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n");
+
+    if let Some((credentials, _)) = proxy
+        .authority()
+        .and_then(|authority| authority.as_str().rsplit_once('@'))
+    {
+        request.push_str("Proxy-Authorization: Basic ");
+        request.push_str(&general_purpose::STANDARD.encode(percent_decode(credentials)));
+        request.push_str("\r\n");
+    }
+
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut response = Vec::with_capacity(MAX_RESPONSE_SIZE);
+
+    loop {
+        if response.len() >= MAX_RESPONSE_SIZE {
+            return Err(Error::Upgrade(crate::upgrade::Error::Parsing(
+                httparse::Error::TooManyHeaders,
+            )));
+        }
+
+        if stream.read_buf(&mut response).await? == 0 {
+            return Err(Error::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+
+        let mut headers = [httparse::EMPTY_HEADER; 25];
+        let mut parsed = httparse::Response::new(&mut headers);
+        let status = parsed
+            .parse(&response)
+            .map_err(|e| Error::Upgrade(crate::upgrade::Error::Parsing(e)))?;
+
+        match status {
+            httparse::Status::Complete(len) if len == response.len() => {
+                return match parsed.code {
+                    Some(200..=299) => Ok(()),
+                    code => Err(Error::ProxyConnectFailed(code.unwrap_or_default())),
+                };
+            }
+            // The client speaks first through the tunnel, so anything following the
+            // response would have to be discarded
+            httparse::Status::Complete(_) => {
+                return Err(Error::Io(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "proxy sent data after its response to the CONNECT request",
+                )));
+            }
+            httparse::Status::Partial => {}
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_is_excluded() {
+    let no_proxy = "localhost, .internal.example,10.0.0.1";
+
+    assert!(is_excluded(no_proxy, "localhost"));
+    assert!(is_excluded(no_proxy, "internal.example"));
+    assert!(is_excluded(no_proxy, "ws.INTERNAL.example"));
+    assert!(is_excluded(no_proxy, "10.0.0.1"));
+    assert!(!is_excluded(no_proxy, "notinternal.example"));
+    assert!(!is_excluded(no_proxy, "example.com"));
+    assert!(is_excluded("*", "example.com"));
+}
+
+#[cfg(test)]
+#[test]
+fn test_percent_decode() {
+    assert_eq!(percent_decode("user%40corp:p%3Ass"), b"user@corp:p:ss");
+    assert_eq!(percent_decode("plain:secret"), b"plain:secret");
+    assert_eq!(percent_decode("50%:%zz%4"), b"50%:%zz%4");
+}
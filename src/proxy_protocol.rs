@@ -0,0 +1,183 @@
+//! Parsing of the [PROXY protocol](https://www.haproxy.org/download/3.0/doc/proxy-protocol.txt)
+//! header that TCP proxies send ahead of the data of a connection to convey the
+//! addresses of the original connection.
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::{upgrade, Error};
+
+/// Signature that version 2 headers start with.
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// Prefix that version 1 headers start with.
+const V1_PREFIX: &[u8] = b"PROXY ";
+
+/// Maximum length of a version 1 header, including the trailing CRLF.
+const V1_MAX_LEN: usize = 107;
+
+/// Addresses of the original connection as conveyed by a PROXY protocol
+/// header, see [`Builder::proxy_protocol`].
+///
+/// It is added to the [extensions] of the handshake request.
+///
+/// [`Builder::proxy_protocol`]: crate::ServerBuilder::proxy_protocol
+/// [extensions]: http::Request::extensions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProxyHeader {
+    /// Address of the client that connected to the proxy.
+    source: SocketAddr,
+    /// Address that the client connected to.
+    destination: SocketAddr,
+}
+
+impl ProxyHeader {
+    /// Returns the address of the client that connected to the proxy.
+    #[must_use]
+    pub fn source(&self) -> SocketAddr {
+        self.source
+    }
+
+    /// Returns the address that the client connected to, usually the one of
+    /// the proxy itself.
+    #[must_use]
+    pub fn destination(&self) -> SocketAddr {
+        self.destination
+    }
+}
+
+/// Reads a version 1 or 2 PROXY protocol header from `stream`, without
+/// reading any of the data following it.
+///
+/// Returns [`None`] if the header does not convey the addresses of the
+/// original connection, e.g. for health checks of the proxy itself or
+/// connections over Unix sockets.
+///
+/// # Errors
+///
+/// This method fails with [`upgrade::Error::InvalidProxyHeader`] if the
+/// stream does not start with a valid header, or if reading fails.
+pub(crate) async fn read<S: AsyncRead + Unpin>(
+    stream: &mut S,
+) -> Result<Option<ProxyHeader>, Error> {
+    // Both versions are at least as long as the version 2 signature
+    let mut buf = vec![0; V2_SIGNATURE.len()];
+    stream.read_exact(&mut buf).await?;
+
+    if buf == V2_SIGNATURE {
+        let mut header = [0; 4];
+        stream.read_exact(&mut header).await?;
+        let mut addresses = vec![0; usize::from(u16::from_be_bytes([header[2], header[3]]))];
+        stream.read_exact(&mut addresses).await?;
+
+        parse_v2(header[0], header[1], &addresses)
+    } else if buf.starts_with(V1_PREFIX) {
+        // The length of the line is unknown, so it is read bytewise to leave
+        // the data following it in the stream
+        while !buf.ends_with(b"\r\n") {
+            if buf.len() == V1_MAX_LEN {
+                return Err(upgrade::Error::InvalidProxyHeader.into());
+            }
+
+            buf.push(stream.read_u8().await?);
+        }
+
+        parse_v1(&buf[V1_PREFIX.len()..buf.len() - 2])
+    } else {
+        Err(upgrade::Error::InvalidProxyHeader.into())
+    }
+}
+
+/// Parses the addresses of a version 1 header line, without its prefix and
+/// trailing CRLF.
+fn parse_v1(line: &[u8]) -> Result<Option<ProxyHeader>, Error> {
+    let invalid = || Error::from(upgrade::Error::InvalidProxyHeader);
+    let line = std::str::from_utf8(line).map_err(|_| invalid())?;
+    let mut parts = line.split(' ');
+
+    let is_v6 = match parts.next() {
+        Some("TCP4") => false,
+        Some("TCP6") => true,
+        // The rest of the line has to be ignored
+        Some("UNKNOWN") => return Ok(None),
+        _ => return Err(invalid()),
+    };
+
+    let mut next = || parts.next().ok_or_else(invalid);
+    let (source_ip, destination_ip) = (next()?, next()?);
+    let (source_port, destination_port) = (next()?, next()?);
+
+    let parse_ip = |ip: &str| -> Result<IpAddr, Error> {
+        if is_v6 {
+            ip.parse::<Ipv6Addr>().map(IpAddr::V6)
+        } else {
+            ip.parse::<Ipv4Addr>().map(IpAddr::V4)
+        }
+        .map_err(|_| invalid())
+    };
+    let parse_port = |port: &str| port.parse::<u16>().map_err(|_| invalid());
+
+    if parts.next().is_some() {
+        return Err(invalid());
+    }
+
+    Ok(Some(ProxyHeader {
+        source: SocketAddr::new(parse_ip(source_ip)?, parse_port(source_port)?),
+        destination: SocketAddr::new(parse_ip(destination_ip)?, parse_port(destination_port)?),
+    }))
+}
+
+/// Parses the addresses of a version 2 header with the given version and
+/// command byte and address family byte.
+fn parse_v2(
+    version_command: u8,
+    family: u8,
+    addresses: &[u8],
+) -> Result<Option<ProxyHeader>, Error> {
+    let invalid = || Error::from(upgrade::Error::InvalidProxyHeader);
+
+    match version_command {
+        // LOCAL command, the connection was established by the proxy itself
+        0x20 => return Ok(None),
+        // PROXY command
+        0x21 => {}
+        _ => return Err(invalid()),
+    }
+
+    let (source, destination) = match family >> 4 {
+        // AF_INET
+        0x1 => {
+            let addresses = addresses.get(..12).ok_or_else(invalid)?;
+            let ip = |offset: usize| {
+                let mut octets = [0; 4];
+                octets.copy_from_slice(&addresses[offset..offset + 4]);
+
+                IpAddr::from(octets)
+            };
+
+            ((ip(0), 8), (ip(4), 10))
+        }
+        // AF_INET6
+        0x2 => {
+            let addresses = addresses.get(..36).ok_or_else(invalid)?;
+            let ip = |offset: usize| {
+                let mut octets = [0; 16];
+                octets.copy_from_slice(&addresses[offset..offset + 16]);
+
+                IpAddr::from(octets)
+            };
+
+            ((ip(0), 32), (ip(16), 34))
+        }
+        // AF_UNSPEC and AF_UNIX, the addresses have to be ignored
+        0x0 | 0x3 => return Ok(None),
+        _ => return Err(invalid()),
+    };
+
+    let port = |offset: usize| u16::from_be_bytes([addresses[offset], addresses[offset + 1]]);
+
+    Ok(Some(ProxyHeader {
+        source: SocketAddr::new(source.0, port(source.1)),
+        destination: SocketAddr::new(destination.0, port(destination.1)),
+    }))
+}
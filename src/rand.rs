@@ -1,4 +1,10 @@
 //! Random numbers generation utilities required in WebSocket clients.
+//!
+//! If multiple generators are enabled via crate features, `rand` takes
+//! precedence over `getrandom`, which takes precedence over `fastrand`.
+//! Only the former two provide the unpredictable masking keys required by
+//! RFC 6455.
+use std::{fmt, sync::Arc};
 
 #[cfg(not(any(feature = "fastrand", feature = "getrandom", feature = "rand")))]
 compile_error!("Using the `client` feature requires enabling a random number generator implementation via one of the following features: `fastrand`, `getrandom` or `rand`.");
@@ -62,3 +68,32 @@ mod imp {
 }
 
 pub use imp::{get_key, get_mask};
+
+/// Source of masking keys for frames sent by a client.
+#[derive(Clone, Default)]
+pub(crate) enum MaskGenerator {
+    /// Use the random number generator enabled via crate features.
+    #[default]
+    Default,
+    /// Use a user-provided generator.
+    Custom(Arc<dyn Fn() -> [u8; 4] + Send + Sync>),
+}
+
+impl MaskGenerator {
+    /// Generate a 4-byte WebSocket mask.
+    pub fn generate(&self) -> [u8; 4] {
+        match self {
+            Self::Default => get_mask(),
+            Self::Custom(generator) => generator(),
+        }
+    }
+}
+
+impl fmt::Debug for MaskGenerator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Default => f.write_str("MaskGenerator::Default"),
+            Self::Custom(_) => f.write_str("MaskGenerator::Custom"),
+        }
+    }
+}
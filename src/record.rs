@@ -0,0 +1,328 @@
+//! Transport wrapper that records the bytes read from and written to a
+//! stream, for reproducing protocol issues observed in production.
+//!
+//! Wrap a stream in a [`Recorder`] before the handshake, e.g. via
+//! [`ServerBuilder::accept`], and save its [`Transcript`] when something goes
+//! wrong. The transcript can later be fed back into the protocol
+//! implementation via [`Transcript::replay`]:
+//!
+//! ```
+//! # #[cfg(feature = "server")]
+//! # async fn example(transcript: tokio_websockets::record::Transcript) {
+//! use futures_util::StreamExt;
+//! use tokio_websockets::ServerBuilder;
+//!
+//! let mut stream = ServerBuilder::new().serve(transcript.replay());
+//!
+//! while let Some(message) = stream.next().await {
+//!     println!("{message:?}");
+//! }
+//! # }
+//! ```
+//!
+//! [`ServerBuilder::accept`]: crate::ServerBuilder::accept
+use std::{
+    io::{self, IoSlice, Read, Write},
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Direction of the bytes of an [`Event`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// The bytes were read from the stream.
+    Read,
+    /// The bytes were written to the stream.
+    Written,
+}
+
+/// Bytes read from or written to a stream in a single operation.
+#[derive(Debug, Clone)]
+pub struct Event {
+    /// Time since the recording started.
+    elapsed: Duration,
+    /// Direction of the bytes.
+    direction: Direction,
+    /// The bytes read or written.
+    data: Bytes,
+}
+
+impl Event {
+    /// Returns the time since the recording started.
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns whether the bytes were read or written.
+    #[must_use]
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Returns the bytes that were read or written.
+    #[must_use]
+    pub fn data(&self) -> &Bytes {
+        &self.data
+    }
+}
+
+/// Recording of all bytes read from and written to a stream, in order.
+#[derive(Debug, Clone, Default)]
+pub struct Transcript {
+    /// The recorded events.
+    events: Vec<Event>,
+}
+
+impl Transcript {
+    /// Returns the recorded events.
+    #[must_use]
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// Returns a stream that yields the recorded reads, with their original
+    /// chunking, and discards all writes.
+    #[must_use]
+    pub fn replay(&self) -> Replay {
+        Replay {
+            chunks: self
+                .events
+                .iter()
+                .filter(|event| event.direction == Direction::Read)
+                .map(|event| event.data.clone())
+                .rev()
+                .collect(),
+        }
+    }
+
+    /// Serializes the transcript into `writer`.
+    ///
+    /// Each event is encoded as its direction (0 for reads, 1 for writes), the
+    /// elapsed time in microseconds as a big-endian `u64`, the length of the
+    /// data as a big-endian `u32` and the data itself.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if writing fails or an event is larger than 4 GiB.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for event in &self.events {
+            let direction = match event.direction {
+                Direction::Read => 0,
+                Direction::Written => 1,
+            };
+            let elapsed = event.elapsed.as_micros() as u64;
+            let len = u32::try_from(event.data.len())
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "event too large"))?;
+
+            writer.write_all(&[direction])?;
+            writer.write_all(&elapsed.to_be_bytes())?;
+            writer.write_all(&len.to_be_bytes())?;
+            writer.write_all(&event.data)?;
+        }
+
+        Ok(())
+    }
+
+    /// Deserializes a transcript written by [`Transcript::write_to`] from
+    /// `reader`.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if reading fails or the data is not a valid
+    /// transcript.
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut events = Vec::new();
+        let mut direction = [0];
+
+        while reader.read(&mut direction)? != 0 {
+            let direction = match direction[0] {
+                0 => Direction::Read,
+                1 => Direction::Written,
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "invalid event direction",
+                    ))
+                }
+            };
+
+            let mut elapsed = [0; 8];
+            reader.read_exact(&mut elapsed)?;
+            let mut len = [0; 4];
+            reader.read_exact(&mut len)?;
+            let mut data = vec![0; u32::from_be_bytes(len) as usize];
+            reader.read_exact(&mut data)?;
+
+            events.push(Event {
+                elapsed: Duration::from_micros(u64::from_be_bytes(elapsed)),
+                direction,
+                data: Bytes::from(data),
+            });
+        }
+
+        Ok(Self { events })
+    }
+}
+
+/// Stream wrapper that records all bytes read and written into a
+/// [`Transcript`].
+///
+/// The transcript grows with every operation, so recording should be limited
+/// to connections that are being debugged.
+#[derive(Debug)]
+pub struct Recorder<T> {
+    /// The wrapped stream.
+    inner: T,
+    /// The recording so far.
+    transcript: Transcript,
+    /// Time the recording started.
+    start: Instant,
+}
+
+impl<T> Recorder<T> {
+    /// Starts recording the I/O of a stream.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            transcript: Transcript::default(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Returns the recording so far.
+    pub fn transcript(&self) -> &Transcript {
+        &self.transcript
+    }
+
+    /// Returns a reference to the wrapped stream.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns the wrapped stream and the recording.
+    pub fn into_parts(self) -> (T, Transcript) {
+        (self.inner, self.transcript)
+    }
+
+    /// Records an event.
+    fn record(&mut self, direction: Direction, data: Bytes) {
+        if !data.is_empty() {
+            self.transcript.events.push(Event {
+                elapsed: self.start.elapsed(),
+                direction,
+                data,
+            });
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Recorder<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+
+        let data = Bytes::copy_from_slice(&buf.filled()[filled..]);
+        self.record(Direction::Read, data);
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Recorder<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let written = ready!(Pin::new(&mut self.inner).poll_write(cx, buf))?;
+        self.record(Direction::Written, Bytes::copy_from_slice(&buf[..written]));
+
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let written = ready!(Pin::new(&mut self.inner).poll_write_vectored(cx, bufs))?;
+
+        let mut data = Vec::with_capacity(written);
+        for buf in bufs {
+            let remaining = written - data.len();
+            data.extend_from_slice(&buf[..buf.len().min(remaining)]);
+        }
+        self.record(Direction::Written, Bytes::from(data));
+
+        Poll::Ready(Ok(written))
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Stream that yields the reads of a [`Transcript`] and discards all writes,
+/// created by [`Transcript::replay`].
+///
+/// Reaching the end of the transcript is reported as the end of the stream.
+#[derive(Debug)]
+pub struct Replay {
+    /// The remaining reads, in reverse order.
+    chunks: Vec<Bytes>,
+}
+
+impl AsyncRead for Replay {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if let Some(chunk) = self.chunks.last_mut() {
+            let len = chunk.len().min(buf.remaining());
+            buf.put_slice(&chunk.split_to(len));
+
+            if chunk.is_empty() {
+                self.chunks.pop();
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for Replay {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
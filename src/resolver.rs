@@ -1,6 +1,8 @@
 //! Abstractions over DNS resolvers.
 
-use std::{future::Future, net::SocketAddr};
+use std::{collections::HashMap, fmt, future::Future, net::SocketAddr, time::Duration};
+
+use tokio::{sync::Mutex, time::Instant};
 
 use crate::Error;
 
@@ -12,10 +14,31 @@ pub trait Resolver: Send {
         host: &str,
         port: u16,
     ) -> impl Future<Output = Result<SocketAddr, Error>> + Send;
+
+    /// Resolve a hostname and port to all of its IP addresses, asynchronously.
+    ///
+    /// The client races connection attempts to these addresses as described in
+    /// [RFC 8305], so returning both IPv4 and IPv6 addresses avoids long
+    /// delays on networks where one of the two is broken.
+    ///
+    /// The default implementation only returns the address returned by
+    /// [`Resolver::resolve`].
+    ///
+    /// [RFC 8305]: https://datatracker.ietf.org/doc/html/rfc8305
+    fn resolve_all(
+        &self,
+        host: &str,
+        port: u16,
+    ) -> impl Future<Output = Result<Vec<SocketAddr>, Error>> + Send {
+        let addr = self.resolve(host, port);
+
+        async move { Ok(vec![addr.await?]) }
+    }
 }
 
 /// A [`Resolver`] that uses the blocking `getaddrinfo` syscall in the tokio
 /// threadpool.
+#[derive(Debug)]
 pub struct Gai;
 
 impl Resolver for Gai {
@@ -28,4 +51,133 @@ impl Resolver for Gai {
             .next()
             .ok_or(Error::CannotResolveHost)
     }
+
+    async fn resolve_all(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, Error> {
+        let host = host.to_owned();
+
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|_| Error::CannotResolveHost)?
+            .collect();
+
+        if addrs.is_empty() {
+            return Err(Error::CannotResolveHost);
+        }
+
+        Ok(addrs)
+    }
+}
+
+/// A [`Resolver`] that caches the addresses resolved by another resolver, so
+/// that reconnects and many connections to the same host do not each wait
+/// for a lookup.
+///
+/// Resolved addresses are reused until they are older than the configured
+/// time to live. Since `getaddrinfo` does not expose the TTLs of DNS records,
+/// the same time to live applies to all hostnames. Failed lookups are not
+/// cached. Concurrent lookups of a hostname that is not cached wait for the
+/// first one to finish instead of all querying the resolver.
+///
+/// Share the resolver between reconnects by configuring the client builder
+/// with a reference to it, e.g. `ClientBuilder::new().resolver(&cached)`.
+pub struct Cached<R> {
+    /// The resolver to resolve uncached hostnames with.
+    resolver: R,
+    /// Duration for which resolved addresses are reused.
+    ttl: Duration,
+    /// Addresses resolved so far, by hostname and port.
+    cache: Mutex<HashMap<(String, u16), CacheEntry>>,
+}
+
+/// Addresses cached by a [`Cached`] resolver.
+struct CacheEntry {
+    /// The resolved addresses.
+    addrs: Vec<SocketAddr>,
+    /// When the addresses were resolved.
+    resolved_at: Instant,
+}
+
+impl<R> Cached<R> {
+    /// Creates a resolver that caches the addresses resolved by `resolver`
+    /// for `ttl`.
+    pub fn new(resolver: R, ttl: Duration) -> Self {
+        Self {
+            resolver,
+            ttl,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Removes the cached addresses of `host` and `port`, e.g. because
+    /// connecting to them failed, so that the next connection resolves them
+    /// again.
+    pub async fn invalidate(&self, host: &str, port: u16) {
+        self.cache.lock().await.remove(&(host.to_owned(), port));
+    }
+
+    /// Removes all cached addresses.
+    pub async fn clear(&self) {
+        self.cache.lock().await.clear();
+    }
+}
+
+impl<R: fmt::Debug> fmt::Debug for Cached<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cached")
+            .field("resolver", &self.resolver)
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<R: Resolver + Sync> Resolver for Cached<R> {
+    async fn resolve(&self, host: &str, port: u16) -> Result<SocketAddr, Error> {
+        self.resolve_all(host, port)
+            .await?
+            .first()
+            .copied()
+            .ok_or(Error::CannotResolveHost)
+    }
+
+    async fn resolve_all(&self, host: &str, port: u16) -> Result<Vec<SocketAddr>, Error> {
+        // The lock is held while resolving so that concurrent connections wait for
+        // the first lookup instead of all performing their own
+        let mut cache = self.cache.lock().await;
+        let key = (host.to_owned(), port);
+
+        if let Some(entry) = cache.get(&key) {
+            if entry.resolved_at.elapsed() < self.ttl {
+                return Ok(entry.addrs.clone());
+            }
+        }
+
+        let addrs = self.resolver.resolve_all(host, port).await?;
+        cache.insert(
+            key,
+            CacheEntry {
+                addrs: addrs.clone(),
+                resolved_at: Instant::now(),
+            },
+        );
+
+        Ok(addrs)
+    }
+}
+
+impl<R: Resolver + Sync> Resolver for &R {
+    fn resolve(
+        &self,
+        host: &str,
+        port: u16,
+    ) -> impl Future<Output = Result<SocketAddr, Error>> + Send {
+        (**self).resolve(host, port)
+    }
+
+    fn resolve_all(
+        &self,
+        host: &str,
+        port: u16,
+    ) -> impl Future<Output = Result<Vec<SocketAddr>, Error>> + Send {
+        (**self).resolve_all(host, port)
+    }
 }
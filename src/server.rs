@@ -1,24 +1,101 @@
 //! Implementation of a WebSocket server.
 //!
-//! This can be used in two ways:
+//! This can be used in three ways:
 //!   - By letting the library perform a HTTP/1.1 Upgrade handshake on an
 //!     established stream, via [`Builder::accept`]
 //!   - By performing the handshake yourself and then using [`Builder::serve`]
 //!     to let it take over a WebSocket stream
-use std::{future::poll_fn, io, pin::Pin};
+//!   - By letting another HTTP server receive the upgrade request and using
+//!     [`Builder::upgrade`] to reply to it
+//!
+//! A [`Router`] dispatches upgrade requests to different handlers by their
+//! path. With `tower` enabled, an [`UpgradeLayer`] serves WebSockets as part
+//! of a tower-based HTTP server. With `rustls` enabled, an [`Acceptor`] can
+//! additionally perform the TLS handshake before the HTTP/1.1 Upgrade
+//! handshake.
+use std::{
+    collections::HashMap,
+    fmt,
+    future::{pending, poll_fn, Future},
+    io,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::{Arc, Mutex, PoisonError},
+    task::{Context, Poll},
+    time::Duration,
+};
 
-use futures_core::Stream;
-use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use futures_core::{ready, Stream};
+use http::{
+    header::{
+        CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_EXTENSIONS, SEC_WEBSOCKET_PROTOCOL, UPGRADE,
+    },
+    HeaderMap, HeaderValue, StatusCode,
+};
+#[cfg(any(
+    feature = "rustls-webpki-roots",
+    feature = "rustls-native-roots",
+    feature = "rustls-platform-verifier"
+))]
+use rustls_pki_types::{CertificateDer, PrivateKeyDer};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+    sync::{watch, OwnedSemaphorePermit, Semaphore},
+};
+#[cfg(any(
+    feature = "rustls-webpki-roots",
+    feature = "rustls-native-roots",
+    feature = "rustls-platform-verifier"
+))]
+use tokio_rustls::rustls::{
+    crypto::CryptoProvider, server::WebPkiClientVerifier, RootCertStore, ServerConfig,
+};
 use tokio_util::codec::FramedRead;
 
+pub use crate::proxy_protocol::ProxyHeader;
 use crate::{
-    proto::{Config, Limits, Role},
-    upgrade::client_request,
+    proto::{Config, ExtensionCodec, Limits, Role},
+    proxy_protocol,
+    socket::SocketOptions,
+    upgrade::{
+        self, client_request,
+        extensions::{self, ServerExtension},
+    },
     Error, WebSocketStream,
 };
 
-/// HTTP/1.1 400 Bad Request response payload.
-const BAD_REQUEST: &[u8] = b"HTTP/1.1 400 Bad Request\r\n\r\n";
+/// Function that selects one of the subprotocols offered by a client.
+type SelectSubprotocol = Arc<dyn for<'a> Fn(&[&'a str]) -> Option<&'a str> + Send + Sync>;
+
+/// Extensions accepted in a handshake along with their codecs.
+type NegotiatedExtensions = (Vec<extensions::Extension>, Vec<Box<dyn ExtensionCodec>>);
+
+/// Handler of a [`Router`] route, called with an accepted stream and the
+/// request it was upgraded with.
+type Handler<S> = Box<
+    dyn Fn(WebSocketStream<S>, upgrade::Request) -> Pin<Box<dyn Future<Output = ()> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// Function that determines the IP address a connection counts towards.
+type ConnectionIp = Arc<dyn Fn(SocketAddr, &upgrade::Request) -> IpAddr + Send + Sync>;
+
+/// PROXY protocol header of a connection that is being accepted.
+enum ProxyHeaderState {
+    /// The header still has to be read, if enabled via
+    /// [`Builder::proxy_protocol`].
+    Unread,
+    /// The header was already read ahead of the TLS handshake.
+    #[cfg(any(
+        feature = "rustls-webpki-roots",
+        feature = "rustls-native-roots",
+        feature = "rustls-platform-verifier",
+        feature = "rustls-bring-your-own-connector"
+    ))]
+    Read(Option<ProxyHeader>),
+}
 
 /// Builder for WebSocket server connections.
 pub struct Builder {
@@ -26,6 +103,58 @@ pub struct Builder {
     config: Config,
     /// Limits to impose on the WebSocket stream.
     limits: Limits,
+    /// Coordinator for a graceful shutdown of accepted streams.
+    shutdown: Option<Shutdown>,
+    /// Semaphore limiting the number of concurrently open streams.
+    connection_limit: Option<Arc<Semaphore>>,
+    /// Hook called whenever accepting has to wait for a free connection slot.
+    on_connection_queued: Option<Arc<dyn Fn() + Send + Sync>>,
+    /// Limit of concurrently open streams per IP address.
+    ip_limit: Option<IpLimit>,
+    /// Function that determines the IP address a connection counts towards.
+    connection_ip: Option<ConnectionIp>,
+    /// Whether accepted streams start with a PROXY protocol header.
+    proxy_protocol: bool,
+    /// Maximum duration of the HTTP upgrade handshake.
+    handshake_timeout: Option<Duration>,
+    /// Maximum number of headers in the HTTP upgrade request.
+    max_handshake_headers: usize,
+    /// Maximum size of the HTTP upgrade request in bytes.
+    max_handshake_size: usize,
+    /// Function that selects one of the subprotocols offered by a client.
+    select_subprotocol: Option<SelectSubprotocol>,
+    /// Extensions to negotiate with clients, in order of preference.
+    extensions: Vec<Arc<dyn ServerExtension>>,
+    /// Options for the TCP sockets of streams accepted via
+    /// [`Builder::accept_tcp`].
+    socket_options: SocketOptions,
+}
+
+impl fmt::Debug for Builder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Builder")
+            .field("config", &self.config)
+            .field("limits", &self.limits)
+            .field("shutdown", &self.shutdown)
+            .field(
+                "available_connections",
+                &self
+                    .connection_limit
+                    .as_ref()
+                    .map(|limit| limit.available_permits()),
+            )
+            .field(
+                "max_connections_per_ip",
+                &self.ip_limit.as_ref().map(|limit| limit.max),
+            )
+            .field("proxy_protocol", &self.proxy_protocol)
+            .field("handshake_timeout", &self.handshake_timeout)
+            .field("max_handshake_headers", &self.max_handshake_headers)
+            .field("max_handshake_size", &self.max_handshake_size)
+            .field("extensions", &self.extensions.len())
+            .field("socket_options", &self.socket_options)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Default for Builder {
@@ -42,6 +171,18 @@ impl Builder {
         Self {
             config: Config::default(),
             limits: Limits::default(),
+            shutdown: None,
+            connection_limit: None,
+            on_connection_queued: None,
+            ip_limit: None,
+            connection_ip: None,
+            proxy_protocol: false,
+            handshake_timeout: None,
+            max_handshake_headers: 64,
+            max_handshake_size: 16 * 1024,
+            select_subprotocol: None,
+            extensions: Vec::new(),
+            socket_options: SocketOptions::new(),
         }
     }
 
@@ -61,9 +202,350 @@ impl Builder {
         self
     }
 
+    /// Sets the options applied to TCP streams accepted via
+    /// [`Builder::accept_tcp`]. By default, the operating system's defaults
+    /// are used.
+    #[must_use]
+    pub fn socket_options(mut self, socket_options: SocketOptions) -> Self {
+        self.socket_options = socket_options;
+
+        self
+    }
+
+    /// Sets the [`Shutdown`] coordinator that streams created by this builder
+    /// are tracked by.
+    #[must_use]
+    pub fn shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = Some(shutdown);
+
+        self
+    }
+
+    /// Sets the maximum number of concurrently open streams created via
+    /// [`Builder::accept`]. Once the limit is reached, accepting waits until
+    /// a stream is dropped before reading the handshake request.
+    ///
+    /// To stop accepting TCP connections while the limit is reached, await
+    /// [`Builder::ready`] before accepting the next one.
+    ///
+    /// By default, there is no limit.
+    #[must_use]
+    pub fn max_connections(mut self, max_connections: usize) -> Self {
+        self.connection_limit = Some(Arc::new(Semaphore::new(max_connections)));
+
+        self
+    }
+
+    /// Sets a hook that is called whenever [`Builder::accept`] or
+    /// [`Builder::ready`] has to wait because the connection limit is
+    /// reached, e.g. to record metrics.
+    #[must_use]
+    pub fn on_connection_queued<F>(mut self, hook: F) -> Self
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.on_connection_queued = Some(Arc::new(hook));
+
+        self
+    }
+
+    /// Sets the subprotocols supported by the server, in order of preference.
+    /// The first of them that is offered by a client via the
+    /// `Sec-WebSocket-Protocol` header is selected and echoed in the response.
+    ///
+    /// If a client offers none of them, the handshake succeeds without a
+    /// subprotocol. The selected subprotocol is available via
+    /// [`WebSocketStream::subprotocol`].
+    #[must_use]
+    pub fn subprotocols<I, S>(self, subprotocols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let subprotocols: Vec<String> = subprotocols.into_iter().map(Into::into).collect();
+
+        self.select_subprotocol(move |offered| {
+            subprotocols.iter().find_map(|subprotocol| {
+                offered
+                    .iter()
+                    .copied()
+                    .find(|offered| *offered == subprotocol)
+            })
+        })
+    }
+
+    /// Sets a function that selects one of the subprotocols offered by a
+    /// client via the `Sec-WebSocket-Protocol` header, in the order they were
+    /// offered. The selected subprotocol is echoed in the response and
+    /// available via [`WebSocketStream::subprotocol`].
+    ///
+    /// If the function returns [`None`], the handshake succeeds without a
+    /// subprotocol. It is not called if the client offers no subprotocols.
+    #[must_use]
+    pub fn select_subprotocol<F>(mut self, select_subprotocol: F) -> Self
+    where
+        F: for<'a> Fn(&[&'a str]) -> Option<&'a str> + Send + Sync + 'static,
+    {
+        self.select_subprotocol = Some(Arc::new(select_subprotocol));
+
+        self
+    }
+
+    /// Registers an extension to negotiate with clients. Extensions are
+    /// negotiated in the order they were registered, an extension is declined
+    /// if it reserves RSV bits already reserved by a previously accepted one.
+    ///
+    /// Accepted extensions are sent in the `Sec-WebSocket-Extensions` header
+    /// of the response and transform the messages of the stream.
+    #[must_use]
+    pub fn extension<E: ServerExtension + 'static>(mut self, extension: E) -> Self {
+        self.extensions.push(Arc::new(extension));
+
+        self
+    }
+
+    /// Sets the maximum duration of the HTTP upgrade handshake, from reading
+    /// the first byte of the request to writing the response. Handshakes
+    /// exceeding it fail with [`upgrade::Error::TimedOut`] and the stream is
+    /// dropped, closing it.
+    ///
+    /// By default, there is no timeout.
+    #[must_use]
+    pub fn handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = Some(timeout);
+
+        self
+    }
+
+    /// Sets the maximum number of headers in the HTTP upgrade request.
+    /// Requests with more headers are rejected.
+    ///
+    /// The default is 64.
+    #[must_use]
+    pub fn max_handshake_headers(mut self, max_headers: usize) -> Self {
+        self.max_handshake_headers = max_headers;
+
+        self
+    }
+
+    /// Sets the maximum size of the HTTP upgrade request in bytes, including
+    /// the request line and headers. Requests exceeding it are rejected with
+    /// [`upgrade::Error::RequestTooLarge`] as soon as the limit is reached.
+    ///
+    /// The default is 16 KiB.
+    #[must_use]
+    pub fn max_handshake_size(mut self, max_size: usize) -> Self {
+        self.max_handshake_size = max_size;
+
+        self
+    }
+
+    /// Sets the maximum number of concurrently open streams per IP address
+    /// created via [`Builder::accept_from`]. Handshakes exceeding the limit
+    /// are rejected with a HTTP 429 Too Many Requests response and fail with
+    /// [`upgrade::Error::TooManyConnections`].
+    ///
+    /// By default, there is no limit.
+    #[must_use]
+    pub fn max_connections_per_ip(mut self, max_connections: usize) -> Self {
+        self.ip_limit = Some(IpLimit {
+            max: max_connections,
+            connections: Arc::default(),
+        });
+
+        self
+    }
+
+    /// Sets the function that determines the IP address a connection counts
+    /// towards for the limit set via [`Builder::max_connections_per_ip`]. It
+    /// is called with the peer address and the handshake request, e.g. to use
+    /// the client address forwarded by a trusted reverse proxy.
+    ///
+    /// By default, the IP address of the peer is used.
+    #[must_use]
+    pub fn connection_ip<F>(mut self, connection_ip: F) -> Self
+    where
+        F: Fn(SocketAddr, &upgrade::Request) -> IpAddr + Send + Sync + 'static,
+    {
+        self.connection_ip = Some(Arc::new(connection_ip));
+
+        self
+    }
+
+    /// Sets whether accepted streams start with a version 1 or 2 PROXY
+    /// protocol header, as sent by TCP proxies and load balancers to convey the
+    /// address of the original client.
+    ///
+    /// The header is read as part of the handshake, ahead of the TLS handshake
+    /// of an [`Acceptor`]. Streams without a valid header fail with
+    /// [`upgrade::Error::InvalidProxyHeader`]. The conveyed addresses are added
+    /// to the handshake request as a [`ProxyHeader`] extension, and the client
+    /// address counts towards the limit set via
+    /// [`Builder::max_connections_per_ip`] instead of the peer address.
+    ///
+    /// Only enable this if all connections pass through a trusted proxy, since
+    /// clients can otherwise claim arbitrary addresses. By default, this is
+    /// disabled.
+    #[must_use]
+    pub fn proxy_protocol(mut self, enabled: bool) -> Self {
+        self.proxy_protocol = enabled;
+
+        self
+    }
+
+    /// Waits until the number of open streams is below the limit set via
+    /// [`Builder::max_connections`], returning immediately if there is none.
+    ///
+    /// This does not reserve a connection slot.
+    pub async fn ready(&self) {
+        if let Some(connection_limit) = &self.connection_limit {
+            if connection_limit.available_permits() == 0 {
+                self.connection_queued();
+            }
+
+            drop(connection_limit.acquire().await);
+        }
+    }
+
+    /// Calls the hook set via [`Builder::on_connection_queued`], if any.
+    fn connection_queued(&self) {
+        if let Some(hook) = &self.on_connection_queued {
+            hook();
+        }
+    }
+
+    /// Waits for a free connection slot if a connection limit is set.
+    async fn acquire_connection_permit(&self) -> Option<OwnedSemaphorePermit> {
+        let connection_limit = self.connection_limit.clone()?;
+
+        if let Ok(permit) = connection_limit.clone().try_acquire_owned() {
+            return Some(permit);
+        }
+
+        self.connection_queued();
+
+        // The semaphore is never closed, so this never fails
+        connection_limit.acquire_owned().await.ok()
+    }
+
+    /// Counts a connection from `peer_addr` towards the per-IP limit, if any.
+    ///
+    /// # Errors
+    ///
+    /// This method fails with [`upgrade::Error::TooManyConnections`] if the
+    /// limit is reached.
+    fn acquire_ip_guard(
+        &self,
+        peer_addr: Option<SocketAddr>,
+        request: &upgrade::Request,
+    ) -> Result<Option<IpGuard>, upgrade::Error> {
+        let (Some(ip_limit), Some(peer_addr)) = (&self.ip_limit, peer_addr) else {
+            return Ok(None);
+        };
+
+        let ip = match &self.connection_ip {
+            Some(connection_ip) => connection_ip(peer_addr, request),
+            None => peer_addr.ip(),
+        };
+
+        ip_limit
+            .acquire(ip)
+            .map(Some)
+            .ok_or(upgrade::Error::TooManyConnections)
+    }
+
+    /// Selects one of the subprotocols offered in the request `headers`, if
+    /// any.
+    fn negotiate_subprotocol(&self, headers: &HeaderMap) -> Option<String> {
+        let select_subprotocol = self.select_subprotocol.as_ref()?;
+
+        let offered: Vec<&str> = headers
+            .get_all(SEC_WEBSOCKET_PROTOCOL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|subprotocol| !subprotocol.is_empty())
+            .collect();
+
+        if offered.is_empty() {
+            return None;
+        }
+
+        select_subprotocol(&offered).map(ToOwned::to_owned)
+    }
+
+    /// Negotiates the registered extensions with the ones offered in the
+    /// request `headers`, returning the accepted extensions and their codecs.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if the `Sec-WebSocket-Extensions` header of the
+    /// request cannot be parsed.
+    fn negotiate_extensions(
+        &self,
+        headers: &HeaderMap,
+    ) -> Result<NegotiatedExtensions, upgrade::Error> {
+        let mut accepted = Vec::new();
+        let mut codecs: Vec<Box<dyn ExtensionCodec>> = Vec::new();
+
+        if self.extensions.is_empty() {
+            return Ok((accepted, codecs));
+        }
+
+        let mut offers = Vec::new();
+
+        for value in headers.get_all(SEC_WEBSOCKET_EXTENSIONS) {
+            let value = value
+                .to_str()
+                .map_err(|_| upgrade::Error::Parsing(httparse::Error::HeaderValue))?;
+
+            offers.extend(extensions::parse(value).map_err(upgrade::Error::InvalidExtensions)?);
+        }
+
+        if offers.is_empty() {
+            return Ok((accepted, codecs));
+        }
+
+        let mut rsv_bits = 0;
+
+        for extension in &self.extensions {
+            if let Some((extension, codec)) = extension.negotiate(&offers) {
+                if codec.rsv_bits() & rsv_bits == 0 {
+                    rsv_bits |= codec.rsv_bits();
+                    accepted.push(extension);
+                    codecs.push(codec);
+                }
+            }
+        }
+
+        Ok((accepted, codecs))
+    }
+
+    /// Creates a [`WebSocketStream`] from a stream that has completed the
+    /// HTTP upgrade handshake, tracked by the [`Shutdown`] coordinator if
+    /// configured.
+    fn stream<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        mut stream: WebSocketStream<S>,
+        connection_guard: ConnectionGuard,
+    ) -> WebSocketStream<S> {
+        if let Some(shutdown) = &self.shutdown {
+            stream.set_shutdown(shutdown.listener());
+        }
+
+        stream.set_connection_guard(connection_guard);
+
+        stream
+    }
+
     /// Perform a HTTP upgrade handshake on an already established stream and
     /// uses it to send and receive WebSocket messages.
     ///
+    /// The stream does not count towards the limit set via
+    /// [`Builder::max_connections_per_ip`], use [`Builder::accept_from`] for
+    /// that.
+    ///
     /// # Errors
     ///
     /// This method returns an [`Error`] if the handshake fails.
@@ -71,21 +553,183 @@ impl Builder {
         &self,
         stream: S,
     ) -> Result<WebSocketStream<S>, Error> {
-        let mut framed = FramedRead::new(stream, client_request::Codec {});
+        self.accept_inner(stream, None, ProxyHeaderState::Unread, |_| Some(()))
+            .await
+            .map(|(stream, ())| stream)
+    }
+
+    /// Perform a HTTP upgrade handshake on an already established stream from
+    /// the peer at `peer_addr` and uses it to send and receive WebSocket
+    /// messages.
+    ///
+    /// Unlike [`Builder::accept`], the stream counts towards the limit set
+    /// via [`Builder::max_connections_per_ip`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if the handshake fails or the peer
+    /// has too many open connections.
+    pub async fn accept_from<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        stream: S,
+        peer_addr: SocketAddr,
+    ) -> Result<WebSocketStream<S>, Error> {
+        self.accept_inner(stream, Some(peer_addr), ProxyHeaderState::Unread, |_| {
+            Some(())
+        })
+        .await
+        .map(|(stream, ())| stream)
+    }
+
+    /// Applies the options set via [`Builder::socket_options`] to a TCP stream
+    /// returned by [`TcpListener::accept`], performs the HTTP upgrade handshake
+    /// on it and uses it to send and receive WebSocket messages.
+    ///
+    /// Like [`Builder::accept_from`], the stream counts towards the limit set
+    /// via [`Builder::max_connections_per_ip`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if setting the socket options or the
+    /// handshake fails or the peer has too many open connections.
+    ///
+    /// [`TcpListener::accept`]: tokio::net::TcpListener::accept
+    pub async fn accept_tcp(&self, stream: TcpStream) -> Result<WebSocketStream<TcpStream>, Error> {
+        self.socket_options.apply(&stream)?;
+        let peer_addr = stream.peer_addr()?;
+
+        self.accept_inner(stream, Some(peer_addr), ProxyHeaderState::Unread, |_| {
+            Some(())
+        })
+        .await
+        .map(|(stream, ())| stream)
+    }
+
+    /// Performs the HTTP upgrade handshake for [`Builder::accept`],
+    /// [`Builder::accept_from`] and [`Builder::accept_tcp`].
+    ///
+    /// The request is only accepted if `route` returns `Some` for it, which
+    /// is returned along with the stream.
+    async fn accept_inner<S, R>(
+        &self,
+        stream: S,
+        peer_addr: Option<SocketAddr>,
+        proxy_header: ProxyHeaderState,
+        route: impl FnOnce(&upgrade::Request) -> Option<R>,
+    ) -> Result<(WebSocketStream<S>, R), Error>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let permit = self.acquire_connection_permit().await;
+
+        let handshake = self.handshake(stream, peer_addr, proxy_header, route);
+        let (stream, ip, route) = match self.handshake_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, handshake)
+                .await
+                .map_err(|_| upgrade::Error::TimedOut)??,
+            None => handshake.await?,
+        };
+
+        Ok((self.stream(stream, ConnectionGuard { permit, ip }), route))
+    }
+
+    /// Reads the HTTP upgrade request from `stream` and replies to it,
+    /// rejecting it with [`upgrade::Error::NotFound`] if `route` returns
+    /// `None` for it.
+    async fn handshake<S, R>(
+        &self,
+        mut stream: S,
+        peer_addr: Option<SocketAddr>,
+        proxy_header: ProxyHeaderState,
+        route: impl FnOnce(&upgrade::Request) -> Option<R>,
+    ) -> Result<(WebSocketStream<S>, Option<IpGuard>, R), Error>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let proxy_header = match proxy_header {
+            ProxyHeaderState::Unread if self.proxy_protocol => {
+                proxy_protocol::read(&mut stream).await?
+            }
+            ProxyHeaderState::Unread => None,
+            #[cfg(any(
+                feature = "rustls-webpki-roots",
+                feature = "rustls-native-roots",
+                feature = "rustls-platform-verifier",
+                feature = "rustls-bring-your-own-connector"
+            ))]
+            ProxyHeaderState::Read(proxy_header) => proxy_header,
+        };
+        // The original client counts towards the per-IP limit
+        let peer_addr = peer_addr
+            .map(|peer_addr| proxy_header.map_or(peer_addr, |proxy_header| proxy_header.source()));
+
+        let codec = client_request::Codec::new(self.max_handshake_headers, self.max_handshake_size);
+        let mut framed = FramedRead::with_capacity(stream, codec, self.config.read_buffer_capacity);
         let reply = poll_fn(|cx| Pin::new(&mut framed).poll_next(cx)).await;
 
         match reply {
-            Some(Ok(response)) => {
+            Some(Ok((mut request, ws_accept))) => {
+                if let Some(proxy_header) = proxy_header {
+                    request.extensions_mut().insert(proxy_header);
+                }
+
+                let Some(route) = route(&request) else {
+                    let e = Error::Upgrade(upgrade::Error::NotFound);
+                    reject(framed.get_mut(), &e).await?;
+
+                    return Err(e);
+                };
+
+                let ip = match self.acquire_ip_guard(peer_addr, &request) {
+                    Ok(ip) => ip,
+                    Err(e) => {
+                        let e = Error::Upgrade(e);
+                        reject(framed.get_mut(), &e).await?;
+
+                        return Err(e);
+                    }
+                };
+
+                let (accepted, codecs) = match self.negotiate_extensions(request.headers()) {
+                    Ok(extensions) => extensions,
+                    Err(e) => {
+                        let e = Error::Upgrade(e);
+                        reject(framed.get_mut(), &e).await?;
+
+                        return Err(e);
+                    }
+                };
+                let accepted = extensions::serialize(&accepted);
+                let subprotocol = self.negotiate_subprotocol(request.headers());
+
+                let mut headers = Vec::new();
+
+                if !accepted.is_empty() {
+                    headers.push(("Sec-WebSocket-Extensions", accepted.as_str()));
+                }
+
+                if let Some(subprotocol) = &subprotocol {
+                    headers.push(("Sec-WebSocket-Protocol", subprotocol.as_str()));
+                }
+
+                let response = client_request::response(&ws_accept, &headers);
                 framed.get_mut().write_all(response.as_bytes()).await?;
-                Ok(WebSocketStream::from_framed(
-                    framed,
-                    Role::Server,
-                    self.config,
-                    self.limits,
-                ))
+
+                let mut stream =
+                    WebSocketStream::from_framed(framed, Role::Server, self.config, self.limits);
+
+                if !codecs.is_empty() {
+                    stream.set_extensions(codecs);
+                }
+
+                if let Some(subprotocol) = subprotocol {
+                    stream.set_subprotocol(subprotocol);
+                }
+
+                Ok((stream, ip, route))
             }
             Some(Err(e)) => {
-                framed.get_mut().write_all(BAD_REQUEST).await?;
+                reject(framed.get_mut(), &e).await?;
 
                 Err(e)
             }
@@ -93,11 +737,918 @@ impl Builder {
         }
     }
 
+    /// Validates an upgrade request received by another HTTP server, such as
+    /// `hyper`, and returns the `101 Switching Protocols` response to reply
+    /// with, along with a future that resolves to the [`WebSocketStream`] once
+    /// the connection was upgraded.
+    ///
+    /// `on_upgrade` has to resolve to the connection once the response was
+    /// sent. With `hyper`, this is `hyper::upgrade::on` with the upgraded
+    /// connection wrapped in `hyper_util::rt::TokioIo`. This allows serving
+    /// WebSockets alongside any HTTP router.
+    ///
+    /// The stream does not count towards the limits set via
+    /// [`Builder::max_connections`] and [`Builder::max_connections_per_ip`],
+    /// and the timeout set via [`Builder::handshake_timeout`] does not apply.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if the request is not a valid upgrade
+    /// request. The returned future fails if `on_upgrade` fails.
+    pub fn upgrade<B, F, S>(
+        &self,
+        request: &http::Request<B>,
+        on_upgrade: F,
+    ) -> Result<(http::Response<()>, PendingUpgrade<F>), Error>
+    where
+        F: Future<Output = io::Result<S>>,
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let ws_accept = client_request::validate(request)?;
+        let (accepted, codecs) = self.negotiate_extensions(request.headers())?;
+        let accepted = extensions::serialize(&accepted);
+        let subprotocol = self.negotiate_subprotocol(request.headers());
+
+        let mut response = http::Response::new(());
+        *response.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
+        let headers = response.headers_mut();
+        headers.insert(UPGRADE, HeaderValue::from_static("websocket"));
+        headers.insert(CONNECTION, HeaderValue::from_static("Upgrade"));
+        headers.insert(
+            SEC_WEBSOCKET_ACCEPT,
+            HeaderValue::try_from(ws_accept)
+                .map_err(|_| upgrade::Error::Parsing(httparse::Error::HeaderValue))?,
+        );
+
+        if !accepted.is_empty() {
+            let value = HeaderValue::try_from(accepted)
+                .map_err(|_| upgrade::Error::Parsing(httparse::Error::HeaderValue))?;
+            headers.insert(SEC_WEBSOCKET_EXTENSIONS, value);
+        }
+
+        if let Some(subprotocol) = &subprotocol {
+            let value = HeaderValue::try_from(subprotocol.as_str())
+                .map_err(|_| upgrade::Error::Parsing(httparse::Error::HeaderValue))?;
+            headers.insert(SEC_WEBSOCKET_PROTOCOL, value);
+        }
+
+        let stream = PendingUpgrade {
+            on_upgrade: Box::pin(on_upgrade),
+            config: self.config,
+            limits: self.limits,
+            codecs,
+            subprotocol,
+            shutdown: self.shutdown.as_ref().map(Shutdown::listener),
+        };
+
+        Ok((response, stream))
+    }
+
     /// Takes over an already established stream and uses it to send and receive
     /// WebSocket messages.
     ///
-    /// This does not perform a HTTP upgrade handshake.
+    /// This does not perform a HTTP upgrade handshake. The stream does not
+    /// count towards the limit set via [`Builder::max_connections`].
     pub fn serve<S: AsyncRead + AsyncWrite + Unpin>(&self, stream: S) -> WebSocketStream<S> {
-        WebSocketStream::from_raw_stream(stream, Role::Server, self.config, self.limits)
+        self.stream(
+            WebSocketStream::from_raw_stream(stream, Role::Server, self.config, self.limits),
+            ConnectionGuard {
+                permit: None,
+                ip: None,
+            },
+        )
+    }
+}
+
+/// Future returned by [`Builder::upgrade`] that resolves to the
+/// [`WebSocketStream`] once the connection was upgraded.
+pub struct PendingUpgrade<F> {
+    /// Future resolving to the upgraded connection.
+    on_upgrade: Pin<Box<F>>,
+    /// Configuration for the WebSocket stream.
+    config: Config,
+    /// Limits to impose on the WebSocket stream.
+    limits: Limits,
+    /// Codecs of the negotiated extensions, taken once resolved.
+    codecs: Vec<Box<dyn ExtensionCodec>>,
+    /// Negotiated subprotocol, taken once resolved.
+    subprotocol: Option<String>,
+    /// Listener for a graceful shutdown, taken once resolved.
+    shutdown: Option<ShutdownListener>,
+}
+
+impl<F> fmt::Debug for PendingUpgrade<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PendingUpgrade")
+            .field("subprotocol", &self.subprotocol)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F, S> Future for PendingUpgrade<F>
+where
+    F: Future<Output = io::Result<S>>,
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    type Output = Result<WebSocketStream<S>, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let stream = ready!(self.on_upgrade.as_mut().poll(cx))?;
+        let mut stream =
+            WebSocketStream::from_raw_stream(stream, Role::Server, self.config, self.limits);
+
+        let codecs = std::mem::take(&mut self.codecs);
+        if !codecs.is_empty() {
+            stream.set_extensions(codecs);
+        }
+
+        if let Some(subprotocol) = self.subprotocol.take() {
+            stream.set_subprotocol(subprotocol);
+        }
+
+        if let Some(shutdown) = self.shutdown.take() {
+            stream.set_shutdown(shutdown);
+        }
+
+        Poll::Ready(Ok(stream))
+    }
+}
+
+/// Returns the status code and additional header to reply to a handshake
+/// request that failed with `error` with.
+fn error_status(error: &Error) -> (StatusCode, Option<(&'static str, &'static str)>) {
+    match error {
+        Error::Upgrade(upgrade::Error::UnsupportedMethod) => {
+            (StatusCode::METHOD_NOT_ALLOWED, Some(("Allow", "GET")))
+        }
+        Error::Upgrade(
+            upgrade::Error::UnsupportedWebSocketVersion
+            | upgrade::Error::MissingHeader("Sec-WebSocket-Version"),
+        ) => (
+            StatusCode::UPGRADE_REQUIRED,
+            Some(("Sec-WebSocket-Version", "13")),
+        ),
+        Error::Upgrade(upgrade::Error::NotFound) => (StatusCode::NOT_FOUND, None),
+        Error::Upgrade(upgrade::Error::TooManyConnections) => (StatusCode::TOO_MANY_REQUESTS, None),
+        Error::Upgrade(
+            upgrade::Error::RequestTooLarge
+            | upgrade::Error::Parsing(httparse::Error::TooManyHeaders),
+        ) => (StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE, None),
+        _ => (StatusCode::BAD_REQUEST, None),
+    }
+}
+
+/// Returns the HTTP response to reply to a handshake request that failed
+/// with `error`, describing the error in a plain text body.
+fn error_response(error: &Error) -> String {
+    let (status, header) = error_status(error);
+    let header = header
+        .map(|(name, value)| format!("{name}: {value}\r\n"))
+        .unwrap_or_default();
+    let body = error.to_string();
+
+    format!(
+        "HTTP/1.1 {status}\r\n{header}Content-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// Replies to a failed handshake request with an error response and shuts
+/// down the write half of the stream.
+async fn reject<S: AsyncWrite + Unpin>(stream: &mut S, error: &Error) -> Result<(), Error> {
+    stream.write_all(error_response(error).as_bytes()).await?;
+    stream.shutdown().await?;
+
+    Ok(())
+}
+
+/// Limit of concurrently open streams per IP address.
+struct IpLimit {
+    /// Maximum number of open streams per IP address.
+    max: usize,
+    /// Number of open streams per IP address, without zero entries.
+    connections: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl IpLimit {
+    /// Counts a stream towards `ip` if its limit is not reached yet.
+    fn acquire(&self, ip: IpAddr) -> Option<IpGuard> {
+        let mut connections = self
+            .connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let count = connections.entry(ip).or_default();
+
+        if *count >= self.max {
+            if *count == 0 {
+                connections.remove(&ip);
+            }
+
+            return None;
+        }
+
+        *count += 1;
+
+        Some(IpGuard {
+            ip,
+            connections: self.connections.clone(),
+        })
+    }
+}
+
+/// Guard that counts a stream towards the limit of its IP address until
+/// dropped.
+#[derive(Debug)]
+pub(crate) struct IpGuard {
+    /// IP address the stream counts towards.
+    ip: IpAddr,
+    /// Number of open streams per IP address, shared with the [`IpLimit`].
+    connections: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl Drop for IpGuard {
+    fn drop(&mut self) {
+        let mut connections = self
+            .connections
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(count) = connections.get_mut(&self.ip) {
+            *count -= 1;
+
+            if *count == 0 {
+                connections.remove(&self.ip);
+            }
+        }
+    }
+}
+
+/// Guard that counts a stream towards the connection limits of the
+/// [`Builder`] it was created by until dropped. Its fields are never read.
+#[derive(Debug)]
+#[allow(dead_code)]
+pub(crate) struct ConnectionGuard {
+    /// Permit for the limit set via [`Builder::max_connections`].
+    permit: Option<OwnedSemaphorePermit>,
+    /// Guard for the limit set via [`Builder::max_connections_per_ip`].
+    ip: Option<IpGuard>,
+}
+
+/// Dispatcher of WebSocket upgrade requests to handlers registered for their
+/// path.
+///
+/// Requests for paths without a handler are answered with `404 Not Found`.
+/// The query of the request is ignored for routing, but available to the
+/// handler via [`Request::uri`].
+///
+/// ```
+/// # async fn example(listener: tokio::net::TcpListener) -> Result<(), tokio_websockets::Error> {
+/// use std::sync::Arc;
+///
+/// use futures_util::StreamExt;
+/// use tokio_websockets::{server::Router, ServerBuilder};
+///
+/// let router = Arc::new(
+///     Router::new(ServerBuilder::new())
+///         .route("/echo", |mut stream, _request| async move {
+///             while let Some(Ok(message)) = stream.next().await {
+///                 let _ = stream.send(message).await;
+///             }
+///         })
+///         .route("/hello", |mut stream, _request| async move {
+///             let _ = stream.send_text("hello").await;
+///         }),
+/// );
+///
+/// loop {
+///     let (stream, peer_addr) = listener.accept().await?;
+///     let router = router.clone();
+///     tokio::spawn(async move { router.accept_from(stream, peer_addr).await });
+/// }
+/// # }
+/// ```
+///
+/// [`Request::uri`]: http::Request::uri
+pub struct Router<S> {
+    /// Builder used for the HTTP upgrade handshake.
+    builder: Builder,
+    /// Handlers by the path they are registered for.
+    routes: HashMap<String, Handler<S>>,
+}
+
+impl<S> fmt::Debug for Router<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Router")
+            .field("builder", &self.builder)
+            .field("routes", &self.routes.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<S> Router<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    /// Creates a [`Router`] without any routes that performs the HTTP upgrade
+    /// handshake with the given [`Builder`].
+    #[must_use]
+    pub fn new(builder: Builder) -> Self {
+        Self {
+            builder,
+            routes: HashMap::new(),
+        }
+    }
+
+    /// Registers `handler` for requests to `path`, replacing any handler
+    /// previously registered for it. The path has to match exactly.
+    #[must_use]
+    pub fn route<F, Fut>(mut self, path: &str, handler: F) -> Self
+    where
+        F: Fn(WebSocketStream<S>, upgrade::Request) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.routes.insert(
+            path.to_owned(),
+            Box::new(move |stream, request| Box::pin(handler(stream, request))),
+        );
+
+        self
+    }
+
+    /// Performs a HTTP upgrade handshake on an already established stream and
+    /// runs the handler registered for the requested path to completion.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if the handshake fails or no handler
+    /// is registered for the requested path.
+    pub async fn accept(&self, stream: S) -> Result<(), Error> {
+        self.accept_inner(stream, None).await
+    }
+
+    /// Like [`Router::accept`], but the stream counts towards the limit set
+    /// via [`Builder::max_connections_per_ip`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if the handshake fails, no handler
+    /// is registered for the requested path or the peer has too many open
+    /// connections.
+    pub async fn accept_from(&self, stream: S, peer_addr: SocketAddr) -> Result<(), Error> {
+        self.accept_inner(stream, Some(peer_addr)).await
+    }
+
+    /// Performs the HTTP upgrade handshake and runs the handler for
+    /// [`Router::accept`] and [`Router::accept_from`].
+    async fn accept_inner(&self, stream: S, peer_addr: Option<SocketAddr>) -> Result<(), Error> {
+        let (stream, (handler, request)) = self
+            .builder
+            .accept_inner(stream, peer_addr, ProxyHeaderState::Unread, |request| {
+                let handler = self.routes.get(request.uri().path())?;
+
+                Some((handler, request.clone()))
+            })
+            .await?;
+
+        handler(stream, request).await;
+
+        Ok(())
+    }
+}
+
+/// Configuration shared by an [`UpgradeLayer`] and its services.
+#[cfg(feature = "tower")]
+struct Upgrader<H, U> {
+    /// Builder used to reply to upgrade requests.
+    builder: Builder,
+    /// Function returning the future that resolves to the upgraded connection
+    /// of a request.
+    on_upgrade: U,
+    /// Handler called with upgraded streams.
+    handler: H,
+}
+
+/// [`tower_layer::Layer`] that intercepts WebSocket upgrade requests to the
+/// wrapped HTTP service and runs a handler with the established
+/// [`WebSocketStream`] instead.
+///
+/// Upgrade requests are answered via [`Builder::upgrade`], invalid ones with
+/// an error status and an empty body. The handler is spawned on the current
+/// tokio runtime once the connection was upgraded, along with the request it
+/// was upgraded with. All other requests are passed to the wrapped service.
+///
+/// With `hyper`, `on_upgrade` is `hyper::upgrade::on` with the upgraded
+/// connection wrapped in `hyper_util::rt::TokioIo`.
+#[cfg(feature = "tower")]
+pub struct UpgradeLayer<H, U> {
+    /// Configuration shared with the services.
+    upgrader: Arc<Upgrader<H, U>>,
+}
+
+#[cfg(feature = "tower")]
+impl<H, U> UpgradeLayer<H, U> {
+    /// Creates an [`UpgradeLayer`] that replies to upgrade requests with the
+    /// given [`Builder`], obtains the upgraded connection via `on_upgrade`
+    /// and runs `handler` with the stream.
+    #[must_use]
+    pub fn new<B, IO, UF, HF>(builder: Builder, on_upgrade: U, handler: H) -> Self
+    where
+        U: Fn(&mut http::Request<B>) -> UF,
+        UF: Future<Output = io::Result<IO>>,
+        H: Fn(WebSocketStream<IO>, upgrade::Request) -> HF,
+        HF: Future<Output = ()>,
+    {
+        Self {
+            upgrader: Arc::new(Upgrader {
+                builder,
+                on_upgrade,
+                handler,
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "tower")]
+impl<H, U> Clone for UpgradeLayer<H, U> {
+    fn clone(&self) -> Self {
+        Self {
+            upgrader: Arc::clone(&self.upgrader),
+        }
+    }
+}
+
+#[cfg(feature = "tower")]
+impl<H, U> fmt::Debug for UpgradeLayer<H, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UpgradeLayer").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "tower")]
+impl<S, H, U> tower_layer::Layer<S> for UpgradeLayer<H, U> {
+    type Service = UpgradeService<S, H, U>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        UpgradeService {
+            inner,
+            upgrader: Arc::clone(&self.upgrader),
+        }
+    }
+}
+
+/// HTTP service created by an [`UpgradeLayer`].
+#[cfg(feature = "tower")]
+pub struct UpgradeService<S, H, U> {
+    /// The wrapped service that all other requests are passed to.
+    inner: S,
+    /// Configuration shared with the layer.
+    upgrader: Arc<Upgrader<H, U>>,
+}
+
+#[cfg(feature = "tower")]
+impl<S: Clone, H, U> Clone for UpgradeService<S, H, U> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            upgrader: Arc::clone(&self.upgrader),
+        }
+    }
+}
+
+#[cfg(feature = "tower")]
+impl<S: fmt::Debug, H, U> fmt::Debug for UpgradeService<S, H, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UpgradeService")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "tower")]
+impl<S, H, U, B, ResBody, IO, UF, HF> tower_service::Service<http::Request<B>>
+    for UpgradeService<S, H, U>
+where
+    S: tower_service::Service<http::Request<B>, Response = http::Response<ResBody>>,
+    S::Error: Send + 'static,
+    S::Future: Send + 'static,
+    ResBody: Default + Send + 'static,
+    U: Fn(&mut http::Request<B>) -> UF + Send + Sync + 'static,
+    UF: Future<Output = io::Result<IO>> + Send + 'static,
+    IO: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    H: Fn(WebSocketStream<IO>, upgrade::Request) -> HF + Send + Sync + 'static,
+    HF: Future<Output = ()> + Send + 'static,
+{
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, S::Error>> + Send>>;
+    type Response = http::Response<ResBody>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<B>) -> Self::Future {
+        let is_upgrade = request
+            .headers()
+            .get(UPGRADE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+
+        if !is_upgrade {
+            return Box::pin(self.inner.call(request));
+        }
+
+        let on_upgrade = (self.upgrader.on_upgrade)(&mut request);
+
+        let response = match self.upgrader.builder.upgrade(&request, on_upgrade) {
+            Ok((response, stream)) => {
+                let (parts, _) = request.into_parts();
+                let request = http::Request::from_parts(parts, ());
+                let upgrader = Arc::clone(&self.upgrader);
+
+                tokio::spawn(async move {
+                    // Upgrading fails if the client disconnected in the meantime
+                    if let Ok(stream) = stream.await {
+                        (upgrader.handler)(stream, request).await;
+                    }
+                });
+
+                response.map(|()| ResBody::default())
+            }
+            Err(e) => {
+                let (status, header) = error_status(&e);
+                let mut response = http::Response::new(ResBody::default());
+                *response.status_mut() = status;
+
+                if let Some((name, value)) = header {
+                    response
+                        .headers_mut()
+                        .insert(name, HeaderValue::from_static(value));
+                }
+
+                response
+            }
+        };
+
+        Box::pin(std::future::ready(Ok(response)))
+    }
+}
+
+/// Acceptor for WebSocket server connections over TLS, performing the TLS
+/// handshake and then the HTTP upgrade handshake on accepted streams.
+#[cfg(any(
+    feature = "rustls-webpki-roots",
+    feature = "rustls-native-roots",
+    feature = "rustls-platform-verifier",
+    feature = "rustls-bring-your-own-connector"
+))]
+pub struct Acceptor {
+    /// Builder used for the HTTP upgrade handshake.
+    builder: Builder,
+    /// The TLS acceptor for the TLS handshake.
+    tls: tokio_rustls::TlsAcceptor,
+}
+
+#[cfg(any(
+    feature = "rustls-webpki-roots",
+    feature = "rustls-native-roots",
+    feature = "rustls-platform-verifier",
+    feature = "rustls-bring-your-own-connector"
+))]
+impl fmt::Debug for Acceptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Acceptor")
+            .field("builder", &self.builder)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(any(
+    feature = "rustls-webpki-roots",
+    feature = "rustls-native-roots",
+    feature = "rustls-platform-verifier",
+    feature = "rustls-bring-your-own-connector"
+))]
+impl Acceptor {
+    /// Creates an [`Acceptor`] that terminates TLS with the given `rustls`
+    /// configuration and then performs the HTTP upgrade handshake with the
+    /// given [`Builder`].
+    ///
+    /// TLS 1.3 early data is not read, so `max_early_data_size` must not be
+    /// set in the configuration, otherwise upgrade requests sent as early
+    /// data are lost.
+    #[must_use]
+    pub fn new(builder: Builder, config: Arc<tokio_rustls::rustls::ServerConfig>) -> Self {
+        Self {
+            builder,
+            tls: tokio_rustls::TlsAcceptor::from(config),
+        }
+    }
+
+    /// Creates an [`AcceptorBuilder`] for an [`Acceptor`] that authenticates
+    /// with the given DER-encoded certificate chain and private key and then
+    /// performs the HTTP upgrade handshake with the given [`Builder`].
+    #[cfg(any(
+        feature = "rustls-webpki-roots",
+        feature = "rustls-native-roots",
+        feature = "rustls-platform-verifier"
+    ))]
+    #[must_use]
+    pub fn builder(
+        builder: Builder,
+        certificate_chain: Vec<CertificateDer<'static>>,
+        key: PrivateKeyDer<'static>,
+    ) -> AcceptorBuilder {
+        AcceptorBuilder {
+            builder,
+            crypto_provider: None,
+            certificate_chain,
+            key,
+            client_root_certificates: Vec::new(),
+            allow_unauthenticated_clients: false,
+            alpn_protocols: Vec::new(),
+        }
+    }
+
+    /// Performs a TLS handshake and then a HTTP upgrade handshake on an
+    /// already established stream and uses it to send and receive WebSocket
+    /// messages.
+    ///
+    /// The timeout set via [`Builder::handshake_timeout`] applies to each of
+    /// the handshakes. If enabled via [`Builder::proxy_protocol`], the PROXY
+    /// protocol header is read ahead of the TLS handshake.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] if either handshake fails.
+    pub async fn accept<S: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        mut stream: S,
+    ) -> Result<WebSocketStream<tokio_rustls::server::TlsStream<S>>, Error> {
+        let handshake = async {
+            let proxy_header = if self.builder.proxy_protocol {
+                proxy_protocol::read(&mut stream).await?
+            } else {
+                None
+            };
+
+            Ok::<_, Error>((self.tls.accept(stream).await?, proxy_header))
+        };
+        let (stream, proxy_header) = match self.builder.handshake_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, handshake)
+                .await
+                .map_err(|_| upgrade::Error::TimedOut)??,
+            None => handshake.await?,
+        };
+
+        self.builder
+            .accept_inner(stream, None, ProxyHeaderState::Read(proxy_header), |_| {
+                Some(())
+            })
+            .await
+            .map(|(stream, ())| stream)
+    }
+}
+
+/// Builder for an [`Acceptor`] using `rustls`, optionally verifying client
+/// certificates against custom root certificates for mutual TLS.
+#[cfg(any(
+    feature = "rustls-webpki-roots",
+    feature = "rustls-native-roots",
+    feature = "rustls-platform-verifier"
+))]
+pub struct AcceptorBuilder {
+    /// Builder used for the HTTP upgrade handshake.
+    builder: Builder,
+    /// Crypto provider to use instead of the default one.
+    crypto_provider: Option<Arc<CryptoProvider>>,
+    /// Certificate chain to authenticate with.
+    certificate_chain: Vec<CertificateDer<'static>>,
+    /// Private key of the certificate to authenticate with.
+    key: PrivateKeyDer<'static>,
+    /// Root certificates that client certificates are verified against.
+    client_root_certificates: Vec<CertificateDer<'static>>,
+    /// Whether clients without a certificate are accepted.
+    allow_unauthenticated_clients: bool,
+    /// Protocols to accept via ALPN.
+    alpn_protocols: Vec<Vec<u8>>,
+}
+
+#[cfg(any(
+    feature = "rustls-webpki-roots",
+    feature = "rustls-native-roots",
+    feature = "rustls-platform-verifier"
+))]
+impl fmt::Debug for AcceptorBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The private key is never printed
+        f.debug_struct("AcceptorBuilder")
+            .field("builder", &self.builder)
+            .field("crypto_provider", &self.crypto_provider.is_some())
+            .field("certificate_chain", &self.certificate_chain.len())
+            .field(
+                "client_root_certificates",
+                &self.client_root_certificates.len(),
+            )
+            .field(
+                "allow_unauthenticated_clients",
+                &self.allow_unauthenticated_clients,
+            )
+            .field("alpn_protocols", &self.alpn_protocols)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(any(
+    feature = "rustls-webpki-roots",
+    feature = "rustls-native-roots",
+    feature = "rustls-platform-verifier"
+))]
+impl AcceptorBuilder {
+    /// Sets the crypto provider to use.
+    ///
+    /// By default, the provider enabled via feature flags is used, or the
+    /// process-wide default if none is enabled.
+    #[must_use]
+    pub fn crypto_provider(mut self, provider: Arc<CryptoProvider>) -> Self {
+        self.crypto_provider = Some(provider);
+
+        self
+    }
+
+    /// Adds a DER-encoded root certificate that client certificates are
+    /// verified against. Adding any root certificate requires clients to
+    /// authenticate with a certificate issued by one of them, unless
+    /// [`AcceptorBuilder::allow_unauthenticated_clients`] is enabled.
+    ///
+    /// By default, no client certificates are requested.
+    #[must_use]
+    pub fn add_client_root_certificate(mut self, certificate: CertificateDer<'static>) -> Self {
+        self.client_root_certificates.push(certificate);
+
+        self
+    }
+
+    /// Sets whether clients that do not send a certificate are accepted if
+    /// root certificates for client certificates were added. Certificates
+    /// that clients do send are verified either way.
+    ///
+    /// By default, clients without a certificate are rejected.
+    #[must_use]
+    pub fn allow_unauthenticated_clients(mut self, allow: bool) -> Self {
+        self.allow_unauthenticated_clients = allow;
+
+        self
+    }
+
+    /// Sets the protocols to accept via ALPN, in order of preference.
+    ///
+    /// By default, no protocols are accepted.
+    #[must_use]
+    pub fn alpn_protocols(mut self, protocols: Vec<Vec<u8>>) -> Self {
+        self.alpn_protocols = protocols;
+
+        self
+    }
+
+    /// Builds the [`Acceptor`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] when creating the underlying TLS
+    /// acceptor fails, e.g. if a certificate or the private key is invalid.
+    pub fn build(self) -> Result<Acceptor, Error> {
+        let provider = match self.crypto_provider {
+            Some(provider) => provider,
+            None => crate::tls::default_crypto_provider()?,
+        };
+
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?;
+
+        let builder = if self.client_root_certificates.is_empty() {
+            builder.with_no_client_auth()
+        } else {
+            let mut roots = RootCertStore::empty();
+
+            for cert in self.client_root_certificates {
+                roots.add(cert)?;
+            }
+
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = if self.allow_unauthenticated_clients {
+                verifier.allow_unauthenticated()
+            } else {
+                verifier
+            };
+
+            builder.with_client_cert_verifier(verifier.build()?)
+        };
+
+        let mut config = builder.with_single_cert(self.certificate_chain, self.key)?;
+        config.alpn_protocols = self.alpn_protocols;
+
+        Ok(Acceptor::new(self.builder, Arc::new(config)))
+    }
+}
+
+/// Coordinator for a graceful shutdown of WebSocket server connections.
+///
+/// Streams created by a [`Builder`] with this coordinator set via
+/// [`Builder::shutdown`] are tracked until they are dropped. Once
+/// [`Shutdown::shutdown`] is called, they start the close handshake with
+/// [`CloseCode::GOING_AWAY`] the next time they are polled for messages.
+///
+/// [`CloseCode::GOING_AWAY`]: crate::CloseCode::GOING_AWAY
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    /// Sender for the shutdown signal, its receivers are the tracked streams.
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shutdown {
+    /// Creates a new [`Shutdown`] coordinator without any tracked streams.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            sender: Arc::new(watch::channel(false).0),
+        }
+    }
+
+    /// Returns the number of tracked streams that have not been dropped yet.
+    #[must_use]
+    pub fn connections(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    /// Triggers the shutdown and waits for all tracked streams to be dropped,
+    /// for at most `timeout`. Streams created after this call start the close
+    /// handshake immediately.
+    ///
+    /// Returns whether all streams were dropped before the timeout elapsed.
+    pub async fn shutdown(&self, timeout: Duration) -> bool {
+        self.sender.send_replace(true);
+
+        tokio::time::timeout(timeout, self.sender.closed())
+            .await
+            .is_ok()
+    }
+
+    /// Creates a listener for a newly tracked stream.
+    fn listener(&self) -> ShutdownListener {
+        let mut receiver = self.sender.subscribe();
+
+        ShutdownListener::Waiting(Box::pin(async move {
+            while !*receiver.borrow_and_update() {
+                if receiver.changed().await.is_err() {
+                    // The coordinator was dropped without shutting down
+                    pending::<()>().await;
+                }
+            }
+
+            receiver
+        }))
+    }
+}
+
+/// Listener for the shutdown signal of a [`Shutdown`] coordinator. It owns a
+/// receiver of the signal, which keeps the stream it belongs to tracked until
+/// dropped.
+pub(crate) enum ShutdownListener {
+    /// Waiting for the shutdown to be triggered.
+    Waiting(Pin<Box<dyn Future<Output = watch::Receiver<bool>> + Send>>),
+    /// The shutdown has been triggered.
+    Triggered(watch::Receiver<bool>),
+}
+
+impl ShutdownListener {
+    /// Polls whether the shutdown has been triggered. This resolves only once.
+    pub(crate) fn poll_triggered(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        match self {
+            Self::Waiting(triggered) => {
+                let receiver = ready!(triggered.as_mut().poll(cx));
+                *self = Self::Triggered(receiver);
+
+                Poll::Ready(())
+            }
+            Self::Triggered(_) => Poll::Pending,
+        }
+    }
+}
+
+impl fmt::Debug for ShutdownListener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Waiting(_) => f.write_str("ShutdownListener::Waiting"),
+            Self::Triggered(receiver) => f
+                .debug_tuple("ShutdownListener::Triggered")
+                .field(receiver)
+                .finish(),
+        }
     }
 }
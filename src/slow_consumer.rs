@@ -0,0 +1,317 @@
+//! Policies for peers that cannot keep up with the messages sent to them.
+//!
+//! The send queue of a [`driver::Sender`] and the buffers of
+//! [`topics::Subscriber`]s hold a limited number of messages. Once one of them
+//! is full, its [`Policy`] decides what happens to the next message and an
+//! [`Event`] is passed to the configured hook, e.g. to record metrics:
+//!
+//! ```
+//! # #[cfg(feature = "server")]
+//! # fn example() {
+//! use tokio_websockets::{
+//!     slow_consumer::{Event, Policy},
+//!     topics::Topics,
+//!     CloseCode,
+//! };
+//!
+//! let topics = Topics::new()
+//!     .slow_consumer_policy(Policy::Disconnect(CloseCode::SERVICE_OVERLOAD))
+//!     .on_slow_consumer(|event| {
+//!         if let Event::Disconnected(code) = event {
+//!             eprintln!("disconnected a slow subscriber with {code:?}");
+//!         }
+//!     });
+//! # }
+//! ```
+//!
+//! [`driver::Sender`]: crate::driver::Sender
+//! [`topics::Subscriber`]: crate::topics::Subscriber
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    task::{Context, Poll, Waker},
+};
+
+use tokio::sync::{Semaphore, TryAcquireError};
+
+use crate::{CloseCode, Error, Message};
+
+/// Hook called with the [`Event`]s of a queue.
+pub(crate) type Hook = Arc<dyn Fn(Event) + Send + Sync>;
+
+/// What to do with a message when the queue it is sent to is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// Wait until there is space in the queue, which applies backpressure to
+    /// the sender.
+    Block,
+    /// Discard the oldest queued message to make space for the new one.
+    DropOldest,
+    /// Discard the new message.
+    DropNewest,
+    /// Discard all queued messages and close the connection with the given
+    /// close code, usually [`CloseCode::POLICY_VIOLATION`] or
+    /// [`CloseCode::SERVICE_OVERLOAD`].
+    Disconnect(CloseCode),
+}
+
+/// What happened because a queue was full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Event {
+    /// Sending waits for space in the queue.
+    Blocked,
+    /// The oldest queued message was discarded.
+    DroppedOldest,
+    /// The new message was discarded.
+    DroppedNewest,
+    /// The connection is closed with the contained close code.
+    Disconnected(CloseCode),
+}
+
+/// Mutable state of a queue.
+struct State {
+    /// Queued messages, oldest first.
+    messages: VecDeque<Message>,
+    /// Whether the queue accepts no more messages.
+    closed: bool,
+    /// Number of [`QueueSender`]s.
+    senders: usize,
+    /// Waker of the [`QueueReceiver`] waiting for a message.
+    receiver: Option<Waker>,
+}
+
+/// State shared by the ends of a queue.
+struct Shared {
+    /// Mutable state of the queue.
+    state: Mutex<State>,
+    /// Free slots of the queue. Closed once the queue is closed.
+    space: Semaphore,
+    /// What to do with messages once the queue is full.
+    policy: Policy,
+    /// Hook called with the events of the queue.
+    hook: Option<Hook>,
+}
+
+impl Shared {
+    /// Locks the state, ignoring poisoning since it is never left in an
+    /// inconsistent state.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Passes `event` to the hook, if any.
+    fn emit(&self, event: Event) {
+        if let Some(hook) = &self.hook {
+            hook(event);
+        }
+    }
+}
+
+/// Creates a queue of `capacity` messages that applies `policy` once full.
+pub(crate) fn queue(
+    capacity: usize,
+    policy: Policy,
+    hook: Option<Hook>,
+) -> (QueueSender, QueueReceiver) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            messages: VecDeque::with_capacity(capacity),
+            closed: false,
+            senders: 1,
+            receiver: None,
+        }),
+        space: Semaphore::new(capacity),
+        policy,
+        hook,
+    });
+
+    (
+        QueueSender {
+            shared: Arc::clone(&shared),
+        },
+        QueueReceiver { shared },
+    )
+}
+
+/// Sending end of a queue.
+pub(crate) struct QueueSender {
+    /// State shared with the other ends.
+    shared: Arc<Shared>,
+}
+
+impl QueueSender {
+    /// Queues a message, applying the policy if the queue is full. Returns
+    /// whether the message was queued.
+    ///
+    /// Only waits with [`Policy::Block`].
+    pub(crate) async fn send(&self, message: Message) -> Result<bool, Error> {
+        if self.shared.policy != Policy::Block {
+            return self.send_or_overflow(message);
+        }
+
+        let permit = match self.shared.space.try_acquire() {
+            Ok(permit) => permit,
+            Err(TryAcquireError::Closed) => return Err(Error::AlreadyClosed),
+            Err(TryAcquireError::NoPermits) => {
+                self.shared.emit(Event::Blocked);
+
+                self.shared
+                    .space
+                    .acquire()
+                    .await
+                    .map_err(|_| Error::AlreadyClosed)?
+            }
+        };
+
+        // The slot is returned once the receiver takes the message
+        permit.forget();
+        let state = self.shared.lock();
+
+        if state.closed {
+            return Err(Error::AlreadyClosed);
+        }
+
+        Self::push(state, message);
+
+        Ok(true)
+    }
+
+    /// Queues a message if there is space, otherwise applies a policy other
+    /// than [`Policy::Block`].
+    fn send_or_overflow(&self, message: Message) -> Result<bool, Error> {
+        let mut state = self.shared.lock();
+
+        if state.closed {
+            return Err(Error::AlreadyClosed);
+        }
+
+        // Acquired with the lock held, so that a full queue is never empty
+        if let Ok(permit) = self.shared.space.try_acquire() {
+            permit.forget();
+            Self::push(state, message);
+
+            return Ok(true);
+        }
+
+        let (event, result) = match self.shared.policy {
+            Policy::Block | Policy::DropNewest => {
+                drop(state);
+
+                (Event::DroppedNewest, Ok(false))
+            }
+            Policy::DropOldest => {
+                state.messages.pop_front();
+                Self::push(state, message);
+
+                (Event::DroppedOldest, Ok(true))
+            }
+            Policy::Disconnect(code) => {
+                state.messages.clear();
+                state.closed = true;
+                self.shared.space.close();
+                Self::push(state, Message::close(Some(code), ""));
+
+                (Event::Disconnected(code), Err(Error::AlreadyClosed))
+            }
+        };
+
+        self.shared.emit(event);
+
+        result
+    }
+
+    /// Pushes a message that a slot was acquired for and wakes the receiver.
+    fn push(mut state: MutexGuard<'_, State>, message: Message) {
+        state.messages.push_back(message);
+        let receiver = state.receiver.take();
+        drop(state);
+
+        if let Some(receiver) = receiver {
+            receiver.wake();
+        }
+    }
+
+    /// Whether the queue accepts no more messages.
+    pub(crate) fn is_closed(&self) -> bool {
+        self.shared.lock().closed
+    }
+}
+
+impl Clone for QueueSender {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+
+        Self {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+}
+
+impl Drop for QueueSender {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.senders -= 1;
+
+        if state.senders == 0 {
+            if let Some(receiver) = state.receiver.take() {
+                drop(state);
+                receiver.wake();
+            }
+        }
+    }
+}
+
+impl fmt::Debug for QueueSender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QueueSender")
+            .field("policy", &self.shared.policy)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Receiving end of a queue.
+pub(crate) struct QueueReceiver {
+    /// State shared with the senders.
+    shared: Arc<Shared>,
+}
+
+impl QueueReceiver {
+    /// Polls for the next message. Returns [`None`] once the queue is closed
+    /// and empty, or all senders were dropped.
+    pub(crate) fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<Message>> {
+        let mut state = self.shared.lock();
+
+        if let Some(message) = state.messages.pop_front() {
+            self.shared.space.add_permits(1);
+
+            return Poll::Ready(Some(message));
+        }
+
+        if state.closed || state.senders == 0 {
+            return Poll::Ready(None);
+        }
+
+        state.receiver = Some(cx.waker().clone());
+
+        Poll::Pending
+    }
+}
+
+impl Drop for QueueReceiver {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.closed = true;
+        state.messages.clear();
+        self.shared.space.close();
+    }
+}
+
+impl fmt::Debug for QueueReceiver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QueueReceiver")
+            .field("policy", &self.shared.policy)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,146 @@
+//! Options for the TCP sockets that WebSocket connections are established on.
+//!
+//! The client applies [`SocketOptions`] configured via
+//! [`ClientBuilder::socket_options`] before connecting. Servers apply them to
+//! accepted connections via [`ServerBuilder::accept_tcp`], or manually via
+//! [`SocketOptions::apply`].
+//!
+//! [`ClientBuilder::socket_options`]: crate::ClientBuilder::socket_options
+//! [`ServerBuilder::accept_tcp`]: crate::ServerBuilder::accept_tcp
+use std::{io, time::Duration};
+
+use socket2::{SockRef, TcpKeepalive};
+use tokio::net::TcpStream;
+
+/// Options to set on a TCP socket. All options default to the operating
+/// system's defaults.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SocketOptions {
+    /// Value of `TCP_NODELAY`.
+    nodelay: Option<bool>,
+    /// Idle time after which keepalive probes are sent.
+    keepalive_time: Option<Duration>,
+    /// Time between keepalive probes.
+    keepalive_interval: Option<Duration>,
+    /// Size of the send buffer in bytes.
+    send_buffer_size: Option<usize>,
+    /// Size of the receive buffer in bytes.
+    recv_buffer_size: Option<usize>,
+}
+
+impl SocketOptions {
+    /// Creates [`SocketOptions`] that leave all options at the operating
+    /// system's defaults.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets `TCP_NODELAY`, which disables Nagle's algorithm if `true`.
+    ///
+    /// WebSocket messages are usually small and latency sensitive, so
+    /// enabling this is often desirable.
+    #[must_use]
+    pub fn nodelay(mut self, nodelay: bool) -> Self {
+        self.nodelay = Some(nodelay);
+
+        self
+    }
+
+    /// Enables TCP keepalive and sets the time a connection has to be idle
+    /// before keepalive probes are sent.
+    ///
+    /// Some platforms only support a granularity of seconds.
+    #[must_use]
+    pub fn keepalive_time(mut self, time: Duration) -> Self {
+        self.keepalive_time = Some(time);
+
+        self
+    }
+
+    /// Enables TCP keepalive and sets the time between keepalive probes.
+    ///
+    /// This is ignored on platforms that do not support configuring it, such
+    /// as OpenBSD and Solaris.
+    #[must_use]
+    pub fn keepalive_interval(mut self, interval: Duration) -> Self {
+        self.keepalive_interval = Some(interval);
+
+        self
+    }
+
+    /// Sets the size of the socket's send buffer (`SO_SNDBUF`) in bytes.
+    #[must_use]
+    pub fn send_buffer_size(mut self, size: usize) -> Self {
+        self.send_buffer_size = Some(size);
+
+        self
+    }
+
+    /// Sets the size of the socket's receive buffer (`SO_RCVBUF`) in bytes.
+    ///
+    /// Larger receive buffers only allow for larger TCP windows if they are
+    /// set before connecting, which the client does.
+    #[must_use]
+    pub fn recv_buffer_size(mut self, size: usize) -> Self {
+        self.recv_buffer_size = Some(size);
+
+        self
+    }
+
+    /// Applies the options to a connected TCP stream.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`io::Error`] if setting any of the options
+    /// fails.
+    pub fn apply(&self, stream: &TcpStream) -> io::Result<()> {
+        self.apply_to(&SockRef::from(stream))
+    }
+
+    /// Applies the options to a socket, which may or may not be connected.
+    pub(crate) fn apply_to(&self, socket: &SockRef<'_>) -> io::Result<()> {
+        if let Some(nodelay) = self.nodelay {
+            socket.set_tcp_nodelay(nodelay)?;
+        }
+
+        if self.keepalive_time.is_some() || self.keepalive_interval.is_some() {
+            let mut keepalive = TcpKeepalive::new();
+
+            if let Some(time) = self.keepalive_time {
+                keepalive = keepalive.with_time(time);
+            }
+
+            // Mirrors the platforms socket2 supports this on
+            #[cfg(any(
+                target_os = "android",
+                target_os = "dragonfly",
+                target_os = "freebsd",
+                target_os = "fuchsia",
+                target_os = "illumos",
+                target_os = "ios",
+                target_os = "linux",
+                target_os = "macos",
+                target_os = "netbsd",
+                target_os = "tvos",
+                target_os = "watchos",
+                target_os = "windows",
+            ))]
+            if let Some(interval) = self.keepalive_interval {
+                keepalive = keepalive.with_interval(interval);
+            }
+
+            socket.set_tcp_keepalive(&keepalive)?;
+        }
+
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+
+        Ok(())
+    }
+}
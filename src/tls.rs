@@ -10,6 +10,8 @@
     feature = "rustls-platform-verifier"
 ))]
 use std::sync::Arc;
+#[cfg(feature = "client")]
+use std::sync::OnceLock;
 use std::{
     fmt::{Debug, Formatter, Result as FmtResult},
     io,
@@ -24,6 +26,11 @@ use std::{
     feature = "rustls-bring-your-own-connector"
 ))]
 use rustls_pki_types::ServerName;
+#[cfg(all(
+    any(feature = "rustls-native-roots", feature = "rustls-webpki-roots"),
+    not(feature = "rustls-platform-verifier")
+))]
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, UnixTime};
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 #[cfg(all(
     any(
@@ -53,7 +60,14 @@ use tokio_rustls::rustls::crypto::CryptoProvider;
     any(feature = "rustls-native-roots", feature = "rustls-webpki-roots"),
     not(feature = "rustls-platform-verifier")
 ))]
-use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_rustls::rustls::{
+    client::{
+        danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        Resumption,
+    },
+    crypto::{verify_tls12_signature, verify_tls13_signature},
+    ClientConfig, DigitallySignedStruct, KeyLogFile, RootCertStore, SignatureScheme,
+};
 
 use crate::Error;
 
@@ -118,6 +132,30 @@ pub enum MaybeTlsStream<S> {
     Rustls(tokio_rustls::client::TlsStream<S>),
 }
 
+impl<S: AsyncRead + AsyncWrite + Unpin> MaybeTlsStream<S> {
+    /// Returns the protocol negotiated via ALPN during the TLS handshake, if
+    /// any.
+    ///
+    /// This always returns `None` for [`MaybeTlsStream::NativeTls`], since
+    /// reading the negotiated protocol requires the `alpn` feature of
+    /// `native-tls`.
+    #[must_use]
+    pub fn alpn_protocol(&self) -> Option<Vec<u8>> {
+        match self {
+            Self::Plain(_) => None,
+            #[cfg(feature = "native-tls")]
+            Self::NativeTls(_) => None,
+            #[cfg(any(
+                feature = "rustls-native-roots",
+                feature = "rustls-webpki-roots",
+                feature = "rustls-platform-verifier",
+                feature = "rustls-bring-your-own-connector"
+            ))]
+            Self::Rustls(s) => s.get_ref().1.alpn_protocol().map(<[u8]>::to_vec),
+        }
+    }
+}
+
 impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for MaybeTlsStream<S> {
     fn poll_read(
         self: Pin<&mut Self>,
@@ -255,43 +293,42 @@ impl Connector {
                 tokio_native_tls::native_tls::TlsConnector::new()?.into(),
             ))
         }
-        #[cfg(all(
-            any(
-                feature = "rustls-webpki-roots",
-                feature = "rustls-native-roots",
-                feature = "rustls-platform-verifier"
-            ),
-            not(any(feature = "ring", feature = "aws_lc_rs"))
-        ))]
-        {
-            Self::new_rustls_with_crypto_provider(
-                CryptoProvider::get_default()
-                    .ok_or(Error::NoCryptoProviderConfigured)?
-                    .clone(),
-            )
-        }
-        #[cfg(all(
-            any(
-                feature = "rustls-webpki-roots",
-                feature = "rustls-native-roots",
-                feature = "rustls-platform-verifier"
-            ),
-            feature = "aws_lc_rs"
+        #[cfg(any(
+            feature = "rustls-webpki-roots",
+            feature = "rustls-native-roots",
+            feature = "rustls-platform-verifier"
         ))]
         {
-            Self::new_rustls_with_crypto_provider(aws_lc_rs::default_provider().into())
+            Self::new_rustls_with_crypto_provider(default_crypto_provider()?)
         }
-        #[cfg(all(
-            any(
-                feature = "rustls-webpki-roots",
-                feature = "rustls-native-roots",
-                feature = "rustls-platform-verifier"
-            ),
-            all(feature = "ring", not(feature = "aws_lc_rs"))
-        ))]
-        {
-            Self::new_rustls_with_crypto_provider(ring::default_provider().into())
+    }
+
+    /// Returns the connector created via [`Connector::new`] that is shared by
+    /// all clients without a configured connector, creating it on first use.
+    ///
+    /// Sharing the connector shares its TLS session cache, so reconnecting to
+    /// the same host resumes the previous session with an abbreviated
+    /// handshake. It also avoids loading the root certificates again for
+    /// every connection.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] when creating the underlying TLS
+    /// connector fails.
+    #[cfg(feature = "client")]
+    pub(crate) fn shared() -> Result<&'static Self, Error> {
+        /// The connector, created once it is first needed.
+        static SHARED: OnceLock<Connector> = OnceLock::new();
+
+        if let Some(connector) = SHARED.get() {
+            return Ok(connector);
         }
+
+        // Creating the connector may fail, so it cannot be created in `get_or_init`.
+        // Should multiple connectors be created concurrently, only one is kept
+        let connector = Self::new()?;
+
+        Ok(SHARED.get_or_init(|| connector))
     }
 
     /// Creates a new `Connector` using `rustls` with a custom crypto provider
@@ -311,42 +348,36 @@ impl Connector {
         // The rustls-platform-verifier changes the certificate verifier and is
         // therefore incompatible with the other two features
         #[cfg(feature = "rustls-platform-verifier")]
-        let config = rustls_platform_verifier::tls_config_with_provider(provider)?;
+        {
+            let config = rustls_platform_verifier::tls_config_with_provider(provider)?;
+            let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
+            Ok(Self::Rustls(connector))
+        }
 
         #[cfg(not(feature = "rustls-platform-verifier"))]
-        let config = {
-            let mut roots = RootCertStore::empty();
-
-            #[cfg(feature = "rustls-native-roots")]
-            {
-                #[cfg_attr(feature = "rustls-webpki-roots", allow(unused))]
-                let rustls_native_certs::CertificateResult { certs, errors, .. } =
-                    rustls_native_certs::load_native_certs();
-
-                // Not finding any native roots is not fatal if webpki roots are enabled
-                #[cfg(not(feature = "rustls-webpki-roots"))]
-                if certs.is_empty() {
-                    return Err(Error::NoNativeRootCertificatesFound(errors));
-                }
-
-                for cert in certs {
-                    roots.add(cert)?;
-                }
-            }
-
-            #[cfg(feature = "rustls-webpki-roots")]
-            {
-                roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
-            };
-
-            ClientConfig::builder_with_provider(provider)
-                .with_safe_default_protocol_versions()?
-                .with_root_certificates(roots)
-                .with_no_client_auth()
-        };
+        Self::builder().crypto_provider(provider).build()
+    }
 
-        let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
-        Ok(Self::Rustls(connector))
+    /// Creates a [`ConnectorBuilder`] for a `Connector` using `rustls` with
+    /// custom root certificates or client certificates.
+    #[cfg(all(
+        any(feature = "rustls-native-roots", feature = "rustls-webpki-roots"),
+        not(feature = "rustls-platform-verifier")
+    ))]
+    #[must_use]
+    pub fn builder() -> ConnectorBuilder {
+        ConnectorBuilder {
+            crypto_provider: None,
+            builtin_roots: true,
+            root_certificates: Vec::new(),
+            client_auth: None,
+            alpn_protocols: Vec::new(),
+            accept_invalid_certs: false,
+            key_log: false,
+            session_cache_size: DEFAULT_SESSION_CACHE_SIZE,
+            #[cfg(feature = "rustls-early-data")]
+            early_data: false,
+        }
     }
 
     /// Wraps a given stream with a layer of TLS.
@@ -389,3 +420,398 @@ impl Connector {
         }
     }
 }
+
+/// Returns the crypto provider enabled via feature flags, or the process-wide
+/// default if none is enabled.
+///
+/// # Errors
+///
+/// This function returns [`Error::NoCryptoProviderConfigured`] if no crypto
+/// provider is enabled or configured.
+#[cfg(any(
+    feature = "rustls-webpki-roots",
+    feature = "rustls-native-roots",
+    feature = "rustls-platform-verifier"
+))]
+#[cfg_attr(
+    any(feature = "ring", feature = "aws_lc_rs"),
+    allow(clippy::unnecessary_wraps)
+)]
+pub(crate) fn default_crypto_provider() -> Result<Arc<CryptoProvider>, Error> {
+    #[cfg(not(any(feature = "ring", feature = "aws_lc_rs")))]
+    {
+        Ok(CryptoProvider::get_default()
+            .ok_or(Error::NoCryptoProviderConfigured)?
+            .clone())
+    }
+    #[cfg(feature = "aws_lc_rs")]
+    {
+        Ok(aws_lc_rs::default_provider().into())
+    }
+    #[cfg(all(feature = "ring", not(feature = "aws_lc_rs")))]
+    {
+        Ok(ring::default_provider().into())
+    }
+}
+
+/// Default number of TLS sessions cached by a [`Connector`] built with a
+/// [`ConnectorBuilder`], matching the default of `rustls`.
+#[cfg(all(
+    any(feature = "rustls-native-roots", feature = "rustls-webpki-roots"),
+    not(feature = "rustls-platform-verifier")
+))]
+const DEFAULT_SESSION_CACHE_SIZE: usize = 256;
+
+/// Builder for a [`Connector`] using `rustls` with custom root certificates,
+/// e.g. for a private PKI, or client certificates for mutual TLS.
+///
+/// This builder is not available with the `rustls-platform-verifier` feature,
+/// since the platform verifier does not use a root certificate store. Use
+/// [`Connector::Rustls`] with a custom configuration instead.
+#[cfg(all(
+    any(feature = "rustls-native-roots", feature = "rustls-webpki-roots"),
+    not(feature = "rustls-platform-verifier")
+))]
+#[allow(clippy::struct_excessive_bools)] // The options are independent of each other
+pub struct ConnectorBuilder {
+    /// Crypto provider to use instead of the default one.
+    crypto_provider: Option<Arc<CryptoProvider>>,
+    /// Whether to trust the root certificates enabled via feature flags.
+    builtin_roots: bool,
+    /// Additional root certificates to trust.
+    root_certificates: Vec<CertificateDer<'static>>,
+    /// Certificate chain and private key to authenticate with.
+    client_auth: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+    /// Protocols to offer via ALPN.
+    alpn_protocols: Vec<Vec<u8>>,
+    /// Whether to skip verification of the server certificate.
+    accept_invalid_certs: bool,
+    /// Whether to log TLS secrets to the file in `SSLKEYLOGFILE`.
+    key_log: bool,
+    /// Number of TLS sessions to cache for resumption.
+    session_cache_size: usize,
+    /// Whether to send the upgrade request as TLS 1.3 early data.
+    #[cfg(feature = "rustls-early-data")]
+    early_data: bool,
+}
+
+#[cfg(all(
+    any(feature = "rustls-native-roots", feature = "rustls-webpki-roots"),
+    not(feature = "rustls-platform-verifier")
+))]
+impl Debug for ConnectorBuilder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        // The private key of the client certificate is never printed
+        let mut debug = f.debug_struct("ConnectorBuilder");
+        debug
+            .field("crypto_provider", &self.crypto_provider.is_some())
+            .field("builtin_roots", &self.builtin_roots)
+            .field("root_certificates", &self.root_certificates.len())
+            .field("client_auth", &self.client_auth.is_some())
+            .field("alpn_protocols", &self.alpn_protocols)
+            .field("accept_invalid_certs", &self.accept_invalid_certs)
+            .field("key_log", &self.key_log)
+            .field("session_cache_size", &self.session_cache_size);
+        #[cfg(feature = "rustls-early-data")]
+        debug.field("early_data", &self.early_data);
+
+        debug.finish()
+    }
+}
+
+#[cfg(all(
+    any(feature = "rustls-native-roots", feature = "rustls-webpki-roots"),
+    not(feature = "rustls-platform-verifier")
+))]
+impl ConnectorBuilder {
+    /// Sets the crypto provider to use.
+    ///
+    /// By default, the provider enabled via feature flags is used, or the
+    /// process-wide default if none is enabled.
+    #[must_use]
+    pub fn crypto_provider(mut self, provider: Arc<CryptoProvider>) -> Self {
+        self.crypto_provider = Some(provider);
+
+        self
+    }
+
+    /// Sets whether the root certificates enabled via feature flags are
+    /// trusted. Disabling them only trusts root certificates added via
+    /// [`ConnectorBuilder::add_root_certificate`].
+    ///
+    /// By default, they are trusted.
+    #[must_use]
+    pub fn builtin_roots(mut self, builtin_roots: bool) -> Self {
+        self.builtin_roots = builtin_roots;
+
+        self
+    }
+
+    /// Adds a DER-encoded root certificate to trust.
+    #[must_use]
+    pub fn add_root_certificate(mut self, certificate: CertificateDer<'static>) -> Self {
+        self.root_certificates.push(certificate);
+
+        self
+    }
+
+    /// Sets the DER-encoded certificate chain and private key to authenticate
+    /// with if the server requests a client certificate.
+    ///
+    /// By default, no client certificate is sent.
+    #[must_use]
+    pub fn client_auth(
+        mut self,
+        certificate_chain: Vec<CertificateDer<'static>>,
+        key: PrivateKeyDer<'static>,
+    ) -> Self {
+        self.client_auth = Some((certificate_chain, key));
+
+        self
+    }
+
+    /// Sets the protocols to offer via ALPN, in order of preference. The
+    /// negotiated protocol can be read via [`MaybeTlsStream::alpn_protocol`].
+    ///
+    /// By default, no protocols are offered.
+    #[must_use]
+    pub fn alpn_protocols(mut self, protocols: Vec<Vec<u8>>) -> Self {
+        self.alpn_protocols = protocols;
+
+        self
+    }
+
+    /// Disables verification of the server certificate, accepting any
+    /// certificate for any server name.
+    ///
+    /// # Danger
+    ///
+    /// This makes the connection vulnerable to man-in-the-middle attacks and
+    /// should only ever be used for local development or tests with
+    /// self-signed certificates. Prefer adding the certificate via
+    /// [`ConnectorBuilder::add_root_certificate`] instead.
+    #[must_use]
+    pub fn danger_accept_invalid_certs(mut self, accept_invalid_certs: bool) -> Self {
+        self.accept_invalid_certs = accept_invalid_certs;
+
+        self
+    }
+
+    /// Sets whether TLS session secrets are written to the file specified by
+    /// the `SSLKEYLOGFILE` environment variable, which allows decrypting
+    /// captured traffic, e.g. in Wireshark. Nothing is written if the variable
+    /// is not set.
+    ///
+    /// This should only be enabled for debugging, anyone with access to the
+    /// file can decrypt the traffic.
+    ///
+    /// By default, no secrets are written.
+    #[must_use]
+    pub fn key_log(mut self, key_log: bool) -> Self {
+        self.key_log = key_log;
+
+        self
+    }
+
+    /// Sets the number of TLS sessions that are cached to resume them when
+    /// reconnecting, which saves a round trip and the certificate
+    /// verification. `0` disables session resumption.
+    ///
+    /// The cache is shared by all connections established with the built
+    /// [`Connector`], so reuse it for reconnects. By default, 256 sessions are
+    /// cached.
+    #[must_use]
+    pub fn session_cache_size(mut self, size: usize) -> Self {
+        self.session_cache_size = size;
+
+        self
+    }
+
+    /// Sets whether the WebSocket upgrade request is sent as TLS 1.3 early
+    /// data (0-RTT) when resuming a session with a server that supports it,
+    /// which saves a round trip when reconnecting.
+    ///
+    /// # Replay
+    ///
+    /// Early data is not protected against replay attacks: an attacker who
+    /// captured it can send it to the server again, which then processes the
+    /// upgrade request, including its cookies and authorization headers,
+    /// once more. Only enable this if the server treats upgrade requests as
+    /// idempotent and does not act on them before the handshake completes.
+    ///
+    /// Early data can only be sent when resuming a session, so the
+    /// [`Connector`] has to be reused for reconnects and sessions have to be
+    /// cached, see [`ConnectorBuilder::session_cache_size`]. If the server
+    /// rejects the early data, the request is sent again once the handshake
+    /// is complete.
+    ///
+    /// By default, no early data is sent.
+    #[cfg(feature = "rustls-early-data")]
+    #[must_use]
+    pub fn early_data(mut self, early_data: bool) -> Self {
+        self.early_data = early_data;
+
+        self
+    }
+
+    /// Builds the [`Connector`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Error`] when creating the underlying TLS
+    /// connector fails, e.g. if a certificate or the private key is invalid.
+    pub fn build(self) -> Result<Connector, Error> {
+        let provider = match self.crypto_provider {
+            Some(provider) => provider,
+            None => default_crypto_provider()?,
+        };
+
+        let mut roots = RootCertStore::empty();
+
+        #[cfg(feature = "rustls-native-roots")]
+        if self.builtin_roots {
+            #[cfg_attr(feature = "rustls-webpki-roots", allow(unused))]
+            let rustls_native_certs::CertificateResult { certs, errors, .. } =
+                rustls_native_certs::load_native_certs();
+
+            // Not finding any native roots is not fatal if webpki roots are enabled
+            #[cfg(not(feature = "rustls-webpki-roots"))]
+            if certs.is_empty() {
+                return Err(Error::NoNativeRootCertificatesFound(errors));
+            }
+
+            for cert in certs {
+                roots.add(cert)?;
+            }
+        }
+
+        #[cfg(feature = "rustls-webpki-roots")]
+        if self.builtin_roots {
+            roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        }
+
+        for cert in self.root_certificates {
+            roots.add(cert)?;
+        }
+
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots);
+
+        let mut config = match self.client_auth {
+            Some((certificate_chain, key)) => {
+                builder.with_client_auth_cert(certificate_chain, key)?
+            }
+            None => builder.with_no_client_auth(),
+        };
+        config.alpn_protocols = self.alpn_protocols;
+        config.resumption = if self.session_cache_size == 0 {
+            Resumption::disabled()
+        } else {
+            Resumption::in_memory_sessions(self.session_cache_size)
+        };
+
+        if self.key_log {
+            config.key_log = Arc::new(KeyLogFile::new());
+        }
+
+        if self.accept_invalid_certs {
+            config
+                .dangerous()
+                .set_certificate_verifier(Arc::new(NoCertificateVerification(provider)));
+        }
+
+        #[cfg(feature = "rustls-early-data")]
+        {
+            config.enable_early_data = self.early_data;
+        }
+
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
+        #[cfg(feature = "rustls-early-data")]
+        let connector = connector.early_data(self.early_data);
+
+        Ok(Connector::Rustls(connector))
+    }
+}
+
+/// A certificate verifier that accepts any server certificate, used by
+/// [`ConnectorBuilder::danger_accept_invalid_certs`]. Handshake signatures
+/// are still verified.
+#[cfg(all(
+    any(feature = "rustls-native-roots", feature = "rustls-webpki-roots"),
+    not(feature = "rustls-platform-verifier")
+))]
+#[derive(Debug)]
+struct NoCertificateVerification(Arc<CryptoProvider>);
+
+#[cfg(all(
+    any(feature = "rustls-native-roots", feature = "rustls-webpki-roots"),
+    not(feature = "rustls-platform-verifier")
+))]
+impl ServerCertVerifier for NoCertificateVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(all(
+    test,
+    any(feature = "rustls-native-roots", feature = "rustls-webpki-roots"),
+    not(feature = "rustls-platform-verifier"),
+    any(feature = "ring", feature = "aws_lc_rs")
+))]
+#[test]
+fn test_key_log() {
+    let client_config = |key_log| match Connector::builder()
+        .builtin_roots(false)
+        .key_log(key_log)
+        .build()
+        .unwrap()
+    {
+        Connector::Rustls(connector) => connector.config().clone(),
+        _ => unreachable!(),
+    };
+
+    // `KeyLogFile` logs all secrets, the default `NoKeyLog` none
+    assert!(client_config(true).key_log.will_log("CLIENT_RANDOM"));
+    assert!(!client_config(false).key_log.will_log("CLIENT_RANDOM"));
+}
@@ -0,0 +1,290 @@
+//! Publish-subscribe manager for fanning out messages to many connections.
+//!
+//! Connections obtain a [`Subscriber`] from a shared [`Topics`] manager and
+//! subscribe it to any number of named topics. Messages published to a topic
+//! are delivered to every subscriber of it. Payloads are reference counted, so
+//! publishing does not copy them per subscriber. What happens when a
+//! subscriber's buffer is full is configured via
+//! [`Topics::slow_consumer_policy`]:
+//!
+//! ```
+//! # #[cfg(feature = "server")]
+//! # async fn example(
+//! #     topics: tokio_websockets::topics::Topics,
+//! #     mut stream: tokio_websockets::WebSocketStream<tokio::net::TcpStream>,
+//! # ) -> Result<(), tokio_websockets::Error> {
+//! use futures_util::StreamExt;
+//!
+//! let mut subscriber = topics.subscriber(32);
+//! subscriber.subscribe("news");
+//!
+//! loop {
+//!     tokio::select! {
+//!         Some(message) = subscriber.recv() => stream.send(message).await?,
+//!         message = stream.next() => match message {
+//!             Some(Ok(message)) if message.is_text() => {
+//!                 topics.publish("news", message).await;
+//!             }
+//!             Some(Ok(_)) => {}
+//!             Some(Err(e)) => return Err(e),
+//!             None => return Ok(()),
+//!         },
+//!     }
+//! }
+//! # }
+//! ```
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    future::poll_fn,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    task::{Context, Poll},
+};
+
+use futures_core::Stream;
+
+use crate::{
+    slow_consumer::{self, Event, Hook, Policy, QueueReceiver, QueueSender},
+    Message,
+};
+
+/// Queues of the subscribers of a topic, by subscriber ID.
+type TopicSubscribers = HashMap<u64, QueueSender>;
+
+/// State shared by a [`Topics`] manager and its clones.
+#[derive(Default)]
+struct Shared {
+    /// Subscribers by topic, without topics that have no subscribers.
+    topics: Mutex<HashMap<String, TopicSubscribers>>,
+    /// ID of the next subscriber.
+    next_id: AtomicU64,
+}
+
+/// Manager of named topics that [`Subscriber`]s can subscribe to.
+///
+/// It is cheap to clone, clones share the same topics.
+#[derive(Clone)]
+pub struct Topics {
+    /// State shared with clones and subscribers.
+    shared: Arc<Shared>,
+    /// What to do with messages for subscribers whose buffer is full.
+    policy: Policy,
+    /// Hook called when a subscriber's buffer is full.
+    on_slow_consumer: Option<Hook>,
+}
+
+impl Topics {
+    /// Creates a [`Topics`] manager without any topics.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            shared: Arc::default(),
+            policy: Policy::DropNewest,
+            on_slow_consumer: None,
+        }
+    }
+
+    /// Sets what happens to messages published to subscribers whose buffer
+    /// is full. The default is [`Policy::DropNewest`].
+    ///
+    /// With [`Policy::Block`], publishing waits for the slowest subscriber.
+    /// With [`Policy::Disconnect`], the subscriber receives a close message
+    /// with the close code, which closes the connection once it is sent, and
+    /// no further messages.
+    ///
+    /// This applies to subscribers created afterwards.
+    #[must_use]
+    pub fn slow_consumer_policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+
+        self
+    }
+
+    /// Sets a hook that is called with an [`Event`] whenever a subscriber's
+    /// buffer is full, e.g. to record metrics.
+    ///
+    /// This applies to subscribers created afterwards.
+    #[must_use]
+    pub fn on_slow_consumer<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(Event) + Send + Sync + 'static,
+    {
+        self.on_slow_consumer = Some(Arc::new(hook));
+
+        self
+    }
+
+    /// Creates a [`Subscriber`] that is not subscribed to any topic yet and
+    /// buffers up to `capacity` messages.
+    ///
+    /// # Panics
+    ///
+    /// If `capacity` is `0`.
+    #[must_use]
+    pub fn subscriber(&self, capacity: usize) -> Subscriber {
+        assert_ne!(capacity, 0, "capacity must be non-zero");
+        let (sender, receiver) =
+            slow_consumer::queue(capacity, self.policy, self.on_slow_consumer.clone());
+
+        Subscriber {
+            id: self.shared.next_id.fetch_add(1, Ordering::Relaxed),
+            topics: self.clone(),
+            sender,
+            receiver,
+            subscribed: HashSet::new(),
+        }
+    }
+
+    /// Publishes a message to all subscribers of `topic` and returns the
+    /// number of subscribers it was delivered to.
+    ///
+    /// Publishing only waits with [`Policy::Block`]. With the default policy,
+    /// subscribers whose buffer is full miss the message, so that slow
+    /// connections do not hold up the others.
+    pub async fn publish<M: Into<Message>>(&self, topic: &str, message: M) -> usize {
+        let message = message.into();
+        // Not holding the lock while publishing, which might wait
+        let senders: Vec<QueueSender> = self
+            .lock()
+            .get(topic)
+            .map(|subscribers| subscribers.values().cloned().collect())
+            .unwrap_or_default();
+
+        let mut delivered = 0;
+
+        for sender in senders {
+            if let Ok(true) = sender.send(message.clone()).await {
+                delivered += 1;
+            }
+        }
+
+        delivered
+    }
+
+    /// Returns the number of subscribers of `topic`.
+    #[must_use]
+    pub fn subscriber_count(&self, topic: &str) -> usize {
+        self.lock().get(topic).map_or(0, HashMap::len)
+    }
+
+    /// Locks the topics, ignoring poisoning since they are never left in an
+    /// inconsistent state.
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, TopicSubscribers>> {
+        self.shared
+            .topics
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for Topics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Topics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Topics")
+            .field("topics", &self.lock().len())
+            .field("policy", &self.policy)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Receiver of the messages published to the topics it is subscribed to,
+/// created via [`Topics::subscriber`].
+///
+/// It is unsubscribed from all topics when dropped. It also implements
+/// [`Stream`].
+pub struct Subscriber {
+    /// ID of the subscriber within its manager.
+    id: u64,
+    /// The manager of the topics.
+    topics: Topics,
+    /// Queue the topics deliver messages to.
+    sender: QueueSender,
+    /// Queue the messages are received from.
+    receiver: QueueReceiver,
+    /// Topics the subscriber is subscribed to.
+    subscribed: HashSet<String>,
+}
+
+impl Subscriber {
+    /// Subscribes to `topic`. Subscribing to a topic more than once has no
+    /// effect.
+    pub fn subscribe(&mut self, topic: &str) {
+        if self.subscribed.insert(topic.to_owned()) {
+            self.topics
+                .lock()
+                .entry(topic.to_owned())
+                .or_default()
+                .insert(self.id, self.sender.clone());
+        }
+    }
+
+    /// Unsubscribes from `topic`. Messages published before are still
+    /// received.
+    pub fn unsubscribe(&mut self, topic: &str) {
+        if self.subscribed.remove(topic) {
+            remove(&mut self.topics.lock(), topic, self.id);
+        }
+    }
+
+    /// Returns whether the subscriber is subscribed to `topic`.
+    #[must_use]
+    pub fn is_subscribed(&self, topic: &str) -> bool {
+        self.subscribed.contains(topic)
+    }
+
+    /// Receives the next message published to any of the subscribed topics.
+    ///
+    /// This waits until a message is published. It only returns [`None`]
+    /// after the subscriber was disconnected by [`Policy::Disconnect`].
+    pub async fn recv(&mut self) -> Option<Message> {
+        poll_fn(|cx| self.receiver.poll_recv(cx)).await
+    }
+}
+
+impl Stream for Subscriber {
+    type Item = Message;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Message>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        let mut topics = self.topics.lock();
+
+        for topic in &self.subscribed {
+            remove(&mut topics, topic, self.id);
+        }
+    }
+}
+
+impl fmt::Debug for Subscriber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subscriber")
+            .field("id", &self.id)
+            .field("subscribed", &self.subscribed)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Removes the subscriber with `id` from `topic`, removing the topic if it
+/// has no subscribers left.
+fn remove(topics: &mut HashMap<String, TopicSubscribers>, topic: &str, id: u64) {
+    if let Some(subscribers) = topics.get_mut(topic) {
+        subscribers.remove(&id);
+
+        if subscribers.is_empty() {
+            topics.remove(topic);
+        }
+    }
+}
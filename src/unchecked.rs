@@ -0,0 +1,164 @@
+//! Wrappers around unchecked slice accesses, unwraps and unreachable branches.
+//!
+//! Callers have to uphold the same safety requirements as for the wrapped
+//! standard library functions. By default, the wrappers skip all checks. With
+//! the `checked` feature, they perform the regular checks instead and panic
+//! if a requirement is violated, rather than causing undefined behavior. The
+//! wrappers are safe functions then, so calls to them are wrapped in
+//! [`unchecked!`] rather than an `unsafe` block.
+use std::{fmt::Debug, slice::SliceIndex};
+
+/// Evaluates a block that calls the wrappers of this module, which is
+/// `unsafe` unless the `checked` feature makes the wrappers safe.
+macro_rules! unchecked {
+    ($($body:tt)*) => {{
+        #[cfg(feature = "checked")]
+        {
+            $($body)*
+        }
+        #[cfg(not(feature = "checked"))]
+        unsafe {
+            $($body)*
+        }
+    }};
+}
+
+pub(crate) use unchecked;
+
+/// Returns a reference to the element or subslice of `slice` at `index`,
+/// like [`slice::get_unchecked`].
+///
+/// # Safety
+///
+/// `index` must be in bounds of `slice`.
+#[cfg(not(feature = "checked"))]
+#[inline]
+pub(crate) unsafe fn get<T, I: SliceIndex<[T]>>(slice: &[T], index: I) -> &I::Output {
+    slice.get_unchecked(index)
+}
+
+/// Returns a reference to the element or subslice of `slice` at `index`,
+/// panicking if `index` is out of bounds.
+#[cfg(feature = "checked")]
+#[inline]
+pub(crate) fn get<T, I: SliceIndex<[T]>>(slice: &[T], index: I) -> &I::Output {
+    &slice[index]
+}
+
+/// Returns a mutable reference to the element or subslice of `slice` at
+/// `index`, like [`slice::get_unchecked_mut`].
+///
+/// # Safety
+///
+/// `index` must be in bounds of `slice`.
+#[cfg(not(feature = "checked"))]
+#[inline]
+pub(crate) unsafe fn get_mut<T, I: SliceIndex<[T]>>(slice: &mut [T], index: I) -> &mut I::Output {
+    slice.get_unchecked_mut(index)
+}
+
+/// Returns a mutable reference to the element or subslice of `slice` at
+/// `index`, panicking if `index` is out of bounds.
+#[cfg(feature = "checked")]
+#[inline]
+pub(crate) fn get_mut<T, I: SliceIndex<[T]>>(slice: &mut [T], index: I) -> &mut I::Output {
+    &mut slice[index]
+}
+
+/// Divides `slice` into two at `mid`, like [`slice::split_at_mut_unchecked`].
+///
+/// # Safety
+///
+/// `mid` must be at most the length of `slice`.
+#[cfg(not(feature = "checked"))]
+#[inline]
+pub(crate) unsafe fn split_at_mut<T>(slice: &mut [T], mid: usize) -> (&mut [T], &mut [T]) {
+    slice.split_at_mut_unchecked(mid)
+}
+
+/// Divides `slice` into two at `mid`, panicking if `mid` is larger than the
+/// length of `slice`.
+#[cfg(feature = "checked")]
+#[inline]
+pub(crate) fn split_at_mut<T>(slice: &mut [T], mid: usize) -> (&mut [T], &mut [T]) {
+    slice.split_at_mut(mid)
+}
+
+/// Returns the value of `result`, like [`Result::unwrap_unchecked`].
+///
+/// # Safety
+///
+/// `result` must be [`Ok`].
+#[cfg(not(feature = "checked"))]
+#[inline]
+pub(crate) unsafe fn unwrap<T, E: Debug>(result: Result<T, E>) -> T {
+    result.unwrap_unchecked()
+}
+
+/// Returns the value of `result`, panicking if it is an [`Err`].
+#[cfg(feature = "checked")]
+#[inline]
+pub(crate) fn unwrap<T, E: Debug>(result: Result<T, E>) -> T {
+    result.unwrap()
+}
+
+/// Converts a slice of bytes to a string slice, like
+/// [`std::str::from_utf8_unchecked`].
+///
+/// # Safety
+///
+/// `bytes` must be valid UTF-8.
+#[cfg(not(feature = "checked"))]
+#[inline]
+pub(crate) unsafe fn from_utf8(bytes: &[u8]) -> &str {
+    std::str::from_utf8_unchecked(bytes)
+}
+
+/// Converts a slice of bytes to a string slice, panicking if it is not valid
+/// UTF-8.
+#[cfg(feature = "checked")]
+#[inline]
+pub(crate) fn from_utf8(bytes: &[u8]) -> &str {
+    crate::utf8::parse_str(bytes).unwrap()
+}
+
+/// Converts bytes to [`Utf8Bytes`], like [`Utf8Bytes::from_bytes_unchecked`].
+///
+/// # Safety
+///
+/// `bytes` must be valid UTF-8.
+///
+/// [`Utf8Bytes`]: tungstenite::Utf8Bytes
+/// [`Utf8Bytes::from_bytes_unchecked`]: tungstenite::Utf8Bytes::from_bytes_unchecked
+#[cfg(all(feature = "tungstenite", not(feature = "checked")))]
+#[inline]
+pub(crate) unsafe fn utf8_bytes(bytes: bytes::Bytes) -> tungstenite::Utf8Bytes {
+    tungstenite::Utf8Bytes::from_bytes_unchecked(bytes)
+}
+
+/// Converts bytes to [`Utf8Bytes`], panicking if they are not valid UTF-8.
+///
+/// [`Utf8Bytes`]: tungstenite::Utf8Bytes
+#[cfg(all(feature = "tungstenite", feature = "checked"))]
+#[inline]
+pub(crate) fn utf8_bytes(bytes: bytes::Bytes) -> tungstenite::Utf8Bytes {
+    tungstenite::Utf8Bytes::try_from(bytes).unwrap()
+}
+
+/// Marks a branch as unreachable, like [`std::hint::unreachable_unchecked`].
+///
+/// # Safety
+///
+/// This must never be called.
+#[cfg(not(feature = "checked"))]
+#[inline]
+pub(crate) unsafe fn unreachable() -> ! {
+    std::hint::unreachable_unchecked()
+}
+
+/// Marks a branch as unreachable, panicking if it is reached.
+#[cfg(feature = "checked")]
+#[inline]
+pub(crate) fn unreachable() -> ! {
+    unreachable!()
+}
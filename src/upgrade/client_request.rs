@@ -1,10 +1,16 @@
 //! A [`Codec`] to parse client HTTP Upgrade handshakes and validate them.
 use base64::{engine::general_purpose::STANDARD, Engine};
 use bytes::{Buf, BytesMut};
+use http::{header::HeaderName, HeaderValue, Method, Uri, Version};
 use httparse::Request;
 use tokio_util::codec::Decoder;
 
-use crate::{sha::digest, upgrade::Error, utf8::parse_str};
+use crate::{
+    sha::digest,
+    unchecked::{self, unchecked},
+    upgrade::Error,
+    utf8::parse_str,
+};
 
 /// A static HTTP/1.1 101 Switching Protocols response up until the
 /// `Sec-WebSocket-Accept` header value.
@@ -19,7 +25,7 @@ fn contains_ignore_ascii_case(mut haystack: &[u8], needle: &[u8]) -> bool {
 
     while haystack.len() >= needle.len() {
         // SAFETY: needle.len() will always be equal to or less than haystack.len()
-        if unsafe { haystack.get_unchecked(..needle.len()) }.eq_ignore_ascii_case(needle) {
+        if unchecked! { unchecked::get(haystack, ..needle.len()) }.eq_ignore_ascii_case(needle) {
             return true;
         }
 
@@ -88,42 +94,133 @@ impl ClientRequest {
 }
 
 /// A codec that implements a [`Decoder`] for HTTP/1.1 upgrade requests and
-/// yields a HTTP/1.1 response to reply with.
+/// yields the parsed request along with the `Sec-WebSocket-Accept` header
+/// value to reply with, see [`response`].
 ///
 /// It does not implement an [`Encoder`].
 ///
 /// [`Encoder`]: tokio_util::codec::Encoder
-pub struct Codec {}
+pub struct Codec {
+    /// Maximum number of headers in the request.
+    max_headers: usize,
+    /// Maximum size of the request in bytes.
+    max_size: usize,
+}
+
+impl Codec {
+    /// Returns a new [`Codec`] that rejects requests with more than
+    /// `max_headers` headers or more than `max_size` bytes.
+    #[must_use]
+    pub fn new(max_headers: usize, max_size: usize) -> Self {
+        Self {
+            max_headers,
+            max_size,
+        }
+    }
+}
 
 impl Decoder for Codec {
     type Error = crate::Error;
-    type Item = String;
+    type Item = (super::Request, String);
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        let mut headers = [httparse::EMPTY_HEADER; 64];
+        let mut headers = vec![httparse::EMPTY_HEADER; self.max_headers];
         let mut request = Request::new(&mut headers);
         let status = request.parse(src).map_err(Error::Parsing)?;
 
         if !status.is_complete() {
+            if src.len() >= self.max_size {
+                return Err(crate::Error::Upgrade(Error::RequestTooLarge));
+            }
+
             return Ok(None);
         }
 
         let request_len = status.unwrap();
 
+        if request_len > self.max_size {
+            return Err(crate::Error::Upgrade(Error::RequestTooLarge));
+        }
+
+        if request.method != Some("GET") {
+            return Err(crate::Error::Upgrade(Error::UnsupportedMethod));
+        }
+
         let ws_accept = ClientRequest::parse(|name| {
-            let h = headers.iter().find(|h| h.name.eq_ignore_ascii_case(name))?;
+            let h = request
+                .headers
+                .iter()
+                .find(|h| h.name.eq_ignore_ascii_case(name))?;
             parse_str(h.value).ok()
         })?
         .ws_accept();
 
+        let mut parsed_request = http::Request::new(());
+        *parsed_request.method_mut() = Method::from_bytes(request.method.unwrap().as_bytes())
+            .map_err(|_| Error::Parsing(httparse::Error::Token))?;
+        *parsed_request.uri_mut() = Uri::try_from(request.path.unwrap())
+            .map_err(|_| Error::Parsing(httparse::Error::Token))?;
+        *parsed_request.version_mut() = Version::HTTP_11;
+
+        let header_map = parsed_request.headers_mut();
+
+        header_map.reserve(request.headers.len());
+
+        for header in request.headers.iter() {
+            let name = HeaderName::from_bytes(header.name.as_bytes())
+                .map_err(|_| Error::Parsing(httparse::Error::HeaderName))?;
+            let value = HeaderValue::from_bytes(header.value)
+                .map_err(|_| Error::Parsing(httparse::Error::HeaderValue))?;
+
+            header_map.append(name, value);
+        }
+
         src.advance(request_len);
 
-        let mut resp = String::with_capacity(SWITCHING_PROTOCOLS_BODY.len() + ws_accept.len() + 4);
+        Ok(Some((parsed_request, ws_accept)))
+    }
+}
+
+/// Validates an upgrade request received by an HTTP server and returns the
+/// `Sec-WebSocket-Accept` header value to reply with.
+///
+/// # Errors
+///
+/// This method fails when the request does not use the `GET` method or a header
+/// required for the WebSocket protocol is missing or invalid.
+pub fn validate<B>(request: &http::Request<B>) -> Result<String, Error> {
+    if request.method() != Method::GET {
+        return Err(Error::UnsupportedMethod);
+    }
 
-        resp.push_str(SWITCHING_PROTOCOLS_BODY);
-        resp.push_str(&ws_accept);
-        resp.push_str("\r\n\r\n");
+    let ws_accept =
+        ClientRequest::parse(|name| request.headers().get(name)?.to_str().ok())?.ws_accept();
 
-        Ok(Some(resp))
+    Ok(ws_accept)
+}
+
+/// Returns the HTTP/1.1 101 Switching Protocols response to reply to a request
+/// with, given the `Sec-WebSocket-Accept` header value and additional headers
+/// to send.
+pub fn response(ws_accept: &str, headers: &[(&str, &str)]) -> String {
+    let headers_len: usize = headers
+        .iter()
+        .map(|(name, value)| name.len() + value.len() + 4)
+        .sum();
+    let mut resp =
+        String::with_capacity(SWITCHING_PROTOCOLS_BODY.len() + ws_accept.len() + headers_len + 4);
+
+    resp.push_str(SWITCHING_PROTOCOLS_BODY);
+    resp.push_str(ws_accept);
+
+    for (name, value) in headers {
+        resp.push_str("\r\n");
+        resp.push_str(name);
+        resp.push_str(": ");
+        resp.push_str(value);
     }
+
+    resp.push_str("\r\n\r\n");
+
+    resp
 }
@@ -0,0 +1,365 @@
+//! Parser and serializer for the `Sec-WebSocket-Extensions` header as
+//! specified in [RFC 6455, Section 9.1].
+//!
+//! [RFC 6455, Section 9.1]: https://datatracker.ietf.org/doc/html/rfc6455#section-9.1
+use std::fmt;
+
+use crate::proto::ExtensionCodec;
+
+/// A WebSocket extension with its parameters, as offered or accepted via the
+/// `Sec-WebSocket-Extensions` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extension {
+    /// Name of the extension.
+    name: String,
+    /// Parameters of the extension with their values, in order.
+    params: Vec<(String, Option<String>)>,
+}
+
+impl Extension {
+    /// Creates an [`Extension`] with the given name and no parameters.
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            params: Vec::new(),
+        }
+    }
+
+    /// Adds a parameter, optionally with a value.
+    #[must_use]
+    pub fn param(mut self, name: impl Into<String>, value: Option<impl Into<String>>) -> Self {
+        self.params.push((name.into(), value.map(Into::into)));
+
+        self
+    }
+
+    /// Returns the name of the extension.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns an iterator over the parameters of the extension and their
+    /// values, in order.
+    pub fn params(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.params
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_deref()))
+    }
+
+    /// Returns the value of the first parameter with the given name, ignoring
+    /// ASCII case. The outer [`Option`] is [`None`] if the parameter is not
+    /// present, the inner one if it has no value.
+    #[must_use]
+    #[allow(clippy::option_option)]
+    pub fn get(&self, name: &str) -> Option<Option<&str>> {
+        self.params()
+            .find(|(param, _)| param.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+}
+
+impl fmt::Display for Extension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)?;
+
+        for (name, value) in &self.params {
+            f.write_str("; ")?;
+            f.write_str(name)?;
+
+            if let Some(value) = value {
+                f.write_str("=")?;
+
+                if !value.is_empty() && value.bytes().all(is_tchar) {
+                    f.write_str(value)?;
+                } else {
+                    f.write_str("\"")?;
+
+                    for c in value.chars() {
+                        if c == '"' || c == '\\' {
+                            f.write_str("\\")?;
+                        }
+
+                        f.write_fmt(format_args!("{c}"))?;
+                    }
+
+                    f.write_str("\"")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Error returned when a `Sec-WebSocket-Extensions` header value cannot be
+/// parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseError {
+    /// Byte offset in the header value at which parsing failed.
+    offset: usize,
+}
+
+impl ParseError {
+    /// Returns the byte offset in the header value at which parsing failed.
+    #[must_use]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid Sec-WebSocket-Extensions header at byte ")?;
+        f.write_fmt(format_args!("{}", self.offset))
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Returns whether a byte is a valid token character as defined in
+/// [RFC 7230, Section 3.2.6].
+///
+/// [RFC 7230, Section 3.2.6]: https://datatracker.ietf.org/doc/html/rfc7230#section-3.2.6
+fn is_tchar(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+/// Cursor over a header value being parsed.
+pub(super) struct Parser<'a> {
+    /// The header value.
+    pub(super) input: &'a str,
+    /// Current byte offset in the header value.
+    pub(super) offset: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Returns the byte at the current offset, if any.
+    pub(super) fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.offset).copied()
+    }
+
+    /// Returns an error at the current offset.
+    pub(super) fn error(&self) -> ParseError {
+        ParseError {
+            offset: self.offset,
+        }
+    }
+
+    /// Skips optional whitespace.
+    pub(super) fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.offset += 1;
+        }
+    }
+
+    /// Consumes `byte` if it is the next byte, returning whether it was.
+    pub(super) fn consume(&mut self, byte: u8) -> bool {
+        if self.peek() == Some(byte) {
+            self.offset += 1;
+
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Parses a non-empty token.
+    pub(super) fn token(&mut self) -> Result<&'a str, ParseError> {
+        let start = self.offset;
+
+        while self.peek().is_some_and(is_tchar) {
+            self.offset += 1;
+        }
+
+        if self.offset == start {
+            return Err(self.error());
+        }
+
+        Ok(&self.input[start..self.offset])
+    }
+
+    /// Parses a quoted string, returning its unescaped contents.
+    pub(super) fn quoted_string(&mut self) -> Result<String, ParseError> {
+        // The opening quote has already been consumed
+        let mut value = String::new();
+
+        loop {
+            let start = self.offset;
+            let c = self.input[start..]
+                .chars()
+                .next()
+                .ok_or_else(|| self.error())?;
+            self.offset += c.len_utf8();
+
+            match c {
+                '"' => return Ok(value),
+                '\\' => {
+                    let escaped = self.input[self.offset..]
+                        .chars()
+                        .next()
+                        .ok_or_else(|| self.error())?;
+                    self.offset += escaped.len_utf8();
+                    value.push(escaped);
+                }
+                c if c.is_control() && c != '\t' => {
+                    return Err(ParseError { offset: start });
+                }
+                c => value.push(c),
+            }
+        }
+    }
+
+    /// Parses a single extension with its parameters.
+    fn extension(&mut self) -> Result<Extension, ParseError> {
+        let mut extension = Extension::new(self.token()?);
+
+        loop {
+            self.skip_whitespace();
+
+            if !self.consume(b';') {
+                return Ok(extension);
+            }
+
+            self.skip_whitespace();
+            let name = self.token()?;
+            self.skip_whitespace();
+
+            let value = if self.consume(b'=') {
+                self.skip_whitespace();
+
+                if self.consume(b'"') {
+                    Some(self.quoted_string()?)
+                } else {
+                    Some(self.token()?.to_owned())
+                }
+            } else {
+                None
+            };
+
+            extension.params.push((name.to_owned(), value));
+        }
+    }
+}
+
+/// Parses a `Sec-WebSocket-Extensions` header value into the list of
+/// extensions it contains, in order. Empty list elements are ignored.
+///
+/// If a request or response contains multiple `Sec-WebSocket-Extensions`
+/// headers, each of them has to be parsed and the results concatenated.
+///
+/// # Errors
+///
+/// This function returns a [`ParseError`] if the value does not conform to
+/// the grammar of the header.
+pub fn parse(value: &str) -> Result<Vec<Extension>, ParseError> {
+    let mut parser = Parser {
+        input: value,
+        offset: 0,
+    };
+    let mut extensions = Vec::new();
+
+    loop {
+        parser.skip_whitespace();
+
+        match parser.peek() {
+            None => return Ok(extensions),
+            Some(b',') => {}
+            Some(_) => {
+                extensions.push(parser.extension()?);
+                parser.skip_whitespace();
+
+                if parser.peek().is_none() {
+                    return Ok(extensions);
+                }
+            }
+        }
+
+        if !parser.consume(b',') {
+            return Err(parser.error());
+        }
+    }
+}
+
+/// Serializes a list of extensions into a `Sec-WebSocket-Extensions` header
+/// value.
+#[must_use]
+pub fn serialize(extensions: &[Extension]) -> String {
+    let mut value = String::new();
+
+    for (i, extension) in extensions.iter().enumerate() {
+        if i != 0 {
+            value.push_str(", ");
+        }
+
+        value.push_str(&extension.to_string());
+    }
+
+    value
+}
+
+/// An extension that a client can offer to servers, registered via
+/// [`ClientBuilder::extension`].
+///
+/// [`ClientBuilder::extension`]: crate::ClientBuilder::extension
+#[cfg(feature = "client")]
+pub trait ClientExtension: Send + Sync {
+    /// Returns the extension with the parameters to offer in the request.
+    fn offer(&self) -> Extension;
+
+    /// Accepts the extension with the parameters chosen by the server in its
+    /// response, which has the same name as the offered one.
+    ///
+    /// Returns the codec to use for the connection, or [`None`] if the
+    /// parameters are not acceptable, which fails the handshake.
+    fn accept(&self, response: &Extension) -> Option<Box<dyn ExtensionCodec>>;
+}
+
+/// An extension that a server can negotiate with clients, registered via
+/// [`ServerBuilder::extension`].
+///
+/// [`ServerBuilder::extension`]: crate::ServerBuilder::extension
+#[cfg(feature = "server")]
+pub trait ServerExtension: Send + Sync {
+    /// Negotiates the extension with the extensions offered by a client, in
+    /// the order they were offered.
+    ///
+    /// Returns the extension with the parameters to accept in the response and
+    /// the codec to use for the connection, or [`None`] to decline.
+    fn negotiate(&self, offers: &[Extension]) -> Option<(Extension, Box<dyn ExtensionCodec>)>;
+}
+
+#[cfg(test)]
+#[test]
+fn test_parse_serialize() {
+    let value = "permessage-deflate; client_max_window_bits, \
+                 permessage-deflate ;server_no_context_takeover; x=\"a \\\"b\\\"\", ,foo";
+    let extensions = parse(value).unwrap();
+
+    assert_eq!(
+        extensions,
+        [
+            Extension::new("permessage-deflate").param("client_max_window_bits", None::<String>),
+            Extension::new("permessage-deflate")
+                .param("server_no_context_takeover", None::<String>)
+                .param("x", Some("a \"b\"")),
+            Extension::new("foo"),
+        ]
+    );
+    assert_eq!(extensions[1].get("X"), Some(Some("a \"b\"")));
+    assert_eq!(extensions[1].get("y"), None);
+
+    let serialized = serialize(&extensions);
+    assert_eq!(
+        serialized,
+        "permessage-deflate; client_max_window_bits, permessage-deflate; \
+         server_no_context_takeover; x=\"a \\\"b\\\"\", foo"
+    );
+    assert_eq!(parse(&serialized).unwrap(), extensions);
+
+    assert_eq!(parse("").unwrap(), []);
+    assert_eq!(parse("foo; =1").unwrap_err().offset(), 5);
+    assert_eq!(parse("foo bar").unwrap_err().offset(), 4);
+    assert!(parse("foo; x=\"unterminated").is_err());
+}
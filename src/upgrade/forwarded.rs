@@ -0,0 +1,349 @@
+//! Parser for the `Forwarded` header as specified in [RFC 7239] and the
+//! `X-Forwarded-For`, `X-Forwarded-Proto` and `X-Forwarded-Host` headers that
+//! reverse proxies add to requests to convey the identity of the client.
+//!
+//! These headers can be sent by clients as well, so they can only be trusted
+//! as far as they were added by trusted proxies. [`client_ip`] takes this into
+//! account, e.g. to count connections towards the limit set via
+//! [`Builder::max_connections_per_ip`] by the address of the client:
+//!
+//! ```
+//! use tokio_websockets::{upgrade::forwarded, ServerBuilder};
+//!
+//! // The server is deployed behind a single reverse proxy
+//! let builder = ServerBuilder::new()
+//!     .max_connections_per_ip(16)
+//!     .connection_ip(|peer_addr, request| {
+//!         forwarded::client_ip(request.headers(), 1).unwrap_or(peer_addr.ip())
+//!     });
+//! ```
+//!
+//! [RFC 7239]: https://datatracker.ietf.org/doc/html/rfc7239
+//! [`Builder::max_connections_per_ip`]: crate::ServerBuilder::max_connections_per_ip
+use std::{
+    fmt,
+    net::{IpAddr, Ipv6Addr},
+};
+
+use http::{header::FORWARDED, HeaderMap};
+
+use super::extensions::Parser;
+
+/// A node identifier of a `for` or `by` parameter, see
+/// [RFC 7239, Section 6].
+///
+/// [RFC 7239, Section 6]: https://datatracker.ietf.org/doc/html/rfc7239#section-6
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Node {
+    /// An IP address, along with the port if it was conveyed and not
+    /// obfuscated.
+    Ip(IpAddr, Option<u16>),
+    /// The `unknown` identifier, used if the proxy does not know the node.
+    Unknown,
+    /// An obfuscated identifier starting with an underscore, including its
+    /// port if any.
+    Obfuscated(String),
+}
+
+impl Node {
+    /// Parses a node identifier, accepting IPv6 addresses with and without
+    /// brackets.
+    fn parse(value: &str) -> Option<Self> {
+        if value.starts_with('_') {
+            return Some(Self::Obfuscated(value.to_owned()));
+        }
+
+        if value
+            .split(':')
+            .next()
+            .is_some_and(|name| name.eq_ignore_ascii_case("unknown"))
+        {
+            return Some(Self::Unknown);
+        }
+
+        if let Some(ip) = parse_ip(value) {
+            return Some(Self::Ip(ip, None));
+        }
+
+        let (ip, port) = value.rsplit_once(':')?;
+        let port = if port.starts_with('_') {
+            None
+        } else {
+            Some(port.parse().ok()?)
+        };
+
+        Some(Self::Ip(parse_ip(ip)?, port))
+    }
+
+    /// Returns the IP address of the node, if it is known.
+    #[must_use]
+    pub fn ip(&self) -> Option<IpAddr> {
+        match self {
+            Self::Ip(ip, _) => Some(*ip),
+            Self::Unknown | Self::Obfuscated(_) => None,
+        }
+    }
+}
+
+/// Parses an IP address, with IPv6 addresses optionally in brackets.
+fn parse_ip(value: &str) -> Option<IpAddr> {
+    match value.strip_prefix('[') {
+        Some(ip) => ip
+            .strip_suffix(']')?
+            .parse::<Ipv6Addr>()
+            .ok()
+            .map(IpAddr::V6),
+        None => value.parse().ok(),
+    }
+}
+
+/// A single element of the `Forwarded` header, describing one hop of the
+/// request through a proxy.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Forwarded {
+    /// The interface where the request came in to the proxy.
+    by: Option<Node>,
+    /// The client that made the request to the proxy.
+    for_: Option<Node>,
+    /// The `Host` header of the request received by the proxy.
+    host: Option<String>,
+    /// The protocol used to make the request to the proxy.
+    proto: Option<String>,
+}
+
+impl Forwarded {
+    /// Returns the interface where the request came in to the proxy.
+    #[must_use]
+    pub fn by(&self) -> Option<&Node> {
+        self.by.as_ref()
+    }
+
+    /// Returns the client that made the request to the proxy.
+    #[must_use]
+    pub fn for_(&self) -> Option<&Node> {
+        self.for_.as_ref()
+    }
+
+    /// Returns the `Host` header of the request received by the proxy.
+    #[must_use]
+    pub fn host(&self) -> Option<&str> {
+        self.host.as_deref()
+    }
+
+    /// Returns the protocol used to make the request to the proxy, e.g.
+    /// `https`.
+    #[must_use]
+    pub fn proto(&self) -> Option<&str> {
+        self.proto.as_deref()
+    }
+}
+
+/// Error returned when a `Forwarded` or `X-Forwarded-*` header value cannot
+/// be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseError {
+    /// Byte offset in the header value at which parsing failed.
+    offset: usize,
+}
+
+impl ParseError {
+    /// Returns the byte offset in the header value at which parsing failed.
+    #[must_use]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid forwarding header at byte ")?;
+        f.write_fmt(format_args!("{}", self.offset))
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<super::extensions::ParseError> for ParseError {
+    fn from(err: super::extensions::ParseError) -> Self {
+        Self {
+            offset: err.offset(),
+        }
+    }
+}
+
+/// Parses a single element of the `Forwarded` header with its parameters.
+fn element(parser: &mut Parser<'_>) -> Result<Forwarded, ParseError> {
+    let mut forwarded = Forwarded::default();
+
+    loop {
+        parser.skip_whitespace();
+
+        if !matches!(parser.peek(), None | Some(b',' | b';')) {
+            let name = parser.token()?;
+
+            if !parser.consume(b'=') {
+                return Err(parser.error().into());
+            }
+
+            let start = parser.offset;
+            let value = if parser.consume(b'"') {
+                parser.quoted_string()?
+            } else {
+                parser.token()?.to_owned()
+            };
+            let node = || Node::parse(&value).ok_or(ParseError { offset: start });
+
+            // Parameter names are case-insensitive, unknown ones are ignored
+            match name.to_ascii_lowercase().as_str() {
+                "by" => forwarded.by = Some(node()?),
+                "for" => forwarded.for_ = Some(node()?),
+                "host" => forwarded.host = Some(value),
+                "proto" => forwarded.proto = Some(value),
+                _ => {}
+            }
+
+            parser.skip_whitespace();
+        }
+
+        if !parser.consume(b';') {
+            return Ok(forwarded);
+        }
+    }
+}
+
+/// Parses a `Forwarded` header value into the list of elements it contains,
+/// in order, i.e. starting with the one added by the proxy closest to the
+/// client. Empty list elements are ignored.
+///
+/// If a request contains multiple `Forwarded` headers, each of them has to be
+/// parsed and the results concatenated, as done by [`from_headers`].
+///
+/// # Errors
+///
+/// This function returns a [`ParseError`] if the value does not conform to
+/// the grammar of the header.
+pub fn parse(value: &str) -> Result<Vec<Forwarded>, ParseError> {
+    let mut parser = Parser {
+        input: value,
+        offset: 0,
+    };
+    let mut elements = Vec::new();
+
+    loop {
+        parser.skip_whitespace();
+
+        match parser.peek() {
+            None => return Ok(elements),
+            Some(b',') => {}
+            Some(_) => {
+                elements.push(element(&mut parser)?);
+                parser.skip_whitespace();
+
+                if parser.peek().is_none() {
+                    return Ok(elements);
+                }
+            }
+        }
+
+        if !parser.consume(b',') {
+            return Err(parser.error().into());
+        }
+    }
+}
+
+/// Returns the non-empty entries of all values of the header `name` along
+/// with their byte offsets in the header value.
+fn list<'a>(
+    headers: &'a HeaderMap,
+    name: &'static str,
+) -> Result<Vec<(&'a str, usize)>, ParseError> {
+    let mut entries = Vec::new();
+
+    for value in headers.get_all(name) {
+        let value = value.to_str().map_err(|_| ParseError { offset: 0 })?;
+        let mut offset = 0;
+
+        for entry in value.split(',') {
+            let trimmed = entry.trim_matches([' ', '\t']);
+
+            if !trimmed.is_empty() {
+                let leading = entry.len() - entry.trim_start_matches([' ', '\t']).len();
+                entries.push((trimmed, offset + leading));
+            }
+
+            offset += entry.len() + 1;
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Returns the element at `index`, adding empty elements up to it first if
+/// there are fewer.
+fn nth(elements: &mut Vec<Forwarded>, index: usize) -> &mut Forwarded {
+    if elements.len() <= index {
+        elements.resize_with(index + 1, Forwarded::default);
+    }
+
+    &mut elements[index]
+}
+
+/// Collects the hops of a request through proxies from its headers, starting
+/// with the one closest to the client.
+///
+/// The `Forwarded` headers are used if there are any. Otherwise, the elements
+/// are assembled from the `X-Forwarded-For`, `X-Forwarded-Proto` and
+/// `X-Forwarded-Host` headers, with their n-th entries making up the n-th
+/// element.
+///
+/// # Errors
+///
+/// This function returns a [`ParseError`] if a header value cannot be parsed.
+pub fn from_headers(headers: &HeaderMap) -> Result<Vec<Forwarded>, ParseError> {
+    if headers.contains_key(FORWARDED) {
+        let mut elements = Vec::new();
+
+        for value in headers.get_all(FORWARDED) {
+            let value = value.to_str().map_err(|_| ParseError { offset: 0 })?;
+            elements.extend(parse(value)?);
+        }
+
+        return Ok(elements);
+    }
+
+    let mut elements = Vec::new();
+
+    for (index, (entry, offset)) in list(headers, "x-forwarded-for")?.into_iter().enumerate() {
+        nth(&mut elements, index).for_ = Some(Node::parse(entry).ok_or(ParseError { offset })?);
+    }
+
+    for (index, (entry, _)) in list(headers, "x-forwarded-proto")?.into_iter().enumerate() {
+        nth(&mut elements, index).proto = Some(entry.to_owned());
+    }
+
+    for (index, (entry, _)) in list(headers, "x-forwarded-host")?.into_iter().enumerate() {
+        nth(&mut elements, index).host = Some(entry.to_owned());
+    }
+
+    Ok(elements)
+}
+
+/// Returns the IP address of the client that made the request, as conveyed
+/// by the forwarding headers added by the `trusted_proxies` reverse proxies
+/// in front of the server.
+///
+/// Each proxy appends the address of the node it received the request from,
+/// so the entries before the ones added by trusted proxies may have been
+/// forged by the client and are ignored.
+///
+/// Returns [`None`] if the headers cannot be parsed, if there are fewer
+/// entries than trusted proxies or if the address of the client is unknown or
+/// obfuscated.
+#[must_use]
+pub fn client_ip(headers: &HeaderMap, trusted_proxies: usize) -> Option<IpAddr> {
+    let elements = from_headers(headers).ok()?;
+    let index = elements.len().checked_sub(trusted_proxies)?;
+
+    elements.get(index)?.for_()?.ip()
+}
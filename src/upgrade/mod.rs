@@ -3,9 +3,17 @@
 use std::fmt;
 #[cfg(feature = "server")]
 pub(crate) mod client_request;
+pub mod extensions;
+#[cfg(feature = "server")]
+pub mod forwarded;
 #[cfg(feature = "client")]
 pub(crate) mod server_response;
 
+/// A parsed HTTP/1.1 upgrade request sent by a client.
+/// These requests typically do not contain a body, therefore it is omitted.
+#[cfg(feature = "server")]
+pub type Request = http::Request<()>;
+
 /// A parsed HTTP/1.1 101 Switching Protocols response.
 /// These responses typically do not contain a body, therefore it is omitted.
 #[cfg(feature = "client")]
@@ -18,6 +26,8 @@ pub type Response = http::Response<()>;
 pub enum Error {
     /// Header required in the request or response is not present.
     MissingHeader(&'static str),
+    /// Request sent by the client does not use the `GET` method.
+    UnsupportedMethod,
     /// `Upgrade` header sent by the client does not match "websocket".
     UpgradeNotWebSocket,
     /// `Connection` header sent by the client does not contain "Upgrade".
@@ -32,6 +42,35 @@ pub enum Error {
     /// Server returned a `Sec-WebSocket-Accept` that is not compatible with the
     /// `Sec-WebSocket-Key` sent by the client.
     WrongWebSocketAccept,
+    /// Server redirected the client to another URI, but the maximum number of
+    /// redirects to follow has been reached.
+    #[cfg(feature = "client")]
+    Redirected(http::Uri),
+    /// Server redirected the client from a `wss` URI to a `ws` URI.
+    #[cfg(feature = "client")]
+    InsecureRedirect,
+    /// Client has reached the limit of concurrently open connections per IP
+    /// address.
+    #[cfg(feature = "server")]
+    TooManyConnections,
+    /// Client did not complete the handshake within the configured timeout.
+    #[cfg(feature = "server")]
+    TimedOut,
+    /// Client request exceeds the configured maximum size.
+    #[cfg(feature = "server")]
+    RequestTooLarge,
+    /// Client requested a path that no route is registered for.
+    #[cfg(feature = "server")]
+    NotFound,
+    /// Connection does not start with a valid PROXY protocol header.
+    #[cfg(feature = "server")]
+    InvalidProxyHeader,
+    /// `Sec-WebSocket-Extensions` header could not be parsed.
+    InvalidExtensions(extensions::ParseError),
+    /// Server accepted an extension that was not offered, or with parameters
+    /// that are not acceptable.
+    #[cfg(feature = "client")]
+    UnexpectedExtension(String),
 }
 
 impl fmt::Display for Error {
@@ -41,6 +80,7 @@ impl fmt::Display for Error {
                 f.write_str("missing required header: ")?;
                 f.write_str(header)
             }
+            Error::UnsupportedMethod => f.write_str("request method was not GET"),
             Error::UpgradeNotWebSocket => f.write_str("upgrade header value was not websocket"),
             Error::ConnectionNotUpgrade => f.write_str("connection header value was not upgrade"),
             Error::UnsupportedWebSocketVersion => f.write_str("unsupported WebSocket version"),
@@ -50,6 +90,29 @@ impl fmt::Display for Error {
                 f.write_fmt(format_args!("{status}"))
             }
             Error::WrongWebSocketAccept => f.write_str("mismatching Sec-WebSocket-Accept header"),
+            #[cfg(feature = "client")]
+            Error::Redirected(location) => {
+                f.write_str("too many redirects, last redirected to ")?;
+                location.fmt(f)
+            }
+            #[cfg(feature = "client")]
+            Error::InsecureRedirect => f.write_str("refusing redirect from wss to ws"),
+            #[cfg(feature = "server")]
+            Error::TooManyConnections => f.write_str("too many connections from IP address"),
+            #[cfg(feature = "server")]
+            Error::TimedOut => f.write_str("handshake timed out"),
+            #[cfg(feature = "server")]
+            Error::RequestTooLarge => f.write_str("request exceeds maximum size"),
+            #[cfg(feature = "server")]
+            Error::NotFound => f.write_str("no route for requested path"),
+            #[cfg(feature = "server")]
+            Error::InvalidProxyHeader => f.write_str("invalid PROXY protocol header"),
+            Error::InvalidExtensions(e) => e.fmt(f),
+            #[cfg(feature = "client")]
+            Error::UnexpectedExtension(name) => {
+                f.write_str("server accepted unexpected extension: ")?;
+                f.write_str(name)
+            }
         }
     }
 }
@@ -58,12 +121,22 @@ impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             Error::MissingHeader(_)
+            | Error::UnsupportedMethod
             | Error::UpgradeNotWebSocket
             | Error::ConnectionNotUpgrade
             | Error::UnsupportedWebSocketVersion
             | Error::DidNotSwitchProtocols(_)
             | Error::WrongWebSocketAccept => None,
+            #[cfg(feature = "client")]
+            Error::Redirected(_) | Error::InsecureRedirect | Error::UnexpectedExtension(_) => None,
+            #[cfg(feature = "server")]
+            Error::TooManyConnections
+            | Error::TimedOut
+            | Error::RequestTooLarge
+            | Error::NotFound
+            | Error::InvalidProxyHeader => None,
             Error::Parsing(e) => Some(e),
+            Error::InvalidExtensions(e) => Some(e),
         }
     }
 }
@@ -13,6 +13,9 @@ use crate::{sha::digest, upgrade::Error};
 /// HTTP status code for Switching Protocols.
 const SWITCHING_PROTOCOLS: u16 = 101;
 
+/// HTTP status codes of redirects that may be followed.
+const REDIRECTS: [u16; 5] = [301, 302, 303, 307, 308];
+
 /// Find a header in an array of headers by name, ignoring ASCII case.
 fn header<'a, 'header: 'a>(
     headers: &'a [Header<'header>],
@@ -31,17 +34,22 @@ fn header<'a, 'header: 'a>(
 pub struct Codec {
     /// The SHA-1 digest of the `Sec-WebSocket-Key` header.
     ws_accept: [u8; 20],
+    /// Whether redirect responses are reported with their target URI.
+    follow_redirects: bool,
 }
 
 impl Codec {
     /// Returns a new [`Codec`].
     ///
     /// The `key` parameter provides the string passed to the server via the
-    /// HTTP `Sec-WebSocket-Key` header.
+    /// HTTP `Sec-WebSocket-Key` header. If `follow_redirects` is set,
+    /// redirect responses are returned for the caller to follow instead of
+    /// failing with [`Error::DidNotSwitchProtocols`].
     #[must_use]
-    pub fn new(key: &[u8]) -> Self {
+    pub fn new(key: &[u8], follow_redirects: bool) -> Self {
         Self {
             ws_accept: digest(key),
+            follow_redirects,
         }
     }
 }
@@ -62,18 +70,24 @@ impl Decoder for Codec {
         let response_len = status.unwrap();
         let code = response.code.unwrap();
 
-        if code != SWITCHING_PROTOCOLS {
+        // Redirects are returned with all headers, so that cookies set by them are
+        // stored before following the Location header
+        let redirect = self.follow_redirects && REDIRECTS.contains(&code);
+
+        if code != SWITCHING_PROTOCOLS && !redirect {
             return Err(crate::Error::Upgrade(Error::DidNotSwitchProtocols(code)));
         }
 
-        let ws_accept_header = header(response.headers, "Sec-WebSocket-Accept")?;
-        let mut ws_accept = [0; 20];
-        STANDARD
-            .decode_slice_unchecked(ws_accept_header, &mut ws_accept)
-            .map_err(|_| Error::WrongWebSocketAccept)?;
+        if !redirect {
+            let ws_accept_header = header(response.headers, "Sec-WebSocket-Accept")?;
+            let mut ws_accept = [0; 20];
+            STANDARD
+                .decode_slice_unchecked(ws_accept_header, &mut ws_accept)
+                .map_err(|_| Error::WrongWebSocketAccept)?;
 
-        if self.ws_accept != ws_accept {
-            return Err(crate::Error::Upgrade(Error::WrongWebSocketAccept));
+            if self.ws_accept != ws_accept {
+                return Err(crate::Error::Upgrade(Error::WrongWebSocketAccept));
+            }
         }
 
         let mut parsed_response = http::Response::new(());
@@ -91,7 +105,7 @@ impl Decoder for Codec {
             let value = HeaderValue::from_bytes(header.value)
                 .map_err(|_| Error::Parsing(httparse::Error::HeaderValue))?;
 
-            header_map.insert(name, value);
+            header_map.append(name, value);
         }
 
         src.advance(response_len);
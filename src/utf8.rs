@@ -1,8 +1,10 @@
 //! UTF-8 validation and parsing helpers that abstract over [`simdutf8`] if the
 //! `simd` feature is enabled, otherwise fall back to [`std`] equivalents.
-use std::hint::unreachable_unchecked;
 
-use crate::proto::ProtocolError;
+use crate::{
+    proto::ProtocolError,
+    unchecked::{self, unchecked},
+};
 
 /// Checks if the passed byte sequence is valid UTF-8 and returns an error if it
 /// isn't.
@@ -61,7 +63,7 @@ impl Validator {
     #[inline]
     fn complete_codepoint_len(&self) -> usize {
         // SAFETY: This is guaranteed to be four bytes large
-        match unsafe { self.partial_codepoint.get_unchecked(0) } {
+        match unchecked! { unchecked::get(&self.partial_codepoint, 0) } {
             // 0b0xxxxxxx (single-byte code point)
             0b0000_0000..=0b0111_1111 => 1,
             // 0b110xxxxx (two-byte code point)
@@ -73,7 +75,7 @@ impl Validator {
             // Invalid first byte.
             // SAFETY: The first byte must be valid UTF-8, otherwise from_str would return
             // a FromUtf8Error with error_len() that is Some(_)
-            _ => unsafe { unreachable_unchecked() },
+            _ => unchecked! { unchecked::unreachable() },
         }
     }
 
@@ -104,26 +106,26 @@ impl Validator {
             let codepoint_len_after_copy = self.partial_codepoint_len + bytes_to_copy;
 
             // Copy the missing codepoint bytes to the partial codepoint
-            unsafe {
-                self.partial_codepoint
-                    .get_unchecked_mut(self.partial_codepoint_len..codepoint_len_after_copy)
-                    .copy_from_slice(input.get_unchecked(..bytes_to_copy));
+            unchecked! {
+                unchecked::get_mut(
+                    &mut self.partial_codepoint,
+                    self.partial_codepoint_len..codepoint_len_after_copy,
+                )
+                .copy_from_slice(unchecked::get(input, ..bytes_to_copy));
             }
 
             // If we know that the codepoint is complete, we can use the basic variant
             if available_bytes >= missing_bytes {
-                if FROM_UTF8_BASIC(unsafe {
-                    self.partial_codepoint
-                        .get_unchecked(..codepoint_len_after_copy)
+                if FROM_UTF8_BASIC(unchecked! {
+                    unchecked::get(&self.partial_codepoint, ..codepoint_len_after_copy)
                 })
                 .is_err()
                 {
                     return Err(ProtocolError::InvalidUtf8);
                 }
             } else {
-                match FROM_UTF8_COMPAT(unsafe {
-                    self.partial_codepoint
-                        .get_unchecked(..codepoint_len_after_copy)
+                match FROM_UTF8_COMPAT(unchecked! {
+                    unchecked::get(&self.partial_codepoint, ..codepoint_len_after_copy)
                 }) {
                     Ok(_) => {}
                     Err(utf8_error) if utf8_error.error_len().is_some() => {
@@ -143,7 +145,7 @@ impl Validator {
 
             self.reset();
 
-            unsafe { input.get_unchecked(bytes_to_copy..) }
+            unchecked! { unchecked::get(input, bytes_to_copy..) }
         };
 
         // Validate the entire rest of the input
@@ -163,12 +165,15 @@ impl Validator {
                 Err(utf8_error) => {
                     // Incomplete input, copy the partial codepoints to the validator
                     self.partial_codepoint_len = remaining_bytes.len() - utf8_error.valid_up_to();
-                    unsafe {
-                        self.partial_codepoint
-                            .get_unchecked_mut(..self.partial_codepoint_len)
-                            .copy_from_slice(
-                                remaining_bytes.get_unchecked(utf8_error.valid_up_to()..),
-                            );
+                    unchecked! {
+                        unchecked::get_mut(
+                            &mut self.partial_codepoint,
+                            ..self.partial_codepoint_len,
+                        )
+                        .copy_from_slice(unchecked::get(
+                            remaining_bytes,
+                            utf8_error.valid_up_to()..,
+                        ));
                     }
 
                     Ok(())
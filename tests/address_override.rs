@@ -0,0 +1,31 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use futures_util::StreamExt;
+use http::Uri;
+use tokio::net::TcpListener;
+use tokio_websockets::{ClientBuilder, ServerBuilder};
+
+#[tokio::test]
+async fn test_connect_to_address() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut server = ServerBuilder::new().accept(stream).await.unwrap();
+
+        let msg = server.next().await.unwrap().unwrap();
+        server.send(msg).await.unwrap();
+    });
+
+    // The host does not resolve, so this only succeeds if the address is used
+    let (mut client, _) = ClientBuilder::from_uri(Uri::from_static("ws://example.invalid/"))
+        .address(addr)
+        .connect()
+        .await
+        .unwrap();
+
+    client.send_text("Hello").await.unwrap();
+    let msg = client.next().await.unwrap().unwrap();
+    assert_eq!(msg.as_text(), Some("Hello"));
+}
@@ -0,0 +1,246 @@
+//! Runs the client and server against the [Autobahn Testsuite] and asserts
+//! that all cases pass.
+//!
+//! The tests are ignored by default since they take several minutes and
+//! require `podman` (or the container runtime set via `AUTOBAHN_RUNTIME`) and
+//! the `crossbario/autobahn-testsuite` image. Run them with:
+//!
+//! ```sh
+//! cargo test --release --features client,server,fastrand,sha1_smol,deflate --test autobahn -- --ignored --test-threads 1
+//! ```
+//!
+//! The compression cases are only run with the `deflate` feature enabled.
+//!
+//! [Autobahn Testsuite]: https://github.com/crossbario/autobahn-testsuite
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+use futures_util::StreamExt;
+use http::Uri;
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_websockets::{ClientBuilder, Error, Limits, ServerBuilder};
+
+/// Name of the agent in the reports.
+const AGENT: &str = "tokio-websockets";
+
+/// Port of the fuzzing server.
+const FUZZING_SERVER_PORT: u16 = 9001;
+
+/// Image of the test suite.
+const IMAGE: &str = "crossbario/autobahn-testsuite";
+
+/// Behaviors of test cases that are considered a pass.
+const ALLOWED_BEHAVIOR: [&str; 3] = ["OK", "INFORMATIONAL", "UNIMPLEMENTED"];
+
+/// Returns the cases to exclude, the compression cases require the `deflate`
+/// feature.
+fn excluded_cases() -> Vec<&'static str> {
+    if cfg!(feature = "deflate") {
+        Vec::new()
+    } else {
+        vec!["12.*", "13.*"]
+    }
+}
+
+/// Returns the container runtime command.
+fn container_runtime() -> Command {
+    Command::new(env::var("AUTOBAHN_RUNTIME").unwrap_or_else(|_| String::from("podman")))
+}
+
+/// Creates an empty working directory for the test suite with `config` as
+/// its configuration file.
+fn prepare_dir(name: &str, config: &Value) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("reports")).unwrap();
+    fs::write(dir.join("config.json"), config.to_string()).unwrap();
+
+    dir
+}
+
+/// Starts the test suite in `mode`, with `dir` mounted as its working
+/// directory.
+fn start_suite(dir: &Path, mode: &str, name: &str) -> Child {
+    container_runtime()
+        .args([
+            "run",
+            "--rm",
+            "--net",
+            "host",
+            "--security-opt",
+            "label=disable",
+        ])
+        .args(["--name", name, "-v"])
+        .arg(format!("{}:/autobahn", dir.display()))
+        .args([IMAGE, "wstest", "-m", mode, "-s", "/autobahn/config.json"])
+        .stdin(Stdio::null())
+        .spawn()
+        .expect("failed to start the container runtime")
+}
+
+/// Asserts that all cases in the report of the agent passed.
+fn assert_report(dir: &Path) {
+    let index = fs::read_to_string(dir.join("reports").join("index.json")).unwrap();
+    let index: Value = serde_json::from_str(&index).unwrap();
+    let cases = index[AGENT].as_object().expect("no report for the agent");
+
+    assert!(!cases.is_empty());
+
+    let failed: Vec<_> = cases
+        .iter()
+        .filter(|(_, result)| {
+            let passed =
+                |key: &str| ALLOWED_BEHAVIOR.contains(&result[key].as_str().unwrap_or_default());
+
+            !passed("behavior") || !passed("behaviorClose")
+        })
+        .map(|(case, result)| format!("{case}: {result}"))
+        .collect();
+
+    assert!(failed.is_empty(), "failed cases:\n{}", failed.join("\n"));
+}
+
+/// Echoes all data messages of a stream until it is closed.
+async fn echo<S>(mut stream: tokio_websockets::WebSocketStream<S>) -> Result<(), Error>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    while let Some(message) = stream.next().await {
+        let message = message?;
+
+        if message.is_text() || message.is_binary() {
+            stream.send(message).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Server driver, echoing messages of every accepted connection.
+async fn serve(listener: TcpListener) {
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(async move {
+            let server = ServerBuilder::new().limits(Limits::unlimited());
+            #[cfg(feature = "deflate")]
+            let server = server.extension(tokio_websockets::deflate::PerMessageDeflate::new());
+
+            if let Ok(stream) = server.accept(stream).await {
+                let _ = echo(stream).await;
+            }
+        });
+    }
+}
+
+/// Creates a client for a path of the fuzzing server.
+fn client(path: &str) -> ClientBuilder<'static> {
+    let uri = format!("ws://127.0.0.1:{FUZZING_SERVER_PORT}{path}");
+    let client = ClientBuilder::from_uri(uri.parse::<Uri>().unwrap()).limits(Limits::unlimited());
+    #[cfg(feature = "deflate")]
+    let client = client.extension(tokio_websockets::deflate::PerMessageDeflate::new());
+
+    client
+}
+
+/// Client driver, running all cases of the fuzzing server.
+async fn run_cases() -> Result<(), Error> {
+    let (mut stream, _) = client("/getCaseCount").connect().await?;
+    let count: u32 = stream
+        .next()
+        .await
+        .unwrap()?
+        .as_text()
+        .unwrap()
+        .parse()
+        .unwrap();
+    stream.close().await?;
+
+    for case in 1..=count {
+        // Failing cases are reported by the test suite
+        if let Ok((stream, _)) = client(&format!("/runCase?case={case}&agent={AGENT}"))
+            .connect()
+            .await
+        {
+            let _ = echo(stream).await;
+        }
+    }
+
+    let (mut stream, _) = client(&format!("/updateReports?agent={AGENT}"))
+        .connect()
+        .await?;
+    stream.close().await?;
+    while stream.next().await.is_some() {}
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires a container runtime and the Autobahn Testsuite image"]
+async fn test_server() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(serve(listener));
+
+    let dir = prepare_dir(
+        "autobahn-server",
+        &json!({
+            "outdir": "/autobahn/reports",
+            "servers": [{ "agent": AGENT, "url": format!("ws://127.0.0.1:{port}") }],
+            "cases": ["*"],
+            "exclude-cases": excluded_cases(),
+            "exclude-agent-cases": {},
+        }),
+    );
+
+    let mut suite = start_suite(&dir, "fuzzingclient", "tokio-websockets-autobahn-server");
+    let status = tokio::task::spawn_blocking(move || suite.wait())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(status.success());
+
+    assert_report(&dir);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires a container runtime and the Autobahn Testsuite image"]
+async fn test_client() {
+    let dir = prepare_dir(
+        "autobahn-client",
+        &json!({
+            "url": format!("ws://127.0.0.1:{FUZZING_SERVER_PORT}"),
+            "outdir": "/autobahn/reports",
+            "cases": ["*"],
+            "exclude-cases": excluded_cases(),
+            "exclude-agent-cases": {},
+        }),
+    );
+
+    let name = "tokio-websockets-autobahn-client";
+    let mut suite = start_suite(&dir, "fuzzingserver", name);
+
+    // Wait for the fuzzing server to come up
+    let mut attempts = 0;
+    while TcpStream::connect(("127.0.0.1", FUZZING_SERVER_PORT))
+        .await
+        .is_err()
+    {
+        attempts += 1;
+        assert!(attempts < 300, "fuzzing server did not start");
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    let result = run_cases().await;
+
+    let _ = container_runtime().args(["stop", name]).status();
+    let _ = suite.wait();
+
+    result.unwrap();
+    assert_report(&dir);
+}
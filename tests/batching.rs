@@ -0,0 +1,131 @@
+// Feeding many messages and flushing once gathers the queued frames into
+// vectored writes. Make sure frames survive being split at arbitrary points
+// across partial writes.
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_util::{stream, StreamExt};
+use tokio::io::{duplex, AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+use tokio_websockets::{
+    proto::{Frame, OpCode, ProtocolError, RSV1},
+    ClientBuilder, Error, Message, ServerBuilder, WebSocketStream,
+};
+
+/// A vectored writer that accepts at most 7 bytes per write.
+struct Trickle(DuplexStream);
+
+impl AsyncRead for Trickle {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Trickle {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let len = buf.len().min(7);
+        Pin::new(&mut self.0).poll_write(cx, &buf[..len])
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<Result<usize, io::Error>> {
+        let buf: Vec<u8> = bufs
+            .iter()
+            .flat_map(|b| b.iter().copied())
+            .take(7)
+            .collect();
+        Pin::new(&mut self.0).poll_write(cx, &buf)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), io::Error>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+#[tokio::test]
+async fn test_feed_and_flush() {
+    let (one, two) = duplex(usize::MAX);
+    let mut client = ClientBuilder::new().take_over(Trickle(one));
+    let mut server = ServerBuilder::new().serve(two);
+
+    for i in 0..100 {
+        client.feed(i.to_string()).await.unwrap();
+    }
+    client.flush().await.unwrap();
+
+    for i in 0..100 {
+        let msg = server.next().await.unwrap().unwrap();
+        assert_eq!(msg.as_text(), Some(i.to_string().as_str()));
+    }
+}
+
+#[tokio::test]
+async fn test_send_all() {
+    let (one, two) = duplex(usize::MAX);
+    let mut client = ClientBuilder::new().take_over(Trickle(one));
+    let mut server = ServerBuilder::new().serve(two);
+
+    let mut messages = stream::iter((0..100).map(|i| i.to_string()));
+    client.send_all(&mut messages).await.unwrap();
+
+    for i in 0..100 {
+        let msg = server.next().await.unwrap().unwrap();
+        assert_eq!(msg.as_text(), Some(i.to_string().as_str()));
+    }
+}
+
+#[tokio::test]
+async fn test_decode_batch() {
+    let (mut client, mut server) = WebSocketStream::pair();
+
+    client.feed(Message::ping("ping")).await.unwrap();
+    for i in 0..100 {
+        client.feed(i.to_string()).await.unwrap();
+    }
+    client
+        .feed_frame(Frame::new(OpCode::Text, true, RSV1, "invalid"))
+        .await
+        .unwrap();
+    client.flush().await.unwrap();
+
+    // The frames decoded in one pass are returned in order, up to the error
+    assert!(server.next().await.unwrap().unwrap().is_ping());
+    for i in 0..100 {
+        let msg = server.next().await.unwrap().unwrap();
+        assert_eq!(msg.as_text(), Some(i.to_string().as_str()));
+    }
+    assert!(matches!(
+        server.next().await,
+        Some(Err(Error::Protocol(ProtocolError::InvalidRsv)))
+    ));
+    assert!(server.next().await.is_none());
+
+    // The ping was answered as usual
+    assert!(client.next().await.unwrap().unwrap().is_pong());
+}
@@ -0,0 +1,69 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bytes::BytesMut;
+use futures_util::StreamExt;
+use tokio_websockets::{
+    proto::{BoundedPool, BufferPool},
+    Config, Limits, Message, WebSocketStream,
+};
+
+#[tokio::test]
+async fn test_recycle_read_buffers() {
+    static POOL: BoundedPool = BoundedPool::new(16);
+    let config = Config::default().buffer_pool(&POOL);
+
+    let (mut client, mut server) = WebSocketStream::pair_with_config(config, Limits::default());
+    client.send(Message::text("hello")).await.unwrap();
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("hello"));
+    assert!(POOL.is_empty());
+
+    drop(client);
+    drop(server);
+    assert_eq!(POOL.len(), 2);
+
+    // New connections reuse the released buffers
+    let (_client, _server) = WebSocketStream::pair_with_config(config, Limits::default());
+    assert!(POOL.is_empty());
+}
+
+/// Counts the buffers acquired from and released to it.
+struct CountingPool {
+    acquired: AtomicUsize,
+    released: AtomicUsize,
+}
+
+impl BufferPool for CountingPool {
+    fn acquire(&self, capacity: usize) -> BytesMut {
+        self.acquired.fetch_add(1, Ordering::Relaxed);
+
+        BytesMut::with_capacity(capacity)
+    }
+
+    fn release(&self, _: BytesMut) {
+        self.released.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[tokio::test]
+async fn test_fragmented_message() {
+    static POOL: CountingPool = CountingPool {
+        acquired: AtomicUsize::new(0),
+        released: AtomicUsize::new(0),
+    };
+    let config = Config::default().frame_size(4).buffer_pool(&POOL);
+
+    let (mut client, mut server) = WebSocketStream::pair_with_config(config, Limits::default());
+    assert_eq!(POOL.acquired.load(Ordering::Relaxed), 2);
+
+    client.send(Message::text("fragmented")).await.unwrap();
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("fragmented"));
+    assert_eq!(POOL.acquired.load(Ordering::Relaxed), 3);
+
+    drop(client);
+    drop(server);
+    assert_eq!(POOL.released.load(Ordering::Relaxed), 2);
+}
@@ -0,0 +1,44 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use futures_util::StreamExt;
+use tokio_util::sync::CancellationToken;
+use tokio_websockets::{CloseCode, Error, WebSocketStream};
+
+#[tokio::test]
+async fn test_cancel_read() {
+    let (mut client, mut server) = WebSocketStream::pair();
+    let token = CancellationToken::new();
+    server.set_cancellation_token(token.clone(), CloseCode::GOING_AWAY);
+
+    let reader = tokio::spawn(async move {
+        assert!(server.next().await.is_none());
+        assert!(matches!(
+            server.send_text("late").await,
+            Err(Error::AlreadyClosed)
+        ));
+    });
+
+    token.cancel();
+    reader.await.unwrap();
+
+    let message = client.next().await.unwrap().unwrap();
+    assert_eq!(
+        message.as_close().map(|(code, _)| code),
+        Some(CloseCode::GOING_AWAY)
+    );
+}
+
+#[tokio::test]
+async fn test_cancel_write() {
+    let (_client, mut server) = WebSocketStream::pair();
+    let token = CancellationToken::new();
+    server.set_cancellation_token(token.clone(), CloseCode::GOING_AWAY);
+
+    // The client never reads, so sending more than the buffer size stalls
+    let writer = tokio::spawn(async move { server.send_binary(vec![0; 1024 * 1024]).await });
+
+    tokio::task::yield_now().await;
+    token.cancel();
+
+    assert!(matches!(writer.await.unwrap(), Err(Error::AlreadyClosed)));
+}
@@ -0,0 +1,28 @@
+#!/bin/bash
+# Generates the certificates used by the TLS tests. The CA key is discarded.
+set -e
+cd "$(dirname "$0")"
+
+EXT=$(mktemp)
+trap 'rm -f "$EXT" ca.key ca.srl ./*.csr' EXIT
+printf "[dn]\n[req]\ndistinguished_name = dn\n\
+[ca]\nbasicConstraints = critical,CA:TRUE\nkeyUsage = critical,keyCertSign,cRLSign\n\
+[server]\nbasicConstraints = critical,CA:FALSE\nkeyUsage = critical,digitalSignature\nextendedKeyUsage = serverAuth\nsubjectAltName = DNS:localhost,IP:127.0.0.1\n\
+[client]\nbasicConstraints = critical,CA:FALSE\nkeyUsage = critical,digitalSignature\nextendedKeyUsage = clientAuth\nsubjectAltName = DNS:client.localhost\n\
+[selfsigned]\nbasicConstraints = critical,CA:FALSE\nkeyUsage = critical,digitalSignature\nextendedKeyUsage = serverAuth,clientAuth\nsubjectAltName = DNS:localhost,IP:127.0.0.1\n" > "$EXT"
+
+for name in ca server client selfsigned; do
+  openssl genpkey -algorithm EC -pkeyopt ec_paramgen_curve:P-256 -out "$name.key"
+done
+
+openssl req -x509 -new -key ca.key -subj '/CN=tokio-websockets test CA' -days 36500 -sha256 \
+  -config "$EXT" -extensions ca -out ca.pem
+
+for name in server client; do
+  openssl req -new -key "$name.key" -subj "/CN=$name" -config "$EXT" -out "$name.csr"
+  openssl x509 -req -in "$name.csr" -CA ca.pem -CAkey ca.key -CAcreateserial -days 36500 -sha256 \
+    -extfile "$EXT" -extensions "$name" -out "$name.pem"
+done
+
+openssl req -x509 -new -key selfsigned.key -subj '/CN=localhost' -days 36500 -sha256 \
+  -config "$EXT" -extensions selfsigned -out selfsigned.pem
@@ -0,0 +1,87 @@
+#![cfg(feature = "client")]
+
+use std::{net::SocketAddr, time::Duration};
+
+use http::Uri;
+use tokio::net::TcpListener;
+use tokio_websockets::{
+    client::{ConnectPhase, RetryPolicy},
+    resolver::Resolver,
+    ClientBuilder, Error,
+};
+
+/// Resolver that never resolves any hostname.
+struct FailingResolver;
+
+impl Resolver for FailingResolver {
+    async fn resolve(&self, _host: &str, _port: u16) -> Result<SocketAddr, Error> {
+        Err(Error::CannotResolveHost)
+    }
+}
+
+#[tokio::test]
+async fn test_upgrade_timeout() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // Accept the connection, but never answer the upgrade request
+    let server = tokio::spawn(async move { listener.accept().await.unwrap() });
+
+    let uri = Uri::try_from(format!("ws://{addr}")).unwrap();
+    let res = ClientBuilder::from_uri(uri)
+        .upgrade_timeout(Duration::from_millis(50))
+        .connect()
+        .await;
+
+    assert!(matches!(
+        res,
+        Err(Error::ConnectTimeout(ConnectPhase::Upgrade))
+    ));
+
+    drop(server.await.unwrap());
+}
+
+#[tokio::test]
+async fn test_overall_timeout() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // Accept the connection, but never answer the upgrade request
+    let server = tokio::spawn(async move { listener.accept().await.unwrap() });
+
+    let uri = Uri::try_from(format!("ws://{addr}")).unwrap();
+    let res = ClientBuilder::from_uri(uri)
+        .overall_timeout(Duration::from_millis(50))
+        .connect()
+        .await;
+
+    assert!(matches!(
+        res,
+        Err(Error::ConnectTimeout(ConnectPhase::Upgrade))
+    ));
+
+    drop(server.await.unwrap());
+}
+
+#[tokio::test]
+async fn test_overall_timeout_with_retries() {
+    // Without the overall timeout, retrying would take more than a minute
+    let policy = RetryPolicy::new()
+        .max_retries(u32::MAX)
+        .initial_backoff(Duration::from_millis(10))
+        .max_backoff(Duration::from_secs(60));
+
+    let res = ClientBuilder::new()
+        .uri("ws://timeout.test")
+        .unwrap()
+        .resolver(FailingResolver)
+        .retry_policy(Some(policy))
+        .overall_timeout(Duration::from_millis(100))
+        .connect()
+        .await;
+
+    assert!(matches!(
+        res,
+        Err(Error::ConnectTimeout(ConnectPhase::Resolve))
+    ));
+}
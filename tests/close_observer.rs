@@ -0,0 +1,197 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::sync::{Arc, Mutex};
+
+use futures_util::StreamExt;
+use tokio::io::DuplexStream;
+use tokio_websockets::{
+    proto::{CloseInitiator, Frame, OpCode},
+    CloseCode, Error, Message, WebSocketStream,
+};
+
+/// A close event as observed by [`observe`].
+#[derive(Debug, PartialEq)]
+struct Observed {
+    initiator: CloseInitiator,
+    code: Option<CloseCode>,
+    reason: String,
+    failed: bool,
+}
+
+/// Installs a close observer on `stream` that records every call.
+fn observe(stream: &mut WebSocketStream<DuplexStream>) -> Arc<Mutex<Vec<Observed>>> {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorder = Arc::clone(&events);
+
+    stream.set_close_observer(move |event| {
+        recorder.lock().unwrap().push(Observed {
+            initiator: event.initiator(),
+            code: event.code(),
+            reason: event.reason().to_owned(),
+            failed: event.error().is_some(),
+        });
+    });
+
+    events
+}
+
+#[tokio::test]
+async fn test_close_handshake() {
+    let (mut client, mut server) = WebSocketStream::pair();
+    let client_events = observe(&mut client);
+    let server_events = observe(&mut server);
+
+    client
+        .send(Message::close(Some(CloseCode::GOING_AWAY), "bye"))
+        .await
+        .unwrap();
+    assert!(server.next().await.unwrap().unwrap().is_close());
+    assert!(server_events.lock().unwrap().is_empty());
+    assert!(server.next().await.is_none());
+    assert!(client.next().await.unwrap().unwrap().is_close());
+    assert!(client.next().await.is_none());
+
+    // Polling the closed streams again does not call the observers again
+    assert!(server.next().await.is_none());
+    assert!(client.next().await.is_none());
+
+    assert_eq!(
+        *client_events.lock().unwrap(),
+        [Observed {
+            initiator: CloseInitiator::Us,
+            code: Some(CloseCode::GOING_AWAY),
+            reason: "bye".to_owned(),
+            failed: false,
+        }]
+    );
+    assert_eq!(
+        *server_events.lock().unwrap(),
+        [Observed {
+            initiator: CloseInitiator::Peer,
+            code: Some(CloseCode::GOING_AWAY),
+            reason: "bye".to_owned(),
+            failed: false,
+        }]
+    );
+}
+
+#[tokio::test]
+async fn test_protocol_error() {
+    let (mut client, mut server) = WebSocketStream::pair();
+    let events = observe(&mut server);
+
+    // Control frames must not be fragmented
+    client
+        .send_frame(Frame::new(OpCode::Ping, false, 0, "ping"))
+        .await
+        .unwrap();
+    assert!(matches!(server.next().await, Some(Err(Error::Protocol(_)))));
+    assert!(server.next().await.is_none());
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].initiator, CloseInitiator::Us);
+    assert_eq!(events[0].code, Some(CloseCode::PROTOCOL_ERROR));
+    assert!(events[0].failed);
+}
+
+#[tokio::test]
+async fn test_peer_gone() {
+    let (client, mut server) = WebSocketStream::pair();
+    let events = observe(&mut server);
+
+    drop(client);
+    assert!(server.next().await.is_none());
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        [Observed {
+            initiator: CloseInitiator::Peer,
+            code: None,
+            reason: String::new(),
+            failed: false,
+        }]
+    );
+}
+
+#[tokio::test]
+async fn test_no_status() {
+    let (mut client, mut server) = WebSocketStream::pair();
+    let events = observe(&mut client);
+
+    client.send(Message::close(None, "")).await.unwrap();
+    assert!(server.next().await.unwrap().unwrap().is_close());
+    assert!(server.next().await.is_none());
+    assert!(client.next().await.unwrap().unwrap().is_close());
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].initiator, CloseInitiator::Us);
+    assert_eq!(events[0].code, None);
+}
+
+#[tokio::test]
+async fn test_connection_id() {
+    let (mut client, server) = WebSocketStream::pair();
+    let ids = Arc::new(Mutex::new(Vec::new()));
+    let recorder = Arc::clone(&ids);
+    client.set_close_observer(move |event| recorder.lock().unwrap().push(event.connection_id()));
+
+    drop(server);
+    assert!(client.next().await.is_none());
+
+    assert_eq!(*ids.lock().unwrap(), [client.id()]);
+}
+
+#[tokio::test]
+async fn test_write_error() {
+    let (mut client, server) = WebSocketStream::pair();
+    let events = observe(&mut client);
+
+    drop(server);
+    assert!(matches!(client.send_text("lost").await, Err(Error::Io(_))));
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        [Observed {
+            initiator: CloseInitiator::Us,
+            code: None,
+            reason: String::new(),
+            failed: true,
+        }]
+    );
+}
+
+#[tokio::test]
+async fn test_dropped() {
+    let (mut client, mut server) = WebSocketStream::pair();
+    let client_events = observe(&mut client);
+    let server_events = observe(&mut server);
+
+    client
+        .send(Message::close(Some(CloseCode::GOING_AWAY), "bye"))
+        .await
+        .unwrap();
+    assert!(server.next().await.unwrap().unwrap().is_close());
+    drop(client);
+    drop(server);
+
+    assert_eq!(
+        *client_events.lock().unwrap(),
+        [Observed {
+            initiator: CloseInitiator::Us,
+            code: Some(CloseCode::GOING_AWAY),
+            reason: "bye".to_owned(),
+            failed: false,
+        }]
+    );
+    assert_eq!(
+        *server_events.lock().unwrap(),
+        [Observed {
+            initiator: CloseInitiator::Peer,
+            code: Some(CloseCode::GOING_AWAY),
+            reason: "bye".to_owned(),
+            failed: false,
+        }]
+    );
+}
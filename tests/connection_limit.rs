@@ -0,0 +1,61 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use http::Uri;
+use tokio::io::{duplex, DuplexStream};
+use tokio_websockets::{ClientBuilder, ServerBuilder};
+
+/// Starts a client handshake on a new in-memory stream and returns the server
+/// end of it.
+fn connect() -> DuplexStream {
+    let (one, two) = duplex(usize::MAX);
+
+    tokio::spawn(async move {
+        let (client, _) = ClientBuilder::from_uri(Uri::from_static("ws://localhost"))
+            .connect_on(two)
+            .await
+            .unwrap();
+
+        // Keep the connection open
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        drop(client);
+    });
+
+    one
+}
+
+#[tokio::test]
+async fn test_max_connections() {
+    let queued = Arc::new(AtomicUsize::new(0));
+    let queued_hook = queued.clone();
+
+    let builder = Arc::new(
+        ServerBuilder::new()
+            .max_connections(1)
+            .on_connection_queued(move || {
+                queued_hook.fetch_add(1, Ordering::Relaxed);
+            }),
+    );
+
+    let first = builder.accept(connect()).await.unwrap();
+
+    let second = tokio::spawn({
+        let builder = builder.clone();
+        async move { builder.accept(connect()).await.unwrap() }
+    });
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!second.is_finished());
+    assert_eq!(queued.load(Ordering::Relaxed), 1);
+
+    drop(first);
+
+    second.await.unwrap();
+}
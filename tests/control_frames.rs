@@ -0,0 +1,159 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::DuplexStream;
+use tokio_websockets::{
+    proto::{CloseReason, PingPayload, ProtocolError},
+    CloseCode, Config, Limits, Message, WebSocketStream,
+};
+
+/// Size of the message that is large enough to stall writing to the pair.
+const LARGE: usize = 256 * 1024;
+
+/// Flushes `client` while receiving messages on `server` until `count` were
+/// received, returning them in order.
+async fn exchange(
+    client: &mut WebSocketStream<DuplexStream>,
+    server: &mut WebSocketStream<DuplexStream>,
+    count: usize,
+) -> Vec<Message> {
+    let receive = async {
+        let mut messages = Vec::new();
+
+        while messages.len() < count {
+            messages.push(server.next().await.unwrap().unwrap());
+        }
+
+        messages
+    };
+
+    let (flushed, messages) = tokio::join!(client.flush(), receive);
+    flushed.unwrap();
+
+    messages
+}
+
+#[tokio::test]
+async fn test_ping_overtakes_large_message() {
+    let config = Config::default().frame_size(4096);
+    let (mut client, mut server) = WebSocketStream::pair_with_config(config, Limits::unlimited());
+
+    client.feed(Message::binary(vec![0; LARGE])).await.unwrap();
+    client.start_send_unpin(Message::ping("keepalive")).unwrap();
+
+    let messages = exchange(&mut client, &mut server, 2).await;
+
+    // The ping is sent in between the frames of the message
+    assert!(messages[0].is_ping());
+    assert_eq!(&**messages[0].as_payload(), b"keepalive");
+    assert!(messages[1].is_binary());
+    assert_eq!(messages[1].as_payload().len(), LARGE);
+}
+
+#[tokio::test]
+async fn test_pings_keep_their_order() {
+    let config = Config::default().frame_size(4096);
+    let (mut client, mut server) = WebSocketStream::pair_with_config(config, Limits::unlimited());
+
+    client.feed(Message::binary(vec![0; LARGE])).await.unwrap();
+    client.start_send_unpin(Message::ping("one")).unwrap();
+    client.start_send_unpin(Message::ping("two")).unwrap();
+
+    let messages = exchange(&mut client, &mut server, 3).await;
+
+    assert_eq!(&**messages[0].as_payload(), b"one");
+    assert_eq!(&**messages[1].as_payload(), b"two");
+    assert!(messages[2].is_binary());
+}
+
+#[tokio::test]
+async fn test_close_waits_for_large_message() {
+    let config = Config::default().frame_size(4096);
+    let (mut client, mut server) = WebSocketStream::pair_with_config(config, Limits::unlimited());
+
+    client.feed(Message::binary(vec![0; LARGE])).await.unwrap();
+    client
+        .start_send_unpin(Message::close(Some(CloseCode::NORMAL_CLOSURE), ""))
+        .unwrap();
+
+    let messages = exchange(&mut client, &mut server, 2).await;
+
+    // No data frames may follow a close frame, so it does not skip the queue
+    assert!(messages[0].is_binary());
+    assert!(messages[1].is_close());
+}
+
+#[tokio::test]
+async fn test_drop_unsolicited_pongs() {
+    let config = Config::default().drop_unsolicited_pongs(true);
+    let (mut client, mut server) = WebSocketStream::pair_with_config(config, Limits::default());
+
+    server.send(Message::pong("heartbeat")).await.unwrap();
+    client.send(Message::ping("probe")).await.unwrap();
+    assert!(client.rtt().is_none());
+
+    // The server answers the ping while receiving it
+    assert!(server.next().await.unwrap().unwrap().is_ping());
+    server.send(Message::text("done")).await.unwrap();
+
+    // Only the pong answering the ping is returned
+    let pong = client.next().await.unwrap().unwrap();
+    assert!(pong.is_pong());
+    assert_eq!(&**pong.as_payload(), b"probe");
+    assert!(client.rtt().is_some());
+    assert!(client.next().await.unwrap().unwrap().is_text());
+
+    // Pongs are matched only once
+    server.send(Message::pong("probe")).await.unwrap();
+    server.send(Message::text("done")).await.unwrap();
+    assert!(client.next().await.unwrap().unwrap().is_text());
+}
+
+#[tokio::test]
+async fn test_unsolicited_pongs_returned_by_default() {
+    let (mut client, mut server) = WebSocketStream::pair();
+
+    server.send(Message::pong("heartbeat")).await.unwrap();
+
+    let pong = client.next().await.unwrap().unwrap();
+    assert!(pong.is_pong());
+    assert_eq!(&**pong.as_payload(), b"heartbeat");
+    assert!(client.rtt().is_none());
+}
+
+#[tokio::test]
+async fn test_validated_control_payloads() {
+    assert!(matches!(
+        PingPayload::new(vec![0; 126]),
+        Err(ProtocolError::InvalidPayloadLength)
+    ));
+    assert!(matches!(
+        CloseReason::new(&"a".repeat(124)),
+        Err(ProtocolError::InvalidPayloadLength)
+    ));
+    assert!(matches!(
+        CloseReason::from_utf8(b"\xff"),
+        Err(ProtocolError::InvalidUtf8)
+    ));
+
+    let (mut client, mut server) = WebSocketStream::pair();
+    let payload = PingPayload::new(vec![7; 125]).unwrap();
+    let reason = CloseReason::new(&"a".repeat(123)).unwrap();
+
+    client.send(Message::ping(payload)).await.unwrap();
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.as_payload().len(), 125);
+
+    client
+        .send(Message::close(
+            Some(CloseCode::NORMAL_CLOSURE),
+            reason.as_str(),
+        ))
+        .await
+        .unwrap();
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(
+        message.as_close(),
+        Some((CloseCode::NORMAL_CLOSURE, reason.as_str()))
+    );
+}
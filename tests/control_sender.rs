@@ -0,0 +1,67 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use futures_util::StreamExt;
+use tokio::io::AsyncWriteExt;
+use tokio_websockets::{CloseCode, Error, WebSocketStream};
+
+#[tokio::test]
+async fn test_ping_while_streaming() {
+    let (mut client, mut server) = WebSocketStream::pair();
+    let control = client.control_sender();
+    let (mut writer, reader) = tokio::io::duplex(1024);
+
+    let sending = tokio::spawn(async move {
+        client.send_binary_reader(reader, 4).await.unwrap();
+        client
+    });
+
+    writer.write_all(b"firs").await.unwrap();
+    control.ping("keepalive").unwrap();
+    writer.write_all(b"t second").await.unwrap();
+    drop(writer);
+
+    // The ping is sent in between the frames of the message
+    let ping = server.next().await.unwrap().unwrap();
+    assert!(ping.is_ping());
+    assert_eq!(&**ping.as_payload(), b"keepalive");
+
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(&**message.as_payload(), b"first second");
+
+    sending.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_close_while_streaming() {
+    let (mut client, mut server) = WebSocketStream::pair();
+    let control = client.control_sender();
+    let (mut writer, reader) = tokio::io::duplex(1024);
+
+    let sending = tokio::spawn(async move { client.send_binary_reader(reader, 4).await });
+
+    writer.write_all(b"firs").await.unwrap();
+    control
+        .close(Some(CloseCode::GOING_AWAY), "shutting down")
+        .unwrap();
+    assert!(matches!(control.ping(""), Err(Error::AlreadyClosed)));
+    writer.write_all(b"t second").await.unwrap();
+
+    // The message is cut short by the close frame
+    assert!(matches!(sending.await.unwrap(), Err(Error::AlreadyClosed)));
+
+    let close = server.next().await.unwrap().unwrap();
+    assert_eq!(
+        close.as_close().map(|(code, _)| code),
+        Some(CloseCode::GOING_AWAY)
+    );
+}
+
+#[tokio::test]
+async fn test_stream_dropped() {
+    let (mut client, _server) = WebSocketStream::pair();
+    let control = client.control_sender();
+    control.pong("").unwrap();
+
+    drop(client);
+    assert!(matches!(control.ping(""), Err(Error::AlreadyClosed)));
+}
@@ -0,0 +1,40 @@
+#![cfg(feature = "client")]
+
+use std::{sync::Arc, time::Duration};
+
+use http::{HeaderValue, Uri};
+use tokio::io::{duplex, AsyncReadExt};
+use tokio_websockets::{cookie::CookieStore, ClientBuilder};
+
+/// A cookie store that always returns the same cookie.
+struct StaticStore;
+
+impl CookieStore for StaticStore {
+    fn set_cookies(&self, _cookies: &mut dyn Iterator<Item = &HeaderValue>, _uri: &Uri) {}
+
+    fn cookies(&self, _uri: &Uri) -> Option<HeaderValue> {
+        Some(HeaderValue::from_static("session=abc"))
+    }
+}
+
+#[tokio::test]
+async fn test_cookies_sent() {
+    let (one, mut two) = duplex(usize::MAX);
+
+    // No response is ever sent, the request is all we are interested in
+    let res = ClientBuilder::from_uri(Uri::from_static("ws://localhost"))
+        .add_cookie("theme", "dark")
+        .unwrap()
+        .add_cookie("lang", "en")
+        .unwrap()
+        .cookie_store(Arc::new(StaticStore))
+        .upgrade_timeout(Duration::from_millis(50))
+        .connect_on(one)
+        .await;
+    assert!(res.is_err());
+
+    let mut request = String::new();
+    two.read_to_string(&mut request).await.unwrap();
+
+    assert!(request.contains("\r\ncookie: theme=dark; lang=en; session=abc\r\n"));
+}
@@ -0,0 +1,394 @@
+#![cfg(all(feature = "client", feature = "server", feature = "deflate"))]
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use futures_util::StreamExt;
+use http::{header::SEC_WEBSOCKET_EXTENSIONS, Uri};
+use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+use tokio_websockets::{
+    deflate::{Compressor, Decompressor, DeflateBackend, DeflateFrame, Flate2, PerMessageDeflate},
+    proto::RSV1,
+    upgrade::extensions::{ClientExtension, ServerExtension},
+    ClientBuilder, CloseCode, Config, Error, Message, ServerBuilder,
+};
+
+/// Connects a client and a server with the given extension configurations,
+/// echoes a few messages through the server and returns the extension header
+/// of the handshake response.
+async fn echo(
+    client: impl ClientExtension + 'static,
+    server: impl ServerExtension + 'static,
+) -> Option<String> {
+    let (one, two) = duplex(usize::MAX);
+
+    let server = tokio::spawn(async move {
+        let mut server = ServerBuilder::new()
+            .extension(server)
+            .accept(one)
+            .await
+            .unwrap();
+
+        while let Some(Ok(message)) = server.next().await {
+            if message.is_close() {
+                break;
+            }
+
+            server.send(message).await.unwrap();
+        }
+    });
+
+    let (mut client, response) = ClientBuilder::from_uri(Uri::from_static("ws://localhost"))
+        .extension(client)
+        .connect_on(two)
+        .await
+        .unwrap();
+
+    let large = "tokio-websockets ".repeat(64 * 1024);
+    let messages = [
+        Message::text("hello"),
+        Message::text("hello"),
+        Message::binary(vec![0; 1024]),
+        Message::text(""),
+        Message::text(large),
+    ];
+
+    for message in messages {
+        client.send(message.clone()).await.unwrap();
+        let echoed = client.next().await.unwrap().unwrap();
+
+        assert_eq!(&echoed.as_payload()[..], &message.as_payload()[..]);
+        assert_eq!(echoed.is_text(), message.is_text());
+    }
+
+    client.close().await.unwrap();
+    server.await.unwrap();
+
+    response
+        .headers()
+        .get(SEC_WEBSOCKET_EXTENSIONS)
+        .map(|value| value.to_str().unwrap().to_owned())
+}
+
+#[tokio::test]
+async fn test_default() {
+    let response = echo(PerMessageDeflate::new(), PerMessageDeflate::new()).await;
+
+    assert_eq!(response.as_deref(), Some("permessage-deflate"));
+}
+
+#[tokio::test]
+async fn test_tuned() {
+    let client = PerMessageDeflate::new()
+        .compression_level(9)
+        .client_max_window_bits(10)
+        .server_no_context_takeover(true);
+    let server = PerMessageDeflate::new()
+        .compression_level(1)
+        .server_max_window_bits(9)
+        .client_no_context_takeover(true);
+
+    let response = echo(client, server).await;
+
+    assert_eq!(
+        response.as_deref(),
+        Some(
+            "permessage-deflate; server_no_context_takeover; client_no_context_takeover; \
+             server_max_window_bits=9; client_max_window_bits=10"
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_compression_threshold() {
+    let (mut client, server) = duplex(usize::MAX);
+    client
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\nSec-WebSocket-Extensions: permessage-deflate\r\n\r\n")
+        .await
+        .unwrap();
+
+    let mut server = ServerBuilder::new()
+        .extension(PerMessageDeflate::new().compression_threshold(64))
+        .accept(server)
+        .await
+        .unwrap();
+
+    // Read the response headers
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        response.push(client.read_u8().await.unwrap());
+    }
+
+    // Messages below the threshold are sent as is
+    let info = server.send_with_info(Message::text("small")).await.unwrap();
+    assert!(!info.compressed());
+    let mut frame = [0; 7];
+    client.read_exact(&mut frame).await.unwrap();
+    assert_eq!(&frame, b"\x81\x05small");
+
+    // Larger messages are compressed and have RSV1 set
+    let info = server
+        .send_with_info(Message::text("a".repeat(1024)))
+        .await
+        .unwrap();
+    assert!(info.compressed());
+    let mut header = [0; 2];
+    client.read_exact(&mut header).await.unwrap();
+    assert_eq!(header[0], 0x80 | RSV1 | 0x1);
+    assert!(header[1] < 64);
+    assert_eq!(info.wire_bytes(), 2 + usize::from(header[1]));
+}
+
+#[tokio::test]
+async fn test_compression_stats() {
+    let (one, two) = duplex(usize::MAX);
+
+    let server = tokio::spawn(async move {
+        ServerBuilder::new()
+            .extension(PerMessageDeflate::new().compression_threshold(64))
+            .accept(one)
+            .await
+            .unwrap()
+    });
+
+    let (mut client, _) = ClientBuilder::from_uri(Uri::from_static("ws://localhost"))
+        .extension(PerMessageDeflate::new())
+        .connect_on(two)
+        .await
+        .unwrap();
+    let mut server = server.await.unwrap();
+
+    let stats = server.compression_stats().unwrap();
+    assert_eq!(stats.sent_ratio(), None);
+
+    // Small messages count towards both sizes
+    server.send(Message::text("small")).await.unwrap();
+    server.send(Message::text("a".repeat(4096))).await.unwrap();
+    client.next().await.unwrap().unwrap();
+    client.next().await.unwrap().unwrap();
+
+    let stats = server.compression_stats().unwrap();
+    assert_eq!(stats.sent_uncompressed(), 4101);
+    assert!(stats.sent_compressed() < 100);
+    assert!(stats.sent_ratio().unwrap() > 40.0);
+    assert_eq!(stats.received_uncompressed(), 0);
+
+    let stats = client.compression_stats().unwrap();
+    assert_eq!(stats.received_uncompressed(), 4101);
+    assert_eq!(
+        stats.received_compressed(),
+        server.compression_stats().unwrap().sent_compressed()
+    );
+
+    // Connections without compression have no statistics
+    let (mut plain, _) = tokio_websockets::WebSocketStream::pair();
+    assert!(plain.compression_stats().is_none());
+}
+
+#[tokio::test]
+async fn test_max_inflated_size() {
+    let (one, two) = duplex(usize::MAX);
+
+    let server = tokio::spawn(async move {
+        ServerBuilder::new()
+            .extension(PerMessageDeflate::new().max_inflated_size(Some(64 * 1024)))
+            .accept(one)
+            .await
+            .unwrap()
+    });
+
+    let (mut client, _) = ClientBuilder::from_uri(Uri::from_static("ws://localhost"))
+        .extension(PerMessageDeflate::new())
+        .connect_on(two)
+        .await
+        .unwrap();
+    let mut server = server.await.unwrap();
+
+    client
+        .send(Message::binary(vec![0; 64 * 1024]))
+        .await
+        .unwrap();
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.as_payload().len(), 64 * 1024);
+
+    // Compresses to about 1 KiB, far below the payload length limit
+    client
+        .send(Message::binary(vec![0; 1024 * 1024]))
+        .await
+        .unwrap();
+    assert!(matches!(
+        server.next().await,
+        Some(Err(Error::PayloadTooLong { max_len: 65536, .. }))
+    ));
+
+    // Polling the failed connection sends the close frame
+    assert!(server.next().await.is_none());
+    let message = client.next().await.unwrap().unwrap();
+    assert_eq!(
+        message.as_close().map(|(code, _)| code),
+        Some(CloseCode::MESSAGE_TOO_BIG)
+    );
+}
+
+/// Backend counting the messages compressed by the default backend.
+struct Counting(Arc<AtomicUsize>);
+
+/// Compressor of the [`Counting`] backend.
+struct CountingCompressor(Box<dyn Compressor>, Arc<AtomicUsize>);
+
+impl DeflateBackend for Counting {
+    fn compressor(&self, level: u32, window_bits: u8) -> Box<dyn Compressor> {
+        Box::new(CountingCompressor(
+            Flate2.compressor(level, window_bits),
+            self.0.clone(),
+        ))
+    }
+
+    fn decompressor(&self, window_bits: u8) -> Box<dyn Decompressor> {
+        Flate2.decompressor(window_bits)
+    }
+}
+
+impl Compressor for CountingCompressor {
+    fn compress(&mut self, input: &[u8], output: &mut Vec<u8>) -> Result<(), Error> {
+        self.1.fetch_add(1, Ordering::Relaxed);
+        self.0.compress(input, output)
+    }
+
+    fn reset(&mut self) {
+        self.0.reset();
+    }
+}
+
+#[tokio::test]
+async fn test_custom_backend() {
+    let client = Arc::new(AtomicUsize::new(0));
+    let server = Arc::new(AtomicUsize::new(0));
+
+    echo(
+        PerMessageDeflate::new().backend(Counting(client.clone())),
+        PerMessageDeflate::new().backend(Counting(server.clone())),
+    )
+    .await;
+
+    assert_eq!(client.load(Ordering::Relaxed), 5);
+    assert_eq!(server.load(Ordering::Relaxed), 5);
+}
+
+#[tokio::test]
+async fn test_deflate_frame() {
+    let response = echo(DeflateFrame::new(), DeflateFrame::new()).await;
+    assert_eq!(response.as_deref(), Some("x-webkit-deflate-frame"));
+
+    let response = echo(
+        DeflateFrame::new().no_context_takeover(true),
+        DeflateFrame::new().compression_threshold(64),
+    )
+    .await;
+    assert_eq!(response.as_deref(), Some("x-webkit-deflate-frame"));
+}
+
+#[tokio::test]
+async fn test_deflate_frame_fragmented() {
+    let (mut client, server) = duplex(usize::MAX);
+    client
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\nSec-WebSocket-Extensions: x-webkit-deflate-frame\r\n\r\n")
+        .await
+        .unwrap();
+
+    let mut server = ServerBuilder::new()
+        .config(Config::default().frame_size(4))
+        .extension(DeflateFrame::new().compression_threshold(4))
+        .accept(server)
+        .await
+        .unwrap();
+
+    // Read the response headers
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        response.push(client.read_u8().await.unwrap());
+    }
+
+    // Every frame of the message is compressed individually, except for the last
+    // one that is below the threshold
+    server.send(Message::text("abcdefghij")).await.unwrap();
+
+    for (opcode, compressed) in [(0x1, true), (0x0, true), (0x0, false)] {
+        let mut header = [0; 2];
+        client.read_exact(&mut header).await.unwrap();
+
+        let fin = if compressed { 0 } else { 0x80 };
+        let rsv = if compressed { RSV1 } else { 0 };
+        assert_eq!(header[0], fin | rsv | opcode);
+
+        let mut payload = vec![0; usize::from(header[1])];
+        client.read_exact(&mut payload).await.unwrap();
+    }
+}
+
+/// Backend counting the compressors and decompressors it creates.
+struct Allocating(Arc<AtomicUsize>, Arc<AtomicUsize>);
+
+impl DeflateBackend for Allocating {
+    fn compressor(&self, level: u32, window_bits: u8) -> Box<dyn Compressor> {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        Flate2.compressor(level, window_bits)
+    }
+
+    fn decompressor(&self, window_bits: u8) -> Box<dyn Decompressor> {
+        self.1.fetch_add(1, Ordering::Relaxed);
+        Flate2.decompressor(window_bits)
+    }
+}
+
+#[tokio::test]
+async fn test_release_contexts() {
+    let compressors = Arc::new(AtomicUsize::new(0));
+    let decompressors = Arc::new(AtomicUsize::new(0));
+
+    // Contexts are kept for the whole connection by default
+    echo(
+        PerMessageDeflate::new(),
+        PerMessageDeflate::new().backend(Allocating(compressors.clone(), decompressors.clone())),
+    )
+    .await;
+    assert_eq!(compressors.load(Ordering::Relaxed), 1);
+    assert_eq!(decompressors.load(Ordering::Relaxed), 1);
+
+    compressors.store(0, Ordering::Relaxed);
+    decompressors.store(0, Ordering::Relaxed);
+
+    // Released contexts are created again for each of the five messages
+    let response = echo(
+        PerMessageDeflate::new(),
+        PerMessageDeflate::new()
+            .backend(Allocating(compressors.clone(), decompressors.clone()))
+            .client_no_context_takeover(true)
+            .server_no_context_takeover(true)
+            .release_contexts(true),
+    )
+    .await;
+    assert_eq!(
+        response.as_deref(),
+        Some("permessage-deflate; server_no_context_takeover; client_no_context_takeover")
+    );
+    assert_eq!(compressors.load(Ordering::Relaxed), 5);
+    assert_eq!(decompressors.load(Ordering::Relaxed), 5);
+
+    compressors.store(0, Ordering::Relaxed);
+    decompressors.store(0, Ordering::Relaxed);
+
+    // The decompressor is released if only the client offers not to take over
+    // its context
+    echo(
+        PerMessageDeflate::new().client_no_context_takeover(true),
+        PerMessageDeflate::new()
+            .backend(Allocating(compressors.clone(), decompressors.clone()))
+            .release_contexts(true),
+    )
+    .await;
+    assert_eq!(compressors.load(Ordering::Relaxed), 1);
+    assert_eq!(decompressors.load(Ordering::Relaxed), 5);
+}
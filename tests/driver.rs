@@ -0,0 +1,118 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use tokio_websockets::{driver, CloseCode, Config, Error, Limits, Message, WebSocketStream};
+
+#[tokio::test]
+async fn test_driver() {
+    let (client, mut server) = WebSocketStream::pair();
+    let (sender, mut receiver) = driver::Builder::new().spawn(client);
+
+    // Senders are cloneable and can be moved to other tasks
+    let other = sender.clone();
+    tokio::spawn(async move { other.send("hello").await.unwrap() })
+        .await
+        .unwrap();
+
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("hello"));
+
+    server.send_text("world").await.unwrap();
+    let message = receiver.recv().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("world"));
+
+    // Dropping all senders closes the connection
+    drop(sender);
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(
+        message.as_close().map(|(code, _)| code),
+        Some(CloseCode::NORMAL_CLOSURE)
+    );
+    assert!(server.next().await.is_none());
+
+    let message = receiver.next().await.unwrap().unwrap();
+    assert!(message.is_close());
+    assert!(receiver.next().await.is_none());
+}
+
+#[tokio::test]
+async fn test_driver_close() {
+    let (client, mut server) = WebSocketStream::pair();
+    let (sender, mut receiver) = driver::Builder::new().channel_capacity(1).spawn(client);
+
+    sender
+        .close(Some(CloseCode::GOING_AWAY), "bye")
+        .await
+        .unwrap();
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.as_close(), Some((CloseCode::GOING_AWAY, "bye")));
+    assert!(server.next().await.is_none());
+
+    assert!(receiver.recv().await.unwrap().unwrap().is_close());
+    assert!(receiver.recv().await.is_none());
+    assert!(sender.is_closed());
+    assert!(matches!(
+        sender.send("late").await,
+        Err(Error::AlreadyClosed)
+    ));
+}
+
+#[tokio::test]
+async fn test_driver_keepalive() {
+    // The task polls the stream, which sends the pings configured for it
+    let config = Config::default().keepalive_interval(Some(Duration::from_millis(10)));
+    let (client, mut server) = WebSocketStream::pair_with_config(config, Limits::default());
+    let (_sender, _receiver) = driver::Builder::new().spawn(client);
+
+    let mut pings = 0;
+    while pings < 3 {
+        let message = server.next().await.unwrap().unwrap();
+        assert!(message.is_ping() || message.is_pong());
+        pings += usize::from(message.is_ping());
+    }
+}
+
+#[tokio::test]
+async fn test_driver_max_buffered_bytes() {
+    let (client, mut server) = WebSocketStream::pair();
+    let (_sender, mut receiver) = driver::Builder::new()
+        .max_buffered_bytes(Some(10))
+        .spawn(client);
+
+    server.send_text("aaaaaa").await.unwrap();
+    server.send_text("bbbbbb").await.unwrap();
+    server.send_text("c".repeat(20)).await.unwrap();
+    server.send(Message::ping("p")).await.unwrap();
+
+    // The task stops reading once the budget is used up, so the ping is not
+    // answered yet
+    assert!(
+        tokio::time::timeout(Duration::from_millis(100), server.next())
+            .await
+            .is_err()
+    );
+
+    for expected in ["aaaaaa", "bbbbbb", &"c".repeat(20)] {
+        let message = receiver.recv().await.unwrap().unwrap();
+        assert_eq!(message.as_text(), Some(expected));
+    }
+    assert!(receiver.recv().await.unwrap().unwrap().is_ping());
+
+    let message = server.next().await.unwrap().unwrap();
+    assert!(message.is_pong());
+}
+
+#[tokio::test]
+async fn test_driver_max_buffered_bytes_capped() {
+    // Limits beyond what a semaphore can hold are capped instead of panicking
+    let (client, mut server) = WebSocketStream::pair();
+    let (_sender, mut receiver) = driver::Builder::new()
+        .max_buffered_bytes(Some(usize::MAX))
+        .spawn(client);
+
+    server.send_text("hello").await.unwrap();
+    let message = receiver.recv().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("hello"));
+}
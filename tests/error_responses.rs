@@ -0,0 +1,44 @@
+#![cfg(feature = "server")]
+
+use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+use tokio_websockets::ServerBuilder;
+
+/// Sends `request` to the server and returns its response.
+async fn respond(request: &str) -> String {
+    let (mut client, server) = duplex(usize::MAX);
+    client.write_all(request.as_bytes()).await.unwrap();
+
+    assert!(ServerBuilder::new().accept(server).await.is_err());
+
+    let mut response = String::new();
+    client.read_to_string(&mut response).await.unwrap();
+
+    response
+}
+
+#[tokio::test]
+async fn test_method_not_allowed() {
+    let response = respond("POST / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n").await;
+
+    assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+    assert!(response.contains("\r\nAllow: GET\r\n"));
+}
+
+#[tokio::test]
+async fn test_upgrade_required() {
+    let response = respond("GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 8\r\n\r\n").await;
+
+    assert!(response.starts_with("HTTP/1.1 426 Upgrade Required\r\n"));
+    assert!(response.contains("\r\nSec-WebSocket-Version: 13\r\n"));
+}
+
+#[tokio::test]
+async fn test_bad_request() {
+    let response = respond("GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Version: 13\r\n\r\n").await;
+
+    assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(head.contains(&format!("\r\nContent-Length: {}\r\n", body.len())));
+    assert_eq!(body, "missing required header: Sec-WebSocket-Key");
+}
@@ -0,0 +1,201 @@
+#![cfg(feature = "server")]
+
+use futures_util::StreamExt;
+use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+use tokio_websockets::{
+    proto::{ExtensionCodec, RSV1},
+    upgrade::extensions::{Extension, ServerExtension},
+    Error, Payload, ServerBuilder,
+};
+
+/// Extension that flips all bits of message payloads with RSV1 set.
+struct Invert;
+
+impl ServerExtension for Invert {
+    fn negotiate(&self, offers: &[Extension]) -> Option<(Extension, Box<dyn ExtensionCodec>)> {
+        offers
+            .iter()
+            .find(|offer| offer.name() == "x-invert")
+            .map(|_| {
+                (
+                    Extension::new("x-invert").param("accepted", None::<String>),
+                    Box::new(InvertCodec) as Box<dyn ExtensionCodec>,
+                )
+            })
+    }
+}
+
+/// Codec of the [`Invert`] extension.
+struct InvertCodec;
+
+/// Flips all bits of a payload.
+fn invert(payload: &[u8]) -> Payload {
+    payload.iter().map(|byte| !byte).collect::<Vec<_>>().into()
+}
+
+impl ExtensionCodec for InvertCodec {
+    fn rsv_bits(&self) -> u8 {
+        RSV1
+    }
+
+    fn encode(&mut self, payload: Payload) -> Result<(Payload, u8), Error> {
+        Ok((invert(&payload), RSV1))
+    }
+
+    fn decode(&mut self, payload: Payload, rsv: u8) -> Result<Payload, Error> {
+        if rsv & RSV1 == 0 {
+            Ok(payload)
+        } else {
+            Ok(invert(&payload))
+        }
+    }
+}
+
+/// Handshake request offering the `x-invert` extension.
+const REQUEST: &[u8] = b"GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\nSec-WebSocket-Extensions: x-unknown, x-invert; foo=bar\r\n\r\n";
+
+#[tokio::test]
+async fn test_negotiate_extension() {
+    let (mut client, server) = duplex(usize::MAX);
+    client.write_all(REQUEST).await.unwrap();
+
+    let server = tokio::spawn(async move {
+        let mut server = ServerBuilder::new()
+            .extension(Invert)
+            .accept(server)
+            .await
+            .unwrap();
+
+        let message = server.next().await.unwrap().unwrap();
+        assert_eq!(message.as_text(), Some("hi"));
+
+        server.send(message).await.unwrap();
+    });
+
+    // Read the response headers
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        response.push(client.read_u8().await.unwrap());
+    }
+    let response = String::from_utf8(response).unwrap();
+    assert!(response.contains("\r\nSec-WebSocket-Extensions: x-invert; accepted\r\n"));
+
+    // Send a masked text frame with RSV1 set and an all-zero masking key
+    let payload = [!b'h', !b'i'];
+    client
+        .write_all(&[0x80 | RSV1 | 0x1, 0x80 | 2, 0, 0, 0, 0])
+        .await
+        .unwrap();
+    client.write_all(&payload).await.unwrap();
+
+    // The echoed frame is transformed again
+    let mut echoed = [0; 4];
+    client.read_exact(&mut echoed).await.unwrap();
+    assert_eq!(echoed, [0x80 | RSV1 | 0x1, 2, payload[0], payload[1]]);
+
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_unnegotiated_rsv() {
+    let (mut client, server) = duplex(usize::MAX);
+    client
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n")
+        .await
+        .unwrap();
+
+    let mut server = ServerBuilder::new()
+        .extension(Invert)
+        .accept(server)
+        .await
+        .unwrap();
+
+    client
+        .write_all(&[0x80 | RSV1 | 0x1, 0x80, 0, 0, 0, 0])
+        .await
+        .unwrap();
+
+    assert!(matches!(server.next().await, Some(Err(Error::Protocol(_)))));
+}
+
+#[tokio::test]
+async fn test_register_extension_between_messages() {
+    let (mut client, server) = duplex(usize::MAX);
+    client
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n")
+        .await
+        .unwrap();
+
+    let mut server = ServerBuilder::new().accept(server).await.unwrap();
+
+    // A plain text frame followed by one transformed by the extension that is
+    // only registered after the first one was received, in a single write
+    client
+        .write_all(&[
+            0x80 | 0x1,
+            0x80 | 2,
+            0,
+            0,
+            0,
+            0,
+            b'h',
+            b'i',
+            0x80 | RSV1 | 0x1,
+            0x80 | 2,
+            0,
+            0,
+            0,
+            0,
+            !b'h',
+            !b'i',
+        ])
+        .await
+        .unwrap();
+
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("hi"));
+
+    server.register_extension(Box::new(InvertCodec));
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("hi"));
+}
+
+#[cfg(feature = "client")]
+#[tokio::test]
+async fn test_register_extension() {
+    use tokio_websockets::{proto::ProtocolError, Message, WebSocketStream};
+
+    let (mut client, mut server) = WebSocketStream::pair();
+    client.register_extension(Box::new(InvertCodec));
+    server.register_extension(Box::new(InvertCodec));
+
+    client.send(Message::text("hi")).await.unwrap();
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("hi"));
+
+    server
+        .send(Message::binary(&b"\x00\xff"[..]))
+        .await
+        .unwrap();
+    let message = client.next().await.unwrap().unwrap();
+    assert_eq!(&message.as_payload()[..], b"\x00\xff");
+
+    // Without the extension, the peer rejects the RSV bits
+    let (mut client, mut server) = WebSocketStream::pair();
+    client.register_extension(Box::new(InvertCodec));
+
+    client.send(Message::text("hi")).await.unwrap();
+    assert!(matches!(
+        server.next().await,
+        Some(Err(Error::Protocol(ProtocolError::InvalidRsv)))
+    ));
+}
+
+#[cfg(feature = "client")]
+#[test]
+#[should_panic = "RSV bits are already reserved by another extension"]
+fn test_register_extension_conflict() {
+    let (mut client, _server) = tokio_websockets::WebSocketStream::pair();
+    client.register_extension(Box::new(InvertCodec));
+    client.register_extension(Box::new(InvertCodec));
+}
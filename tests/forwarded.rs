@@ -0,0 +1,135 @@
+#![cfg(feature = "server")]
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use http::{HeaderMap, HeaderValue};
+use tokio_websockets::upgrade::forwarded::{self, Node};
+
+/// Builds a header map from a list of header names and values.
+fn headers(headers: &[(&'static str, &'static str)]) -> HeaderMap {
+    let mut map = HeaderMap::new();
+
+    for (name, value) in headers {
+        map.append(*name, HeaderValue::from_static(value));
+    }
+
+    map
+}
+
+#[test]
+fn test_parse() {
+    let elements = forwarded::parse(
+        r#"for="_gazonk", For="[2001:db8:cafe::17]:4711";proto=https, for=192.0.2.60;proto=http;by=203.0.113.43;host="example.com""#,
+    )
+    .unwrap();
+
+    assert_eq!(elements.len(), 3);
+    assert_eq!(
+        elements[0].for_(),
+        Some(&Node::Obfuscated("_gazonk".to_owned()))
+    );
+    assert_eq!(
+        elements[1].for_(),
+        Some(&Node::Ip(
+            IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0xcafe, 0, 0, 0, 0, 0x17)),
+            Some(4711)
+        ))
+    );
+    assert_eq!(elements[1].proto(), Some("https"));
+    assert_eq!(
+        elements[2].for_().and_then(Node::ip),
+        Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 60)))
+    );
+    assert_eq!(
+        elements[2].by().and_then(Node::ip),
+        Some(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 43)))
+    );
+    assert_eq!(elements[2].host(), Some("example.com"));
+
+    assert_eq!(
+        forwarded::parse("for=unknown, ,for=\"192.0.2.1:_port\"").unwrap()[1].for_(),
+        Some(&Node::Ip(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)), None))
+    );
+    assert_eq!(
+        forwarded::parse("for=unknown").unwrap()[0].for_(),
+        Some(&Node::Unknown)
+    );
+    assert!(forwarded::parse("").unwrap().is_empty());
+}
+
+#[test]
+fn test_parse_invalid() {
+    assert_eq!(forwarded::parse("for").unwrap_err().offset(), 3);
+    assert_eq!(forwarded::parse("for=example.com").unwrap_err().offset(), 4);
+    assert_eq!(
+        forwarded::parse("for=\"unterminated").unwrap_err().offset(),
+        17
+    );
+    assert_eq!(forwarded::parse("for=1.2.3.4 x").unwrap_err().offset(), 12);
+}
+
+#[test]
+fn test_from_headers() {
+    // Forwarded takes precedence over X-Forwarded-*
+    let elements = forwarded::from_headers(&headers(&[
+        ("forwarded", "for=192.0.2.1"),
+        ("x-forwarded-for", "192.0.2.2"),
+        ("forwarded", "for=192.0.2.3"),
+    ]))
+    .unwrap();
+    assert_eq!(elements.len(), 2);
+    assert_eq!(
+        elements[1].for_().and_then(Node::ip),
+        Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 3)))
+    );
+
+    let elements = forwarded::from_headers(&headers(&[
+        ("x-forwarded-for", "192.0.2.1, 2001:db8::1"),
+        ("x-forwarded-for", "[2001:db8::2]:443"),
+        ("x-forwarded-proto", "https"),
+        ("x-forwarded-host", "example.com"),
+    ]))
+    .unwrap();
+    assert_eq!(elements.len(), 3);
+    assert_eq!(elements[0].proto(), Some("https"));
+    assert_eq!(elements[0].host(), Some("example.com"));
+    assert_eq!(
+        elements[1].for_().and_then(Node::ip),
+        Some("2001:db8::1".parse().unwrap())
+    );
+    assert_eq!(
+        elements[2].for_(),
+        Some(&Node::Ip("2001:db8::2".parse().unwrap(), Some(443)))
+    );
+    assert_eq!(elements[2].proto(), None);
+
+    assert_eq!(
+        forwarded::from_headers(&headers(&[("x-forwarded-for", "192.0.2.1,  example.com")]))
+            .unwrap_err()
+            .offset(),
+        12
+    );
+    assert!(forwarded::from_headers(&HeaderMap::new())
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_client_ip() {
+    let map = headers(&[("x-forwarded-for", "198.51.100.1, 192.0.2.1, 192.0.2.2")]);
+
+    // The first entry may have been forged by the client
+    assert_eq!(
+        forwarded::client_ip(&map, 1),
+        Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)))
+    );
+    assert_eq!(
+        forwarded::client_ip(&map, 2),
+        Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)))
+    );
+    assert_eq!(forwarded::client_ip(&map, 0), None);
+    assert_eq!(forwarded::client_ip(&map, 4), None);
+
+    let map = headers(&[("forwarded", "for=_hidden")]);
+    assert_eq!(forwarded::client_ip(&map, 1), None);
+}
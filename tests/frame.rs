@@ -0,0 +1,195 @@
+use bytes::BytesMut;
+use tokio_websockets::{
+    proto::{decode_frame, encode_frame, Frame, OpCode, ProtocolError, RSV1},
+    Error, Limits,
+};
+
+fn roundtrip(frame: &Frame, mask: Option<[u8; 4]>) {
+    let mut buf = BytesMut::new();
+    encode_frame(frame, mask, &mut buf);
+    // Trailing bytes of the next frame must be left untouched
+    buf.extend_from_slice(&[0x81]);
+
+    let decoded = decode_frame(&mut buf, &Limits::unlimited())
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(decoded.opcode(), frame.opcode());
+    assert_eq!(decoded.is_final(), frame.is_final());
+    assert_eq!(decoded.rsv(), frame.rsv());
+    assert_eq!(&decoded.payload()[..], &frame.payload()[..]);
+    assert_eq!(&buf[..], &[0x81]);
+}
+
+#[test]
+fn test_roundtrip() {
+    for len in [0, 1, 125, 126, 127, 65535, 65536, 100_000] {
+        let payload = vec![b'a'; len];
+        let frames = [
+            Frame::new(OpCode::Text, true, 0, payload.clone()),
+            Frame::new(OpCode::Binary, false, RSV1, payload.clone()),
+            Frame::new(OpCode::Continuation, true, 0, payload),
+        ];
+
+        for frame in &frames {
+            roundtrip(frame, None);
+            roundtrip(frame, Some([1, 2, 3, 4]));
+        }
+    }
+
+    roundtrip(&Frame::new(OpCode::Ping, true, 0, vec![0; 125]), None);
+    roundtrip(
+        &Frame::from(tokio_websockets::Message::close(None, "")),
+        None,
+    );
+}
+
+#[test]
+fn test_encoding() {
+    let mut buf = BytesMut::new();
+    encode_frame(&Frame::new(OpCode::Text, true, 0, "Hi"), None, &mut buf);
+    assert_eq!(&buf[..], b"\x81\x02Hi");
+
+    buf.clear();
+    encode_frame(
+        &Frame::new(OpCode::Binary, false, 0, vec![0; 4]),
+        Some([1, 2, 3, 4]),
+        &mut buf,
+    );
+    assert_eq!(&buf[..], &[0x02, 0x84, 1, 2, 3, 4, 1, 2, 3, 4]);
+}
+
+#[test]
+fn test_incomplete() {
+    let mut buf = BytesMut::new();
+    encode_frame(
+        &Frame::new(OpCode::Binary, true, 0, vec![0; 1000]),
+        Some([1, 2, 3, 4]),
+        &mut buf,
+    );
+
+    for len in 0..buf.len() {
+        let mut partial = BytesMut::from(&buf[..len]);
+        assert!(decode_frame(&mut partial, &Limits::default())
+            .unwrap()
+            .is_none());
+        assert_eq!(partial.len(), len);
+    }
+}
+
+#[test]
+fn test_invalid() {
+    let invalid: [(&[u8], ProtocolError); 8] = [
+        (b"\x83\x00", ProtocolError::InvalidOpcode),
+        (b"\x09\x00", ProtocolError::FragmentedControlFrame),
+        (b"\x88\x01\x03", ProtocolError::InvalidPayloadLength),
+        (b"\x82\x7e\x00\x7d", ProtocolError::InvalidPayloadLength),
+        (
+            b"\x82\x7f\x00\x00\x00\x00\x00\x00\xff\xff",
+            ProtocolError::InvalidPayloadLength,
+        ),
+        // The most significant bit of 64-bit lengths must be 0
+        (
+            b"\x82\x7f\x80\x00\x00\x00\x00\x00\x00\x00",
+            ProtocolError::InvalidPayloadLength,
+        ),
+        (b"\x88\x02\x03\xed", ProtocolError::InvalidCloseCode),
+        (b"\x81\x01\xff", ProtocolError::InvalidUtf8),
+    ];
+
+    for (data, expected) in invalid {
+        for limits in [Limits::default(), Limits::unlimited()] {
+            match decode_frame(&mut BytesMut::from(data), &limits) {
+                Err(Error::Protocol(err)) => {
+                    assert_eq!(format!("{err:?}"), format!("{expected:?}"));
+                }
+                other => panic!("expected {expected:?}, got {other:?}"),
+            }
+        }
+    }
+
+    // Fragmented text is only validated once assembled
+    assert!(decode_frame(
+        &mut BytesMut::from(&b"\x01\x01\xc3"[..]),
+        &Limits::default()
+    )
+    .is_ok());
+
+    assert!(matches!(
+        decode_frame(
+            &mut BytesMut::from(&b"\x82\x05hello"[..]),
+            &Limits::default().max_payload_len(Some(4))
+        ),
+        Err(Error::PayloadTooLong { len: 5, max_len: 4 })
+    ));
+}
+
+#[cfg(feature = "arbitrary")]
+#[test]
+fn test_arbitrary_roundtrip() {
+    use arbitrary::{Arbitrary, Unstructured};
+
+    // Deterministic pseudo-random input so that failures are reproducible
+    let mut state: u32 = 0x9E37_79B9;
+    let data: Vec<u8> = (0..64 * 1024)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state.to_le_bytes()[0]
+        })
+        .collect();
+    let mut u = Unstructured::new(&data);
+
+    while !u.is_empty() {
+        let frame = Frame::arbitrary(&mut u).unwrap();
+        let mask = Option::<[u8; 4]>::arbitrary(&mut u).unwrap();
+        roundtrip(&frame, mask);
+    }
+}
+
+#[test]
+fn test_validate() {
+    let valid = [
+        Frame::new(OpCode::Text, true, 0, "hello"),
+        Frame::new(OpCode::Text, false, 0, &b"\xe2\x82"[..]),
+        Frame::new(OpCode::Continuation, true, 0, &b"\xac"[..]),
+        Frame::new(OpCode::Binary, true, RSV1, vec![0; 126]),
+        Frame::new(OpCode::Ping, true, 0, vec![0; 125]),
+        Frame::new(OpCode::Close, true, 0, ""),
+        Frame::new(OpCode::Close, true, 0, &b"\x03\xe8bye"[..]),
+    ];
+    for frame in valid {
+        assert!(frame.validate().is_ok());
+    }
+
+    let invalid = [
+        (
+            Frame::new(OpCode::Ping, false, 0, ""),
+            ProtocolError::FragmentedControlFrame,
+        ),
+        (
+            Frame::new(OpCode::Pong, true, 0, vec![0; 126]),
+            ProtocolError::InvalidPayloadLength,
+        ),
+        (
+            Frame::new(OpCode::Close, true, 0, &b"\x03"[..]),
+            ProtocolError::InvalidPayloadLength,
+        ),
+        (
+            Frame::new(OpCode::Close, true, 0, &b"\x03\xed"[..]),
+            ProtocolError::InvalidCloseCode,
+        ),
+        (
+            Frame::new(OpCode::Close, true, 0, &b"\x03\xe8\xff"[..]),
+            ProtocolError::InvalidUtf8,
+        ),
+        (
+            Frame::new(OpCode::Text, true, 0, &b"\xe2\x82"[..]),
+            ProtocolError::InvalidUtf8,
+        ),
+    ];
+    for (frame, error) in invalid {
+        assert_eq!(frame.validate().unwrap_err().to_string(), error.to_string());
+    }
+}
@@ -0,0 +1,57 @@
+#![cfg(feature = "server")]
+
+use std::time::Duration;
+
+use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+use tokio_websockets::{upgrade, Error, ServerBuilder};
+
+#[tokio::test]
+async fn test_handshake_timeout() {
+    let builder = ServerBuilder::new().handshake_timeout(Duration::from_millis(50));
+    let (mut client, server) = duplex(usize::MAX);
+
+    // Trickle in an incomplete request and never finish it
+    client.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+
+    let err = builder.accept(server).await.unwrap_err();
+    assert!(matches!(err, Error::Upgrade(upgrade::Error::TimedOut)));
+
+    // The server end was dropped, closing the stream
+    let mut buf = Vec::new();
+    assert_eq!(client.read_to_end(&mut buf).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_max_handshake_size() {
+    let builder = ServerBuilder::new().max_handshake_size(1024);
+    let (mut client, server) = duplex(usize::MAX);
+
+    client.write_all(b"GET / HTTP/1.1\r\n").await.unwrap();
+    client
+        .write_all(format!("X-Padding: {}\r\n", "a".repeat(2048)).as_bytes())
+        .await
+        .unwrap();
+
+    let err = builder.accept(server).await.unwrap_err();
+    assert!(matches!(
+        err,
+        Error::Upgrade(upgrade::Error::RequestTooLarge)
+    ));
+}
+
+#[tokio::test]
+async fn test_max_handshake_headers() {
+    let builder = ServerBuilder::new().max_handshake_headers(4);
+    let (mut client, server) = duplex(usize::MAX);
+
+    client
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n")
+        .await
+        .unwrap();
+
+    let err = builder.accept(server).await.unwrap_err();
+    assert!(matches!(
+        err,
+        Error::Upgrade(upgrade::Error::Parsing(httparse::Error::TooManyHeaders))
+    ));
+}
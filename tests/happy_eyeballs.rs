@@ -0,0 +1,54 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::net::SocketAddr;
+
+use futures_util::StreamExt;
+use http::Uri;
+use tokio::net::TcpListener;
+use tokio_websockets::{resolver::Resolver, ClientBuilder, Error, ServerBuilder};
+
+/// Resolves every host to a fixed list of addresses.
+struct StaticResolver(Vec<SocketAddr>);
+
+impl Resolver for StaticResolver {
+    async fn resolve(&self, _host: &str, _port: u16) -> Result<SocketAddr, Error> {
+        self.0.first().copied().ok_or(Error::CannotResolveHost)
+    }
+
+    async fn resolve_all(&self, _host: &str, _port: u16) -> Result<Vec<SocketAddr>, Error> {
+        Ok(self.0.clone())
+    }
+}
+
+#[tokio::test]
+async fn test_fallback_to_next_address() {
+    // Bind and drop a listener to obtain a port that refuses connections
+    let refused = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut server = ServerBuilder::new().accept(stream).await.unwrap();
+
+        let msg = server.next().await.unwrap().unwrap();
+        server.send(msg).await.unwrap();
+    });
+
+    let (mut client, _) = ClientBuilder::from_uri(Uri::from_static("ws://example.invalid"))
+        .resolver(StaticResolver(vec![refused, addr]))
+        .connect()
+        .await
+        .unwrap();
+
+    client.send_text("Hello").await.unwrap();
+    let msg = client.next().await.unwrap().unwrap();
+    assert_eq!(msg.as_text(), Some("Hello"));
+
+    server.await.unwrap();
+}
@@ -0,0 +1,118 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::sync::{Arc, Mutex};
+
+use futures_util::StreamExt;
+use tokio_websockets::{
+    proto::{ConnectionId, Frame, FrameInterceptor, OpCode},
+    CloseCode, Config, Error, Limits, WebSocketStream,
+};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Records the opcodes of all frames and the connections they belong to.
+#[derive(Default, Clone)]
+struct Recorder {
+    ids: Arc<Mutex<Vec<ConnectionId>>>,
+    received: Arc<Mutex<Vec<OpCode>>>,
+    sent: Arc<Mutex<Vec<OpCode>>>,
+}
+
+impl FrameInterceptor for Recorder {
+    fn on_frame_received(&mut self, id: ConnectionId, frame: &Frame) -> Result<(), BoxError> {
+        self.ids.lock().unwrap().push(id);
+        self.received.lock().unwrap().push(frame.opcode());
+
+        Ok(())
+    }
+
+    fn on_frame_sent(&mut self, id: ConnectionId, frame: &Frame) -> Result<(), BoxError> {
+        self.ids.lock().unwrap().push(id);
+        self.sent.lock().unwrap().push(frame.opcode());
+
+        Ok(())
+    }
+}
+
+/// Rejects binary frames.
+struct RejectBinary;
+
+impl FrameInterceptor for RejectBinary {
+    fn on_frame_received(&mut self, _: ConnectionId, frame: &Frame) -> Result<(), BoxError> {
+        if frame.opcode() == OpCode::Binary {
+            return Err("binary frames are not allowed".into());
+        }
+
+        Ok(())
+    }
+
+    fn on_frame_sent(&mut self, id: ConnectionId, frame: &Frame) -> Result<(), BoxError> {
+        self.on_frame_received(id, frame)
+    }
+}
+
+#[tokio::test]
+async fn test_observe() {
+    let config = Config::default().frame_size(4);
+    let (mut client, mut server) = WebSocketStream::pair_with_config(config, Limits::default());
+    let recorder = Recorder::default();
+    server.set_frame_interceptor(recorder.clone());
+
+    client.send_text("fragmented").await.unwrap();
+    client
+        .send(tokio_websockets::Message::ping(""))
+        .await
+        .unwrap();
+    assert!(server.next().await.unwrap().unwrap().is_text());
+    assert!(server.next().await.unwrap().unwrap().is_ping());
+    server.flush().await.unwrap();
+
+    assert_eq!(
+        *recorder.received.lock().unwrap(),
+        [
+            OpCode::Text,
+            OpCode::Continuation,
+            OpCode::Continuation,
+            OpCode::Ping
+        ]
+    );
+    assert_eq!(*recorder.sent.lock().unwrap(), [OpCode::Pong]);
+    assert!(recorder
+        .ids
+        .lock()
+        .unwrap()
+        .iter()
+        .all(|&id| id == server.id()));
+}
+
+#[tokio::test]
+async fn test_reject_received() {
+    let (mut client, mut server) = WebSocketStream::pair();
+    server.set_frame_interceptor(RejectBinary);
+
+    client.send_binary("data").await.unwrap();
+    assert!(matches!(
+        server.next().await,
+        Some(Err(Error::FrameRejected(_)))
+    ));
+    assert!(server.next().await.is_none());
+
+    let message = client.next().await.unwrap().unwrap();
+    assert_eq!(message.as_close().unwrap().0, CloseCode::POLICY_VIOLATION);
+}
+
+#[tokio::test]
+async fn test_reject_sent() {
+    let (mut client, mut server) = WebSocketStream::pair();
+    client.set_frame_interceptor(RejectBinary);
+
+    assert!(matches!(
+        client.send_binary("data").await,
+        Err(Error::FrameRejected(_))
+    ));
+
+    // Rejecting a message does not affect the connection
+    client.send_text("text").await.unwrap();
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("text"));
+}
@@ -0,0 +1,116 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use http::Uri;
+use tokio::{
+    io::{duplex, AsyncReadExt, AsyncWriteExt, DuplexStream},
+    sync::oneshot,
+};
+use tokio_websockets::{upgrade, ClientBuilder, Error, ServerBuilder};
+
+/// Address of the first peer.
+const FIRST: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)), 1000);
+
+/// Address of the second peer.
+const SECOND: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)), 1000);
+
+/// Starts a client handshake on a new in-memory stream and returns the server
+/// end of it. The client is kept open until the returned sender is dropped.
+fn connect() -> (DuplexStream, oneshot::Sender<()>) {
+    let (one, two) = duplex(usize::MAX);
+    let (tx, rx) = oneshot::channel();
+
+    tokio::spawn(async move {
+        if let Ok((client, _)) = ClientBuilder::from_uri(Uri::from_static("ws://localhost"))
+            .connect_on(two)
+            .await
+        {
+            let _ = rx.await;
+            drop(client);
+        }
+    });
+
+    (one, tx)
+}
+
+#[tokio::test]
+async fn test_max_connections_per_ip() {
+    let builder = ServerBuilder::new().max_connections_per_ip(1);
+
+    let (stream, _first_client) = connect();
+    let first = builder.accept_from(stream, FIRST).await.unwrap();
+
+    let (stream, _) = connect();
+    let err = builder.accept_from(stream, FIRST).await.unwrap_err();
+    assert!(matches!(
+        err,
+        Error::Upgrade(upgrade::Error::TooManyConnections)
+    ));
+
+    let (stream, _second_client) = connect();
+    builder.accept_from(stream, SECOND).await.unwrap();
+
+    drop(first);
+
+    let (stream, _third_client) = connect();
+    builder.accept_from(stream, FIRST).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_rejection_response() {
+    let builder = ServerBuilder::new().max_connections_per_ip(0);
+    let (mut client, server) = duplex(usize::MAX);
+
+    client
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n")
+        .await
+        .unwrap();
+
+    assert!(builder.accept_from(server, FIRST).await.is_err());
+
+    let mut response = Vec::new();
+    client.read_to_end(&mut response).await.unwrap();
+    assert!(response.starts_with(b"HTTP/1.1 429 Too Many Requests\r\n"));
+}
+
+#[tokio::test]
+async fn test_connection_ip() {
+    let builder = ServerBuilder::new()
+        .max_connections_per_ip(1)
+        .connection_ip(|peer_addr, request| {
+            request
+                .headers()
+                .get("X-Forwarded-For")
+                .and_then(|value| value.to_str().ok()?.parse().ok())
+                .unwrap_or(peer_addr.ip())
+        });
+
+    // Without the header, both connections come from the same proxy address
+    let (stream, _first_client) = connect();
+    builder.accept_from(stream, FIRST).await.unwrap();
+
+    let (one, two) = duplex(usize::MAX);
+    let (_tx, rx) = oneshot::channel::<()>();
+    tokio::spawn(async move {
+        let (client, _) = ClientBuilder::from_uri(Uri::from_static("ws://localhost"))
+            .add_header(
+                http::header::HeaderName::from_static("x-forwarded-for"),
+                http::HeaderValue::from_static("198.51.100.1"),
+            )
+            .connect_on(two)
+            .await
+            .unwrap();
+
+        let _ = rx.await;
+        drop(client);
+    });
+
+    tokio::time::timeout(Duration::from_secs(5), builder.accept_from(one, FIRST))
+        .await
+        .unwrap()
+        .unwrap();
+}
@@ -0,0 +1,71 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+use futures_util::StreamExt;
+use tokio::net::TcpListener;
+use tokio_websockets::{ClientBuilder, Error, ServerBuilder};
+
+/// Returns a local port that is currently unused.
+async fn free_port() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+    listener.local_addr().unwrap().port()
+}
+
+#[tokio::test]
+async fn test_local_bind() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let port = free_port().await;
+
+    let server = tokio::spawn(async move {
+        let (stream, peer_addr) = listener.accept().await.unwrap();
+        let mut server = ServerBuilder::new().accept(stream).await.unwrap();
+        assert_eq!(server.next().await.unwrap().unwrap().as_text(), Some("hi"));
+
+        peer_addr
+    });
+
+    let (mut client, _) = ClientBuilder::new()
+        .uri(&format!("ws://{addr}/"))
+        .unwrap()
+        .local_address(IpAddr::V4(Ipv4Addr::LOCALHOST))
+        .local_port_range(port..=port)
+        .connect()
+        .await
+        .unwrap();
+    client.send_text("hi").await.unwrap();
+
+    let peer_addr = server.await.unwrap();
+    assert_eq!(peer_addr.ip(), Ipv4Addr::LOCALHOST);
+    assert_eq!(peer_addr.port(), port);
+}
+
+#[tokio::test]
+async fn test_local_bind_errors() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // All ports of the range are in use
+    let port = listener.local_addr().unwrap().port();
+    let result = ClientBuilder::new()
+        .uri(&format!("ws://{addr}/"))
+        .unwrap()
+        .local_port_range(port..=port)
+        .connect()
+        .await;
+    assert!(matches!(result, Err(Error::Io(e)) if e.kind() == io::ErrorKind::AddrInUse));
+
+    // No address of the same family as the local address
+    let result = ClientBuilder::new()
+        .uri(&format!("ws://{addr}/"))
+        .unwrap()
+        .local_address(IpAddr::V6(Ipv6Addr::LOCALHOST))
+        .connect()
+        .await;
+    assert!(matches!(result, Err(Error::CannotResolveHost)));
+}
@@ -0,0 +1,51 @@
+#![cfg(feature = "client")]
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use tokio::io::{duplex, AsyncReadExt};
+use tokio_websockets::{ClientBuilder, Message};
+
+/// Masks `payload` with `key` like a client does.
+fn masked(payload: &[u8], key: [u8; 4]) -> Vec<u8> {
+    payload
+        .iter()
+        .zip(key.iter().cycle())
+        .map(|(byte, key)| byte ^ key)
+        .collect()
+}
+
+#[tokio::test]
+async fn test_fixed_mask() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut client = ClientBuilder::new()
+        .mask_generator(|| [0x12, 0x34, 0x56, 0x78])
+        .take_over(one);
+
+    client.send(Message::text("Hello")).await.unwrap();
+
+    let mut frame = [0; 11];
+    two.read_exact(&mut frame).await.unwrap();
+    assert_eq!(frame[..6], [0x81, 0x85, 0x12, 0x34, 0x56, 0x78]);
+    assert_eq!(frame[6..], masked(b"Hello", [0x12, 0x34, 0x56, 0x78]));
+}
+
+#[tokio::test]
+async fn test_mask_per_frame() {
+    static NEXT: AtomicU8 = AtomicU8::new(0);
+
+    let (one, mut two) = duplex(usize::MAX);
+    let mut client = ClientBuilder::new()
+        .mask_generator(|| [NEXT.fetch_add(1, Ordering::Relaxed); 4])
+        .take_over(one);
+
+    // Every frame is masked with a new key from the generator
+    client.send(Message::binary(&b"ab"[..])).await.unwrap();
+    client.send(Message::binary(&b"cd"[..])).await.unwrap();
+
+    let mut frames = [0; 16];
+    two.read_exact(&mut frames).await.unwrap();
+    assert_eq!(frames[..6], [0x82, 0x82, 0, 0, 0, 0]);
+    assert_eq!(frames[6..8], masked(b"ab", [0; 4]));
+    assert_eq!(frames[8..14], [0x82, 0x82, 1, 1, 1, 1]);
+    assert_eq!(frames[14..], masked(b"cd", [1; 4]));
+}
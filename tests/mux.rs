@@ -0,0 +1,142 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use tokio::time::timeout;
+use tokio_websockets::{mux, CloseCode, Message, WebSocketStream};
+
+#[tokio::test]
+async fn test_mux_channels() {
+    let (client, server) = WebSocketStream::pair();
+    let client = mux::Builder::new().spawn(client);
+    let mut server = mux::Builder::new().spawn(server);
+
+    let mut one = client.open(1).unwrap();
+    let mut two = client.open(2).unwrap();
+    assert!(client.open(1).is_none());
+
+    one.send("one").await.unwrap();
+    two.send("two").await.unwrap();
+
+    let mut accepted_one = server.accept().await.unwrap();
+    let mut accepted_two = server.accept().await.unwrap();
+    assert_eq!(accepted_one.id(), 1);
+    assert_eq!(accepted_two.id(), 2);
+    assert_eq!(accepted_one.recv().await.unwrap(), "one");
+    assert_eq!(accepted_two.recv().await.unwrap(), "two");
+
+    // Replies travel on the same channel, also via the Sink implementation
+    accepted_two
+        .send(bytes::Bytes::from("reply"))
+        .await
+        .unwrap();
+    assert_eq!(two.next().await.unwrap(), "reply");
+
+    // Closing a channel ends it on both sides
+    drop(one);
+    assert!(accepted_one.recv().await.is_none());
+    assert!(accepted_one.send("late").await.is_err());
+}
+
+#[tokio::test]
+async fn test_mux_flow_control() {
+    let (client, server) = WebSocketStream::pair();
+    let client = mux::Builder::new().window(2).spawn(client);
+    let mut server = mux::Builder::new().window(2).spawn(server);
+
+    let mut slow = client.open(1).unwrap();
+    let mut fast = client.open(2).unwrap();
+
+    slow.send("a").await.unwrap();
+    slow.send("b").await.unwrap();
+    // The window is exhausted until the peer consumes a message
+    assert!(timeout(Duration::from_millis(100), slow.send("c"))
+        .await
+        .is_err());
+
+    // Other channels are not held up
+    fast.send("fast").await.unwrap();
+
+    let mut accepted_slow = server.accept().await.unwrap();
+    let mut accepted_fast = server.accept().await.unwrap();
+    assert_eq!(accepted_fast.recv().await.unwrap(), "fast");
+
+    assert_eq!(accepted_slow.recv().await.unwrap(), "a");
+    timeout(Duration::from_secs(1), slow.send("c"))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(accepted_slow.recv().await.unwrap(), "b");
+    assert_eq!(accepted_slow.recv().await.unwrap(), "c");
+}
+
+#[tokio::test]
+async fn test_mux_invalid_frame() {
+    let (mut client, server) = WebSocketStream::pair();
+    let mut server = mux::Builder::new().spawn(server);
+
+    client.send(Message::text("not multiplexed")).await.unwrap();
+
+    let close = client.next().await.unwrap().unwrap();
+    assert_eq!(close.as_close().unwrap().0, CloseCode::PROTOCOL_ERROR);
+    client.close().await.ok();
+    assert!(server.accept().await.is_none());
+}
+
+#[tokio::test]
+async fn test_mux_window_exceeded() {
+    let (mut client, server) = WebSocketStream::pair();
+    let _server = mux::Builder::new().window(1).spawn(server);
+
+    // Ignore the window and send two messages on channel 7
+    let frame: &[u8] = &[0, 0, 0, 0, 7, b'x'];
+    client.send(Message::binary(frame)).await.unwrap();
+    client.send(Message::binary(frame)).await.unwrap();
+
+    let close = client.next().await.unwrap().unwrap();
+    assert_eq!(close.as_close().unwrap().0, CloseCode::POLICY_VIOLATION);
+}
+
+#[tokio::test]
+async fn test_mux_invalid_credit() {
+    let (mut client, server) = WebSocketStream::pair();
+    let server = mux::Builder::new().window(1).spawn(server);
+
+    let _channel = server.open(3).unwrap();
+
+    // Grant more credit than the window allows on channel 3
+    let frame: &[u8] = &[1, 0, 0, 0, 3, 0xff, 0xff, 0xff, 0xff];
+    client.send(Message::binary(frame)).await.unwrap();
+
+    let close = client.next().await.unwrap().unwrap();
+    assert_eq!(close.as_close().unwrap().0, CloseCode::PROTOCOL_ERROR);
+}
+
+#[tokio::test]
+async fn test_mux_max_channels() {
+    let (mut client, server) = WebSocketStream::pair();
+    let mut server = mux::Builder::new().max_channels(2).spawn(server);
+
+    // Open three channels, each with a single message
+    for id in 1..=3 {
+        let frame = vec![0, 0, 0, 0, id, b'x'];
+        client.send(Message::binary(frame)).await.unwrap();
+    }
+
+    assert_eq!(server.accept().await.unwrap().id(), 1);
+    assert_eq!(server.accept().await.unwrap().id(), 2);
+
+    let close = client.next().await.unwrap().unwrap();
+    assert_eq!(close.as_close().unwrap().0, CloseCode::POLICY_VIOLATION);
+}
+
+#[tokio::test]
+async fn test_mux_drop_closes_connection() {
+    let (mut client, server) = WebSocketStream::pair();
+    let multiplexer = mux::Builder::new().spawn(server);
+    drop(multiplexer);
+
+    let close = client.next().await.unwrap().unwrap();
+    assert_eq!(close.as_close().unwrap().0, CloseCode::NORMAL_CLOSURE);
+}
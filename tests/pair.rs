@@ -0,0 +1,353 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use bytes::Bytes;
+use futures_util::StreamExt;
+use tokio_websockets::{
+    proto::{Frame, OpCode, ProtocolError, StreamState, RSV1},
+    CloseCode, Config, Error, Limits, Message, WebSocketStream,
+};
+
+#[tokio::test]
+async fn test_pair() {
+    let (mut client, mut server) = WebSocketStream::pair();
+
+    client.send(Message::text("ping")).await.unwrap();
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("ping"));
+
+    server.send(Message::text("pong")).await.unwrap();
+    let message = client.next().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("pong"));
+
+    // Closing waits for the server to acknowledge the close frame
+    let (closed, message) = tokio::join!(client.close(), async {
+        let message = server.next().await;
+        assert!(server.next().await.is_none());
+
+        message
+    });
+    assert_eq!(
+        closed.unwrap().unwrap().as_close(),
+        Some((CloseCode::NORMAL_CLOSURE, ""))
+    );
+    assert!(message.unwrap().unwrap().is_close());
+}
+
+#[tokio::test]
+async fn test_close_collect() {
+    let (mut client, mut server) = WebSocketStream::pair();
+
+    // The server flushes final data before it reads the close frame
+    let mut messages = Vec::new();
+    let (closed, _) = tokio::join!(client.close_collect(&mut messages), async {
+        server.send(Message::text("final")).await.unwrap();
+        server.send(Message::ping("")).await.unwrap();
+        assert!(server.next().await.unwrap().unwrap().is_close());
+        assert!(server.next().await.is_none());
+    });
+    assert!(closed.unwrap().unwrap().is_close());
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].as_text(), Some("final"));
+}
+
+#[tokio::test]
+async fn test_pair_with_config() {
+    let (mut client, mut server) = WebSocketStream::pair_with_config(
+        Config::default().frame_size(4),
+        Limits::default().max_payload_len(Some(16)),
+    );
+
+    client
+        .send(Message::binary(vec![1; 16 * 1024]))
+        .await
+        .unwrap_or_default();
+    assert!(server.next().await.unwrap().is_err());
+}
+
+#[tokio::test]
+async fn test_connection_id() {
+    let (client, server) = WebSocketStream::pair();
+    let (other, _) = WebSocketStream::pair();
+
+    assert_ne!(client.id(), server.id());
+    assert_ne!(client.id(), other.id());
+    assert_eq!(client.id(), client.id());
+    assert!(format!("{client:?}").contains(&format!("{:?}", client.id())));
+}
+
+#[tokio::test]
+async fn test_state() {
+    let (mut client, mut server) = WebSocketStream::pair();
+    assert_eq!(client.state(), StreamState::Active);
+    assert!(client.is_active());
+    assert!(!client.is_closed());
+
+    client.send(Message::close(None, "")).await.unwrap();
+    assert_eq!(client.state(), StreamState::ClosedByUs);
+    assert!(!client.is_active());
+
+    assert!(server.next().await.unwrap().unwrap().is_close());
+    assert_eq!(server.state(), StreamState::ClosedByPeer);
+
+    // Ending the stream sends the acknowledgement
+    assert!(server.next().await.is_none());
+    assert!(server.is_closed());
+    assert!(client.next().await.unwrap().unwrap().is_close());
+    assert_eq!(client.state(), StreamState::CloseAcknowledged);
+    assert!(client.is_closed());
+}
+
+#[tokio::test]
+async fn test_debug() {
+    let (mut client, _server) = WebSocketStream::pair();
+
+    client.feed(Message::text("secret")).await.unwrap();
+    let debug = format!("{client:?}");
+    assert!(debug.contains("role: Client"));
+    assert!(debug.contains("state: Active"));
+    assert!(debug.contains("queued_frames: 1"));
+    assert!(!debug.contains("secret"));
+}
+
+#[tokio::test]
+async fn test_into_text() {
+    let (mut client, mut server) = WebSocketStream::pair();
+
+    client.send(Message::text("hello")).await.unwrap();
+    let message = server.next().await.unwrap().unwrap();
+    let ptr = message.as_payload().as_ptr();
+    let text = message.into_text().unwrap();
+
+    // The received payload is reused without copying
+    assert_eq!(text, "hello");
+    assert_eq!(text.as_ptr(), ptr);
+
+    assert!(Message::binary("hello").into_text().is_none());
+    assert_eq!(Message::text(&b"bytes"[..]).into_text().unwrap(), "bytes");
+
+    // Received text can be sent on as is
+    server.send_text(text).await.unwrap();
+    let message = client.next().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("hello"));
+}
+
+#[tokio::test]
+async fn test_try_read_message() {
+    let (mut client, mut server) = WebSocketStream::pair();
+
+    assert!(client.try_read_message().unwrap().is_none());
+
+    server.send_text("first").await.unwrap();
+    server.send_text("second").await.unwrap();
+
+    let message = client.try_read_message().unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("first"));
+    let message = client.try_read_message().unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("second"));
+    assert!(client.try_read_message().unwrap().is_none());
+
+    server.send(Message::close(None, "")).await.unwrap();
+    assert!(client.try_read_message().unwrap().unwrap().is_close());
+    assert!(matches!(
+        client.try_read_message(),
+        Err(Error::AlreadyClosed)
+    ));
+}
+
+#[tokio::test]
+async fn test_read_ready_messages() {
+    let (mut client, mut server) = WebSocketStream::pair();
+    let mut messages = Vec::new();
+
+    for i in 0..5 {
+        server.feed(i.to_string()).await.unwrap();
+    }
+    server.flush().await.unwrap();
+
+    assert_eq!(
+        client.read_ready_messages(&mut messages, 3).await.unwrap(),
+        3
+    );
+    assert_eq!(
+        client.read_ready_messages(&mut messages, 10).await.unwrap(),
+        2
+    );
+    assert_eq!(
+        client.read_ready_messages(&mut messages, 0).await.unwrap(),
+        0
+    );
+    let texts: Vec<_> = messages.iter().filter_map(Message::as_text).collect();
+    assert_eq!(texts, ["0", "1", "2", "3", "4"]);
+
+    // Waits for the first message
+    let reader = tokio::spawn(async move {
+        let mut messages = Vec::new();
+        let count = client.read_ready_messages(&mut messages, 10).await.unwrap();
+
+        (count, client)
+    });
+    tokio::task::yield_now().await;
+    server.send_text("late").await.unwrap();
+    let (count, mut client) = reader.await.unwrap();
+    assert_eq!(count, 1);
+
+    server.send(Message::close(None, "")).await.unwrap();
+    messages.clear();
+    assert_eq!(
+        client.read_ready_messages(&mut messages, 10).await.unwrap(),
+        1
+    );
+    assert!(messages[0].is_close());
+    assert_eq!(
+        client.read_ready_messages(&mut messages, 10).await.unwrap(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_poll_budget() {
+    let config = Config::default().poll_budget(Some(2));
+    let (mut client, mut server) = WebSocketStream::pair_with_config(config, Limits::default());
+
+    for i in 0..5 {
+        server.feed(i.to_string()).await.unwrap();
+    }
+    server.flush().await.unwrap();
+
+    // The stream yields after every two frames
+    assert!(client.try_read_message().unwrap().is_some());
+    assert!(client.try_read_message().unwrap().is_some());
+    assert!(client.try_read_message().unwrap().is_none());
+
+    // Yielding wakes the task right away
+    for i in 2..5 {
+        let message = client.next().await.unwrap().unwrap();
+        assert_eq!(message.as_text(), Some(i.to_string().as_str()));
+    }
+}
+
+#[tokio::test]
+async fn test_buffer_sizes() {
+    // Both buffers grow and flush as needed regardless of their configured size
+    let config = Config::default()
+        .read_buffer_capacity(16)
+        .flush_threshold(16);
+    let (mut client, mut server) = WebSocketStream::pair_with_config(config, Limits::default());
+
+    let payload = vec![7; 64 * 1024];
+    let sender = tokio::spawn(async move { client.send_binary(payload).await });
+
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.as_payload().len(), 64 * 1024);
+    sender.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_auto_pong() {
+    let config = Config::default().auto_pong(false);
+    let (mut client, mut server) = WebSocketStream::pair_with_config(config, Limits::default());
+
+    client.send(Message::ping("ping")).await.unwrap();
+    let message = server.next().await.unwrap().unwrap();
+    assert!(message.is_ping());
+    assert!(client.try_read_message().unwrap().is_none());
+
+    server.send(Message::pong("ping")).await.unwrap();
+    let message = client.next().await.unwrap().unwrap();
+    assert!(message.is_pong());
+}
+
+#[tokio::test]
+async fn test_read_buffer_shrink_threshold() {
+    for (threshold, unique) in [(Some(1024), true), (None, false)] {
+        let config = Config::default().read_buffer_shrink_threshold(threshold);
+        let (mut client, mut server) = WebSocketStream::pair_with_config(config, Limits::default());
+
+        let sender = tokio::spawn(async move {
+            client.send_binary(vec![7; 64 * 1024]).await.unwrap();
+            client
+        });
+
+        // The payload only shares its allocation with the read buffer if that
+        // was not shrunk
+        let message = server.next().await.unwrap().unwrap();
+        assert_eq!(Bytes::from(message.into_payload()).is_unique(), unique);
+
+        let mut client = sender.await.unwrap();
+        client.send_text("small").await.unwrap();
+        let message = server.next().await.unwrap().unwrap();
+        assert_eq!(message.as_text(), Some("small"));
+    }
+}
+
+#[tokio::test]
+async fn test_send_with_info() {
+    let config = Config::default().frame_size(4);
+    let (mut client, mut server) = WebSocketStream::pair_with_config(config, Limits::default());
+
+    let info = server.send_with_info("hi").await.unwrap();
+    assert_eq!((info.frames(), info.wire_bytes()), (1, 4));
+    assert!(!info.compressed());
+
+    // Frames of clients carry a masking key
+    let info = client.send_with_info("hello world").await.unwrap();
+    assert_eq!((info.frames(), info.wire_bytes()), (3, 3 * 6 + 11));
+
+    assert_eq!(client.next().await.unwrap().unwrap().as_text(), Some("hi"));
+    assert_eq!(
+        server.next().await.unwrap().unwrap().as_text(),
+        Some("hello world")
+    );
+}
+
+#[tokio::test]
+async fn test_sent_close() {
+    let (mut client, mut server) = WebSocketStream::pair();
+    assert_eq!(server.sent_close(), None);
+
+    client
+        .send_frame(Frame::new(OpCode::Text, true, RSV1, "hello"))
+        .await
+        .unwrap();
+    assert!(matches!(
+        server.next().await,
+        Some(Err(Error::Protocol(ProtocolError::InvalidRsv)))
+    ));
+
+    // The close frame sent in response to the violation is known before it
+    // is flushed
+    let reason = ProtocolError::InvalidRsv.to_string();
+    assert_eq!(
+        server.sent_close(),
+        Some((Some(CloseCode::PROTOCOL_ERROR), reason.as_str()))
+    );
+    assert!(server.next().await.is_none());
+
+    let message = client.next().await.unwrap().unwrap();
+    assert_eq!(
+        message.as_close(),
+        Some((CloseCode::PROTOCOL_ERROR, reason.as_str()))
+    );
+}
+
+#[tokio::test]
+async fn test_fragment_shared_payload() {
+    let config = Config::default().frame_size(1000);
+    let (mut client, mut server) = WebSocketStream::pair_with_config(config, Limits::default());
+
+    let payload: Vec<u8> = (0..10_000).map(|i| (i % 251) as u8).collect();
+    let shared = Bytes::from(payload.clone());
+    // Keep a second reference so that the payload cannot be made unique
+    let _other = shared.clone();
+
+    client.send(Message::binary(shared)).await.unwrap();
+    server
+        .send(Message::binary(Bytes::from_static(b"static")))
+        .await
+        .unwrap();
+
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(&**message.as_payload(), &payload[..]);
+    let message = client.next().await.unwrap().unwrap();
+    assert_eq!(&**message.as_payload(), b"static");
+}
@@ -0,0 +1,40 @@
+#![cfg(feature = "server")]
+
+use futures_util::StreamExt;
+use tokio::io::{duplex, AsyncWriteExt};
+use tokio_websockets::{proto::ProtocolError, Error, Limits, ServerBuilder};
+
+/// Header of a masked binary frame with a 64-bit extended payload length whose
+/// most significant bit is set, followed by the masking key.
+const MSB_SET_FRAME: &[u8] = b"\x82\xff\x80\x00\x00\x00\x00\x00\x00\x00\x01\x02\x03\x04";
+
+#[tokio::test]
+async fn test_msb_set_rejected() {
+    for limits in [Limits::default(), Limits::unlimited()] {
+        let (one, mut two) = duplex(1024);
+        let mut server = ServerBuilder::new().limits(limits).serve(one);
+
+        two.write_all(MSB_SET_FRAME).await.unwrap();
+
+        assert!(matches!(
+            server.next().await,
+            Some(Err(Error::Protocol(ProtocolError::InvalidPayloadLength)))
+        ));
+    }
+}
+
+#[tokio::test]
+async fn test_large_length_over_limit() {
+    let (one, mut two) = duplex(1024);
+    let mut server = ServerBuilder::new().serve(one);
+
+    // 2^62 bytes is a valid length, but exceeds the default limit
+    two.write_all(b"\x82\xff\x40\x00\x00\x00\x00\x00\x00\x00\x01\x02\x03\x04")
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        server.next().await,
+        Some(Err(Error::PayloadTooLong { .. }))
+    ));
+}
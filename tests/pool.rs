@@ -0,0 +1,80 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use futures_util::StreamExt;
+use tokio::net::TcpListener;
+use tokio_websockets::{resolver::Resolver, ClientBuilder, ClientPool, Error, ServerBuilder};
+
+struct CountingResolver {
+    addr: SocketAddr,
+    lookups: Arc<AtomicUsize>,
+}
+
+impl Resolver for CountingResolver {
+    async fn resolve(&self, host: &str, port: u16) -> Result<SocketAddr, Error> {
+        assert_eq!((host, port), ("pool.test", 80));
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+
+        Ok(self.addr)
+    }
+}
+
+#[tokio::test]
+async fn test_pool() {
+    const CONNECTIONS: usize = 16;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let mut server = ServerBuilder::new().accept(stream).await.unwrap();
+                server.send_text("hello").await.unwrap();
+            });
+        }
+    });
+
+    let lookups = Arc::new(AtomicUsize::new(0));
+    let builder = ClientBuilder::new()
+        .uri("ws://pool.test")
+        .unwrap()
+        .resolver(CountingResolver {
+            addr,
+            lookups: Arc::clone(&lookups),
+        });
+    let pool = Arc::new(ClientPool::new(builder, 4).unwrap());
+
+    let tasks: Vec<_> = (0..CONNECTIONS)
+        .map(|_| {
+            let pool = Arc::clone(&pool);
+            tokio::spawn(async move {
+                let (mut client, _) = pool.connect().await.unwrap();
+                let message = client.next().await.unwrap().unwrap();
+                assert_eq!(message.as_text(), Some("hello"));
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        task.await.unwrap();
+    }
+
+    // The hostname is only resolved once for all connections
+    assert_eq!(lookups.load(Ordering::Relaxed), 1);
+}
+
+#[tokio::test]
+async fn test_pool_without_uri() {
+    let pool = ClientPool::new(ClientBuilder::new(), 1).unwrap();
+
+    assert!(matches!(pool.connect().await, Err(Error::NoUriConfigured)));
+}
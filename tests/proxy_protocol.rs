@@ -0,0 +1,151 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use http::Uri;
+use tokio::{
+    io::{duplex, AsyncWriteExt, DuplexStream},
+    sync::{mpsc, oneshot},
+};
+use tokio_websockets::{
+    server::{ProxyHeader, Router},
+    upgrade, ClientBuilder, Error, ServerBuilder,
+};
+
+/// Address of the proxy that all connections are accepted from.
+const PROXY: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 100)), 4000);
+
+/// Writes `header` to a new in-memory stream and starts a client handshake on
+/// it, returning the server end of it. The client is kept open until the
+/// returned sender is dropped.
+async fn connect(header: &[u8]) -> (DuplexStream, oneshot::Sender<()>) {
+    let (one, mut two) = duplex(usize::MAX);
+    let (tx, rx) = oneshot::channel();
+    two.write_all(header).await.unwrap();
+
+    tokio::spawn(async move {
+        if let Ok((client, _)) = ClientBuilder::from_uri(Uri::from_static("ws://localhost"))
+            .connect_on(two)
+            .await
+        {
+            let _ = rx.await;
+            drop(client);
+        }
+    });
+
+    (one, tx)
+}
+
+/// Accepts a stream starting with `header` via a [`Router`] and returns the
+/// [`ProxyHeader`] of the handshake request.
+async fn proxy_header(header: &[u8]) -> Option<ProxyHeader> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let router = Router::new(ServerBuilder::new().proxy_protocol(true)).route(
+        "/",
+        move |_stream, request| {
+            tx.send(request.extensions().get::<ProxyHeader>().copied())
+                .unwrap();
+            async {}
+        },
+    );
+
+    let (stream, _client) = connect(header).await;
+    router.accept_from(stream, PROXY).await.unwrap();
+
+    rx.recv().await.unwrap()
+}
+
+#[tokio::test]
+async fn test_v1() {
+    let header = proxy_header(b"PROXY TCP4 198.51.100.1 203.0.113.7 56324 443\r\n")
+        .await
+        .unwrap();
+    assert_eq!(header.source(), "198.51.100.1:56324".parse().unwrap());
+    assert_eq!(header.destination(), "203.0.113.7:443".parse().unwrap());
+
+    let header = proxy_header(b"PROXY TCP6 2001:db8::1 2001:db8::2 56324 443\r\n")
+        .await
+        .unwrap();
+    assert_eq!(header.source(), "[2001:db8::1]:56324".parse().unwrap());
+
+    assert_eq!(proxy_header(b"PROXY UNKNOWN ignored\r\n").await, None);
+}
+
+#[tokio::test]
+async fn test_v2() {
+    let mut header = b"\r\n\r\n\0\r\nQUIT\n\x21\x21\x00\x24".to_vec();
+    header.extend_from_slice(&Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1).octets());
+    header.extend_from_slice(&Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2).octets());
+    header.extend_from_slice(&56324u16.to_be_bytes());
+    header.extend_from_slice(&443u16.to_be_bytes());
+
+    let header = proxy_header(&header).await.unwrap();
+    assert_eq!(header.source(), "[2001:db8::1]:56324".parse().unwrap());
+    assert_eq!(header.destination(), "[2001:db8::2]:443".parse().unwrap());
+
+    // IPv4 addresses followed by a TLV that is ignored
+    let header = proxy_header(
+        b"\r\n\r\n\0\r\nQUIT\n\x21\x11\x00\x10\xc6\x33\x64\x01\xcb\x00\x71\x07\xdc\x04\x01\xbb\x04\x00\x01\x00",
+    )
+    .await
+    .unwrap();
+    assert_eq!(header.source(), "198.51.100.1:56324".parse().unwrap());
+    assert_eq!(header.destination(), "203.0.113.7:443".parse().unwrap());
+
+    // LOCAL command
+    assert_eq!(
+        proxy_header(b"\r\n\r\n\0\r\nQUIT\n\x20\x00\x00\x00").await,
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_invalid() {
+    let builder = ServerBuilder::new().proxy_protocol(true);
+
+    for header in [
+        &b"GET / HTTP/1.1\r\n"[..],
+        b"PROXY TCP4 198.51.100.1 203.0.113.7 56324\r\n",
+        b"PROXY TCP4 2001:db8::1 2001:db8::2 56324 443\r\n",
+        b"\r\n\r\n\0\r\nQUIT\n\x22\x11\x00\x00",
+        b"\r\n\r\n\0\r\nQUIT\n\x21\x11\x00\x04\x00\x00\x00\x00",
+    ] {
+        let (stream, _client) = connect(header).await;
+
+        assert!(matches!(
+            builder.accept(stream).await,
+            Err(Error::Upgrade(upgrade::Error::InvalidProxyHeader))
+        ));
+    }
+
+    // Lines longer than the maximum length
+    let mut header = b"PROXY ".to_vec();
+    header.resize(200, b'A');
+    let (stream, _client) = connect(&header).await;
+    assert!(matches!(
+        builder.accept(stream).await,
+        Err(Error::Upgrade(upgrade::Error::InvalidProxyHeader))
+    ));
+}
+
+#[tokio::test]
+async fn test_max_connections_per_ip() {
+    let builder = ServerBuilder::new()
+        .proxy_protocol(true)
+        .max_connections_per_ip(1);
+
+    let (stream, _first_client) =
+        connect(b"PROXY TCP4 198.51.100.1 203.0.113.7 1000 443\r\n").await;
+    let _first = builder.accept_from(stream, PROXY).await.unwrap();
+
+    // Connections from other clients via the same proxy are accepted
+    let (stream, _second_client) =
+        connect(b"PROXY TCP4 198.51.100.2 203.0.113.7 1000 443\r\n").await;
+    let _second = builder.accept_from(stream, PROXY).await.unwrap();
+
+    let (stream, _) = connect(b"PROXY TCP4 198.51.100.1 203.0.113.7 1001 443\r\n").await;
+    assert!(matches!(
+        builder.accept_from(stream, PROXY).await,
+        Err(Error::Upgrade(upgrade::Error::TooManyConnections))
+    ));
+}
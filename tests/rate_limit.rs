@@ -0,0 +1,90 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use tokio_websockets::{
+    proto::{Frame, OpCode},
+    CloseCode, Config, Error, Limits, WebSocketStream,
+};
+
+#[tokio::test]
+async fn test_message_rate() {
+    let limits = Limits::default().max_message_rate(3, Duration::from_secs(10));
+    let (mut client, mut server) = WebSocketStream::pair_with_config(Config::default(), limits);
+
+    for _ in 0..4 {
+        client.send_text("hello").await.unwrap();
+    }
+
+    for _ in 0..3 {
+        assert!(server.next().await.unwrap().unwrap().is_text());
+    }
+    assert!(matches!(
+        server.next().await,
+        Some(Err(Error::RateLimitExceeded))
+    ));
+    // The close frame is sent once the stream is polled again
+    assert!(server.next().await.is_none());
+
+    let message = client.next().await.unwrap().unwrap();
+    assert_eq!(
+        message.as_close(),
+        Some((CloseCode::POLICY_VIOLATION, "rate limit exceeded"))
+    );
+}
+
+#[tokio::test]
+async fn test_message_rate_fragments() {
+    let limits = Limits::default().max_message_rate(1, Duration::from_secs(10));
+    let (mut client, mut server) = WebSocketStream::pair_with_config(Config::default(), limits);
+
+    // The frames of a single message only count once
+    client
+        .feed_frame(Frame::new(OpCode::Text, false, 0, "hel"))
+        .await
+        .unwrap();
+    client
+        .send_frame(Frame::new(OpCode::Continuation, true, 0, "lo"))
+        .await
+        .unwrap();
+    client.send_text("again").await.unwrap();
+
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("hello"));
+    assert!(matches!(
+        server.next().await,
+        Some(Err(Error::RateLimitExceeded))
+    ));
+}
+
+#[tokio::test]
+async fn test_message_rate_refill() {
+    let limits = Limits::default().max_message_rate(2, Duration::from_millis(100));
+    let (mut client, mut server) = WebSocketStream::pair_with_config(Config::default(), limits);
+
+    for _ in 0..3 {
+        for _ in 0..2 {
+            client.send_text("hello").await.unwrap();
+            assert!(server.next().await.unwrap().unwrap().is_text());
+        }
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_byte_rate() {
+    let limits = Limits::default().max_byte_rate(10, Duration::from_secs(10));
+    let (mut client, mut server) = WebSocketStream::pair_with_config(Config::default(), limits);
+
+    client.send_binary(&b"123456"[..]).await.unwrap();
+    client.send_binary(&b"123456"[..]).await.unwrap();
+
+    assert!(server.next().await.unwrap().unwrap().is_binary());
+    assert!(matches!(
+        server.next().await,
+        Some(Err(Error::RateLimitExceeded))
+    ));
+    assert!(server.next().await.is_none());
+}
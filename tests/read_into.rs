@@ -0,0 +1,173 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::BytesMut;
+use futures_util::StreamExt;
+use tokio::io::{duplex, AsyncWrite, AsyncWriteExt};
+use tokio_websockets::{
+    proto::{encode_frame, Frame, OpCode},
+    ClientBuilder, Config, Error, Limits, Message, ServerBuilder, WebSocketStream,
+};
+
+/// Encodes unmasked frames as sent by a server.
+fn encode(frames: &[Frame]) -> BytesMut {
+    let mut buf = BytesMut::new();
+    for frame in frames {
+        encode_frame(frame, None, &mut buf);
+    }
+
+    buf
+}
+
+/// Writer that collects the data written to it and records the largest
+/// write.
+#[derive(Default)]
+struct Recorder {
+    /// Data written so far.
+    data: Vec<u8>,
+    /// Length of the largest write.
+    max_write: usize,
+}
+
+impl AsyncWrite for Recorder {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.data.extend_from_slice(buf);
+        self.max_write = self.max_write.max(buf.len());
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[tokio::test]
+async fn test_read_message_into() {
+    let (mut client, mut server) = WebSocketStream::pair();
+
+    server
+        .send_binary_reader(&b"hello world"[..], 4)
+        .await
+        .unwrap();
+    server.send_text("text").await.unwrap();
+    server.send_binary("single").await.unwrap();
+
+    let mut out = Vec::new();
+    let message = client.read_message_into(&mut out).await.unwrap().unwrap();
+    assert!(message.is_binary());
+    assert!(message.as_payload().is_empty());
+    assert_eq!(out, b"hello world");
+
+    // Other messages are returned as usual
+    let message = client.read_message_into(&mut out).await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("text"));
+
+    out.clear();
+    let message = client.read_message_into(&mut out).await.unwrap().unwrap();
+    assert!(message.is_binary());
+    assert_eq!(out, b"single");
+}
+
+#[tokio::test]
+async fn test_read_message_into_control_frames() {
+    let (one, mut two) = duplex(1024);
+    let mut client = ClientBuilder::new().take_over(one);
+
+    let frames = encode(&[
+        Frame::new(OpCode::Binary, false, 0, "hello"),
+        Frame::new(OpCode::Ping, true, 0, "ping"),
+        Frame::new(OpCode::Continuation, true, 0, " world"),
+        Frame::new(OpCode::Binary, false, 0, "incomplete"),
+        Frame::from(Message::close(None, "")),
+    ]);
+    two.write_all(&frames).await.unwrap();
+
+    // The ping in the middle of the message is answered, but not returned
+    let mut out = Vec::new();
+    let message = client.read_message_into(&mut out).await.unwrap().unwrap();
+    assert!(message.is_binary());
+    assert_eq!(out, b"hello world");
+
+    // A close frame ends the message early
+    out.clear();
+    let message = client.read_message_into(&mut out).await.unwrap().unwrap();
+    assert!(message.is_close());
+    assert_eq!(out, b"incomplete");
+    assert!(client.read_message_into(&mut out).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_read_message_into_limit() {
+    let limits = Limits::default().max_payload_len(Some(8));
+    let (mut client, mut server) = WebSocketStream::pair_with_config(Config::default(), limits);
+
+    server
+        .send_binary_reader(&b"hello world"[..], 4)
+        .await
+        .unwrap();
+
+    let mut out = Vec::new();
+    assert!(matches!(
+        client.read_message_into(&mut out).await,
+        Err(Error::PayloadTooLong {
+            len: 11,
+            max_len: 8
+        })
+    ));
+}
+
+#[tokio::test]
+async fn test_read_message_into_chunked() {
+    let (one, mut two) = duplex(1024);
+    let mut client = ClientBuilder::new()
+        .config(Config::default().chunk_threshold(Some(4096)))
+        .take_over(one);
+
+    // A single frame that only arrives bit by bit
+    let payload: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let frames = encode(&[
+        Frame::new(OpCode::Binary, true, 0, payload.clone()),
+        Frame::new(OpCode::Text, true, 0, "after"),
+    ]);
+    tokio::spawn(async move { two.write_all(&frames).await });
+
+    let mut out = Recorder::default();
+    let message = client.read_message_into(&mut out).await.unwrap().unwrap();
+    assert!(message.is_binary());
+    assert_eq!(out.data, payload);
+    assert!(out.max_write < 16 * 1024);
+
+    let message = client.next().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("after"));
+}
+
+#[tokio::test]
+async fn test_chunked_text() {
+    let (one, two) = duplex(64);
+    let mut client = ClientBuilder::new().take_over(one);
+    let mut server = ServerBuilder::new()
+        .config(Config::default().chunk_threshold(Some(16)))
+        .serve(two);
+
+    // Chunks split multi-byte characters, which are validated across them
+    let text = "héllo wörld ✓ ".repeat(100);
+    let sent = text.clone();
+    tokio::spawn(async move { client.send_text(sent).await });
+
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some(text.as_str()));
+}
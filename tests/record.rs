@@ -0,0 +1,44 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use futures_util::StreamExt;
+use tokio::io::duplex;
+use tokio_websockets::{
+    record::{Direction, Recorder, Transcript},
+    ClientBuilder, Message, ServerBuilder,
+};
+
+#[tokio::test]
+async fn test_record_and_replay() {
+    let (one, two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().serve(Recorder::new(one));
+    let mut client = ClientBuilder::new().take_over(two);
+
+    for text in ["hello", "world"] {
+        client.send(Message::text(text)).await.unwrap();
+        let message = server.next().await.unwrap().unwrap();
+        server.send(message).await.unwrap();
+        client.next().await.unwrap().unwrap();
+    }
+
+    let transcript = server.get_ref().transcript();
+    assert!(transcript
+        .events()
+        .iter()
+        .any(|event| event.direction() == Direction::Read));
+    assert!(transcript
+        .events()
+        .iter()
+        .any(|event| event.direction() == Direction::Written));
+
+    // Round-trip through the serialized format
+    let mut serialized = Vec::new();
+    transcript.write_to(&mut serialized).unwrap();
+    let transcript = Transcript::read_from(&serialized[..]).unwrap();
+
+    let mut replayed = ServerBuilder::new().serve(transcript.replay());
+    let messages: Vec<_> = (&mut replayed)
+        .map(|message| message.unwrap().as_text().unwrap().to_owned())
+        .collect()
+        .await;
+    assert_eq!(messages, ["hello", "world"]);
+}
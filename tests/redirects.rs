@@ -0,0 +1,151 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use futures_util::StreamExt;
+use http::{HeaderValue, Uri};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+use tokio_websockets::{cookie::CookieStore, upgrade, ClientBuilder, Error, ServerBuilder};
+
+/// A cookie store that keeps the last cookie set and records the URIs that
+/// cookies were requested for.
+#[derive(Default)]
+struct RecordingStore {
+    cookie: Mutex<Option<HeaderValue>>,
+    requested: Mutex<Vec<Uri>>,
+}
+
+impl CookieStore for RecordingStore {
+    fn set_cookies(&self, cookies: &mut dyn Iterator<Item = &HeaderValue>, _uri: &Uri) {
+        if let Some(cookie) = cookies.last() {
+            *self.cookie.lock().unwrap() = Some(cookie.clone());
+        }
+    }
+
+    fn cookies(&self, uri: &Uri) -> Option<HeaderValue> {
+        self.requested.lock().unwrap().push(uri.clone());
+        self.cookie.lock().unwrap().clone()
+    }
+}
+
+/// Spawns a server that redirects a single request to `location`.
+async fn redirecting_server(location: String) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        let mut request = Vec::new();
+        while !request.ends_with(b"\r\n\r\n") {
+            request.push(stream.read_u8().await.unwrap());
+        }
+
+        let response = format!("HTTP/1.1 301 Moved Permanently\r\nLocation: {location}\r\n\r\n");
+        stream.write_all(response.as_bytes()).await.unwrap();
+    });
+
+    addr
+}
+
+#[tokio::test]
+async fn test_follow_redirect() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut server = ServerBuilder::new().accept(stream).await.unwrap();
+
+        let msg = server.next().await.unwrap().unwrap();
+        server.send(msg).await.unwrap();
+    });
+
+    let redirect = redirecting_server(format!("http://{addr}/")).await;
+
+    let uri = Uri::try_from(format!("ws://{redirect}/")).unwrap();
+    let (mut client, _) = ClientBuilder::from_uri(uri)
+        .max_redirects(1)
+        .connect()
+        .await
+        .unwrap();
+
+    client.send_text("Hello").await.unwrap();
+    let msg = client.next().await.unwrap().unwrap();
+    assert_eq!(msg.as_text(), Some("Hello"));
+}
+
+#[tokio::test]
+async fn test_redirects_not_followed_by_default() {
+    let redirect = redirecting_server(String::from("ws://127.0.0.1:1/")).await;
+
+    let uri = Uri::try_from(format!("ws://{redirect}/")).unwrap();
+    let res = ClientBuilder::from_uri(uri).connect().await;
+
+    assert!(matches!(
+        res,
+        Err(Error::Upgrade(upgrade::Error::DidNotSwitchProtocols(301)))
+    ));
+}
+
+#[tokio::test]
+async fn test_too_many_redirects() {
+    let second = redirecting_server(String::from("ws://127.0.0.1:1/")).await;
+    let first = redirecting_server(format!("ws://{second}/")).await;
+
+    let uri = Uri::try_from(format!("ws://{first}/")).unwrap();
+    let res = ClientBuilder::from_uri(uri)
+        .max_redirects(1)
+        .connect()
+        .await;
+
+    assert!(matches!(
+        res,
+        Err(Error::Upgrade(upgrade::Error::Redirected(_)))
+    ));
+}
+
+#[tokio::test]
+async fn test_redirect_cookies_and_relative_location() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        let mut request = Vec::new();
+        while !request.ends_with(b"\r\n\r\n") {
+            request.push(stream.read_u8().await.unwrap());
+        }
+
+        let response = "HTTP/1.1 302 Found\r\nLocation: next\r\nSet-Cookie: session=abc\r\n\r\n";
+        stream.write_all(response.as_bytes()).await.unwrap();
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut server = ServerBuilder::new().accept(stream).await.unwrap();
+        while server.next().await.is_some() {}
+    });
+
+    let store = Arc::new(RecordingStore::default());
+    let uri = Uri::try_from(format!("ws://{addr}/login/start")).unwrap();
+    ClientBuilder::from_uri(uri.clone())
+        .max_redirects(1)
+        .cookie_store(store.clone())
+        .connect()
+        .await
+        .unwrap();
+
+    // The cookie set by the redirect is sent to the relative target
+    let next = Uri::try_from(format!("ws://{addr}/login/next")).unwrap();
+    assert_eq!(*store.requested.lock().unwrap(), [uri, next]);
+    assert_eq!(
+        *store.cookie.lock().unwrap(),
+        Some(HeaderValue::from_static("session=abc"))
+    );
+}
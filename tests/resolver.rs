@@ -0,0 +1,87 @@
+#![cfg(feature = "client")]
+
+use std::{
+    net::SocketAddr,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use tokio_websockets::{
+    resolver::{Cached, Resolver},
+    ClientBuilder, Error,
+};
+
+struct CountingResolver {
+    lookups: AtomicUsize,
+}
+
+impl Resolver for CountingResolver {
+    async fn resolve(&self, host: &str, port: u16) -> Result<SocketAddr, Error> {
+        if host == "invalid.test" {
+            return Err(Error::CannotResolveHost);
+        }
+
+        let lookup = self.lookups.fetch_add(1, Ordering::Relaxed);
+
+        Ok(SocketAddr::from(([127, 0, 0, 1], port + lookup as u16)))
+    }
+}
+
+fn counting(ttl: Duration) -> Cached<CountingResolver> {
+    Cached::new(
+        CountingResolver {
+            lookups: AtomicUsize::new(0),
+        },
+        ttl,
+    )
+}
+
+#[tokio::test]
+async fn test_cached() {
+    let resolver = counting(Duration::from_secs(60));
+
+    let addr = resolver.resolve("cached.test", 80).await.unwrap();
+    assert_eq!(resolver.resolve("cached.test", 80).await.unwrap(), addr);
+    assert_eq!(
+        resolver.resolve_all("cached.test", 80).await.unwrap(),
+        [addr]
+    );
+
+    // Ports are cached separately
+    assert_ne!(resolver.resolve("cached.test", 443).await.unwrap(), addr);
+
+    // Failed lookups are not cached
+    assert!(resolver.resolve("invalid.test", 80).await.is_err());
+    assert!(resolver.resolve("invalid.test", 80).await.is_err());
+
+    // The cache can be shared by builders for reconnecting
+    let _builder = ClientBuilder::new().resolver(&resolver);
+}
+
+#[tokio::test]
+async fn test_ttl() {
+    let resolver = counting(Duration::from_millis(50));
+
+    let addr = resolver.resolve("cached.test", 80).await.unwrap();
+    assert_eq!(resolver.resolve("cached.test", 80).await.unwrap(), addr);
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_ne!(resolver.resolve("cached.test", 80).await.unwrap(), addr);
+}
+
+#[tokio::test]
+async fn test_invalidate() {
+    let resolver = counting(Duration::from_secs(60));
+
+    let addr = resolver.resolve("cached.test", 80).await.unwrap();
+    let other = resolver.resolve("other.test", 80).await.unwrap();
+
+    resolver.invalidate("cached.test", 80).await;
+    let renewed = resolver.resolve("cached.test", 80).await.unwrap();
+    assert_ne!(renewed, addr);
+    assert_eq!(resolver.resolve("other.test", 80).await.unwrap(), other);
+
+    resolver.clear().await;
+    assert_ne!(resolver.resolve("cached.test", 80).await.unwrap(), renewed);
+    assert_ne!(resolver.resolve("other.test", 80).await.unwrap(), other);
+}
@@ -0,0 +1,147 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use tokio::net::TcpListener;
+use tokio_websockets::{
+    client::{ConnectPhase, RetryPolicy},
+    resolver::Resolver,
+    ClientBuilder, Error, ServerBuilder,
+};
+
+/// Resolver that fails the first `failures` lookups.
+struct FlakyResolver {
+    addr: SocketAddr,
+    failures: usize,
+    lookups: Arc<AtomicUsize>,
+}
+
+impl Resolver for FlakyResolver {
+    async fn resolve(&self, _host: &str, _port: u16) -> Result<SocketAddr, Error> {
+        if self.lookups.fetch_add(1, Ordering::Relaxed) < self.failures {
+            return Err(Error::CannotResolveHost);
+        }
+
+        Ok(self.addr)
+    }
+}
+
+fn policy() -> RetryPolicy {
+    RetryPolicy::new()
+        .initial_backoff(Duration::from_millis(1))
+        .jitter(false)
+}
+
+async fn server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move { ServerBuilder::new().accept(stream).await });
+        }
+    });
+
+    addr
+}
+
+fn flaky_builder(
+    addr: SocketAddr,
+    failures: usize,
+) -> (ClientBuilder<'static, FlakyResolver>, Arc<AtomicUsize>) {
+    let lookups = Arc::new(AtomicUsize::new(0));
+    let builder = ClientBuilder::new()
+        .uri("ws://retry.test")
+        .unwrap()
+        .resolver(FlakyResolver {
+            addr,
+            failures,
+            lookups: Arc::clone(&lookups),
+        });
+
+    (builder, lookups)
+}
+
+#[tokio::test]
+async fn test_retry() {
+    let addr = server().await;
+
+    let (builder, lookups) = flaky_builder(addr, 2);
+    builder
+        .retry_policy(Some(policy()))
+        .connect()
+        .await
+        .unwrap();
+    assert_eq!(lookups.load(Ordering::Relaxed), 3);
+
+    // Attempts are not retried by default
+    let (builder, lookups) = flaky_builder(addr, 2);
+    assert!(matches!(
+        builder.connect().await,
+        Err(Error::CannotResolveHost)
+    ));
+    assert_eq!(lookups.load(Ordering::Relaxed), 1);
+}
+
+#[tokio::test]
+async fn test_max_retries() {
+    let (builder, lookups) = flaky_builder(server().await, usize::MAX);
+    let res = builder
+        .retry_policy(Some(policy().max_retries(5)))
+        .connect()
+        .await;
+
+    assert!(matches!(res, Err(Error::CannotResolveHost)));
+    assert_eq!(lookups.load(Ordering::Relaxed), 6);
+}
+
+#[tokio::test]
+async fn test_retry_phase() {
+    let (builder, lookups) = flaky_builder(server().await, 2);
+    let res = builder
+        .retry_policy(Some(policy().retry_phase(ConnectPhase::Resolve, false)))
+        .connect()
+        .await;
+
+    assert!(matches!(res, Err(Error::CannotResolveHost)));
+    assert_eq!(lookups.load(Ordering::Relaxed), 1);
+}
+
+#[tokio::test]
+async fn test_retry_upgrade() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let accepted = Arc::new(AtomicUsize::new(0));
+
+    // Close every connection without answering the upgrade request
+    let counter = Arc::clone(&accepted);
+    tokio::spawn(async move {
+        loop {
+            let connection = listener.accept().await.unwrap();
+            counter.fetch_add(1, Ordering::Relaxed);
+            drop(connection);
+        }
+    });
+
+    // Failed upgrades are not retried by default
+    let (builder, _) = flaky_builder(addr, 0);
+    assert!(builder
+        .retry_policy(Some(policy()))
+        .connect()
+        .await
+        .is_err());
+    assert_eq!(accepted.load(Ordering::Relaxed), 1);
+
+    let (builder, _) = flaky_builder(addr, 0);
+    let policy = policy().retry_phase(ConnectPhase::Upgrade, true);
+    assert!(builder.retry_policy(Some(policy)).connect().await.is_err());
+    assert_eq!(accepted.load(Ordering::Relaxed), 5);
+}
@@ -0,0 +1,61 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use futures_util::StreamExt;
+use tokio::io::{duplex, DuplexStream};
+use tokio_websockets::{server::Router, upgrade, ClientBuilder, Error, ServerBuilder};
+
+/// Creates a router that replies with a single message identifying the route.
+fn router() -> Router<DuplexStream> {
+    Router::new(ServerBuilder::new())
+        .route("/a", |mut stream, _| async move {
+            stream.send_text("a").await.unwrap();
+        })
+        .route("/b", |mut stream, request| async move {
+            let query = request.uri().query().unwrap_or_default().to_owned();
+            stream.send_text(format!("b {query}")).await.unwrap();
+        })
+}
+
+#[tokio::test]
+async fn test_router() {
+    let router = router();
+
+    for (uri, expected) in [
+        ("ws://localhost/a", "a"),
+        ("ws://localhost/b?id=1", "b id=1"),
+    ] {
+        let (one, two) = duplex(1024);
+        let server = router.accept(two);
+        let client = async {
+            let (mut client, _) = ClientBuilder::new()
+                .uri(uri)
+                .unwrap()
+                .connect_on(one)
+                .await
+                .unwrap();
+            let message = client.next().await.unwrap().unwrap();
+            assert_eq!(message.as_text(), Some(expected));
+        };
+
+        let (result, ()) = tokio::join!(server, client);
+        result.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_router_not_found() {
+    let router = router();
+    let (one, two) = duplex(1024);
+
+    let builder = ClientBuilder::new().uri("ws://localhost/c").unwrap();
+    let (server, client) = tokio::join!(router.accept(two), builder.connect_on(one));
+
+    assert!(matches!(
+        server,
+        Err(Error::Upgrade(upgrade::Error::NotFound))
+    ));
+    assert!(matches!(
+        client,
+        Err(Error::Upgrade(upgrade::Error::DidNotSwitchProtocols(404)))
+    ));
+}
@@ -0,0 +1,61 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::sync::{Arc, Mutex};
+
+use bytes::{Buf, Bytes};
+use futures_util::StreamExt;
+use tokio_websockets::{
+    proto::{ConnectionId, Frame, FrameInterceptor},
+    Config, Error, Limits, Message, WebSocketStream,
+};
+
+/// Records the payload lengths of all received frames.
+#[derive(Default, Clone)]
+struct FrameLengths(Arc<Mutex<Vec<usize>>>);
+
+impl FrameInterceptor for FrameLengths {
+    fn on_frame_received(
+        &mut self,
+        _: ConnectionId,
+        frame: &Frame,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.0.lock().unwrap().push(frame.payload().len());
+
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_send_binary_buf() {
+    let config = Config::default().frame_size(4);
+    let (mut client, mut server) = WebSocketStream::pair_with_config(config, Limits::default());
+    let lengths = FrameLengths::default();
+    server.set_frame_interceptor(lengths.clone());
+
+    let payload = Bytes::from_static(b"hello ").chain(Bytes::from_static(b"world"));
+    client.send_binary_buf(payload).await.unwrap();
+
+    let message = server.next().await.unwrap().unwrap();
+    assert!(message.is_binary());
+    assert_eq!(&*message.into_payload(), b"hello world");
+
+    // Frames end at chunk boundaries and after at most frame_size bytes
+    assert_eq!(*lengths.0.lock().unwrap(), [4, 2, 4, 1]);
+}
+
+#[tokio::test]
+async fn test_send_empty_binary_buf() {
+    let (mut client, mut server) = WebSocketStream::pair();
+
+    server.send_binary_buf(Bytes::new()).await.unwrap();
+    let message = client.next().await.unwrap().unwrap();
+    assert!(message.is_binary());
+    assert!(message.as_payload().is_empty());
+
+    client.send(Message::close(None, "")).await.unwrap();
+    assert!(server.next().await.unwrap().unwrap().is_close());
+    assert!(matches!(
+        server.send_binary_buf(&b"closed"[..]).await,
+        Err(Error::AlreadyClosed)
+    ));
+}
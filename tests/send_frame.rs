@@ -0,0 +1,67 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use futures_util::StreamExt;
+use tokio_websockets::{
+    proto::{Frame, OpCode, ProtocolError, StreamState},
+    Error, WebSocketStream,
+};
+
+#[tokio::test]
+async fn test_send_fragmented() {
+    let (mut client, mut server) = WebSocketStream::pair();
+
+    client
+        .feed_frame(Frame::new(OpCode::Text, false, 0, "hel"))
+        .await
+        .unwrap();
+    client
+        .feed_frame(Frame::new(OpCode::Ping, true, 0, "ping"))
+        .await
+        .unwrap();
+    client
+        .send_frame(Frame::new(OpCode::Continuation, true, 0, "lo"))
+        .await
+        .unwrap();
+
+    // The ping is received in between the frames of the message
+    assert!(server.next().await.unwrap().unwrap().is_ping());
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("hello"));
+}
+
+#[tokio::test]
+async fn test_send_invalid() {
+    let (mut client, mut server) = WebSocketStream::pair();
+
+    // Frames are sent without validating them
+    let frame = Frame::new(OpCode::Ping, false, 0, "");
+    assert!(matches!(
+        frame.validate(),
+        Err(ProtocolError::FragmentedControlFrame)
+    ));
+    client.send_frame(frame).await.unwrap();
+
+    assert!(matches!(
+        server.next().await,
+        Some(Err(Error::Protocol(ProtocolError::FragmentedControlFrame)))
+    ));
+}
+
+#[tokio::test]
+async fn test_send_close_frame() {
+    let (mut client, mut server) = WebSocketStream::pair();
+
+    client
+        .send_frame(Frame::new(OpCode::Close, true, 0, ""))
+        .await
+        .unwrap();
+    assert_eq!(client.state(), StreamState::ClosedByUs);
+    assert!(matches!(
+        client
+            .send_frame(Frame::new(OpCode::Text, true, 0, ""))
+            .await,
+        Err(Error::AlreadyClosed)
+    ));
+
+    assert!(server.next().await.unwrap().unwrap().is_close());
+}
@@ -0,0 +1,102 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::{
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use futures_util::StreamExt;
+use tokio::io::{AsyncRead, ReadBuf};
+use tokio_websockets::{
+    proto::{ConnectionId, Frame, FrameInterceptor},
+    CloseCode, Error, WebSocketStream,
+};
+
+/// Records the payload lengths of all received frames.
+#[derive(Default, Clone)]
+struct FrameLengths(Arc<Mutex<Vec<usize>>>);
+
+impl FrameInterceptor for FrameLengths {
+    fn on_frame_received(
+        &mut self,
+        _: ConnectionId,
+        frame: &Frame,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.0.lock().unwrap().push(frame.payload().len());
+
+        Ok(())
+    }
+}
+
+/// Yields a single byte per read and fails after `remaining` bytes.
+struct FailingReader {
+    remaining: usize,
+}
+
+impl AsyncRead for FailingReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.remaining == 0 {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+
+        self.remaining -= 1;
+        buf.put_slice(b"a");
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[tokio::test]
+async fn test_send_binary_reader() {
+    for (data, expected) in [
+        (&b"hello world"[..], &[4, 4, 3][..]),
+        (b"12345678", &[4, 4, 0]),
+        (b"", &[0]),
+    ] {
+        let (mut client, mut server) = WebSocketStream::pair();
+        let lengths = FrameLengths::default();
+        server.set_frame_interceptor(lengths.clone());
+
+        client.send_binary_reader(data, 4).await.unwrap();
+
+        let message = server.next().await.unwrap().unwrap();
+        assert!(message.is_binary());
+        assert_eq!(&*message.into_payload(), data);
+        assert_eq!(*lengths.0.lock().unwrap(), expected);
+    }
+}
+
+#[tokio::test]
+async fn test_send_binary_reader_error() {
+    let (mut client, mut server) = WebSocketStream::pair();
+
+    assert!(matches!(
+        client
+            .send_binary_reader(FailingReader { remaining: 6 }, 4)
+            .await,
+        Err(Error::Io(err)) if err.kind() == io::ErrorKind::BrokenPipe
+    ));
+
+    // The incomplete message is abandoned by closing the connection
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(
+        message.as_close().map(|(code, _)| code),
+        Some(CloseCode::INTERNAL_SERVER_ERROR)
+    );
+
+    // Failing before anything was sent leaves the connection intact
+    let (mut client, mut server) = WebSocketStream::pair();
+    assert!(client
+        .send_binary_reader(FailingReader { remaining: 3 }, 4)
+        .await
+        .is_err());
+    client.send_text("still open").await.unwrap();
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("still open"));
+}
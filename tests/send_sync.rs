@@ -0,0 +1,231 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::{
+    cell::RefCell,
+    future::Future,
+    io,
+    marker::PhantomData,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use futures_util::StreamExt;
+use http::Uri;
+use tokio::{
+    io::{duplex, AsyncRead, AsyncWrite, DuplexStream, ReadBuf},
+    net::TcpStream,
+    task::LocalSet,
+};
+use tokio_websockets::{
+    driver, mux,
+    proto::{ConnectionId, ControlSender, Frame, FrameInterceptor, OpCode},
+    ClientBuilder, Config, Error, Limits, Message, ServerBuilder, WebSocketStream,
+};
+
+thread_local! {
+    /// State that is not [`Send`], written to by hooks on a [`LocalSet`].
+    static LOG: Rc<RefCell<Vec<String>>> = Rc::default();
+}
+
+fn assert_send<T: Send>() {}
+
+fn assert_sync<T: Sync>() {}
+
+fn assert_send_future<F: Future + Send>(_: F) {}
+
+/// Transport that is neither [`Send`] nor [`Sync`], like those of
+/// single-threaded runtimes.
+struct LocalStream {
+    inner: DuplexStream,
+    _local: PhantomData<Rc<()>>,
+}
+
+impl LocalStream {
+    fn pair() -> (Self, Self) {
+        let (one, two) = duplex(64 * 1024);
+
+        (
+            Self {
+                inner: one,
+                _local: PhantomData,
+            },
+            Self {
+                inner: two,
+                _local: PhantomData,
+            },
+        )
+    }
+}
+
+impl AsyncRead for LocalStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for LocalStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Interceptor that records received text frames in the thread-local log.
+struct LocalLogger;
+
+impl FrameInterceptor for LocalLogger {
+    fn on_frame_received(
+        &mut self,
+        _: ConnectionId,
+        frame: &Frame,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if frame.opcode() == OpCode::Text {
+            let text = String::from_utf8_lossy(frame.payload()).into_owned();
+            LOG.with(|log| log.borrow_mut().push(text));
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_send_sync() {
+    assert_send::<WebSocketStream<TcpStream>>();
+    assert_sync::<WebSocketStream<TcpStream>>();
+    assert_send::<WebSocketStream<DuplexStream>>();
+    assert_sync::<WebSocketStream<DuplexStream>>();
+    assert_send::<Message>();
+    assert_send::<Error>();
+    assert_sync::<Error>();
+    assert_send::<Config>();
+    assert_send::<Limits>();
+    assert_send::<ControlSender>();
+    assert_sync::<ControlSender>();
+    assert_send::<ClientBuilder<'static>>();
+    assert_sync::<ServerBuilder>();
+    assert_send::<driver::Sender>();
+    assert_sync::<driver::Sender>();
+    assert_send::<driver::Receiver>();
+    assert_send::<mux::Multiplexer>();
+    assert_send::<mux::Channel>();
+}
+
+#[tokio::test]
+async fn test_futures_are_send() {
+    let (one, two) = duplex(64 * 1024);
+    let client = ClientBuilder::from_uri(Uri::from_static("ws://localhost"));
+    let server = ServerBuilder::new();
+
+    assert_send_future(client.connect_on(one));
+    assert_send_future(server.accept(two));
+
+    let (mut client, mut server) = WebSocketStream::pair();
+    assert_send_future(client.send(Message::text("hello")));
+    assert_send_future(server.next());
+    assert_send_future(client.close());
+}
+
+#[tokio::test]
+async fn test_not_send_transport() {
+    let (one, two) = LocalStream::pair();
+
+    LocalSet::new()
+        .run_until(async move {
+            let server = tokio::task::spawn_local(async move {
+                let mut server = ServerBuilder::new().accept(two).await.unwrap();
+
+                while let Some(Ok(message)) = server.next().await {
+                    if message.is_text() {
+                        server.send(message).await.unwrap();
+                    }
+                }
+            });
+
+            let (mut client, _) = ClientBuilder::from_uri(Uri::from_static("ws://localhost"))
+                .connect_on(one)
+                .await
+                .unwrap();
+
+            client.send(Message::text("hello")).await.unwrap();
+            let message = client.next().await.unwrap().unwrap();
+            assert_eq!(message.as_text(), Some("hello"));
+
+            client.close().await.unwrap();
+            server.await.unwrap();
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn test_spawn_local() {
+    let (one, two) = LocalStream::pair();
+    let (three, four) = LocalStream::pair();
+
+    LocalSet::new()
+        .run_until(async move {
+            let (sender, mut receiver) =
+                driver::Builder::new().spawn_local(ClientBuilder::new().take_over(one));
+            let mut server = ServerBuilder::new().serve(two);
+
+            sender.send("hello").await.unwrap();
+            let message = server.next().await.unwrap().unwrap();
+            assert_eq!(message.as_text(), Some("hello"));
+
+            server.send(Message::text("world")).await.unwrap();
+            let message = receiver.recv().await.unwrap().unwrap();
+            assert_eq!(message.as_text(), Some("world"));
+
+            let client = mux::Builder::new().spawn_local(ClientBuilder::new().take_over(three));
+            let mut server = mux::Builder::new().spawn_local(ServerBuilder::new().serve(four));
+
+            let mut channel = client.open(1).unwrap();
+            channel.send("ping").await.unwrap();
+
+            let mut accepted = server.accept().await.unwrap();
+            assert_eq!(accepted.id(), 1);
+            assert_eq!(&accepted.recv().await.unwrap()[..], b"ping");
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn test_local_hooks() {
+    let (one, two) = LocalStream::pair();
+    let log = LOG.with(Rc::clone);
+
+    LocalSet::new()
+        .run_until(async move {
+            let mut client = ClientBuilder::new().take_over(one);
+            let mut server = ServerBuilder::new().serve(two);
+            server.set_frame_interceptor(LocalLogger);
+            server.set_close_observer(|event| {
+                LOG.with(|log| log.borrow_mut().push(format!("{:?}", event.initiator())));
+            });
+
+            let server =
+                tokio::task::spawn_local(async move { while server.next().await.is_some() {} });
+
+            client.send(Message::text("hello")).await.unwrap();
+            client.close().await.unwrap();
+            server.await.unwrap();
+        })
+        .await;
+
+    assert_eq!(*log.borrow(), ["hello", "Peer"]);
+}
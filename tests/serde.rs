@@ -0,0 +1,77 @@
+#![cfg(feature = "serde")]
+
+use serde_json::json;
+use tokio_websockets::{CloseCode, Message};
+
+#[test]
+fn test_serialize() {
+    let cases = [
+        (Message::text("hello"), json!({ "Text": "hello" })),
+        (
+            Message::binary(&b"\x01\x02"[..]),
+            json!({ "Binary": [1, 2] }),
+        ),
+        (
+            Message::ping(&b"ping"[..]),
+            json!({ "Ping": [112, 105, 110, 103] }),
+        ),
+        (Message::pong(&b""[..]), json!({ "Pong": [] })),
+        (Message::close(None, ""), json!({ "Close": null })),
+        (
+            Message::close(Some(CloseCode::GOING_AWAY), "bye"),
+            json!({ "Close": { "code": 1001, "reason": "bye" } }),
+        ),
+    ];
+
+    for (message, value) in cases {
+        assert_eq!(serde_json::to_value(&message).unwrap(), value);
+
+        let decoded: Message = serde_json::from_value(value).unwrap();
+        assert_eq!(
+            serde_json::to_value(&decoded).unwrap(),
+            serde_json::to_value(&message).unwrap()
+        );
+    }
+}
+
+#[test]
+fn test_serialize_invalid_utf8() {
+    // Text messages created from bytes are not validated up front
+    let message = Message::text(&b"\xff"[..]);
+    assert!(serde_json::to_value(&message).is_err());
+}
+
+#[test]
+fn test_roundtrip() {
+    let message = Message::close(Some(CloseCode::try_from(4000).unwrap()), "done");
+    let json = serde_json::to_string(&message).unwrap();
+    let decoded: Message = serde_json::from_str(&json).unwrap();
+
+    assert!(decoded.is_close());
+    assert_eq!(
+        decoded.as_close(),
+        Some((CloseCode::try_from(4000).unwrap(), "done"))
+    );
+}
+
+#[test]
+fn test_invalid() {
+    // Close codes outside of the valid ranges
+    assert!(serde_json::from_value::<CloseCode>(json!(999)).is_err());
+    assert!(serde_json::from_value::<CloseCode>(json!(2000)).is_err());
+    assert_eq!(
+        serde_json::from_value::<CloseCode>(json!(1000)).unwrap(),
+        CloseCode::NORMAL_CLOSURE
+    );
+
+    // Control frame payloads larger than 125 bytes
+    let large = vec![0; 126];
+    assert!(serde_json::from_value::<Message>(json!({ "Ping": large })).is_err());
+    let reason = "a".repeat(124);
+    assert!(serde_json::from_value::<Message>(
+        json!({ "Close": { "code": 1000, "reason": reason } })
+    )
+    .is_err());
+
+    assert!(serde_json::from_value::<Message>(json!({ "Unknown": [] })).is_err());
+}
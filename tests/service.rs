@@ -0,0 +1,42 @@
+#![cfg(all(feature = "client", feature = "server", feature = "tower"))]
+
+use std::future::poll_fn;
+
+use futures_util::StreamExt;
+use http::Uri;
+use tokio::net::TcpListener;
+use tokio_websockets::{ClientBuilder, Error, ServerBuilder};
+use tower_service::Service;
+
+#[tokio::test]
+async fn test_connect_service() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        for _ in 0..2 {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut server = ServerBuilder::new().accept(stream).await.unwrap();
+            server.send_text("hello").await.unwrap();
+        }
+    });
+
+    let mut service = ClientBuilder::new().into_service();
+    let uri: Uri = format!("ws://{addr}").parse().unwrap();
+
+    // Clones share the configuration and may be used concurrently
+    for mut service in [service.clone(), service.clone()] {
+        poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+        let (mut client, _) = service.call(uri.clone()).await.unwrap();
+        let message = client.next().await.unwrap().unwrap();
+        assert_eq!(message.as_text(), Some("hello"));
+    }
+
+    // Connection errors are returned from the response future
+    poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+    let uri: Uri = "/no-host".parse().unwrap();
+    assert!(matches!(
+        service.call(uri).await,
+        Err(Error::CannotResolveHost)
+    ));
+}
@@ -0,0 +1,54 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use http::Uri;
+use tokio::io::duplex;
+use tokio_websockets::{server::Shutdown, ClientBuilder, CloseCode, ServerBuilder};
+
+#[tokio::test]
+async fn test_shutdown_closes_connections() {
+    let shutdown = Shutdown::new();
+    let builder = ServerBuilder::new().shutdown(shutdown.clone());
+
+    let (one, two) = duplex(usize::MAX);
+
+    let server = tokio::spawn(async move {
+        let mut server = builder.accept(one).await.unwrap();
+
+        while let Some(Ok(_)) = server.next().await {}
+    });
+
+    let (mut client, _) = ClientBuilder::from_uri(Uri::from_static("ws://localhost"))
+        .connect_on(two)
+        .await
+        .unwrap();
+
+    assert_eq!(shutdown.connections(), 1);
+
+    let client = tokio::spawn(async move {
+        let msg = client.next().await.unwrap().unwrap();
+        assert_eq!(msg.as_close().unwrap().0, CloseCode::GOING_AWAY);
+
+        assert!(client.next().await.is_none());
+    });
+
+    assert!(shutdown.shutdown(Duration::from_secs(5)).await);
+    assert_eq!(shutdown.connections(), 0);
+
+    server.await.unwrap();
+    client.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_shutdown_timeout() {
+    let shutdown = Shutdown::new();
+    let (one, _two) = duplex(usize::MAX);
+
+    // The stream is never polled, so it is never dropped
+    let _server = ServerBuilder::new().shutdown(shutdown.clone()).serve(one);
+
+    assert!(!shutdown.shutdown(Duration::from_millis(50)).await);
+    assert_eq!(shutdown.connections(), 1);
+}
@@ -0,0 +1,145 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures_util::StreamExt;
+use tokio::time::timeout;
+use tokio_websockets::{
+    driver,
+    slow_consumer::{Event, Policy},
+    topics::Topics,
+    CloseCode, Error, WebSocketStream,
+};
+
+/// Returns a hook that records events and the events recorded by it.
+fn recorder() -> (impl Fn(Event) + Send + Sync, Arc<Mutex<Vec<Event>>>) {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = Arc::clone(&events);
+
+    (move |event| events.lock().unwrap().push(event), recorded)
+}
+
+#[tokio::test]
+async fn test_drop_newest() {
+    let (hook, events) = recorder();
+    let topics = Topics::new().on_slow_consumer(hook);
+    let mut subscriber = topics.subscriber(1);
+    subscriber.subscribe("a");
+
+    assert_eq!(topics.publish("a", "first").await, 1);
+    assert_eq!(topics.publish("a", "second").await, 0);
+
+    assert_eq!(subscriber.recv().await.unwrap().as_text(), Some("first"));
+    assert_eq!(*events.lock().unwrap(), [Event::DroppedNewest]);
+}
+
+#[tokio::test]
+async fn test_drop_oldest() {
+    let (hook, events) = recorder();
+    let topics = Topics::new()
+        .slow_consumer_policy(Policy::DropOldest)
+        .on_slow_consumer(hook);
+    let mut subscriber = topics.subscriber(2);
+    subscriber.subscribe("a");
+
+    for message in ["first", "second", "third"] {
+        assert_eq!(topics.publish("a", message).await, 1);
+    }
+
+    assert_eq!(subscriber.recv().await.unwrap().as_text(), Some("second"));
+    assert_eq!(subscriber.recv().await.unwrap().as_text(), Some("third"));
+    assert_eq!(*events.lock().unwrap(), [Event::DroppedOldest]);
+}
+
+#[tokio::test]
+async fn test_block() {
+    let (hook, events) = recorder();
+    let topics = Topics::new()
+        .slow_consumer_policy(Policy::Block)
+        .on_slow_consumer(hook);
+    let mut subscriber = topics.subscriber(1);
+    subscriber.subscribe("a");
+
+    assert_eq!(topics.publish("a", "first").await, 1);
+
+    let publisher = topics.clone();
+    let publish = tokio::spawn(async move { publisher.publish("a", "second").await });
+    // Publishing waits until the subscriber catches up
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!publish.is_finished());
+
+    assert_eq!(subscriber.recv().await.unwrap().as_text(), Some("first"));
+    assert_eq!(publish.await.unwrap(), 1);
+    assert_eq!(subscriber.recv().await.unwrap().as_text(), Some("second"));
+    assert_eq!(*events.lock().unwrap(), [Event::Blocked]);
+}
+
+#[tokio::test]
+async fn test_disconnect() {
+    let (hook, events) = recorder();
+    let topics = Topics::new()
+        .slow_consumer_policy(Policy::Disconnect(CloseCode::POLICY_VIOLATION))
+        .on_slow_consumer(hook);
+    let mut subscriber = topics.subscriber(1);
+    subscriber.subscribe("a");
+
+    assert_eq!(topics.publish("a", "first").await, 1);
+    assert_eq!(topics.publish("a", "second").await, 0);
+    assert_eq!(topics.publish("a", "third").await, 0);
+
+    // Queued messages are replaced by a close message
+    let message = subscriber.recv().await.unwrap();
+    assert_eq!(
+        message.as_close().map(|(code, _)| code),
+        Some(CloseCode::POLICY_VIOLATION)
+    );
+    assert!(subscriber.recv().await.is_none());
+    assert_eq!(
+        *events.lock().unwrap(),
+        [Event::Disconnected(CloseCode::POLICY_VIOLATION)]
+    );
+}
+
+#[tokio::test]
+async fn test_driver_disconnect() {
+    let (hook, events) = recorder();
+    let (client, mut server) = WebSocketStream::pair();
+    let (sender, _receiver) = driver::Builder::new()
+        .channel_capacity(1)
+        .slow_consumer_policy(Policy::Disconnect(CloseCode::SERVICE_OVERLOAD))
+        .on_slow_consumer(hook)
+        .spawn(client);
+
+    // The server does not read, so writes eventually stall and the queue fills
+    let payload = vec![0; 64 * 1024];
+    let result = timeout(Duration::from_secs(5), async {
+        loop {
+            if let Err(e) = sender.send(payload.clone()).await {
+                return e;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert!(matches!(result, Error::AlreadyClosed));
+    assert!(sender.is_closed());
+    assert_eq!(
+        *events.lock().unwrap(),
+        [Event::Disconnected(CloseCode::SERVICE_OVERLOAD)]
+    );
+
+    // The close message follows the messages that were already being sent
+    let close = loop {
+        let message = server.next().await.unwrap().unwrap();
+        if message.is_close() {
+            break message;
+        }
+    };
+    assert_eq!(
+        close.as_close().map(|(code, _)| code),
+        Some(CloseCode::SERVICE_OVERLOAD)
+    );
+}
@@ -0,0 +1,51 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use tokio::net::TcpListener;
+use tokio_websockets::{socket::SocketOptions, ClientBuilder, MaybeTlsStream, ServerBuilder};
+
+#[tokio::test]
+async fn test_socket_options() {
+    let options = SocketOptions::new()
+        .nodelay(true)
+        .keepalive_time(Duration::from_secs(60))
+        .keepalive_interval(Duration::from_secs(10))
+        .send_buffer_size(64 * 1024)
+        .recv_buffer_size(64 * 1024);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut server = ServerBuilder::new()
+            .socket_options(options)
+            .accept_tcp(stream)
+            .await
+            .unwrap();
+        assert!(server.get_ref().nodelay().unwrap());
+
+        let msg = server.next().await.unwrap().unwrap();
+        server.send(msg).await.unwrap();
+    });
+
+    let (mut client, _) = ClientBuilder::new()
+        .uri(&format!("ws://{addr}/"))
+        .unwrap()
+        .socket_options(options)
+        .connect()
+        .await
+        .unwrap();
+    #[allow(irrefutable_let_patterns)]
+    let MaybeTlsStream::Plain(stream) = client.get_ref() else {
+        unreachable!()
+    };
+    assert!(stream.nodelay().unwrap());
+
+    client.send_text("Hello").await.unwrap();
+    let msg = client.next().await.unwrap().unwrap();
+    assert_eq!(msg.as_text(), Some("Hello"));
+    server.await.unwrap();
+}
@@ -0,0 +1,61 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use http::{header::SEC_WEBSOCKET_PROTOCOL, HeaderValue, Uri};
+use tokio::io::duplex;
+use tokio_websockets::{ClientBuilder, ServerBuilder};
+
+/// Performs a handshake offering `offered` and returns the subprotocol
+/// selected by the server and the one echoed in its response.
+async fn negotiate(
+    server: ServerBuilder,
+    offered: &'static str,
+) -> (Option<String>, Option<String>) {
+    let (one, two) = duplex(usize::MAX);
+
+    let client = tokio::spawn(async move {
+        let (_, response) = ClientBuilder::from_uri(Uri::from_static("ws://localhost"))
+            .add_header(SEC_WEBSOCKET_PROTOCOL, HeaderValue::from_static(offered))
+            .connect_on(two)
+            .await
+            .unwrap();
+
+        response
+            .headers()
+            .get(SEC_WEBSOCKET_PROTOCOL)
+            .map(|value| value.to_str().unwrap().to_owned())
+    });
+
+    let stream = server.accept(one).await.unwrap();
+
+    (
+        stream.subprotocol().map(ToOwned::to_owned),
+        client.await.unwrap(),
+    )
+}
+
+#[tokio::test]
+async fn test_priority_list() {
+    let server = ServerBuilder::new().subprotocols(["graphql-transport-ws", "graphql-ws"]);
+    let (selected, echoed) = negotiate(server, "graphql-ws, graphql-transport-ws").await;
+
+    assert_eq!(selected.as_deref(), Some("graphql-transport-ws"));
+    assert_eq!(echoed, selected);
+}
+
+#[tokio::test]
+async fn test_no_match() {
+    let server = ServerBuilder::new().subprotocols(["mqtt"]);
+    let (selected, echoed) = negotiate(server, "chat, superchat").await;
+
+    assert_eq!(selected, None);
+    assert_eq!(echoed, None);
+}
+
+#[tokio::test]
+async fn test_callback() {
+    let server = ServerBuilder::new().select_subprotocol(|offered| offered.last().copied());
+    let (selected, echoed) = negotiate(server, "chat, superchat").await;
+
+    assert_eq!(selected.as_deref(), Some("superchat"));
+    assert_eq!(echoed, selected);
+}
@@ -0,0 +1,141 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use tokio_websockets::{CloseCode, Config, Error, Limits, WebSocketStream};
+
+#[tokio::test]
+async fn test_recv_timeout() {
+    let (mut client, mut server) = WebSocketStream::pair();
+
+    assert!(matches!(
+        server.recv_timeout(Duration::from_millis(10)).await,
+        Err(Error::ReadTimeout)
+    ));
+
+    // The stream remains usable after timing out
+    client.send_text("hello").await.unwrap();
+    let message = server
+        .recv_timeout(Duration::from_secs(5))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(message.as_text(), Some("hello"));
+
+    let (closed, message) = tokio::join!(client.close(), async {
+        let message = server.recv_timeout(Duration::from_secs(5)).await;
+        assert!(server
+            .recv_timeout(Duration::from_secs(5))
+            .await
+            .unwrap()
+            .is_none());
+
+        message
+    });
+    closed.unwrap();
+    assert!(message.unwrap().unwrap().is_close());
+}
+
+#[tokio::test]
+async fn test_idle_timeout() {
+    let config = Config::default()
+        .idle_timeout(Some(Duration::from_millis(100)))
+        .idle_timeout_close_code(CloseCode::POLICY_VIOLATION);
+    let (mut client, mut server) = WebSocketStream::pair_with_config(config, Limits::default());
+
+    // Received frames reset the timer
+    let reader = tokio::spawn(async move {
+        let mut messages = 0;
+        let error = loop {
+            match server.next().await {
+                Some(Ok(_)) => messages += 1,
+                Some(Err(e)) => break e,
+                None => panic!("stream ended without an error"),
+            }
+        };
+        assert!(matches!(error, Error::IdleTimeout));
+        assert!(server.next().await.is_none());
+
+        messages
+    });
+
+    for _ in 0..5 {
+        client.send_text("hello").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    assert_eq!(reader.await.unwrap(), 5);
+
+    // The server closed the connection with the configured close code
+    let message = client.next().await.unwrap().unwrap();
+    assert_eq!(message.as_close().unwrap().0, CloseCode::POLICY_VIOLATION);
+}
+
+#[tokio::test]
+async fn test_write_timeout() {
+    let config = Config::default().write_timeout(Some(Duration::from_millis(100)));
+    let (mut client, _server) = WebSocketStream::pair_with_config(config, Limits::default());
+
+    // The server never reads, so the in-memory buffer fills up eventually
+    let payload = vec![0; 1024 * 1024];
+    let error = client.send_binary(payload.clone()).await.unwrap_err();
+    assert!(matches!(error, Error::WriteTimeout));
+
+    // The connection is abandoned afterwards
+    assert!(matches!(
+        client.send_binary(payload).await,
+        Err(Error::AlreadyClosed)
+    ));
+}
+
+#[tokio::test]
+async fn test_close_timeout() {
+    let config = Config::default().close_timeout(Some(Duration::from_millis(50)));
+    let (mut client, mut server) = WebSocketStream::pair_with_config(config, Limits::default());
+
+    // The server does not answer the close frame in time
+    assert!(client.close().await.unwrap().is_none());
+
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.as_close().unwrap().0, CloseCode::NORMAL_CLOSURE);
+}
+
+#[tokio::test]
+async fn test_keepalive_interval() {
+    let config = Config::default().keepalive_interval(Some(Duration::from_millis(10)));
+    let (mut client, mut server) = WebSocketStream::pair_with_config(config, Limits::default());
+
+    // Pings are only sent while the stream is polled
+    tokio::spawn(async move { while client.next().await.is_some() {} });
+
+    // The server sends pings too, so it also receives the client's pongs
+    let mut pings = 0;
+    while pings < 3 {
+        let message = server.next().await.unwrap().unwrap();
+        assert!(message.is_ping() || message.is_pong());
+        pings += usize::from(message.is_ping());
+    }
+}
+
+#[tokio::test]
+async fn test_keepalive_jitter() {
+    let config = Config::default()
+        .keepalive_interval(Some(Duration::from_millis(10)))
+        .keepalive_jitter(Some(Duration::from_millis(10)));
+    let (mut client, mut server) = WebSocketStream::pair_with_config(config, Limits::default());
+
+    tokio::spawn(async move { while client.next().await.is_some() {} });
+
+    // Pings still arrive, just not at exactly the configured interval
+    let mut pings = 0;
+    while pings < 3 {
+        let message = tokio::time::timeout(Duration::from_secs(1), server.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert!(message.is_ping() || message.is_pong());
+        pings += usize::from(message.is_ping());
+    }
+}
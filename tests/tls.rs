@@ -0,0 +1,499 @@
+#![cfg(all(
+    feature = "client",
+    feature = "server",
+    any(feature = "ring", feature = "aws_lc_rs"),
+    any(feature = "rustls-webpki-roots", feature = "rustls-native-roots"),
+    not(feature = "rustls-platform-verifier")
+))]
+
+use std::time::Duration;
+#[cfg(feature = "rustls-early-data")]
+use std::{
+    io::{self, Read},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures_util::StreamExt;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer};
+#[cfg(feature = "rustls-early-data")]
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+use tokio_rustls::{rustls::HandshakeKind, server::TlsStream};
+#[cfg(feature = "rustls-early-data")]
+use tokio_rustls::{rustls::ServerConfig, TlsAcceptor};
+use tokio_websockets::{
+    server::{Acceptor, AcceptorBuilder},
+    upgrade, ClientBuilder, Connector, Error, MaybeTlsStream, Message, ServerBuilder,
+    WebSocketStream,
+};
+
+/// Reads the certificate chain in `tests/certs/{name}.pem`.
+fn certificates(name: &str) -> Vec<CertificateDer<'static>> {
+    let path = format!("{}/tests/certs/{name}.pem", env!("CARGO_MANIFEST_DIR"));
+    let pem = std::fs::read(path).unwrap();
+
+    rustls_pemfile::certs(&mut pem.as_slice())
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+/// Reads the private key in `tests/certs/{name}.key`.
+fn key(name: &str) -> PrivateKeyDer<'static> {
+    let path = format!("{}/tests/certs/{name}.key", env!("CARGO_MANIFEST_DIR"));
+    let pem = std::fs::read(path).unwrap();
+
+    rustls_pemfile::private_key(&mut pem.as_slice())
+        .unwrap()
+        .unwrap()
+}
+
+/// Returns a builder for an acceptor that authenticates with the certificate
+/// issued by the test CA.
+fn acceptor() -> AcceptorBuilder {
+    Acceptor::builder(ServerBuilder::new(), certificates("server"), key("server"))
+}
+
+/// Returns a connector that only trusts the test CA.
+fn connector() -> tokio_websockets::tls::ConnectorBuilder {
+    Connector::builder()
+        .builtin_roots(false)
+        .add_root_certificate(certificates("ca").remove(0))
+}
+
+/// Accepts a single connection with `acceptor` and returns the port it
+/// listens on.
+async fn serve(
+    acceptor: Acceptor,
+) -> (
+    u16,
+    JoinHandle<Result<WebSocketStream<TlsStream<TcpStream>>, Error>>,
+) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+
+        acceptor.accept(stream).await
+    });
+
+    (port, server)
+}
+
+#[tokio::test]
+async fn test_client_certificate() {
+    let acceptor = acceptor()
+        .add_client_root_certificate(certificates("ca").remove(0))
+        .build()
+        .unwrap();
+    let (port, server) = serve(acceptor).await;
+
+    let connector = connector()
+        .client_auth(certificates("client"), key("client"))
+        .build()
+        .unwrap();
+    let (mut client, _) = ClientBuilder::new()
+        .uri(&format!("wss://localhost:{port}"))
+        .unwrap()
+        .connector(&connector)
+        .connect()
+        .await
+        .unwrap();
+
+    let mut server = server.await.unwrap().unwrap();
+    let peer_certificates = server.get_ref().get_ref().1.peer_certificates();
+    assert_eq!(peer_certificates, Some(&certificates("client")[..]));
+
+    client.send(Message::text("hello")).await.unwrap();
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("hello"));
+}
+
+#[tokio::test]
+async fn test_missing_client_certificate() {
+    let acceptor = acceptor()
+        .add_client_root_certificate(certificates("ca").remove(0))
+        .build()
+        .unwrap();
+    let (port, server) = serve(acceptor).await;
+
+    let connector = connector().build().unwrap();
+    let client = ClientBuilder::new()
+        .uri(&format!("wss://localhost:{port}"))
+        .unwrap()
+        .connector(&connector)
+        .connect()
+        .await;
+
+    assert!(matches!(server.await.unwrap(), Err(Error::Io(_))));
+    assert!(client.is_err());
+}
+
+#[tokio::test]
+async fn test_untrusted_client_certificate() {
+    let acceptor = acceptor()
+        .add_client_root_certificate(certificates("ca").remove(0))
+        .build()
+        .unwrap();
+    let (port, server) = serve(acceptor).await;
+
+    // The self-signed certificate is not issued by the client root certificate
+    let connector = connector()
+        .client_auth(certificates("selfsigned"), key("selfsigned"))
+        .build()
+        .unwrap();
+    let client = ClientBuilder::new()
+        .uri(&format!("wss://localhost:{port}"))
+        .unwrap()
+        .connector(&connector)
+        .connect()
+        .await;
+
+    assert!(matches!(server.await.unwrap(), Err(Error::Io(_))));
+    assert!(client.is_err());
+}
+
+#[tokio::test]
+async fn test_allow_unauthenticated_clients() {
+    let acceptor = acceptor()
+        .add_client_root_certificate(certificates("ca").remove(0))
+        .allow_unauthenticated_clients(true)
+        .build()
+        .unwrap();
+    let (port, server) = serve(acceptor).await;
+
+    let connector = connector().build().unwrap();
+    ClientBuilder::new()
+        .uri(&format!("wss://localhost:{port}"))
+        .unwrap()
+        .connector(&connector)
+        .connect()
+        .await
+        .unwrap();
+
+    let server = server.await.unwrap().unwrap();
+    assert!(server.get_ref().get_ref().1.peer_certificates().is_none());
+}
+
+#[tokio::test]
+async fn test_alpn_protocol() {
+    let acceptor = acceptor()
+        .alpn_protocols(vec![b"x-second".to_vec(), b"x-first".to_vec()])
+        .build()
+        .unwrap();
+    let (port, server) = serve(acceptor).await;
+
+    let connector = connector()
+        .alpn_protocols(vec![b"x-first".to_vec(), b"x-second".to_vec()])
+        .build()
+        .unwrap();
+    let (client, _) = ClientBuilder::new()
+        .uri(&format!("wss://localhost:{port}"))
+        .unwrap()
+        .connector(&connector)
+        .connect()
+        .await
+        .unwrap();
+
+    // The server picks the protocol by its own order of preference
+    assert_eq!(client.get_ref().alpn_protocol(), Some(b"x-second".to_vec()));
+    let server = server.await.unwrap().unwrap();
+    assert_eq!(
+        server.get_ref().get_ref().1.alpn_protocol(),
+        Some(&b"x-second"[..])
+    );
+}
+
+#[tokio::test]
+async fn test_no_alpn_protocol() {
+    let (port, server) = serve(acceptor().build().unwrap()).await;
+
+    let connector = connector()
+        .alpn_protocols(vec![b"x-first".to_vec()])
+        .build()
+        .unwrap();
+    let (client, _) = ClientBuilder::new()
+        .uri(&format!("wss://localhost:{port}"))
+        .unwrap()
+        .connector(&connector)
+        .connect()
+        .await
+        .unwrap();
+
+    assert_eq!(client.get_ref().alpn_protocol(), None);
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_danger_accept_invalid_certs() {
+    let acceptor = || {
+        Acceptor::builder(
+            ServerBuilder::new(),
+            certificates("selfsigned"),
+            key("selfsigned"),
+        )
+        .build()
+        .unwrap()
+    };
+
+    // The self-signed certificate is rejected by default
+    let (port, server) = serve(acceptor()).await;
+    let verifying = connector().build().unwrap();
+    let client = ClientBuilder::new()
+        .uri(&format!("wss://localhost:{port}"))
+        .unwrap()
+        .connector(&verifying)
+        .connect()
+        .await;
+    assert!(matches!(client, Err(Error::Io(_))));
+    assert!(server.await.unwrap().is_err());
+
+    let (port, server) = serve(acceptor()).await;
+    let accepting = connector()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+    ClientBuilder::new()
+        .uri(&format!("wss://localhost:{port}"))
+        .unwrap()
+        .connector(&accepting)
+        .connect()
+        .await
+        .unwrap();
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_acceptor_upgrade() {
+    let acceptor = Acceptor::builder(
+        ServerBuilder::new(),
+        certificates("selfsigned"),
+        key("selfsigned"),
+    )
+    .build()
+    .unwrap();
+    let (port, server) = serve(acceptor).await;
+
+    // Trust the self-signed certificate itself rather than skipping verification
+    let connector = Connector::builder()
+        .builtin_roots(false)
+        .add_root_certificate(certificates("selfsigned").remove(0))
+        .build()
+        .unwrap();
+    let (mut client, response) = ClientBuilder::new()
+        .uri(&format!("wss://localhost:{port}"))
+        .unwrap()
+        .connector(&connector)
+        .connect()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 101);
+
+    let mut server = server.await.unwrap().unwrap();
+    client.send(Message::text("ping")).await.unwrap();
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("ping"));
+
+    server.send(Message::text("pong")).await.unwrap();
+    let message = client.next().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("pong"));
+
+    let (closed, ()) = tokio::join!(client.close(), async {
+        assert!(server.next().await.unwrap().unwrap().is_close());
+        assert!(server.next().await.is_none());
+    });
+    assert!(closed.unwrap().unwrap().is_close());
+}
+
+#[tokio::test]
+async fn test_acceptor_handshake_timeout() {
+    let builder = ServerBuilder::new().handshake_timeout(Duration::from_millis(50));
+    let acceptor = Acceptor::builder(builder, certificates("server"), key("server"))
+        .build()
+        .unwrap();
+    let (port, server) = serve(acceptor).await;
+
+    // The TLS handshake never starts
+    let _stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+
+    assert!(matches!(
+        server.await.unwrap(),
+        Err(Error::Upgrade(upgrade::Error::TimedOut))
+    ));
+}
+
+/// Accepts connections with `acceptor` one after another and returns the port
+/// it listens on.
+async fn serve_all(acceptor: Acceptor) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let _ = acceptor.accept(stream).await;
+        }
+    });
+
+    port
+}
+
+/// Connects to the server at `port` and returns the kind of TLS handshake
+/// that was performed.
+async fn handshake_kind(connector: &Connector, port: u16) -> HandshakeKind {
+    let (client, _) = ClientBuilder::new()
+        .uri(&format!("wss://localhost:{port}"))
+        .unwrap()
+        .connector(connector)
+        .connect()
+        .await
+        .unwrap();
+
+    match client.get_ref() {
+        MaybeTlsStream::Rustls(stream) => stream.get_ref().1.handshake_kind().unwrap(),
+        _ => unreachable!(),
+    }
+}
+
+#[tokio::test]
+async fn test_session_resumption() {
+    let port = serve_all(acceptor().build().unwrap()).await;
+
+    // Reconnecting with the same connector resumes the cached session
+    let first = connector().build().unwrap();
+    assert_eq!(handshake_kind(&first, port).await, HandshakeKind::Full);
+    assert_eq!(handshake_kind(&first, port).await, HandshakeKind::Resumed);
+    assert_eq!(handshake_kind(&first, port).await, HandshakeKind::Resumed);
+
+    // Sessions are not shared between connectors
+    let second = connector().build().unwrap();
+    assert_eq!(handshake_kind(&second, port).await, HandshakeKind::Full);
+}
+
+#[tokio::test]
+async fn test_session_resumption_disabled() {
+    let port = serve_all(acceptor().build().unwrap()).await;
+
+    let connector = connector().session_cache_size(0).build().unwrap();
+    assert_eq!(handshake_kind(&connector, port).await, HandshakeKind::Full);
+    assert_eq!(handshake_kind(&connector, port).await, HandshakeKind::Full);
+}
+
+/// A stream that yields the TLS 1.3 early data received during the handshake
+/// before reading from the TLS stream.
+#[cfg(feature = "rustls-early-data")]
+struct EarlyDataStream {
+    early_data: Vec<u8>,
+    stream: TlsStream<TcpStream>,
+}
+
+#[cfg(feature = "rustls-early-data")]
+impl AsyncRead for EarlyDataStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.early_data.is_empty() {
+            return Pin::new(&mut self.stream).poll_read(cx, buf);
+        }
+
+        let len = buf.remaining().min(self.early_data.len());
+        buf.put_slice(&self.early_data[..len]);
+        self.early_data.drain(..len);
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "rustls-early-data")]
+impl AsyncWrite for EarlyDataStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+#[cfg(feature = "rustls-early-data")]
+#[tokio::test]
+async fn test_early_data() {
+    #[cfg(feature = "aws_lc_rs")]
+    let provider = tokio_rustls::rustls::crypto::aws_lc_rs::default_provider();
+    #[cfg(not(feature = "aws_lc_rs"))]
+    let provider = tokio_rustls::rustls::crypto::ring::default_provider();
+
+    let mut config = ServerConfig::builder_with_provider(Arc::new(provider))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(certificates("server"), key("server"))
+        .unwrap();
+    config.max_early_data_size = 4096;
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    // Accepts two connections and returns the early data received on each
+    let server = tokio::spawn(async move {
+        let mut received = Vec::new();
+
+        for _ in 0..2 {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = acceptor.accept(stream).await.unwrap();
+
+            let mut early_data = Vec::new();
+            if let Some(mut reader) = stream.get_mut().1.early_data() {
+                reader.read_to_end(&mut early_data).unwrap();
+            }
+            received.push(early_data.clone());
+
+            let stream = EarlyDataStream { early_data, stream };
+            ServerBuilder::new().accept(stream).await.unwrap();
+        }
+
+        received
+    });
+
+    let connector = connector().early_data(true).build().unwrap();
+    let mut accepted = Vec::new();
+
+    for _ in 0..2 {
+        let (client, _) = ClientBuilder::new()
+            .uri(&format!("wss://localhost:{port}"))
+            .unwrap()
+            .connector(&connector)
+            .connect()
+            .await
+            .unwrap();
+
+        match client.get_ref() {
+            MaybeTlsStream::Rustls(stream) => {
+                accepted.push(stream.get_ref().1.is_early_data_accepted());
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // Only the resumed session sends the upgrade request as early data
+    assert_eq!(accepted, [false, true]);
+    let received = server.await.unwrap();
+    assert!(received[0].is_empty());
+    assert!(received[1].starts_with(b"GET / HTTP/1.1\r\n"));
+}
@@ -0,0 +1,54 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use tokio_websockets::topics::Topics;
+
+#[tokio::test]
+async fn test_topics() {
+    let topics = Topics::new();
+    let mut one = topics.subscriber(8);
+    let mut two = topics.subscriber(8);
+
+    one.subscribe("a");
+    one.subscribe("b");
+    two.subscribe("b");
+    assert!(one.is_subscribed("a"));
+    assert!(!two.is_subscribed("a"));
+    assert_eq!(topics.subscriber_count("b"), 2);
+
+    assert_eq!(topics.publish("a", "first").await, 1);
+    assert_eq!(topics.publish("b", "second").await, 2);
+    assert_eq!(topics.publish("c", "third").await, 0);
+
+    assert_eq!(one.recv().await.unwrap().as_text(), Some("first"));
+    let message = one.recv().await.unwrap();
+    assert_eq!(message.as_text(), Some("second"));
+    // The payload is shared between subscribers instead of copied
+    let other = two.recv().await.unwrap();
+    assert_eq!(message.as_payload().as_ptr(), other.as_payload().as_ptr());
+
+    one.unsubscribe("b");
+    assert_eq!(topics.publish("b", "fourth").await, 1);
+    assert_eq!(two.recv().await.unwrap().as_text(), Some("fourth"));
+
+    // Dropping a subscriber unsubscribes it and removes empty topics
+    drop(two);
+    assert_eq!(topics.subscriber_count("b"), 0);
+    assert_eq!(topics.publish("b", "fifth").await, 0);
+}
+
+#[tokio::test]
+async fn test_topics_full_subscriber() {
+    let topics = Topics::new();
+    let mut slow = topics.subscriber(1);
+    let mut fast = topics.subscriber(8);
+    slow.subscribe("a");
+    fast.subscribe("a");
+
+    assert_eq!(topics.publish("a", "first").await, 2);
+    // The slow subscriber misses messages while its buffer is full
+    assert_eq!(topics.publish("a", "second").await, 1);
+
+    assert_eq!(slow.recv().await.unwrap().as_text(), Some("first"));
+    assert_eq!(fast.recv().await.unwrap().as_text(), Some("first"));
+    assert_eq!(fast.recv().await.unwrap().as_text(), Some("second"));
+}
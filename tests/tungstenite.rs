@@ -0,0 +1,85 @@
+#![cfg(feature = "tungstenite")]
+use tokio_websockets::{proto::ProtocolError, CloseCode, Message};
+use tungstenite::protocol::{frame::coding::CloseCode as TungsteniteCloseCode, CloseFrame};
+
+fn roundtrip(message: Message) -> Message {
+    Message::try_from(tungstenite::Message::from(message)).unwrap()
+}
+
+#[test]
+fn test_close_code() {
+    for code in [CloseCode::NORMAL_CLOSURE, CloseCode::BAD_GATEWAY] {
+        let converted = TungsteniteCloseCode::from(code);
+        assert_eq!(u16::from(converted), u16::from(code));
+        assert_eq!(CloseCode::try_from(converted).unwrap(), code);
+    }
+
+    assert_eq!(
+        TungsteniteCloseCode::from(CloseCode::POLICY_VIOLATION),
+        TungsteniteCloseCode::Policy
+    );
+    assert!(matches!(
+        CloseCode::try_from(TungsteniteCloseCode::Bad(999)),
+        Err(ProtocolError::InvalidCloseCode)
+    ));
+}
+
+#[test]
+fn test_message() {
+    assert_eq!(
+        tungstenite::Message::from(Message::text("hello")),
+        tungstenite::Message::text("hello")
+    );
+    assert_eq!(
+        tungstenite::Message::from(Message::binary(vec![1, 2, 3])),
+        tungstenite::Message::binary(vec![1, 2, 3])
+    );
+    assert_eq!(
+        tungstenite::Message::from(Message::close(None, "")),
+        tungstenite::Message::Close(None)
+    );
+    assert_eq!(
+        tungstenite::Message::from(Message::close(Some(CloseCode::GOING_AWAY), "bye")),
+        tungstenite::Message::Close(Some(CloseFrame {
+            code: TungsteniteCloseCode::Away,
+            reason: "bye".into(),
+        }))
+    );
+
+    assert_eq!(
+        roundtrip(Message::text(String::from("hello"))).as_text(),
+        Some("hello")
+    );
+    assert_eq!(&*roundtrip(Message::ping("ping")).into_payload(), b"ping");
+    assert!(roundtrip(Message::pong("pong")).is_pong());
+    assert_eq!(
+        roundtrip(Message::close(Some(CloseCode::GOING_AWAY), "bye")).as_close(),
+        Some((CloseCode::GOING_AWAY, "bye"))
+    );
+    assert!(roundtrip(Message::close(None, ""))
+        .as_close()
+        .is_some_and(|(_, reason)| reason.is_empty()));
+}
+
+#[test]
+#[should_panic = "invalid utf-8"]
+fn test_invalid_text() {
+    let _ = tungstenite::Message::from(Message::text(vec![0xff]));
+}
+
+#[test]
+fn test_unconvertible() {
+    assert!(matches!(
+        Message::try_from(tungstenite::Message::Close(Some(CloseFrame {
+            code: TungsteniteCloseCode::Status,
+            reason: "".into(),
+        }))),
+        Err(ProtocolError::InvalidCloseCode)
+    ));
+    assert!(matches!(
+        Message::try_from(tungstenite::Message::Frame(
+            tungstenite::protocol::frame::Frame::ping(vec![])
+        )),
+        Err(ProtocolError::InvalidOpcode)
+    ));
+}
@@ -0,0 +1,34 @@
+#![cfg(feature = "server")]
+
+use futures_util::StreamExt;
+use tokio::io::{duplex, AsyncWriteExt};
+use tokio_websockets::{proto::ProtocolError, Config, Error, ServerBuilder};
+
+/// An unmasked text frame with the payload "Hello".
+const UNMASKED_FRAME: &[u8] = b"\x81\x05Hello";
+
+#[tokio::test]
+async fn test_unmasked_frame_rejected() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new().serve(one);
+
+    two.write_all(UNMASKED_FRAME).await.unwrap();
+
+    assert!(matches!(
+        server.next().await,
+        Some(Err(Error::Protocol(ProtocolError::UnexpectedUnmaskedFrame)))
+    ));
+}
+
+#[tokio::test]
+async fn test_unmasked_frame_accepted() {
+    let (one, mut two) = duplex(usize::MAX);
+    let mut server = ServerBuilder::new()
+        .config(Config::default().accept_unmasked_frames(true))
+        .serve(one);
+
+    two.write_all(UNMASKED_FRAME).await.unwrap();
+
+    let msg = server.next().await.unwrap().unwrap();
+    assert_eq!(msg.as_text(), Some("Hello"));
+}
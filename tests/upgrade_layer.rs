@@ -0,0 +1,103 @@
+#![cfg(all(feature = "client", feature = "server", feature = "tower"))]
+
+use std::{
+    convert::Infallible,
+    future::{poll_fn, ready, Ready},
+    io,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use futures_util::StreamExt;
+use http::{Request, Response, StatusCode};
+use tokio::io::{duplex, DuplexStream};
+use tokio_websockets::{server::UpgradeLayer, ClientBuilder, ServerBuilder};
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// Connection of a request, as stored in the request extensions by HTTP
+/// servers that support upgrades.
+#[derive(Clone)]
+struct Connection(Arc<Mutex<Option<DuplexStream>>>);
+
+/// HTTP service that replies to every request with a fixed body.
+struct Fallback;
+
+impl Service<Request<()>> for Fallback {
+    type Error = Infallible;
+    type Future = Ready<Result<Response<String>, Infallible>>;
+    type Response = Response<String>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _request: Request<()>) -> Self::Future {
+        ready(Ok(Response::new("fallback".to_owned())))
+    }
+}
+
+/// Creates a request to `/ws`, with the headers of an upgrade request if
+/// `upgrade` is set.
+fn request(upgrade: bool, connection: Option<DuplexStream>) -> Request<()> {
+    let mut builder = Request::builder()
+        .uri("/ws?name=test")
+        .header("Host", "localhost");
+
+    if upgrade {
+        builder = builder
+            .header("Upgrade", "websocket")
+            .header("Connection", "Upgrade")
+            .header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==")
+            .header("Sec-WebSocket-Version", "13");
+    }
+
+    builder
+        .extension(Connection(Arc::new(Mutex::new(connection))))
+        .body(())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_upgrade_layer() {
+    let layer = UpgradeLayer::new(
+        ServerBuilder::new(),
+        |request: &mut Request<()>| {
+            let connection = request
+                .extensions()
+                .get::<Connection>()
+                .and_then(|connection| connection.0.lock().unwrap().take());
+
+            async move { connection.ok_or_else(|| io::Error::from(io::ErrorKind::NotConnected)) }
+        },
+        |mut stream, request| async move {
+            let query = request.uri().query().unwrap_or_default().to_owned();
+            stream.send_text(query).await.unwrap();
+        },
+    );
+    let mut service = layer.layer(Fallback);
+
+    // Other requests are passed to the wrapped service
+    poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+    let response = service.call(request(false, None)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.body(), "fallback");
+
+    // Upgrade requests spawn the handler
+    let (one, two) = duplex(1024);
+    poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+    let response = service.call(request(true, Some(two))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
+    assert!(response.body().is_empty());
+
+    let mut client = ClientBuilder::new().take_over(one);
+    let message = client.next().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("name=test"));
+
+    // Invalid upgrade requests are rejected
+    let mut invalid = request(true, None);
+    invalid.headers_mut().remove("Sec-WebSocket-Key");
+    poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+    let response = service.call(invalid).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,57 @@
+#![cfg(all(feature = "client", feature = "server"))]
+
+use futures_util::StreamExt;
+use http::{Method, Request, StatusCode};
+use tokio::io::duplex;
+use tokio_websockets::{upgrade, ClientBuilder, Error, ServerBuilder};
+
+/// Creates an upgrade request as received by an HTTP server.
+fn request(method: Method) -> Request<()> {
+    Request::builder()
+        .method(method)
+        .uri("/ws")
+        .header("Host", "localhost")
+        .header("Upgrade", "websocket")
+        .header("Connection", "keep-alive, Upgrade")
+        .header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==")
+        .header("Sec-WebSocket-Version", "13")
+        .header("Sec-WebSocket-Protocol", "chat, superchat")
+        .body(())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_upgrade_request() {
+    let (one, two) = duplex(1024);
+    let builder = ServerBuilder::new().subprotocols(["superchat"]);
+
+    let (response, stream) = builder
+        .upgrade(&request(Method::GET), async { Ok(two) })
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
+    assert_eq!(response.headers()["Upgrade"], "websocket");
+    assert_eq!(response.headers()["Connection"], "Upgrade");
+    assert_eq!(
+        response.headers()["Sec-WebSocket-Accept"],
+        "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+    );
+    assert_eq!(response.headers()["Sec-WebSocket-Protocol"], "superchat");
+
+    let mut server = stream.await.unwrap();
+    assert_eq!(server.subprotocol(), Some("superchat"));
+
+    let mut client = ClientBuilder::new().take_over(one);
+    client.send_text("hello").await.unwrap();
+    let message = server.next().await.unwrap().unwrap();
+    assert_eq!(message.as_text(), Some("hello"));
+}
+
+#[tokio::test]
+async fn test_upgrade_request_invalid() {
+    let result = ServerBuilder::new().upgrade(&request(Method::POST), async { Ok(duplex(1024).0) });
+
+    assert!(matches!(
+        result,
+        Err(Error::Upgrade(upgrade::Error::UnsupportedMethod))
+    ));
+}